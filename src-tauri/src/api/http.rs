@@ -0,0 +1,203 @@
+// HTTP Framing
+// Just enough HTTP/1.1 for the local API: one request per connection,
+// Content-Length bodies, JSON responses
+
+use std::io;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Largest request body accepted (exports carry whole result sets)
+pub const MAX_BODY_BYTES: usize = 64 * 1024 * 1024;
+const MAX_HEADERS: usize = 64;
+const MAX_LINE_BYTES: usize = 8 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// Header names are lowercased
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Parse the request line and headers (without the blank line)
+pub fn parse_head(lines: &[String]) -> io::Result<Request> {
+    let (request_line, header_lines) = lines
+        .split_first()
+        .ok_or_else(|| invalid("Empty request"))?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(path), Some(version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid(format!("Malformed request line: {}", request_line)));
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Err(invalid(format!("Unsupported HTTP version: {}", version)));
+    }
+
+    let mut headers = Vec::with_capacity(header_lines.len());
+    for line in header_lines {
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| invalid(format!("Malformed header: {}", line)))?;
+        headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+    }
+
+    Ok(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        headers,
+        body: Vec::new(),
+    })
+}
+
+/// Read the request line and headers, leaving the body unread so the caller
+/// can reject the request first. Returns None when the peer closed the
+/// connection before sending anything.
+pub async fn read_head<R>(reader: &mut R) -> io::Result<Option<Request>>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        let read = (&mut *reader)
+            .take(MAX_LINE_BYTES as u64)
+            .read_line(&mut line)
+            .await?;
+        if read == 0 {
+            if lines.is_empty() {
+                return Ok(None);
+            }
+            return Err(invalid("Connection closed in the middle of the headers"));
+        }
+        if !line.ends_with('\n') {
+            return Err(invalid("Header line too long"));
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            break;
+        }
+        if lines.len() > MAX_HEADERS {
+            return Err(invalid("Too many headers"));
+        }
+        lines.push(line.to_string());
+    }
+    parse_head(&lines).map(Some)
+}
+
+/// Read the Content-Length body of a request whose head was read
+pub async fn read_body<R>(reader: &mut R, request: &mut Request) -> io::Result<()>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let length = match request.header("content-length") {
+        Some(value) => value
+            .parse::<usize>()
+            .map_err(|_| invalid(format!("Invalid Content-Length: {}", value)))?,
+        None => 0,
+    };
+    if length > MAX_BODY_BYTES {
+        return Err(invalid(format!(
+            "Request body over {} bytes",
+            MAX_BODY_BYTES
+        )));
+    }
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body).await?;
+    Ok(())
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Status line, headers and body of a JSON response
+pub fn format_response(status: u16, body: &[u8]) -> Vec<u8> {
+    let mut out = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        reason(status),
+        body.len()
+    )
+    .into_bytes();
+    out.extend_from_slice(body);
+    out
+}
+
+pub async fn write_response<W>(writer: &mut W, status: u16, body: &[u8]) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    writer.write_all(&format_response(status, body)).await?;
+    writer.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn parses_request_heads() {
+        let request = parse_head(&lines(
+            "POST /rpc HTTP/1.1\nHost: 127.0.0.1:5000\nAuthorization: Bearer abc\nContent-Length: 2",
+        ))
+        .unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/rpc");
+        assert_eq!(request.header("authorization"), Some("Bearer abc"));
+        assert_eq!(request.header("Content-Length"), Some("2"));
+
+        assert!(parse_head(&lines("GARBAGE")).is_err());
+        assert!(parse_head(&lines("GET / SPDY/3")).is_err());
+        assert!(parse_head(&lines("GET / HTTP/1.1\nno colon")).is_err());
+    }
+
+    #[tokio::test]
+    async fn reads_the_body_only_when_asked() {
+        let mut input: &[u8] = b"POST /rpc HTTP/1.1\r\nContent-Length: 4\r\n\r\n{}{}";
+        let mut request = read_head(&mut input).await.unwrap().unwrap();
+        assert!(request.body.is_empty());
+        assert_eq!(input, b"{}{}");
+        read_body(&mut input, &mut request).await.unwrap();
+        assert_eq!(request.body, b"{}{}");
+
+        let mut huge: &[u8] = b"POST /rpc HTTP/1.1\r\nContent-Length: 99999999999\r\n\r\n";
+        let mut request = read_head(&mut huge).await.unwrap().unwrap();
+        assert!(read_body(&mut huge, &mut request).await.is_err());
+        assert!(read_head(&mut &b""[..]).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn formats_json_responses() {
+        let response = String::from_utf8(format_response(401, b"{}")).unwrap();
+        assert!(response.starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+        assert!(response.contains("Content-Length: 2\r\n"));
+        assert!(response.ends_with("\r\n\r\n{}"));
+    }
+}
@@ -0,0 +1,205 @@
+// API Methods
+// The subset of Tauri commands reachable through the local API. Parameters
+// use the same camelCase names as `invoke` from the frontend, and results are
+// the commands' own types serialized as JSON.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::commands::{self, AppState};
+use crate::jsonrpc::{INVALID_PARAMS, METHOD_NOT_FOUND};
+
+/// JSON-RPC error code for a command that returned an error
+pub const COMMAND_ERROR: i64 = -32000;
+
+/// Event emitted when a tab is opened through the API, with the Tab as payload
+pub const OPEN_TAB_EVENT: &str = "api-open-tab";
+
+pub const METHODS: &[&str] = &[
+    "list_methods",
+    "get_spaces",
+    "get_space",
+    "connect_to_space",
+    "get_tabs_by_space",
+    "get_tab",
+    "create_tab",
+    "update_tab",
+    "open_tab",
+    "run_tab",
+    "execute_query",
+    "cancel_query",
+    "get_query_status",
+    "export_to_csv",
+    "export_to_json",
+    "export_to_string",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// A named parameter, or null when it is missing (for optional parameters)
+fn arg<T: DeserializeOwned>(params: &Value, name: &str) -> Result<T, RpcError> {
+    let value = params.get(name).cloned().unwrap_or(Value::Null);
+    serde_json::from_value(value)
+        .map_err(|e| RpcError::new(INVALID_PARAMS, format!("{}: {}", name, e)))
+}
+
+fn reply<T: Serialize>(result: Result<T, String>) -> Result<Value, RpcError> {
+    let value = result.map_err(|e| RpcError::new(COMMAND_ERROR, e))?;
+    serde_json::to_value(value).map_err(|e| RpcError::new(COMMAND_ERROR, e.to_string()))
+}
+
+/// Run one API method
+pub async fn call(app: &AppHandle, method: &str, params: &Value) -> Result<Value, RpcError> {
+    let state = app.state::<AppState>();
+    let p = params;
+    match method {
+        "list_methods" => Ok(json!(METHODS)),
+        "get_spaces" => reply(commands::get_spaces(state)),
+        "get_space" => reply(commands::get_space(state, arg(p, "id")?)),
+        "connect_to_space" => reply(commands::connect_to_space(state, arg(p, "spaceId")?).await),
+        "get_tabs_by_space" => reply(commands::get_tabs_by_space(state, arg(p, "spaceId")?)),
+        "get_tab" => reply(commands::get_tab(state, arg(p, "id")?)),
+        "create_tab" => reply(commands::create_tab(
+            state,
+            arg(p, "spaceId")?,
+            arg(p, "title")?,
+            arg::<Option<String>>(p, "tabType")?.unwrap_or_else(|| "query".to_string()),
+            arg(p, "content")?,
+            arg(p, "metadata")?,
+            arg(p, "database")?,
+        )),
+        "update_tab" => reply(commands::update_tab(
+            state,
+            arg(p, "id")?,
+            arg(p, "title")?,
+            arg(p, "content")?,
+            arg(p, "metadata")?,
+            arg(p, "database")?,
+            arg(p, "sortOrder")?,
+        )),
+        "open_tab" => {
+            let tab = commands::create_tab(
+                state,
+                arg(p, "spaceId")?,
+                arg::<Option<String>>(p, "title")?.unwrap_or_else(|| "API Query".to_string()),
+                "query".to_string(),
+                Some(arg(p, "sql")?),
+                None,
+                arg(p, "database")?,
+            );
+            if let Ok(tab) = &tab {
+                let _ = app.emit(OPEN_TAB_EVENT, tab);
+            }
+            reply(tab)
+        }
+        "run_tab" => {
+            let id: String = arg(p, "tabId")?;
+            let tab = commands::get_tab(state.clone(), id.clone())
+                .map_err(|e| RpcError::new(COMMAND_ERROR, e))?
+                .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("No tab with id {}", id)))?;
+            reply(
+                commands::execute_query(
+                    state,
+                    tab.space_id,
+                    tab.content.unwrap_or_default(),
+                    tab.database,
+                    None,
+                    arg(p, "maxRows")?,
+                    None,
+                    None,
+                    None,
+                )
+                .await,
+            )
+        }
+        "execute_query" => reply(
+            commands::execute_query(
+                state,
+                arg(p, "connectionId")?,
+                arg(p, "query")?,
+                arg(p, "database")?,
+                arg(p, "selectedText")?,
+                arg(p, "maxRows")?,
+                arg(p, "sqlcmdMode")?,
+                arg(p, "filePath")?,
+                arg(p, "sqlcmdVariables")?,
+            )
+            .await,
+        ),
+        "cancel_query" => reply(commands::cancel_query(state, arg(p, "queryId")?).await),
+        "get_query_status" => reply(commands::get_query_status(state, arg(p, "queryId")?).await),
+        "export_to_csv" => reply(
+            commands::export_to_csv(
+                app.clone(),
+                state,
+                arg(p, "filePath")?,
+                arg(p, "columns")?,
+                arg(p, "rows")?,
+                arg(p, "options")?,
+            )
+            .await,
+        ),
+        "export_to_json" => reply(
+            commands::export_to_json(
+                app.clone(),
+                state,
+                arg(p, "filePath")?,
+                arg(p, "columns")?,
+                arg(p, "rows")?,
+                arg(p, "options")?,
+            )
+            .await,
+        ),
+        "export_to_string" => reply(
+            commands::export_to_string(
+                arg(p, "format")?,
+                arg(p, "columns")?,
+                arg(p, "rows")?,
+                arg(p, "options")?,
+            )
+            .await,
+        ),
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Unknown method: {}", method),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_named_parameters() {
+        let params = json!({ "spaceId": "s1", "maxRows": 10 });
+        assert_eq!(arg::<String>(&params, "spaceId"), Ok("s1".to_string()));
+        assert_eq!(arg::<Option<usize>>(&params, "maxRows"), Ok(Some(10)));
+        assert_eq!(arg::<Option<String>>(&params, "database"), Ok(None));
+
+        let missing = arg::<String>(&params, "query").unwrap_err();
+        assert_eq!(missing.code, INVALID_PARAMS);
+        assert!(missing.message.starts_with("query: "));
+        assert_eq!(
+            arg::<usize>(&json!({ "maxRows": "ten" }), "maxRows")
+                .unwrap_err()
+                .code,
+            INVALID_PARAMS
+        );
+    }
+}
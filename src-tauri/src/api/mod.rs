@@ -0,0 +1,9 @@
+// Local Automation API
+// Opt-in JSON-RPC endpoint on localhost that lets other tools drive the
+// running app through a curated set of commands; every call is audited
+
+pub mod http;
+pub mod methods;
+pub mod server;
+
+pub use server::{start_if_enabled, ApiServer, ApiStatus};
@@ -0,0 +1,399 @@
+// API Server
+// Localhost-only JSON-RPC over HTTP. Listens on a random port; the port and
+// bearer token are written to api.json in the app data directory.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager};
+use tokio::io::BufReader;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+
+use super::http::{read_body, read_head, write_response, Request};
+use super::methods;
+use crate::commands::AppState;
+use crate::jsonrpc::{error_response, response, INVALID_REQUEST, PARSE_ERROR};
+use crate::storage::get_default_db_path;
+
+/// Longest parameter text kept in the audit log
+const MAX_AUDIT_PARAMS: usize = 2000;
+/// Time a client gets to send its whole request
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Contents of api.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiInfo {
+    pub url: String,
+    pub port: u16,
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiStatus {
+    pub enabled: bool,
+    pub running: bool,
+    pub port: Option<u16>,
+    /// Where clients find the port and token
+    pub info_path: Option<String>,
+}
+
+/// A running API server. It stops accepting connections on `stop` or when
+/// dropped, since either ends the shutdown channel.
+pub struct ApiServer {
+    port: u16,
+    info_path: PathBuf,
+    shutdown: oneshot::Sender<()>,
+}
+
+/// api.json next to the app database
+pub fn info_path() -> io::Result<PathBuf> {
+    let db_path = get_default_db_path()
+        .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e.to_string()))?;
+    Ok(db_path.with_file_name("api.json"))
+}
+
+/// The token from an existing api.json, so clients keep working across restarts
+fn existing_token(path: &Path) -> Option<String> {
+    let text = std::fs::read_to_string(path).ok()?;
+    let info: ApiInfo = serde_json::from_str(&text).ok()?;
+    Some(info.token).filter(|t| t.len() >= 32)
+}
+
+fn new_token() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+/// Write api.json readable by the current user only
+fn write_info(path: &Path, info: &ApiInfo) -> io::Result<()> {
+    use std::io::Write;
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    // `mode` only applies to new files; tighten an existing one before the
+    // token goes in
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(serde_json::to_string_pretty(info)?.as_bytes())
+}
+
+/// Compare tokens without exiting at the first differing byte
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Only accept Host headers naming the loopback listener, so a web page
+/// resolving its own domain to 127.0.0.1 can't reach the API
+fn host_allowed(host: Option<&str>, port: u16) -> bool {
+    let Some(host) = host else { return false };
+    let port = port.to_string();
+    ["127.0.0.1", "localhost"].iter().any(|name| {
+        host.strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix(':'))
+            == Some(port.as_str())
+    })
+}
+
+/// Parameters as stored in the audit log
+fn audit_params(params: &Value) -> Option<String> {
+    match params {
+        Value::Null => None,
+        Value::Object(map) if map.is_empty() => None,
+        _ => {
+            let text = params.to_string();
+            if text.chars().count() > MAX_AUDIT_PARAMS {
+                let mut cut: String = text.chars().take(MAX_AUDIT_PARAMS).collect();
+                cut.push('…');
+                Some(cut)
+            } else {
+                Some(text)
+            }
+        }
+    }
+}
+
+fn audit(
+    app: &AppHandle,
+    method: &str,
+    params: Option<&str>,
+    error: Option<&str>,
+    started: Instant,
+) {
+    let state = app.state::<AppState>();
+    let Ok(db) = state.db.lock() else { return };
+    let duration_ms = started.elapsed().as_millis() as i64;
+    if let Err(e) = db.add_api_audit_entry(method, params, error, duration_ms) {
+        eprintln!("[API] Failed to record audit entry: {}", e);
+    }
+}
+
+impl ApiServer {
+    /// Bind a random loopback port, write api.json and start serving
+    pub async fn start(app: AppHandle) -> io::Result<Self> {
+        let info_path = info_path()?;
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let port = listener.local_addr()?.port();
+        let token = existing_token(&info_path).unwrap_or_else(new_token);
+        write_info(
+            &info_path,
+            &ApiInfo {
+                url: format!("http://127.0.0.1:{}/rpc", port),
+                port,
+                token: token.clone(),
+            },
+        )?;
+
+        let (shutdown, mut stopped) = oneshot::channel();
+        tauri::async_runtime::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = &mut stopped => break,
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, _)) => {
+                            let app = app.clone();
+                            let token = token.clone();
+                            tauri::async_runtime::spawn(async move {
+                                if let Err(e) = handle_connection(stream, &app, &token, port).await {
+                                    eprintln!("[API] Connection error: {}", e);
+                                }
+                            });
+                        }
+                        Err(e) => eprintln!("[API] Accept failed: {}", e),
+                    },
+                }
+            }
+        });
+
+        println!("[API] Listening on 127.0.0.1:{}", port);
+        Ok(Self {
+            port,
+            info_path,
+            shutdown,
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn info_path(&self) -> &Path {
+        &self.info_path
+    }
+
+    /// Stop accepting connections. Requests already running finish.
+    pub fn stop(self) {
+        let _ = self.shutdown.send(());
+        println!("[API] Stopped listening on 127.0.0.1:{}", self.port);
+    }
+}
+
+/// Start the server at launch when the user has enabled it
+pub async fn start_if_enabled(app: AppHandle) {
+    let enabled = {
+        let state = app.state::<AppState>();
+        let Ok(db) = state.db.lock() else { return };
+        db.get_api_enabled().unwrap_or(false)
+    };
+    if !enabled {
+        return;
+    }
+    match ApiServer::start(app.clone()).await {
+        Ok(server) => {
+            let state = app.state::<AppState>();
+            *state.api_server.lock().await = Some(server);
+        }
+        Err(e) => eprintln!("[API] Failed to start: {}", e),
+    }
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    app: &AppHandle,
+    token: &str,
+    port: u16,
+) -> io::Result<()> {
+    let (read, mut write) = stream.into_split();
+    let mut reader = BufReader::new(read);
+    let deadline = tokio::time::Instant::now() + READ_TIMEOUT;
+    let timed_out = || (408, json!({ "error": "Timed out reading the request" }));
+
+    let (status, body) = match tokio::time::timeout_at(deadline, read_head(&mut reader)).await {
+        Err(_) => timed_out(),
+        Ok(Ok(None)) => return Ok(()),
+        Ok(Err(e)) => (400, json!({ "error": e.to_string() })),
+        // The body is only read once the caller has been let in
+        Ok(Ok(Some(mut request))) => match check_request(app, token, port, &request) {
+            Err(rejection) => rejection,
+            Ok(()) => match tokio::time::timeout_at(deadline, read_body(&mut reader, &mut request))
+                .await
+            {
+                Err(_) => timed_out(),
+                Ok(Err(e)) => (400, json!({ "error": e.to_string() })),
+                Ok(Ok(())) => respond(app, request).await,
+            },
+        },
+    };
+    write_response(&mut write, status, body.to_string().as_bytes()).await
+}
+
+/// Reject a request on its head alone: wrong path, method, host or token
+fn check_request(
+    app: &AppHandle,
+    token: &str,
+    port: u16,
+    request: &Request,
+) -> Result<(), (u16, Value)> {
+    if request.path != "/rpc" && request.path != "/" {
+        return Err((
+            404,
+            json!({ "error": "Not found; POST JSON-RPC requests to /rpc" }),
+        ));
+    }
+    if request.method != "POST" {
+        return Err((405, json!({ "error": "Use POST" })));
+    }
+    if !host_allowed(request.header("host"), port) {
+        return Err((403, json!({ "error": "Host not allowed" })));
+    }
+    let authorized = request
+        .header("authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| tokens_match(given.trim(), token));
+    if !authorized {
+        audit(
+            app,
+            "(unauthorized)",
+            None,
+            Some("Missing or wrong token"),
+            Instant::now(),
+        );
+        return Err((401, json!({ "error": "Missing or wrong bearer token" })));
+    }
+    Ok(())
+}
+
+/// HTTP status and JSON body for an authorized request
+async fn respond(app: &AppHandle, request: Request) -> (u16, Value) {
+    let message: Value = match serde_json::from_slice(&request.body) {
+        Ok(message) => message,
+        Err(e) => {
+            return (
+                200,
+                error_response(&Value::Null, PARSE_ERROR, e.to_string()),
+            )
+        }
+    };
+    let id = message.get("id").cloned().unwrap_or(Value::Null);
+    let Some(method) = message.get("method").and_then(Value::as_str) else {
+        return (200, error_response(&id, INVALID_REQUEST, "Missing method"));
+    };
+    let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+    let started = Instant::now();
+    let result = methods::call(app, method, &params).await;
+    let error = result.as_ref().err().map(|e| e.message.as_str());
+    audit(
+        app,
+        method,
+        audit_params(&params).as_deref(),
+        error,
+        started,
+    );
+
+    match result {
+        Ok(value) => (200, response(&id, value)),
+        Err(e) => (200, error_response(&id, e.code, e.message)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_tokens_and_hosts() {
+        assert!(tokens_match("abc123", "abc123"));
+        assert!(!tokens_match("abc124", "abc123"));
+        assert!(!tokens_match("abc", "abc123"));
+
+        assert!(host_allowed(Some("127.0.0.1:5123"), 5123));
+        assert!(host_allowed(Some("localhost:5123"), 5123));
+        assert!(!host_allowed(Some("localhost:80"), 5123));
+        assert!(!host_allowed(Some("evil.example:5123"), 5123));
+        assert!(!host_allowed(Some("127.0.0.1.evil.example:5123"), 5123));
+        assert!(!host_allowed(None, 5123));
+    }
+
+    #[test]
+    fn shortens_audited_params() {
+        assert_eq!(audit_params(&Value::Null), None);
+        assert_eq!(audit_params(&json!({})), None);
+        assert_eq!(
+            audit_params(&json!({ "queryId": "q1" })).as_deref(),
+            Some(r#"{"queryId":"q1"}"#)
+        );
+        let long = audit_params(&json!({ "query": "x".repeat(5000) })).unwrap();
+        assert_eq!(long.chars().count(), MAX_AUDIT_PARAMS + 1);
+        assert!(long.ends_with('…'));
+    }
+
+    #[test]
+    fn keeps_the_token_across_restarts() {
+        let path = std::env::temp_dir().join(format!("larik_api_{}.json", uuid::Uuid::new_v4()));
+        assert_eq!(existing_token(&path), None);
+
+        let token = new_token();
+        assert_eq!(token.len(), 64);
+        let info = ApiInfo {
+            url: "http://127.0.0.1:1/rpc".to_string(),
+            port: 1,
+            token: token.clone(),
+        };
+        write_info(&path, &info).unwrap();
+        assert_eq!(existing_token(&path), Some(token));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tightens_permissions_of_an_existing_info_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("larik_api_{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "{}").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let info = ApiInfo {
+            url: "http://127.0.0.1:1/rpc".to_string(),
+            port: 1,
+            token: new_token(),
+        };
+        write_info(&path, &info).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,9 @@
+// SQL language server for editors such as VS Code and Neovim. Speaks LSP over
+// stdio using the spaces and schema cache of the Larik app database.
+
+fn main() {
+    if let Err(e) = larik_sql_studio_lib::lsp::run_stdio() {
+        eprintln!("larik-lsp: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,7 @@
+// Headless CLI: list spaces, tabs and snippets, or run them against a space.
+// See `larik help` for usage.
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    std::process::exit(larik_sql_studio_lib::cli::run(&args));
+}
@@ -0,0 +1,610 @@
+// Command Line Interface
+// Headless access to spaces, tabs and snippets: list them, or run a tab,
+// snippet or SQL file against a space and write results as text, CSV or JSON
+
+pub mod table;
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::db::{
+    set_query_logging, ColumnInfo, ConnectionConfig, MssqlConnectionManager, QueryEngine,
+    QueryResult,
+};
+use crate::export::{CsvExporter, ExportOptions, JsonExporter};
+use crate::storage::{get_default_db_path, DatabaseManager, Snippet, Space, Tab};
+
+/// Exit codes
+pub const EXIT_OK: i32 = 0;
+/// A statement failed on the server
+pub const EXIT_SQL_ERROR: i32 = 1;
+/// Bad arguments, or a space, tab or snippet that doesn't exist
+pub const EXIT_USAGE: i32 = 2;
+/// Storage, connection or file errors
+pub const EXIT_FAILURE: i32 = 3;
+
+pub const USAGE: &str = "\
+Usage:
+  larik spaces                      List spaces
+  larik tabs <space>                List the tabs of a space
+  larik snippets                    List snippets
+  larik run <space> <source> [options]
+
+Sources (one of):
+  --tab <id|title>                  A tab of the space
+  --snippet <id|trigger|name>       A snippet; fill placeholders with --set
+  --file <path>                     A .sql file
+  --sql <text>                      Literal SQL
+
+Options:
+  --database <name>                 Database (default: the tab's, then the space's)
+  --format table|csv|json           Output format (default: table)
+  --output <path>                   Write results to a file instead of stdout;
+                                    further result sets go to <name>_2.<ext>, ...
+  --max-rows <n>                    Limit rows per result set
+  --set <name>=<value>              Value for a snippet placeholder such as
+                                    ${cursor} or ${1:default}
+
+Exit codes: 0 success, 1 SQL error, 2 usage error, 3 connection or I/O error";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Spaces,
+    Tabs { space: String },
+    Snippets,
+    Run(RunOptions),
+    Help,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    Tab(String),
+    Snippet(String),
+    File(PathBuf),
+    Sql(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Table,
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunOptions {
+    pub space: String,
+    pub source: Source,
+    pub database: Option<String>,
+    pub format: OutputFormat,
+    pub output: Option<PathBuf>,
+    pub max_rows: Option<usize>,
+    /// Snippet placeholder values
+    pub values: Vec<(String, String)>,
+}
+
+#[derive(Debug)]
+enum CliError {
+    Usage(String),
+    Failure(String),
+}
+
+impl CliError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Usage(_) => EXIT_USAGE,
+            CliError::Failure(_) => EXIT_FAILURE,
+        }
+    }
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::Usage(message) | CliError::Failure(message) => f.write_str(message),
+        }
+    }
+}
+
+fn failure(e: impl ToString) -> CliError {
+    CliError::Failure(e.to_string())
+}
+
+/// Parse arguments (without the program name)
+pub fn parse_args(args: &[String]) -> Result<Command, String> {
+    let Some((command, rest)) = args.split_first() else {
+        return Ok(Command::Help);
+    };
+    match command.as_str() {
+        "help" | "--help" | "-h" => Ok(Command::Help),
+        "spaces" if rest.is_empty() => Ok(Command::Spaces),
+        "snippets" if rest.is_empty() => Ok(Command::Snippets),
+        "tabs" => match rest {
+            [space] => Ok(Command::Tabs {
+                space: space.clone(),
+            }),
+            _ => Err("tabs takes exactly one space".to_string()),
+        },
+        "run" => parse_run(rest).map(Command::Run),
+        _ => Err(format!("Unknown command: {}", args.join(" "))),
+    }
+}
+
+fn parse_run(args: &[String]) -> Result<RunOptions, String> {
+    let mut space = None;
+    let mut source = None;
+    let mut database = None;
+    let mut format = OutputFormat::Table;
+    let mut output = None;
+    let mut max_rows = None;
+    let mut values = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            if space.replace(arg.clone()).is_some() {
+                return Err(format!("Unexpected argument: {}", arg));
+            }
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} needs a value", arg))?
+            .clone();
+        let new_source = match arg.as_str() {
+            "--tab" => Some(Source::Tab(value)),
+            "--snippet" => Some(Source::Snippet(value)),
+            "--file" => Some(Source::File(PathBuf::from(value))),
+            "--sql" => Some(Source::Sql(value)),
+            "--database" => {
+                database = Some(value);
+                None
+            }
+            "--format" => {
+                format = match value.to_ascii_lowercase().as_str() {
+                    "table" => OutputFormat::Table,
+                    "csv" => OutputFormat::Csv,
+                    "json" => OutputFormat::Json,
+                    _ => return Err(format!("Unknown format: {}", value)),
+                };
+                None
+            }
+            "--output" => {
+                output = Some(PathBuf::from(value));
+                None
+            }
+            "--max-rows" => {
+                let n = value
+                    .parse::<usize>()
+                    .map_err(|_| format!("--max-rows expects a number, got {}", value))?;
+                max_rows = Some(n);
+                None
+            }
+            "--set" => {
+                let (name, value) = value
+                    .split_once('=')
+                    .ok_or_else(|| format!("--set expects name=value, got {}", value))?;
+                values.push((name.to_string(), value.to_string()));
+                None
+            }
+            _ => return Err(format!("Unknown option: {}", arg)),
+        };
+        if let Some(new_source) = new_source {
+            if source.replace(new_source).is_some() {
+                return Err("Give only one of --tab, --snippet, --file and --sql".to_string());
+            }
+        }
+    }
+
+    Ok(RunOptions {
+        space: space.ok_or("run needs a space")?,
+        source: source.ok_or("run needs one of --tab, --snippet, --file or --sql")?,
+        database,
+        format,
+        output,
+        max_rows,
+        values,
+    })
+}
+
+/// Run the CLI and return the process exit code
+pub fn run(args: &[String]) -> i32 {
+    let command = match parse_args(args) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("larik: {}\n\n{}", e, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+    if command == Command::Help {
+        println!("{}", USAGE);
+        return EXIT_OK;
+    }
+
+    let result = get_default_db_path()
+        .and_then(DatabaseManager::new)
+        .map_err(failure)
+        .and_then(|db| match command {
+            Command::Spaces => list_spaces(&db),
+            Command::Tabs { space } => list_tabs(&db, &space),
+            Command::Snippets => list_snippets(&db),
+            Command::Run(options) => tokio::runtime::Runtime::new()
+                .map_err(failure)
+                .and_then(|runtime| runtime.block_on(execute(&db, &options))),
+            Command::Help => Ok(EXIT_OK),
+        });
+    match result {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("larik: {}", e);
+            e.exit_code()
+        }
+    }
+}
+
+fn list_spaces(db: &DatabaseManager) -> Result<i32, CliError> {
+    let rows: Vec<Vec<String>> = db
+        .get_all_spaces()
+        .map_err(failure)?
+        .into_iter()
+        .map(|s| {
+            vec![
+                s.name.clone(),
+                s.server_name().unwrap_or_default(),
+                s.connection_database.clone().unwrap_or_default(),
+                s.id,
+            ]
+        })
+        .collect();
+    print!(
+        "{}",
+        table::render(&headers(&["Space", "Server", "Database", "Id"]), &rows)
+    );
+    Ok(EXIT_OK)
+}
+
+fn list_tabs(db: &DatabaseManager, space: &str) -> Result<i32, CliError> {
+    let space = find_space(db, space)?;
+    let rows: Vec<Vec<String>> = db
+        .get_tabs_by_space(&space.id)
+        .map_err(failure)?
+        .into_iter()
+        .map(|t| {
+            vec![
+                t.title,
+                t.tab_type.as_str().to_string(),
+                t.database.unwrap_or_default(),
+                t.id,
+            ]
+        })
+        .collect();
+    print!(
+        "{}",
+        table::render(&headers(&["Tab", "Type", "Database", "Id"]), &rows)
+    );
+    Ok(EXIT_OK)
+}
+
+fn list_snippets(db: &DatabaseManager) -> Result<i32, CliError> {
+    let rows: Vec<Vec<String>> = db
+        .get_all_snippets()
+        .map_err(failure)?
+        .into_iter()
+        .map(|s| {
+            vec![
+                s.trigger,
+                s.name,
+                s.category.unwrap_or_default(),
+                if s.enabled { "yes" } else { "no" }.to_string(),
+                s.id,
+            ]
+        })
+        .collect();
+    let headers = headers(&["Trigger", "Name", "Category", "Enabled", "Id"]);
+    print!("{}", table::render(&headers, &rows));
+    Ok(EXIT_OK)
+}
+
+fn headers(names: &[&str]) -> Vec<String> {
+    names.iter().map(|n| n.to_string()).collect()
+}
+
+/// The one item whose id matches, or else whose name matches case-insensitively
+fn find_one<T>(
+    items: Vec<T>,
+    kind: &str,
+    wanted: &str,
+    id: impl Fn(&T) -> &str,
+    names: impl Fn(&T) -> Vec<&str>,
+) -> Result<T, CliError> {
+    if let Some(index) = items.iter().position(|item| id(item) == wanted) {
+        return Ok(items.into_iter().nth(index).expect("index is in range"));
+    }
+    let mut matches: Vec<T> = items
+        .into_iter()
+        .filter(|item| names(item).iter().any(|n| n.eq_ignore_ascii_case(wanted)))
+        .collect();
+    match matches.len() {
+        0 => Err(CliError::Usage(format!("No {} named {}", kind, wanted))),
+        1 => Ok(matches.remove(0)),
+        n => Err(CliError::Usage(format!(
+            "{} {}s are named {}; use the id",
+            n, kind, wanted
+        ))),
+    }
+}
+
+fn find_space(db: &DatabaseManager, wanted: &str) -> Result<Space, CliError> {
+    let spaces = db.get_all_spaces().map_err(failure)?;
+    find_one(spaces, "space", wanted, |s| &s.id, |s| vec![&s.name])
+}
+
+fn find_tab(db: &DatabaseManager, space: &Space, wanted: &str) -> Result<Tab, CliError> {
+    let tabs = db.get_tabs_by_space(&space.id).map_err(failure)?;
+    find_one(tabs, "tab", wanted, |t| &t.id, |t| vec![&t.title])
+}
+
+fn find_snippet(db: &DatabaseManager, wanted: &str) -> Result<Snippet, CliError> {
+    let snippets = db.get_all_snippets().map_err(failure)?;
+    find_one(
+        snippets,
+        "snippet",
+        wanted,
+        |s| &s.id,
+        |s| vec![&s.trigger, &s.name],
+    )
+}
+
+/// Expand a snippet's placeholders from `--set` values and their defaults
+pub fn expand_snippet(snippet: &Snippet, values: &[(String, String)]) -> Result<String, String> {
+    snippet.expand(values).map_err(|missing| {
+        let sets: Vec<String> = missing.iter().map(|n| format!("--set {}=...", n)).collect();
+        format!("The snippet needs {}", sets.join(" "))
+    })
+}
+
+/// `path` for the first result set, `<stem>_<n>.<ext>` for the n-th after it
+pub fn numbered_path(path: &Path, index: usize) -> PathBuf {
+    if index == 0 {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}_{}.{}", stem, index + 1, ext.to_string_lossy()),
+        None => format!("{}_{}", stem, index + 1),
+    };
+    path.with_file_name(name)
+}
+
+async fn execute(db: &DatabaseManager, options: &RunOptions) -> Result<i32, CliError> {
+    let space = find_space(db, &options.space)?;
+    if !space.has_connection() {
+        return Err(CliError::Usage(format!(
+            "Space {} has no connection configured",
+            space.name
+        )));
+    }
+
+    let mut database = options.database.clone();
+    let sql = match &options.source {
+        Source::Tab(wanted) => {
+            let tab = find_tab(db, &space, wanted)?;
+            database = database.or(tab.database);
+            tab.content
+                .filter(|c| !c.trim().is_empty())
+                .ok_or_else(|| CliError::Usage(format!("Tab {} is empty", tab.title)))?
+        }
+        Source::Snippet(wanted) => {
+            let snippet = find_snippet(db, wanted)?;
+            expand_snippet(&snippet, &options.values).map_err(CliError::Usage)?
+        }
+        Source::File(path) => std::fs::read_to_string(path)
+            .map_err(|e| failure(format!("Cannot read {}: {}", path.display(), e)))?,
+        Source::Sql(sql) => sql.clone(),
+    };
+    let database = database
+        .or_else(|| space.connection_database.clone())
+        .unwrap_or_default();
+
+    let password = db
+        .get_space_password(&space.id)
+        .map_err(failure)?
+        .unwrap_or_default();
+    let mut config = ConnectionConfig::new(
+        space.name.clone(),
+        space.connection_host.clone().unwrap_or_default(),
+        space.connection_port.unwrap_or(1433) as u16,
+        space.connection_database.clone().unwrap_or_default(),
+        space.connection_username.clone().unwrap_or_default(),
+        password,
+    );
+    config.id = space.id.clone();
+    config.trust_certificate = space.connection_trust_cert;
+    config.encrypt = space.connection_encrypt;
+
+    let manager = Arc::new(MssqlConnectionManager::new());
+    manager.add_connection(config).await.map_err(failure)?;
+    // The engine's progress lines would mix with the results on stdout
+    set_query_logging(false);
+    let engine = QueryEngine::new(Arc::clone(&manager));
+    let results = engine
+        .execute_query(&space.id, &sql, Some(&database), false, options.max_rows)
+        .await
+        .map_err(failure)?;
+    let _ = manager.disconnect(&space.id).await;
+
+    write_results(&results, options)
+}
+
+/// Write result sets to stdout or files and report errors and row counts on
+/// stderr. Returns EXIT_SQL_ERROR when any statement failed.
+fn write_results(results: &[QueryResult], options: &RunOptions) -> Result<i32, CliError> {
+    let mut code = EXIT_OK;
+    let mut rendered = Vec::new();
+    let mut index = 0;
+    for (n, result) in results.iter().enumerate() {
+        if let Some(error) = &result.error {
+            eprintln!(
+                "Statement {}: {}",
+                result.statement_index.unwrap_or(n) + 1,
+                error
+            );
+            code = EXIT_SQL_ERROR;
+            continue;
+        }
+        if result.columns.is_empty() {
+            eprintln!("({} rows affected)", result.row_count);
+            continue;
+        }
+        if result.truncated {
+            eprintln!(
+                "(result set {} truncated to {} rows)",
+                index + 1,
+                result.rows.len()
+            );
+        }
+
+        match &options.output {
+            Some(path) => {
+                let path = numbered_path(path, index);
+                write_file(&path, options.format, &result.columns, &result.rows)?;
+                eprintln!("{} rows written to {}", result.rows.len(), path.display());
+            }
+            None => rendered.push(render(options.format, &result.columns, &result.rows)?),
+        }
+        index += 1;
+    }
+
+    if !rendered.is_empty() {
+        println!("{}", rendered.join("\n"));
+    }
+    Ok(code)
+}
+
+fn render(
+    format: OutputFormat,
+    columns: &[ColumnInfo],
+    rows: &[Vec<crate::db::CellValue>],
+) -> Result<String, CliError> {
+    let text = match format {
+        OutputFormat::Table => {
+            let headers: Vec<String> = columns.iter().map(|c| c.name.clone()).collect();
+            let cells: Vec<Vec<String>> = rows
+                .iter()
+                .map(|row| row.iter().map(table::cell_text).collect())
+                .collect();
+            let mut text = table::render(&headers, &cells);
+            text.push_str(&format!("({} rows)\n", rows.len()));
+            text
+        }
+        OutputFormat::Csv => CsvExporter::with_default_options()
+            .export_to_string(columns, rows)
+            .map_err(failure)?,
+        OutputFormat::Json => {
+            let mut text = JsonExporter::with_default_options()
+                .export_to_string(columns, rows)
+                .map_err(failure)?;
+            text.push('\n');
+            text
+        }
+    };
+    Ok(text)
+}
+
+fn write_file(
+    path: &Path,
+    format: OutputFormat,
+    columns: &[ColumnInfo],
+    rows: &[Vec<crate::db::CellValue>],
+) -> Result<(), CliError> {
+    let written = match format {
+        OutputFormat::Table => {
+            let text = render(format, columns, rows)?;
+            std::fs::write(path, text).map_err(failure)
+        }
+        OutputFormat::Csv => CsvExporter::new(ExportOptions::default())
+            .export_to_file(path, columns, rows, None)
+            .map(|_| ())
+            .map_err(failure),
+        OutputFormat::Json => JsonExporter::new(ExportOptions::default())
+            .export_to_file(path, columns, rows, None)
+            .map(|_| ())
+            .map_err(failure),
+    };
+    written.map_err(|e| failure(format!("Cannot write {}: {}", path.display(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn parses_commands_and_run_options() {
+        assert_eq!(parse_args(&[]), Ok(Command::Help));
+        assert_eq!(parse_args(&args("spaces")), Ok(Command::Spaces));
+        assert_eq!(
+            parse_args(&args("tabs Sales")),
+            Ok(Command::Tabs {
+                space: "Sales".to_string()
+            })
+        );
+
+        let run = parse_args(&args(
+            "run Sales --snippet sel --set cursor=dbo.Orders --format csv --output out.csv --max-rows 10",
+        ));
+        assert_eq!(
+            run,
+            Ok(Command::Run(RunOptions {
+                space: "Sales".to_string(),
+                source: Source::Snippet("sel".to_string()),
+                database: None,
+                format: OutputFormat::Csv,
+                output: Some(PathBuf::from("out.csv")),
+                max_rows: Some(10),
+                values: vec![("cursor".to_string(), "dbo.Orders".to_string())],
+            }))
+        );
+
+        assert!(parse_args(&args("run Sales")).is_err());
+        assert!(parse_args(&args("run Sales --tab a --file b.sql")).is_err());
+        assert!(parse_args(&args("run Sales --sql x --format xml")).is_err());
+        assert!(parse_args(&args("drop everything")).is_err());
+    }
+
+    #[test]
+    fn expands_snippet_placeholders() {
+        let snippet = |content: &str| Snippet {
+            id: "s1".to_string(),
+            trigger: "sel".to_string(),
+            name: "Select".to_string(),
+            content: content.to_string(),
+            description: None,
+            is_builtin: false,
+            enabled: true,
+            category: None,
+            created_at: String::new(),
+            updated_at: String::new(),
+        };
+        let values = vec![("cursor".to_string(), "dbo.Orders".to_string())];
+        assert_eq!(
+            expand_snippet(&snippet("SELECT TOP ${1:100} * FROM ${cursor}"), &values),
+            Ok("SELECT TOP 100 * FROM dbo.Orders".to_string())
+        );
+        assert_eq!(
+            expand_snippet(&snippet("SELECT ${cursor} FROM ${cursor}"), &[]),
+            Err("The snippet needs --set cursor=...".to_string())
+        );
+    }
+
+    #[test]
+    fn numbers_additional_output_files() {
+        let path = Path::new("out/report.csv");
+        assert_eq!(numbered_path(path, 0), PathBuf::from("out/report.csv"));
+        assert_eq!(numbered_path(path, 2), PathBuf::from("out/report_3.csv"));
+        assert_eq!(numbered_path(Path::new("data"), 1), PathBuf::from("data_2"));
+    }
+}
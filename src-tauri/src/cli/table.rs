@@ -0,0 +1,92 @@
+// Text Tables
+// Aligned plain-text rendering of result sets and listings for the terminal
+
+use crate::db::CellValue;
+
+/// Longest cell shown before truncating with `…`
+const MAX_CELL_WIDTH: usize = 60;
+
+/// A cell as shown in a text table
+pub fn cell_text(value: &CellValue) -> String {
+    match value {
+        CellValue::Null => "NULL".to_string(),
+        CellValue::Bool(b) => b.to_string(),
+        CellValue::Int(i) => i.to_string(),
+        CellValue::Float(f) => f.to_string(),
+        CellValue::String(s) | CellValue::DateTime(s) => s.clone(),
+        CellValue::Binary(bytes) => {
+            let hex: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+            format!("0x{}", hex)
+        }
+    }
+}
+
+/// Render rows under a header line, columns padded to their widest cell
+pub fn render(headers: &[String], rows: &[Vec<String>]) -> String {
+    let clean = |text: &str| -> String {
+        let text = text.replace(['\r', '\n', '\t'], " ");
+        if text.chars().count() > MAX_CELL_WIDTH {
+            let mut cut: String = text.chars().take(MAX_CELL_WIDTH - 1).collect();
+            cut.push('…');
+            cut
+        } else {
+            text
+        }
+    };
+    let headers: Vec<String> = headers.iter().map(|h| clean(h)).collect();
+    let rows: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| row.iter().map(|c| clean(c)).collect())
+        .collect();
+
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in &rows {
+        for (i, cell) in row.iter().enumerate() {
+            if let Some(width) = widths.get_mut(i) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+    }
+
+    let line = |cells: &[String]| -> String {
+        let padded: Vec<String> = widths
+            .iter()
+            .enumerate()
+            .map(|(i, width)| {
+                let cell = cells.get(i).map_or("", String::as_str);
+                format!("{}{}", cell, " ".repeat(width - cell.chars().count()))
+            })
+            .collect();
+        padded.join(" | ").trim_end().to_string()
+    };
+
+    let mut out = line(&headers);
+    out.push('\n');
+    let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+    out.push_str(&rule.join("-+-"));
+    out.push('\n');
+    for row in &rows {
+        out.push_str(&line(row));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pads_columns_and_flattens_cells() {
+        let headers = vec!["Id".to_string(), "Name".to_string()];
+        let rows = vec![
+            vec!["1".to_string(), "Ada\nLovelace".to_string()],
+            vec!["10".to_string(), "NULL".to_string()],
+        ];
+        assert_eq!(
+            render(&headers, &rows),
+            "Id | Name\n---+-------------\n1  | Ada Lovelace\n10 | NULL\n"
+        );
+        assert_eq!(cell_text(&CellValue::Binary(vec![0, 255])), "0x00FF");
+    }
+}
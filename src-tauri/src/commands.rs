@@ -5,7 +5,7 @@ use tauri::{command, State, AppHandle, Emitter};
 use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::path::PathBuf;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use tokio::sync::{mpsc, RwLock};
 
 use crate::storage::{
@@ -15,13 +15,23 @@ use crate::storage::{
     TabFolder, CreateFolderInput, UpdateFolderInput,
     Snippet, CreateSnippetInput, UpdateSnippetInput,
     ArchivedTab, ArchiveSearchResult, AutoArchiveSettings, AppSettings,
-    StickyNote, VirtualReference,
+    StickyNote, VirtualReference, VirtualReferenceColumn, VirtualReferenceSet,
+    CreateQueryHistoryInput, QueryHistoryEntry, QueryHistoryFilter, QueryHistorySearchResult,
+    ApiAuditEntry, Schedule, ScheduleInput, ScheduleRun,
 };
 
 use crate::db::{
     ConnectionConfig, ConnectionConfigUpdate, ConnectionInfo,
     MssqlConnectionManager, QueryEngine, QueryResult, QueryInfo,
     SchemaMetadataManager, SchemaInfo, SchemaColumnInfo,
+    ObjectRef, ScriptAction, SchemaComparison, SchemaSource,
+    DiagramFormat, DiagramOptions, DescriptionTarget, DictionaryFormat,
+    InferenceOptions, ReferenceCandidate, ReferenceRows, CellValue, JoinPathOptions, JoinPlan,
+    DependencyDirection, DependencyGraph, DependencyTarget,
+    DefinitionSearch, DefinitionSearchResult,
+    DataSearch, DataSearchEvent, DataSearchSummary,
+    FanOutResult, FanOutTarget, PreparedTarget,
+    DatabaseStorageSummary, FragmentationScanMode, IndexFragmentationInfo, TableStorageInfo,
     management::{export_database as export_db, import_database as import_db},
 };
 
@@ -29,6 +39,17 @@ use crate::export::{
     CsvExporter, JsonExporter, ExportOptions, ExportProgress,
 };
 
+use crate::api::{ApiServer, ApiStatus};
+use crate::scheduler;
+
+use crate::sql::{
+    complete, CompletionCatalog, Completions,
+    format::{format_edits, FormatOptions},
+    lint,
+    sqlcmd::SqlcmdOptions,
+    Diagnostic, TextEdit,
+};
+
 /// Application state managed by Tauri
 pub struct AppState {
     pub db: Mutex<DatabaseManager>,
@@ -36,6 +57,12 @@ pub struct AppState {
     pub query_engine: Arc<QueryEngine>,
     pub schema_manager: Arc<SchemaMetadataManager>,
     pub export_cancel_flags: RwLock<HashMap<String, Arc<AtomicBool>>>,
+    pub data_search_cancel_flags: RwLock<HashMap<String, Arc<AtomicBool>>>,
+    pub fan_out_cancel_flags: RwLock<HashMap<String, Arc<AtomicBool>>>,
+    /// The local automation API, while it is running
+    pub api_server: tokio::sync::Mutex<Option<ApiServer>>,
+    /// Ids of schedules with a run in progress, scheduled or manual
+    pub running_schedules: Mutex<HashSet<String>>,
 }
 
 /// Convert StorageError to a string for IPC
@@ -273,14 +300,23 @@ pub fn update_tab(
 }
 
 /// Update just the database selection for a tab
+/// Kicks off a background refresh if that database's cached schema is past its TTL
 #[command]
 pub fn update_tab_database(
+    app: AppHandle,
     state: State<'_, AppState>,
     id: String,
     database: Option<String>,
 ) -> Result<bool, String> {
     let db = state.db.lock().map_err(|e| e.to_string())?;
-    db.update_tab_database(&id, database.as_deref()).map_err(|e| e.to_string())
+    let updated = db.update_tab_database(&id, database.as_deref()).map_err(|e| e.to_string())?;
+
+    if let (true, Some(database)) = (updated, database) {
+        if let Some(tab) = db.get_tab(&id).map_err(|e| e.to_string())? {
+            spawn_stale_schema_refresh(app, state.schema_manager.clone(), tab.space_id, database);
+        }
+    }
+    Ok(updated)
 }
 
 /// Auto-save tab content (optimized for frequent saves)
@@ -725,7 +761,9 @@ pub async fn check_connection_health(
 /// Execute a SQL query with optional database context and selected text
 /// Supports batch execution - if the query contains multiple statements (separated by GO or semicolons),
 /// executes them sequentially and returns multiple results
+/// With sqlcmd_mode, directives are expanded first and `:r` paths resolve relative to file_path
 #[command]
+#[allow(clippy::too_many_arguments)]
 pub async fn execute_query(
     state: State<'_, AppState>,
     connection_id: String,
@@ -733,22 +771,120 @@ pub async fn execute_query(
     database: Option<String>,
     selected_text: Option<String>,
     max_rows: Option<usize>,
+    sqlcmd_mode: Option<bool>,
+    file_path: Option<String>,
+    sqlcmd_variables: Option<HashMap<String, String>>,
 ) -> Result<Vec<QueryResult>, String> {
     // Use selected_text if provided, otherwise use full query
     let query_to_execute = selected_text.as_ref().unwrap_or(&query);
     let is_selection = selected_text.is_some();
 
-    state
-        .query_engine
-        .execute_query(
-            &connection_id,
-            query_to_execute,
-            database.as_deref(),
+    let started_at = chrono::Utc::now();
+
+    // sqlcmd mode: `:r` includes resolve relative to the tab's file
+    let result = if sqlcmd_mode.unwrap_or(false) {
+        let options = SqlcmdOptions {
+            variables: sqlcmd_variables.unwrap_or_default(),
+            base_dir: file_path
+                .as_deref()
+                .and_then(|p| PathBuf::from(p).parent().map(|d| d.to_path_buf())),
+        };
+        state
+            .query_engine
+            .execute_sqlcmd_script(
+                &connection_id,
+                query_to_execute,
+                database.as_deref(),
+                is_selection,
+                max_rows,
+                &options,
+            )
+            .await
+    } else {
+        state
+            .query_engine
+            .execute_query(
+                &connection_id,
+                query_to_execute,
+                database.as_deref(),
+                is_selection,
+                max_rows,
+            )
+            .await
+    };
+
+    let entries = query_history_inputs(
+        &connection_id,
+        database.as_deref(),
+        query_to_execute,
+        is_selection,
+        started_at,
+        &result,
+    );
+    if let Ok(db) = state.db.lock() {
+        if let Err(e) = db.add_query_history_entries(entries) {
+            eprintln!("[History] Failed to record query history: {}", e);
+        }
+    }
+
+    result.map_err(|e| e.to_string())
+}
+
+/// One history entry per executed statement. Result sets of the same
+/// statement are merged; start times are offset by the preceding durations.
+fn query_history_inputs(
+    connection_id: &str,
+    database: Option<&str>,
+    query: &str,
+    is_selection: bool,
+    started_at: chrono::DateTime<chrono::Utc>,
+    result: &Result<Vec<QueryResult>, crate::db::ConnectionError>,
+) -> Vec<CreateQueryHistoryInput> {
+    let input = |text: &str, offset_ms: u64, duration_ms: u64, row_count: usize, error: Option<String>| {
+        CreateQueryHistoryInput {
+            connection_id: connection_id.to_string(),
+            database: database.map(str::to_string),
+            query_text: text.to_string(),
+            started_at: (started_at + chrono::Duration::milliseconds(offset_ms as i64))
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string(),
+            duration_ms: duration_ms as i64,
+            row_count: row_count as i64,
+            error,
             is_selection,
-            max_rows,
-        )
-        .await
-        .map_err(|e| e.to_string())
+        }
+    };
+
+    let results = match result {
+        Ok(results) => results,
+        Err(e) => return vec![input(query, 0, 0, 0, Some(e.to_string()))],
+    };
+
+    let mut entries: Vec<CreateQueryHistoryInput> = Vec::new();
+    let mut elapsed_ms = 0u64;
+    let mut previous: Option<(Option<usize>, Option<&str>)> = None;
+
+    for r in results {
+        let key = (r.statement_index, r.statement_text.as_deref());
+        let same_statement = previous == Some(key) && r.statement_text.is_some();
+        previous = Some(key);
+
+        if same_statement {
+            if let Some(last) = entries.last_mut() {
+                last.row_count += r.row_count as i64;
+                if last.error.is_none() {
+                    last.error = r.error.clone();
+                }
+            }
+            continue;
+        }
+
+        let text = r.statement_text.as_deref().unwrap_or(query);
+        entries.push(input(text, elapsed_ms, r.execution_time_ms, r.row_count, r.error.clone()));
+        elapsed_ms += r.execution_time_ms;
+    }
+
+    entries
 }
 
 /// Cancel a running query
@@ -784,14 +920,72 @@ pub async fn get_query_status(
     Ok(state.query_engine.get_query_status(&query_id).await)
 }
 
+/// Validate a query on the server without executing it (SET PARSEONLY, or
+/// SET NOEXEC when compile is true). Diagnostics use the query's line numbers.
+#[command]
+pub async fn validate_query(
+    state: State<'_, AppState>,
+    connection_id: String,
+    query: String,
+    database: Option<String>,
+    compile: Option<bool>,
+) -> Result<Vec<Diagnostic>, String> {
+    state
+        .query_engine
+        .validate_query(
+            &connection_id,
+            &query,
+            database.as_deref(),
+            compile.unwrap_or(false),
+        )
+        .await
+        .map_err(|e| e.to_string())
+}
+
 // ============================================================================
 // Schema Metadata Commands (T025)
 // ============================================================================
 
+/// Payload of the `schema-refreshed` event
+#[derive(Clone, serde::Serialize)]
+struct SchemaRefreshedEvent {
+    connection_id: String,
+    database: String,
+    fetched_at: String,
+}
+
+/// Refresh a cached schema in the background if it is older than the TTL,
+/// emitting `schema-refreshed` once the new version is cached
+fn spawn_stale_schema_refresh(
+    app: AppHandle,
+    schema_manager: Arc<SchemaMetadataManager>,
+    connection_id: String,
+    database: String,
+) {
+    tauri::async_runtime::spawn(async move {
+        match schema_manager.refresh_if_stale(&connection_id, &database).await {
+            Ok(Some(schema)) => {
+                let _ = app.emit(
+                    "schema-refreshed",
+                    SchemaRefreshedEvent {
+                        connection_id,
+                        database,
+                        fetched_at: schema.fetched_at,
+                    },
+                );
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("[Schema] Background refresh of {} failed: {}", database, e),
+        }
+    });
+}
+
 /// Get complete schema information for a database (tables, views, columns, routines)
-/// Uses caching to avoid repeated queries - schema is cached per connection/database
+/// Uses caching to avoid repeated queries - schema is cached per connection/database.
+/// A cache older than the TTL is returned as-is and refreshed in the background.
 #[command]
 pub async fn get_schema_info(
+    app: AppHandle,
     state: State<'_, AppState>,
     connection_id: String,
     database: String,
@@ -799,16 +993,23 @@ pub async fn get_schema_info(
     force_refresh: Option<bool>,
 ) -> Result<SchemaInfo, String> {
     // Check cache first unless force refresh requested
-    if !force_refresh.unwrap_or(false) {
+    let force_refresh = force_refresh.unwrap_or(false);
+    if !force_refresh {
         if let Some(cached) = state.schema_manager.get_cached_schema(&connection_id, &database).await {
+            if state.schema_manager.is_stale(&cached) {
+                spawn_stale_schema_refresh(app, state.schema_manager.clone(), connection_id, database);
+            }
             return Ok(cached);
         }
     }
-    
-    // Fetch fresh schema
-    state.schema_manager.fetch_schema(&connection_id, &database, schema_filter.as_deref())
-        .await
-        .map_err(|e| e.to_string())
+
+    // Unfiltered cache misses only re-read objects changed since the cached copy;
+    // a forced refresh re-reads the whole catalog to repair any drift
+    let result = match schema_filter.as_deref() {
+        None if !force_refresh => state.schema_manager.refresh_schema(&connection_id, &database).await,
+        filter => state.schema_manager.fetch_schema(&connection_id, &database, filter).await,
+    };
+    result.map_err(|e| e.to_string())
 }
 
 /// Get columns for a specific table (more efficient than fetching full schema)
@@ -837,149 +1038,210 @@ pub async fn refresh_schema(
 }
 
 // ============================================================================
-// Export Commands (T034, T035, T036)
+// Object Scripting Commands
 // ============================================================================
 
-/// Export query results to CSV file
-/// Returns export progress with file path and statistics
+/// Script an object as CREATE, CREATE OR ALTER, ALTER or DROP
 #[command]
-pub async fn export_to_csv(
-    app: AppHandle,
+pub async fn script_object(
     state: State<'_, AppState>,
-    file_path: String,
-    columns: Vec<crate::db::query::ColumnInfo>,
-    rows: Vec<Vec<crate::db::query::CellValue>>,
-    options: Option<ExportOptions>,
-) -> Result<ExportProgress, String> {
-    let export_id = uuid::Uuid::new_v4().to_string();
-    let cancel_flag = Arc::new(AtomicBool::new(false));
-    
-    // Store cancel flag
-    {
-        let mut flags = state.export_cancel_flags.write().await;
-        flags.insert(export_id.clone(), Arc::clone(&cancel_flag));
-    }
+    connection_id: String,
+    database: String,
+    schema_name: String,
+    object_name: String,
+    action: ScriptAction,
+) -> Result<String, String> {
+    let object = ObjectRef { schema_name, object_name };
+    state
+        .schema_manager
+        .script_object(&connection_id, &database, &object, action)
+        .await
+        .map_err(|e| e.to_string())
+}
 
-    let path = PathBuf::from(&file_path);
-    let options = options.unwrap_or_default();
-    let exporter = CsvExporter::new(options);
-    
-    // Create progress channel
-    let (tx, mut rx) = mpsc::channel::<ExportProgress>(10);
-    
-    // Spawn progress emitter
-    let app_handle = app.clone();
-    let export_id_clone = export_id.clone();
-    tokio::spawn(async move {
-        while let Some(progress) = rx.recv().await {
-            let _ = app_handle.emit(&format!("export-progress-{}", export_id_clone), &progress);
+/// Script DROP statements for several objects in dependency order
+#[command]
+pub async fn script_drop_objects(
+    state: State<'_, AppState>,
+    connection_id: String,
+    database: String,
+    objects: Vec<ObjectRef>,
+) -> Result<String, String> {
+    state
+        .schema_manager
+        .script_drop(&connection_id, &database, &objects)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// ============================================================================
+// Schema Compare Commands
+// ============================================================================
+
+async fn load_schema_source(state: &AppState, source: &SchemaSource) -> Result<SchemaInfo, String> {
+    match source {
+        SchemaSource::Live { connection_id, database } => state
+            .schema_manager
+            .fetch_schema(connection_id, database, None)
+            .await
+            .map_err(|e| e.to_string()),
+        SchemaSource::Snapshot { path } => {
+            let json = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read snapshot {}: {}", path, e))?;
+            serde_json::from_str(&json).map_err(|e| format!("Invalid schema snapshot {}: {}", path, e))
         }
-    });
-    
-    // Perform export
-    let result = exporter
-        .export_to_file_with_progress(&path, &columns, &rows, cancel_flag.clone(), tx)
+    }
+}
+
+/// Save a database's current schema to a JSON file for later comparison
+#[command]
+pub async fn save_schema_snapshot(
+    state: State<'_, AppState>,
+    connection_id: String,
+    database: String,
+    file_path: String,
+) -> Result<(), String> {
+    let schema = state
+        .schema_manager
+        .fetch_schema(&connection_id, &database, None)
         .await
         .map_err(|e| e.to_string())?;
-    
-    // Clean up cancel flag
-    {
-        let mut flags = state.export_cancel_flags.write().await;
-        flags.remove(&export_id);
-    }
-    
-    Ok(result)
+    let json = serde_json::to_string_pretty(&schema).map_err(|e| e.to_string())?;
+    std::fs::write(&file_path, json).map_err(|e| e.to_string())
 }
 
-/// Export query results to JSON file
-/// Returns export progress with file path and statistics
+/// Compare two schemas and generate a script that makes the target match the source
 #[command]
-pub async fn export_to_json(
+pub async fn compare_schemas(
+    state: State<'_, AppState>,
+    source: SchemaSource,
+    target: SchemaSource,
+) -> Result<SchemaComparison, String> {
+    let source = load_schema_source(&state, &source).await?;
+    let target = load_schema_source(&state, &target).await?;
+    Ok(crate::db::compare::compare_schemas(&source, &target))
+}
+
+// ============================================================================
+// Dependency Analysis Commands
+// ============================================================================
+
+/// Dependency graph for a table, column, view or routine: what it uses, what
+/// uses it, or both. `max_depth` limits transitive hops (unlimited by default)
+#[command]
+pub async fn get_object_dependencies(
+    state: State<'_, AppState>,
+    connection_id: String,
+    database: String,
+    target: DependencyTarget,
+    direction: DependencyDirection,
+    max_depth: Option<u32>,
+) -> Result<DependencyGraph, String> {
+    let virtual_references = {
+        let db = state.db.lock().map_err(|e| e.to_string())?;
+        db.get_virtual_references(&connection_id, &database)
+            .map_err(|e| e.to_string())?
+    };
+
+    state
+        .schema_manager
+        .object_dependencies(
+            &connection_id,
+            &database,
+            &virtual_references,
+            &target,
+            direction,
+            max_depth,
+        )
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// ============================================================================
+// Definition Search Commands
+// ============================================================================
+
+/// Search procedure/view/function/trigger definitions, object names and column
+/// names by text or regex, across the given databases or every accessible one
+#[command]
+pub async fn search_definitions(
+    state: State<'_, AppState>,
+    connection_id: String,
+    search: DefinitionSearch,
+) -> Result<DefinitionSearchResult, String> {
+    state
+        .schema_manager
+        .search_definitions(&connection_id, &search)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// ============================================================================
+// Data Search Commands
+// ============================================================================
+
+/// Search every table's type-compatible columns for a value. Hits and progress
+/// are emitted as `data-search-{search_id}` events while the search runs;
+/// `cancel_data_search` stops it early
+#[command]
+pub async fn search_data(
     app: AppHandle,
     state: State<'_, AppState>,
-    file_path: String,
-    columns: Vec<crate::db::query::ColumnInfo>,
-    rows: Vec<Vec<crate::db::query::CellValue>>,
-    options: Option<ExportOptions>,
-) -> Result<ExportProgress, String> {
-    let export_id = uuid::Uuid::new_v4().to_string();
+    connection_id: String,
+    database: String,
+    search_id: String,
+    search: DataSearch,
+) -> Result<DataSearchSummary, String> {
+    if search.value.is_empty() {
+        return Err("Search value is empty".to_string());
+    }
+
+    let schema = match state.schema_manager.get_cached_schema(&connection_id, &database).await {
+        Some(cached) => cached,
+        None => state
+            .schema_manager
+            .fetch_schema(&connection_id, &database, None)
+            .await
+            .map_err(|e| e.to_string())?,
+    };
+    let plans = crate::db::data_search::plan_data_search(&schema, &search);
+
     let cancel_flag = Arc::new(AtomicBool::new(false));
-    
-    // Store cancel flag
     {
-        let mut flags = state.export_cancel_flags.write().await;
-        flags.insert(export_id.clone(), Arc::clone(&cancel_flag));
+        let mut flags = state.data_search_cancel_flags.write().await;
+        flags.insert(search_id.clone(), Arc::clone(&cancel_flag));
     }
 
-    let path = PathBuf::from(&file_path);
-    let options = options.unwrap_or_else(|| ExportOptions {
-        pretty_print: true,
-        ..Default::default()
-    });
-    let exporter = JsonExporter::new(options);
-    
-    // Create progress channel
-    let (tx, mut rx) = mpsc::channel::<ExportProgress>(10);
-    
-    // Spawn progress emitter
+    let (tx, mut rx) = mpsc::channel::<DataSearchEvent>(32);
     let app_handle = app.clone();
-    let export_id_clone = export_id.clone();
+    let event_name = format!("data-search-{}", search_id);
     tokio::spawn(async move {
-        while let Some(progress) = rx.recv().await {
-            let _ = app_handle.emit(&format!("export-progress-{}", export_id_clone), &progress);
+        while let Some(event) = rx.recv().await {
+            let _ = app_handle.emit(&event_name, &event);
         }
     });
-    
-    // Perform export
-    let result = exporter
-        .export_to_file_with_progress(&path, &columns, &rows, cancel_flag.clone(), tx)
-        .await
-        .map_err(|e| e.to_string())?;
-    
-    // Clean up cancel flag
+
+    let summary = state
+        .schema_manager
+        .search_data(&connection_id, &database, plans, &search, &cancel_flag, &tx)
+        .await;
+
     {
-        let mut flags = state.export_cancel_flags.write().await;
-        flags.remove(&export_id);
+        let mut flags = state.data_search_cancel_flags.write().await;
+        flags.remove(&search_id);
     }
-    
-    Ok(result)
-}
 
-/// Export query results to string (CSV or JSON) for clipboard copy
-/// Useful for quick copy without file dialog
-#[command]
-pub async fn export_to_string(
-    format: String,
-    columns: Vec<crate::db::query::ColumnInfo>,
-    rows: Vec<Vec<crate::db::query::CellValue>>,
-    options: Option<ExportOptions>,
-) -> Result<String, String> {
-    let options = options.unwrap_or_default();
-    
-    match format.to_lowercase().as_str() {
-        "csv" => {
-            let exporter = CsvExporter::new(options);
-            exporter.export_to_string(&columns, &rows).map_err(|e| e.to_string())
-        }
-        "json" => {
-            let exporter = JsonExporter::new(ExportOptions {
-                pretty_print: true,
-                ..options
-            });
-            exporter.export_to_string(&columns, &rows).map_err(|e| e.to_string())
-        }
-        _ => Err(format!("Unsupported export format: {}", format)),
-    }
+    Ok(summary)
 }
 
-/// Cancel an ongoing export operation
+/// Cancel a running data search
 #[command]
-pub async fn cancel_export(
+pub async fn cancel_data_search(
     state: State<'_, AppState>,
-    export_id: String,
+    search_id: String,
 ) -> Result<bool, String> {
-    let flags = state.export_cancel_flags.read().await;
-    if let Some(flag) = flags.get(&export_id) {
+    let flags = state.data_search_cancel_flags.read().await;
+    if let Some(flag) = flags.get(&search_id) {
         flag.store(true, Ordering::Relaxed);
         Ok(true)
     } else {
@@ -988,12 +1250,405 @@ pub async fn cancel_export(
 }
 
 // ============================================================================
-// SQL File Import/Export Commands
+// Fan-Out Query Commands
 // ============================================================================
 
-/// Export tab content to a SQL file
+/// Run one query on several space/database targets, at most `max_parallel` at
+/// a time, and merge the result sets under `_server`/`_database` columns.
+/// `cancel_fan_out` stops every target still running or waiting
 #[command]
-pub fn export_tab_as_sql(
+pub async fn execute_fan_out(
+    state: State<'_, AppState>,
+    fan_out_id: String,
+    query: String,
+    targets: Vec<FanOutTarget>,
+    max_rows: Option<usize>,
+    max_parallel: Option<usize>,
+) -> Result<FanOutResult, String> {
+    if targets.is_empty() {
+        return Err("No targets to run on".to_string());
+    }
+
+    let prepared = {
+        let db = state.db.lock().map_err(|e| e.to_string())?;
+        let mut prepared = Vec::with_capacity(targets.len());
+        for target in targets {
+            let space = db
+                .get_space(&target.space_id)
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("Space {} not found", target.space_id))?;
+            if !space.has_connection() {
+                return Err(format!("Space '{}' has no connection configured", space.name));
+            }
+            let password = db
+                .get_space_password(&space.id)
+                .map_err(|e| e.to_string())?
+                .unwrap_or_default();
+            let database = target
+                .database
+                .or_else(|| space.connection_database.clone())
+                .unwrap_or_default();
+
+            let mut config = ConnectionConfig::new(
+                space.name.clone(),
+                space.connection_host.clone().unwrap_or_default(),
+                space.connection_port.unwrap_or(1433) as u16,
+                database.clone(),
+                space.connection_username.clone().unwrap_or_default(),
+                password,
+            );
+            config.trust_certificate = space.connection_trust_cert;
+            config.encrypt = space.connection_encrypt;
+            prepared.push(PreparedTarget {
+                server: space.server_name().unwrap_or_default(),
+                space_id: space.id,
+                database,
+                config,
+            });
+        }
+        prepared
+    };
+
+    let cancel_flag = Arc::new(AtomicBool::new(false));
+    {
+        let mut flags = state.fan_out_cancel_flags.write().await;
+        flags.insert(fan_out_id.clone(), Arc::clone(&cancel_flag));
+    }
+
+    let result = crate::db::fanout::run_fan_out(
+        &state.mssql_manager,
+        &state.query_engine,
+        &fan_out_id,
+        &query,
+        prepared,
+        max_rows,
+        max_parallel.unwrap_or(crate::db::fanout::DEFAULT_PARALLELISM),
+        &cancel_flag,
+    )
+    .await;
+
+    {
+        let mut flags = state.fan_out_cancel_flags.write().await;
+        flags.remove(&fan_out_id);
+    }
+
+    Ok(result)
+}
+
+/// Cancel a running fan-out query on all of its targets
+#[command]
+pub async fn cancel_fan_out(
+    state: State<'_, AppState>,
+    fan_out_id: String,
+) -> Result<bool, String> {
+    let flags = state.fan_out_cancel_flags.read().await;
+    if let Some(flag) = flags.get(&fan_out_id) {
+        flag.store(true, Ordering::Relaxed);
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+// ============================================================================
+// Storage Statistics Commands
+// ============================================================================
+
+/// Row counts and reserved/used/data/index space per table, largest first
+#[command]
+pub async fn get_table_storage_stats(
+    state: State<'_, AppState>,
+    connection_id: String,
+    database: String,
+    schema_name: Option<String>,
+) -> Result<Vec<TableStorageInfo>, String> {
+    state
+        .schema_manager
+        .table_storage_stats(&connection_id, &database, schema_name.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Index fragmentation and page counts, for one table or the whole database.
+/// `mode` defaults to LIMITED; SAMPLED also reports page density
+#[command]
+pub async fn get_index_fragmentation(
+    state: State<'_, AppState>,
+    connection_id: String,
+    database: String,
+    table: Option<ObjectRef>,
+    mode: Option<FragmentationScanMode>,
+) -> Result<Vec<IndexFragmentationInfo>, String> {
+    state
+        .schema_manager
+        .index_fragmentation(&connection_id, &database, table.as_ref(), mode.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Database size, log usage, recovery model and compatibility level
+#[command]
+pub async fn get_database_storage_summary(
+    state: State<'_, AppState>,
+    connection_id: String,
+    database: String,
+) -> Result<DatabaseStorageSummary, String> {
+    state
+        .schema_manager
+        .database_storage_summary(&connection_id, &database)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// ============================================================================
+// ER Diagram Commands
+// ============================================================================
+
+/// Generate an ER diagram (Mermaid, DOT, PlantUML or SVG) from the schema and
+/// the database's virtual references
+#[command]
+pub async fn generate_er_diagram(
+    state: State<'_, AppState>,
+    connection_id: String,
+    database: String,
+    format: DiagramFormat,
+    options: Option<DiagramOptions>,
+) -> Result<String, String> {
+    let virtual_references = {
+        let db = state.db.lock().map_err(|e| e.to_string())?;
+        db.get_virtual_references(&connection_id, &database)
+            .map_err(|e| e.to_string())?
+    };
+
+    let schema = match state.schema_manager.get_cached_schema(&connection_id, &database).await {
+        Some(cached) => cached,
+        None => state
+            .schema_manager
+            .fetch_schema(&connection_id, &database, None)
+            .await
+            .map_err(|e| e.to_string())?,
+    };
+
+    Ok(crate::db::diagram::generate_er_diagram(
+        &schema,
+        &virtual_references,
+        &options.unwrap_or_default(),
+        format,
+    ))
+}
+
+// ============================================================================
+// Data Dictionary Commands
+// ============================================================================
+
+/// Set (or, when `description` is empty, drop) the MS_Description of a table,
+/// view, routine or column. With `execute` false only the script is returned.
+#[command]
+pub async fn set_object_description(
+    state: State<'_, AppState>,
+    connection_id: String,
+    database: String,
+    target: DescriptionTarget,
+    description: Option<String>,
+    execute: bool,
+) -> Result<String, String> {
+    if !execute {
+        return Ok(crate::db::dictionary::description_script(
+            &target,
+            description.as_deref(),
+        ));
+    }
+    state
+        .schema_manager
+        .set_description(&connection_id, &database, &target, description.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Generate a Markdown or single-file HTML data dictionary covering tables,
+/// columns, keys, relationships, routines and their descriptions
+#[command]
+pub async fn generate_data_dictionary(
+    state: State<'_, AppState>,
+    connection_id: String,
+    database: String,
+    format: DictionaryFormat,
+    schema_name: Option<String>,
+) -> Result<String, String> {
+    let schema = match state.schema_manager.get_cached_schema(&connection_id, &database).await {
+        Some(cached) => cached,
+        None => state
+            .schema_manager
+            .fetch_schema(&connection_id, &database, None)
+            .await
+            .map_err(|e| e.to_string())?,
+    };
+
+    Ok(crate::db::dictionary::generate_data_dictionary(
+        &schema,
+        format,
+        schema_name.as_deref(),
+    ))
+}
+
+// ============================================================================
+// Export Commands (T034, T035, T036)
+// ============================================================================
+
+/// Export query results to CSV file
+/// Returns export progress with file path and statistics
+#[command]
+pub async fn export_to_csv(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    file_path: String,
+    columns: Vec<crate::db::query::ColumnInfo>,
+    rows: Vec<Vec<crate::db::query::CellValue>>,
+    options: Option<ExportOptions>,
+) -> Result<ExportProgress, String> {
+    let export_id = uuid::Uuid::new_v4().to_string();
+    let cancel_flag = Arc::new(AtomicBool::new(false));
+    
+    // Store cancel flag
+    {
+        let mut flags = state.export_cancel_flags.write().await;
+        flags.insert(export_id.clone(), Arc::clone(&cancel_flag));
+    }
+
+    let path = PathBuf::from(&file_path);
+    let options = options.unwrap_or_default();
+    let exporter = CsvExporter::new(options);
+    
+    // Create progress channel
+    let (tx, mut rx) = mpsc::channel::<ExportProgress>(10);
+    
+    // Spawn progress emitter
+    let app_handle = app.clone();
+    let export_id_clone = export_id.clone();
+    tokio::spawn(async move {
+        while let Some(progress) = rx.recv().await {
+            let _ = app_handle.emit(&format!("export-progress-{}", export_id_clone), &progress);
+        }
+    });
+    
+    // Perform export
+    let result = exporter
+        .export_to_file_with_progress(&path, &columns, &rows, cancel_flag.clone(), tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    
+    // Clean up cancel flag
+    {
+        let mut flags = state.export_cancel_flags.write().await;
+        flags.remove(&export_id);
+    }
+    
+    Ok(result)
+}
+
+/// Export query results to JSON file
+/// Returns export progress with file path and statistics
+#[command]
+pub async fn export_to_json(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    file_path: String,
+    columns: Vec<crate::db::query::ColumnInfo>,
+    rows: Vec<Vec<crate::db::query::CellValue>>,
+    options: Option<ExportOptions>,
+) -> Result<ExportProgress, String> {
+    let export_id = uuid::Uuid::new_v4().to_string();
+    let cancel_flag = Arc::new(AtomicBool::new(false));
+    
+    // Store cancel flag
+    {
+        let mut flags = state.export_cancel_flags.write().await;
+        flags.insert(export_id.clone(), Arc::clone(&cancel_flag));
+    }
+
+    let path = PathBuf::from(&file_path);
+    let options = options.unwrap_or_else(|| ExportOptions {
+        pretty_print: true,
+        ..Default::default()
+    });
+    let exporter = JsonExporter::new(options);
+    
+    // Create progress channel
+    let (tx, mut rx) = mpsc::channel::<ExportProgress>(10);
+    
+    // Spawn progress emitter
+    let app_handle = app.clone();
+    let export_id_clone = export_id.clone();
+    tokio::spawn(async move {
+        while let Some(progress) = rx.recv().await {
+            let _ = app_handle.emit(&format!("export-progress-{}", export_id_clone), &progress);
+        }
+    });
+    
+    // Perform export
+    let result = exporter
+        .export_to_file_with_progress(&path, &columns, &rows, cancel_flag.clone(), tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    
+    // Clean up cancel flag
+    {
+        let mut flags = state.export_cancel_flags.write().await;
+        flags.remove(&export_id);
+    }
+    
+    Ok(result)
+}
+
+/// Export query results to string (CSV or JSON) for clipboard copy
+/// Useful for quick copy without file dialog
+#[command]
+pub async fn export_to_string(
+    format: String,
+    columns: Vec<crate::db::query::ColumnInfo>,
+    rows: Vec<Vec<crate::db::query::CellValue>>,
+    options: Option<ExportOptions>,
+) -> Result<String, String> {
+    let options = options.unwrap_or_default();
+    
+    match format.to_lowercase().as_str() {
+        "csv" => {
+            let exporter = CsvExporter::new(options);
+            exporter.export_to_string(&columns, &rows).map_err(|e| e.to_string())
+        }
+        "json" => {
+            let exporter = JsonExporter::new(ExportOptions {
+                pretty_print: true,
+                ..options
+            });
+            exporter.export_to_string(&columns, &rows).map_err(|e| e.to_string())
+        }
+        _ => Err(format!("Unsupported export format: {}", format)),
+    }
+}
+
+/// Cancel an ongoing export operation
+#[command]
+pub async fn cancel_export(
+    state: State<'_, AppState>,
+    export_id: String,
+) -> Result<bool, String> {
+    let flags = state.export_cancel_flags.read().await;
+    if let Some(flag) = flags.get(&export_id) {
+        flag.store(true, Ordering::Relaxed);
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+// ============================================================================
+// SQL File Import/Export Commands
+// ============================================================================
+
+/// Export tab content to a SQL file
+#[command]
+pub fn export_tab_as_sql(
     state: State<'_, AppState>,
     tab_id: String,
     file_path: String,
@@ -1298,6 +1953,8 @@ pub fn purge_archived_tabs_now(
 ) -> Result<usize, String> {
     let db = state.db.lock().map_err(|e| e.to_string())?;
     let retention_days = db.get_history_retention_days().map_err(|e| e.to_string())?;
+    db.cleanup_old_query_history(retention_days)
+        .map_err(|e| e.to_string())?;
     db.cleanup_old_archived_tabs(retention_days)
         .map_err(|e| e.to_string())
 }
@@ -1311,6 +1968,98 @@ pub fn purge_all_archived_tabs(
     db.purge_all_archived_tabs().map_err(|e| e.to_string())
 }
 
+// ============================================================================
+// Query History Commands
+// ============================================================================
+
+/// List query history (newest first), or search it when query is not empty
+#[command]
+pub fn search_query_history(
+    state: State<'_, AppState>,
+    query: Option<String>,
+    filter: Option<QueryHistoryFilter>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<Vec<QueryHistorySearchResult>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.search_query_history(
+        query.as_deref().unwrap_or(""),
+        &filter.unwrap_or_default(),
+        limit,
+        offset,
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Get a single query history entry
+#[command]
+pub fn get_query_history_entry(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<Option<QueryHistoryEntry>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.get_query_history_entry(&id).map_err(|e| e.to_string())
+}
+
+/// Open a history entry's SQL in a new tab, in its own space unless one is given
+#[command]
+pub fn reopen_query_history_entry(
+    state: State<'_, AppState>,
+    id: String,
+    target_space_id: Option<String>,
+) -> Result<Tab, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let entry = db
+        .get_query_history_entry(&id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "History entry not found".to_string())?;
+
+    let space_id = target_space_id
+        .or_else(|| entry.space_id.clone())
+        .ok_or_else(|| "History entry has no associated space".to_string())?;
+
+    let first_line = entry
+        .query_text
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .unwrap_or("Query");
+    let title = if first_line.chars().count() > 40 {
+        format!("{}...", first_line.chars().take(40).collect::<String>())
+    } else {
+        first_line.to_string()
+    };
+
+    db.create_tab(CreateTabInput {
+        space_id,
+        title,
+        tab_type: TabType::Query,
+        content: Some(entry.query_text),
+        metadata: None,
+        database: entry.database,
+    })
+    .map_err(|e| e.to_string())
+}
+
+/// Permanently delete a query history entry
+#[command]
+pub fn delete_query_history_entry(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<bool, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.delete_query_history_entry(&id).map_err(|e| e.to_string())
+}
+
+/// Permanently delete ALL query history
+#[command]
+pub fn purge_query_history(
+    state: State<'_, AppState>,
+) -> Result<usize, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.purge_query_history().map_err(|e| e.to_string())
+}
+
 // ============================================================================
 // App Settings Commands
 // ============================================================================
@@ -1326,6 +2075,7 @@ pub fn get_app_settings(
 
 /// Update app settings
 #[command]
+#[allow(clippy::too_many_arguments)]
 pub fn update_app_settings(
     state: State<'_, AppState>,
     validation_enabled: bool,
@@ -1334,8 +2084,13 @@ pub fn update_app_settings(
     enable_sticky_notes: bool,
     max_result_rows: i32,
     reference_preview_row_limit: i32,
+    schema_cache_ttl_minutes: Option<i32>,
 ) -> Result<(), String> {
     let db = state.db.lock().map_err(|e| e.to_string())?;
+    let schema_cache_ttl_minutes = match schema_cache_ttl_minutes {
+        Some(minutes) => minutes,
+        None => db.get_schema_cache_ttl_minutes().map_err(|e| e.to_string())?,
+    };
     let settings = AppSettings {
         validation_enabled,
         last_space_id,
@@ -1343,6 +2098,7 @@ pub fn update_app_settings(
         enable_sticky_notes,
         max_result_rows,
         reference_preview_row_limit,
+        schema_cache_ttl_minutes,
     };
     db.update_app_settings(&settings).map_err(|e| e.to_string())
 }
@@ -1443,7 +2199,9 @@ pub fn get_virtual_references(
         .map_err(|e| e.to_string())
 }
 
-/// Create or replace the user-defined reference for a source column
+/// Create or replace the user-defined reference for a source column.
+/// `columns` lists every pair of a composite key in order; when given, its
+/// first pair takes the place of `source_column`/`target_column`.
 #[command]
 #[allow(clippy::too_many_arguments)]
 pub fn save_virtual_reference(
@@ -1456,9 +2214,15 @@ pub fn save_virtual_reference(
     target_schema: String,
     target_table: String,
     target_column: String,
+    columns: Option<Vec<VirtualReferenceColumn>>,
 ) -> Result<VirtualReference, String> {
+    let columns = columns.unwrap_or_default();
+    let (source_column, target_column) = match columns.first() {
+        Some(first) => (first.source_column.clone(), first.target_column.clone()),
+        None => (source_column, target_column),
+    };
     let now = chrono::Utc::now().to_rfc3339();
-    let reference = VirtualReference {
+    let mut reference = VirtualReference {
         id: uuid::Uuid::new_v4().to_string(),
         connection_id,
         database_name: database,
@@ -1468,9 +2232,11 @@ pub fn save_virtual_reference(
         target_schema,
         target_table,
         target_column,
+        columns,
         created_at: now.clone(),
         updated_at: now,
     };
+    reference.columns = reference.column_pairs();
 
     let db = state.db.lock().map_err(|e| e.to_string())?;
     db.save_virtual_reference(&reference)
@@ -1487,3 +2253,469 @@ pub fn delete_virtual_reference(
     let db = state.db.lock().map_err(|e| e.to_string())?;
     db.delete_virtual_reference(&id).map_err(|e| e.to_string())
 }
+
+/// Suggest virtual references from column names that match another table's
+/// primary key, optionally confirmed by a sampled data overlap check
+#[command]
+pub async fn infer_virtual_references(
+    state: State<'_, AppState>,
+    connection_id: String,
+    database: String,
+    options: Option<InferenceOptions>,
+) -> Result<Vec<ReferenceCandidate>, String> {
+    let options = options.unwrap_or_default();
+    let existing = {
+        let db = state.db.lock().map_err(|e| e.to_string())?;
+        db.get_virtual_references(&connection_id, &database)
+            .map_err(|e| e.to_string())?
+    };
+
+    let schema = match state.schema_manager.get_cached_schema(&connection_id, &database).await {
+        Some(cached) => cached,
+        None => state
+            .schema_manager
+            .fetch_schema(&connection_id, &database, None)
+            .await
+            .map_err(|e| e.to_string())?,
+    };
+
+    let mut candidates =
+        crate::db::inference::infer_reference_candidates(&schema, &existing, &options);
+    if options.verify_data {
+        state
+            .schema_manager
+            .verify_reference_candidates(&connection_id, &database, &mut candidates, &options)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(candidates)
+}
+
+/// Save accepted suggestions as virtual references in one go
+#[command]
+pub fn accept_reference_candidates(
+    state: State<'_, AppState>,
+    connection_id: String,
+    database: String,
+    candidates: Vec<ReferenceCandidate>,
+) -> Result<Vec<VirtualReference>, String> {
+    let references: Vec<VirtualReference> = candidates
+        .iter()
+        .map(|c| c.to_virtual_reference(&connection_id, &database))
+        .collect();
+
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.save_virtual_references(&references)
+        .map_err(|e| e.to_string())?;
+    Ok(references)
+}
+
+/// Write the virtual references of a database to a JSON file keyed by server
+/// and database name, so they can be shared and imported into another space
+#[command]
+pub fn export_virtual_references(
+    state: State<'_, AppState>,
+    connection_id: String,
+    database: String,
+    file_path: String,
+) -> Result<usize, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let references = db
+        .get_virtual_references(&connection_id, &database)
+        .map_err(|e| e.to_string())?;
+    let server = db
+        .get_space(&connection_id)
+        .map_err(|e| e.to_string())?
+        .and_then(|space| space.server_name());
+
+    let set = VirtualReferenceSet::new(server, &database, &references);
+    let json = serde_json::to_string_pretty(&set).map_err(|e| e.to_string())?;
+    std::fs::write(&file_path, json).map_err(|e| e.to_string())?;
+    Ok(references.len())
+}
+
+/// Import a JSON reference set into a space. The set applies to its own
+/// database unless `database` is given; a set exported from a different
+/// server is refused unless `force` is set.
+#[command]
+pub fn import_virtual_references(
+    state: State<'_, AppState>,
+    connection_id: String,
+    database: Option<String>,
+    file_path: String,
+    force: Option<bool>,
+) -> Result<Vec<VirtualReference>, String> {
+    let json = std::fs::read_to_string(&file_path)
+        .map_err(|e| format!("Failed to read {}: {}", file_path, e))?;
+    let set: VirtualReferenceSet = serde_json::from_str(&json)
+        .map_err(|e| format!("Invalid virtual reference file {}: {}", file_path, e))?;
+    if set.version > VirtualReferenceSet::VERSION {
+        return Err(format!(
+            "Virtual reference file version {} is newer than supported version {}",
+            set.version,
+            VirtualReferenceSet::VERSION
+        ));
+    }
+
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let server = db
+        .get_space(&connection_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Space not found: {}", connection_id))?
+        .server_name();
+    if let (Some(expected), Some(actual)) = (&set.server, &server) {
+        if !expected.eq_ignore_ascii_case(actual) && !force.unwrap_or(false) {
+            return Err(format!(
+                "References were exported from server '{}' but this space connects to '{}'",
+                expected, actual
+            ));
+        }
+    }
+
+    let database = database.unwrap_or_else(|| set.database.clone());
+    let references = set.to_references(&connection_id, &database);
+    db.save_virtual_references(&references)
+        .map_err(|e| e.to_string())?;
+    Ok(references)
+}
+
+// ============================================================================
+// Reference Navigation Commands
+// ============================================================================
+
+/// Load the schema and virtual references used to resolve a reference
+async fn load_reference_context(
+    state: &State<'_, AppState>,
+    connection_id: &str,
+    database: &str,
+) -> Result<(SchemaInfo, Vec<VirtualReference>, usize), String> {
+    let (virtual_references, row_limit) = {
+        let db = state.db.lock().map_err(|e| e.to_string())?;
+        (
+            db.get_virtual_references(connection_id, database)
+                .map_err(|e| e.to_string())?,
+            db.get_reference_preview_row_limit()
+                .map_err(|e| e.to_string())?,
+        )
+    };
+
+    let schema = match state.schema_manager.get_cached_schema(connection_id, database).await {
+        Some(cached) => cached,
+        None => state
+            .schema_manager
+            .fetch_schema(connection_id, database, None)
+            .await
+            .map_err(|e| e.to_string())?,
+    };
+    Ok((schema, virtual_references, row_limit.max(1) as usize))
+}
+
+/// Resolve the declared or virtual reference behind a cell and fetch the
+/// parent row(s) it points at. `row` holds the current row's values by column
+/// name. Returns None when the column has no reference.
+#[command]
+pub async fn get_reference_parent_rows(
+    state: State<'_, AppState>,
+    connection_id: String,
+    database: String,
+    schema_name: String,
+    table_name: String,
+    column_name: String,
+    row: HashMap<String, CellValue>,
+) -> Result<Option<ReferenceRows>, String> {
+    let (schema, virtual_references, row_limit) =
+        load_reference_context(&state, &connection_id, &database).await?;
+    let Some(reference) = crate::db::navigation::resolve_reference(
+        &schema,
+        &virtual_references,
+        &schema_name,
+        &table_name,
+        &column_name,
+    ) else {
+        return Ok(None);
+    };
+
+    state
+        .schema_manager
+        .fetch_parent_rows(&connection_id, &database, reference, &row, row_limit)
+        .await
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
+
+/// List every declared or virtual relationship referencing the current row,
+/// with the number of child rows for each and, when `include_rows` is set,
+/// the child rows themselves
+#[command]
+pub async fn get_reference_child_rows(
+    state: State<'_, AppState>,
+    connection_id: String,
+    database: String,
+    schema_name: String,
+    table_name: String,
+    row: HashMap<String, CellValue>,
+    include_rows: Option<bool>,
+) -> Result<Vec<ReferenceRows>, String> {
+    let (schema, virtual_references, row_limit) =
+        load_reference_context(&state, &connection_id, &database).await?;
+    let references = crate::db::navigation::referencing_references(
+        &schema,
+        &virtual_references,
+        &schema_name,
+        &table_name,
+    );
+
+    state
+        .schema_manager
+        .fetch_child_rows(
+            &connection_id,
+            &database,
+            references,
+            &row,
+            include_rows.unwrap_or(false),
+            row_limit,
+        )
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Find the shortest join paths connecting `tables` over declared and
+/// virtual references and render each as a FROM ... JOIN ... ON ... clause
+#[command]
+pub async fn generate_join_clause(
+    state: State<'_, AppState>,
+    connection_id: String,
+    database: String,
+    tables: Vec<ObjectRef>,
+    options: Option<JoinPathOptions>,
+) -> Result<Vec<JoinPlan>, String> {
+    let (schema, virtual_references, _) =
+        load_reference_context(&state, &connection_id, &database).await?;
+    crate::db::joins::find_join_paths(
+        &schema,
+        &virtual_references,
+        &tables,
+        &options.unwrap_or_default(),
+    )
+}
+
+// ============================================================================
+// SQL Formatting and Linting Commands
+// ============================================================================
+
+/// Format SQL and return the edits that apply the result (empty if unchanged)
+#[command]
+pub fn format_sql(
+    sql: String,
+    options: Option<FormatOptions>,
+) -> Result<Vec<TextEdit>, String> {
+    Ok(format_edits(&sql, &options.unwrap_or_default()))
+}
+
+/// Lint SQL. With a connection and database, the cached schema enables
+/// type-aware checks such as implicit conversions on joins.
+#[command]
+pub async fn lint_sql(
+    state: State<'_, AppState>,
+    sql: String,
+    connection_id: Option<String>,
+    database: Option<String>,
+) -> Result<Vec<Diagnostic>, String> {
+    let schema = match (connection_id, database) {
+        (Some(connection_id), Some(database)) => {
+            state.schema_manager.get_cached_schema(&connection_id, &database).await
+        }
+        _ => None,
+    };
+    Ok(lint::lint_sql(&sql, schema.as_ref()))
+}
+
+/// Context-aware completions for the cursor at byte `offset` of a tab's text.
+/// Uses the cached schema of `database` and of any other database already
+/// cached, so three-part names resolve without extra round trips.
+#[command]
+pub async fn get_completions(
+    state: State<'_, AppState>,
+    connection_id: String,
+    database: String,
+    sql: String,
+    offset: usize,
+) -> Result<Completions, String> {
+    let (current, virtual_references, _) =
+        load_reference_context(&state, &connection_id, &database).await?;
+    let databases = state
+        .mssql_manager
+        .get_databases(&connection_id)
+        .await
+        .unwrap_or_default();
+
+    let mut schemas = vec![current];
+    for name in databases.iter().filter(|d| !d.eq_ignore_ascii_case(&database)) {
+        if let Some(cached) = state.schema_manager.get_cached_schema(&connection_id, name).await {
+            schemas.push(cached);
+        }
+    }
+
+    let catalog = CompletionCatalog {
+        database: &database,
+        schemas: &schemas,
+        databases: &databases,
+        virtual_references: &virtual_references,
+    };
+    Ok(complete(&sql, offset, &catalog))
+}
+
+// ============================================================================
+// Local Automation API Commands
+// ============================================================================
+
+async fn api_status(state: &AppState) -> Result<ApiStatus, String> {
+    let enabled = {
+        let db = state.db.lock().map_err(|e| e.to_string())?;
+        db.get_api_enabled().map_err(|e| e.to_string())?
+    };
+    let server = state.api_server.lock().await;
+    Ok(ApiStatus {
+        enabled,
+        running: server.is_some(),
+        port: server.as_ref().map(|s| s.port()),
+        info_path: server.as_ref().map(|s| s.info_path().display().to_string()),
+    })
+}
+
+/// Whether the API is enabled and where it listens
+#[command]
+pub async fn get_api_status(state: State<'_, AppState>) -> Result<ApiStatus, String> {
+    api_status(&state).await
+}
+
+/// Turn the API on or off; the choice persists across restarts
+#[command]
+pub async fn set_api_enabled(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+) -> Result<ApiStatus, String> {
+    {
+        let db = state.db.lock().map_err(|e| e.to_string())?;
+        db.set_api_enabled(enabled).map_err(|e| e.to_string())?;
+    }
+
+    let mut server = state.api_server.lock().await;
+    if enabled && server.is_none() {
+        *server = Some(ApiServer::start(app).await.map_err(|e| e.to_string())?);
+    } else if !enabled {
+        if let Some(running) = server.take() {
+            running.stop();
+        }
+    }
+    drop(server);
+    api_status(&state).await
+}
+
+/// Recent calls made through the API, newest first
+#[command]
+pub fn get_api_audit_log(
+    state: State<'_, AppState>,
+    limit: Option<usize>,
+) -> Result<Vec<ApiAuditEntry>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.get_api_audit_log(limit.unwrap_or(200)).map_err(|e| e.to_string())
+}
+
+/// Delete the API audit trail
+#[command]
+pub fn clear_api_audit_log(state: State<'_, AppState>) -> Result<usize, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.clear_api_audit_log().map_err(|e| e.to_string())
+}
+
+// ============================================================================
+// Scheduled Query Commands
+// ============================================================================
+
+/// List schedules, optionally only those of one space
+#[command]
+pub fn get_schedules(
+    state: State<'_, AppState>,
+    space_id: Option<String>,
+) -> Result<Vec<Schedule>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.get_schedules(space_id.as_deref()).map_err(|e| e.to_string())
+}
+
+/// Create a schedule; its first run is computed from now
+#[command]
+pub fn create_schedule(state: State<'_, AppState>, input: ScheduleInput) -> Result<Schedule, String> {
+    scheduler::validate(&input)?;
+    let next_run_at = scheduler::next_run_text(&input, chrono::Utc::now())?;
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    scheduler::check_source(&db, &input)?;
+    db.create_schedule(&input, next_run_at.as_deref()).map_err(|e| e.to_string())
+}
+
+/// Replace a schedule's settings; its next run is recomputed from now
+#[command]
+pub fn update_schedule(
+    state: State<'_, AppState>,
+    id: String,
+    input: ScheduleInput,
+) -> Result<Option<Schedule>, String> {
+    scheduler::validate(&input)?;
+    let next_run_at = scheduler::next_run_text(&input, chrono::Utc::now())?;
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    scheduler::check_source(&db, &input)?;
+    db.update_schedule(&id, &input, next_run_at.as_deref()).map_err(|e| e.to_string())
+}
+
+/// Delete a schedule and its run history
+#[command]
+pub fn delete_schedule(state: State<'_, AppState>, id: String) -> Result<bool, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.delete_schedule(&id).map_err(|e| e.to_string())
+}
+
+/// Run a schedule immediately without moving its next regular run
+#[command]
+pub async fn run_schedule_now(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<ScheduleRun, String> {
+    let schedule = {
+        let db = state.db.lock().map_err(|e| e.to_string())?;
+        db.get_schedule(&id).map_err(|e| e.to_string())?
+    }
+    .ok_or("Schedule not found")?;
+
+    let claim = scheduler::start_run(&app, &id)?
+        .ok_or_else(|| format!("{} is already running", schedule.name))?;
+    let run = scheduler::run_schedule(&app, &schedule).await;
+    drop(claim);
+    // next_run_at belongs to the scheduler, which may have advanced it meanwhile
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.set_schedule_last_run(&id, &run.started_at)
+        .map_err(|e| e.to_string())?;
+    Ok(run)
+}
+
+/// Run history of a schedule, newest first
+#[command]
+pub fn get_schedule_runs(
+    state: State<'_, AppState>,
+    schedule_id: String,
+    limit: Option<usize>,
+) -> Result<Vec<ScheduleRun>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.get_schedule_runs(&schedule_id, limit.unwrap_or(100)).map_err(|e| e.to_string())
+}
+
+/// Recent runs that raised an alert, across all schedules
+#[command]
+pub fn get_schedule_alerts(
+    state: State<'_, AppState>,
+    limit: Option<usize>,
+) -> Result<Vec<ScheduleRun>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    db.get_schedule_alerts(limit.unwrap_or(50)).map_err(|e| e.to_string())
+}
@@ -0,0 +1,1346 @@
+// Schema Compare
+// Diffs two SchemaInfo snapshots and generates a script that migrates the target to the source
+
+use crate::db::schema::{ColumnInfo, IndexInfo, RelationshipInfo, SchemaInfo, TableInfo};
+use crate::db::scripting::{
+    foreign_key_statement, index_statement, key_constraint_clause, module_header, qualified_name,
+    ForeignKeyDefinition, ScriptAction,
+};
+use crate::sql::quote_identifier;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Where one side of a comparison comes from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SchemaSource {
+    /// Fetched fresh from a connected database
+    Live {
+        connection_id: String,
+        database: String,
+    },
+    /// A SchemaInfo previously saved as JSON
+    Snapshot { path: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffObjectType {
+    Table,
+    View,
+    Routine,
+    Column,
+    Key,
+    Index,
+    ForeignKey,
+}
+
+/// Added/removed are from the target's point of view: Added exists only in
+/// the source and will be created in the target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaDifference {
+    pub object_type: DiffObjectType,
+    pub change: ChangeKind,
+    pub schema_name: String,
+    /// Table, view or routine name
+    pub object_name: String,
+    /// Column, key, index or foreign key name within the table
+    pub child_name: Option<String>,
+    pub details: Vec<String>,
+    pub data_loss: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaComparison {
+    pub source_database: String,
+    pub target_database: String,
+    pub differences: Vec<SchemaDifference>,
+    /// Ordered migration script to run against the target
+    pub script: String,
+    pub has_data_loss: bool,
+}
+
+/// Script phases, in execution order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Phase {
+    DropForeignKeys,
+    DropModules,
+    DropIndexes,
+    DropTables,
+    CreateTables,
+    AlterColumns,
+    CreateIndexes,
+    CreateForeignKeys,
+    CreateViews,
+    CreateRoutines,
+}
+
+struct Step {
+    phase: Phase,
+    sql: String,
+    data_loss: bool,
+}
+
+type Key = (String, String);
+
+fn key(schema_name: &str, name: &str) -> Key {
+    (schema_name.to_lowercase(), name.to_lowercase())
+}
+
+/// Column type as it would be declared
+pub(crate) fn column_type(column: &ColumnInfo) -> String {
+    let data_type = column.data_type.to_lowercase();
+    match data_type.as_str() {
+        "varchar" | "char" | "nvarchar" | "nchar" | "varbinary" | "binary" => {
+            match column.max_length {
+                Some(-1) => format!("{}(max)", data_type),
+                Some(length) => format!("{}({})", data_type, length),
+                None => data_type,
+            }
+        }
+        "decimal" | "numeric" => format!(
+            "{}({}, {})",
+            data_type,
+            column.precision.unwrap_or(18),
+            column.scale.unwrap_or(0)
+        ),
+        "datetime2" | "time" | "datetimeoffset" => match column.scale {
+            Some(scale) => format!("{}({})", data_type, scale),
+            None => data_type,
+        },
+        _ => data_type,
+    }
+}
+
+fn column_declaration(column: &ColumnInfo) -> String {
+    let mut sql = format!("{} {}", quote_identifier(&column.name), column_type(column));
+    if column.is_identity {
+        sql.push_str(&format!(
+            " IDENTITY({}, {})",
+            column.identity_seed.as_deref().unwrap_or("1"),
+            column.identity_increment.as_deref().unwrap_or("1")
+        ));
+    }
+    sql.push_str(if column.is_nullable {
+        " NULL"
+    } else {
+        " NOT NULL"
+    });
+    if let Some(default) = &column.column_default {
+        sql.push_str(&format!(" DEFAULT {}", default));
+    }
+    sql
+}
+
+/// Whether changing `old` to `new` keeps every existing value
+fn is_widening(old: &ColumnInfo, new: &ColumnInfo) -> bool {
+    const INTEGERS: [&str; 4] = ["tinyint", "smallint", "int", "bigint"];
+    let old_type = old.data_type.to_lowercase();
+    let new_type = new.data_type.to_lowercase();
+
+    let rank = |t: &str| INTEGERS.iter().position(|i| *i == t);
+    if let (Some(from), Some(to)) = (rank(&old_type), rank(&new_type)) {
+        return to >= from;
+    }
+
+    let length_fits = match (old.max_length, new.max_length) {
+        (_, Some(-1)) => true,
+        (Some(-1), _) => false,
+        (Some(from), Some(to)) => to >= from,
+        _ => false,
+    };
+    match (old_type.as_str(), new_type.as_str()) {
+        (a, b) if a == b && matches!(a, "decimal" | "numeric") => {
+            let (p1, s1) = (old.precision.unwrap_or(18), old.scale.unwrap_or(0));
+            let (p2, s2) = (new.precision.unwrap_or(18), new.scale.unwrap_or(0));
+            s2 >= s1 && p2 - s2 >= p1 - s1
+        }
+        (a, b) if a == b && matches!(a, "datetime2" | "time" | "datetimeoffset") => {
+            new.scale.unwrap_or(7) >= old.scale.unwrap_or(7)
+        }
+        (a, b) if a == b => length_fits,
+        ("varchar", "nvarchar")
+        | ("char", "nchar")
+        | ("char", "varchar")
+        | ("nchar", "nvarchar")
+        | ("binary", "varbinary") => length_fits,
+        _ => false,
+    }
+}
+
+/// Drop whatever default constraint a column has; its name is looked up at run time
+fn drop_default_statement(table: &str, column: &str) -> String {
+    let table_literal = table.replace('\'', "''");
+    format!(
+        "DECLARE @df sysname = (SELECT dc.name FROM sys.default_constraints dc \
+         WHERE dc.parent_object_id = OBJECT_ID(N'{0}') \
+         AND dc.parent_column_id = COLUMNPROPERTY(OBJECT_ID(N'{0}'), N'{1}', 'ColumnId'));\n\
+         IF @df IS NOT NULL EXEC (N'ALTER TABLE {0} DROP CONSTRAINT ' + QUOTENAME(@df));",
+        table_literal,
+        column.replace('\'', "''")
+    )
+}
+
+fn normalize_definition(definition: &str) -> String {
+    definition.replace("\r\n", "\n").trim().to_string()
+}
+
+/// Group FK relationship rows into one definition per constraint
+fn foreign_keys(
+    relationships: &[RelationshipInfo],
+) -> BTreeMap<(Key, String), (String, String, ForeignKeyDefinition)> {
+    let mut rows: Vec<&RelationshipInfo> = relationships.iter().collect();
+    rows.sort_by_key(|r| r.ordinal_position);
+
+    let mut fks: BTreeMap<(Key, String), (String, String, ForeignKeyDefinition)> = BTreeMap::new();
+    for r in rows {
+        let entry = fks
+            .entry((
+                key(&r.source_schema_name, &r.source_table_name),
+                r.constraint_name.to_lowercase(),
+            ))
+            .or_insert_with(|| {
+                (
+                    r.source_schema_name.clone(),
+                    r.source_table_name.clone(),
+                    ForeignKeyDefinition {
+                        name: r.constraint_name.clone(),
+                        columns: Vec::new(),
+                        referenced_schema: r.target_schema_name.clone(),
+                        referenced_table: r.target_table_name.clone(),
+                        referenced_columns: Vec::new(),
+                        delete_action: r.delete_action.clone(),
+                        update_action: r.update_action.clone(),
+                    },
+                )
+            });
+        entry.2.columns.push(r.source_column_name.clone());
+        entry
+            .2
+            .referenced_columns
+            .push(r.target_column_name.clone());
+    }
+    fks
+}
+
+/// Order removed tables so referencing tables are dropped before the tables
+/// they reference. Tables caught in a reference cycle come last, in name
+/// order, and are returned separately so their foreign keys can be dropped
+/// first.
+fn drop_order(removed: &[Key], relationships: &[RelationshipInfo]) -> (Vec<Key>, Vec<Key>) {
+    let removed_set: BTreeSet<&Key> = removed.iter().collect();
+    let mut references: BTreeSet<(Key, Key)> = BTreeSet::new();
+    for r in relationships {
+        let child = key(&r.source_schema_name, &r.source_table_name);
+        let parent = key(&r.target_schema_name, &r.target_table_name);
+        if child != parent && removed_set.contains(&child) && removed_set.contains(&parent) {
+            references.insert((child, parent));
+        }
+    }
+
+    let mut remaining: Vec<Key> = removed.to_vec();
+    let mut ordered = Vec::new();
+    while let Some(position) = remaining.iter().position(|table| {
+        !references
+            .iter()
+            .any(|(child, parent)| parent == table && remaining.contains(child))
+    }) {
+        ordered.push(remaining.remove(position));
+    }
+    (ordered, remaining)
+}
+
+struct Comparer<'a> {
+    differences: Vec<SchemaDifference>,
+    steps: Vec<Step>,
+    source_tables: BTreeMap<Key, &'a TableInfo>,
+    target_tables: BTreeMap<Key, &'a TableInfo>,
+    target: &'a SchemaInfo,
+    /// Target indexes and foreign keys that depend on an altered column: they
+    /// are dropped before the ALTER COLUMN and created again after it
+    rebuild_indexes: BTreeSet<(Key, String)>,
+    rebuild_foreign_keys: BTreeSet<(Key, String)>,
+}
+
+impl<'a> Comparer<'a> {
+    fn new(source: &'a SchemaInfo, target: &'a SchemaInfo) -> Self {
+        let tables = |schema: &'a SchemaInfo| {
+            schema
+                .tables
+                .iter()
+                .filter(|t| t.table_type != "VIEW")
+                .map(|t| (key(&t.schema_name, &t.table_name), t))
+                .collect()
+        };
+        Self {
+            differences: Vec::new(),
+            steps: Vec::new(),
+            source_tables: tables(source),
+            target_tables: tables(target),
+            target,
+            rebuild_indexes: BTreeSet::new(),
+            rebuild_foreign_keys: BTreeSet::new(),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn difference(
+        &mut self,
+        object_type: DiffObjectType,
+        change: ChangeKind,
+        schema_name: &str,
+        object_name: &str,
+        child_name: Option<&str>,
+        details: Vec<String>,
+        data_loss: bool,
+    ) {
+        self.differences.push(SchemaDifference {
+            object_type,
+            change,
+            schema_name: schema_name.to_string(),
+            object_name: object_name.to_string(),
+            child_name: child_name.map(str::to_string),
+            details,
+            data_loss,
+        });
+    }
+
+    fn step(&mut self, phase: Phase, sql: String, data_loss: bool) {
+        self.steps.push(Step {
+            phase,
+            sql,
+            data_loss,
+        });
+    }
+
+    /// Both sides have the table, so child objects are diffed individually
+    fn table_in_both(&self, table_key: &Key) -> bool {
+        self.source_tables.contains_key(table_key) && self.target_tables.contains_key(table_key)
+    }
+
+    fn tables(&mut self) {
+        let source_tables = self.source_tables.clone();
+        let target_tables = self.target_tables.clone();
+
+        let removed: Vec<Key> = target_tables
+            .keys()
+            .filter(|k| !source_tables.contains_key(*k))
+            .cloned()
+            .collect();
+        let (ordered, cyclic) = drop_order(&removed, &self.target.relationships);
+        for (k, (schema_name, table_name, fk)) in foreign_keys(&self.target.relationships) {
+            let parent = key(&fk.referenced_schema, &fk.referenced_table);
+            if cyclic.contains(&k.0) && cyclic.contains(&parent) && k.0 != parent {
+                self.step(
+                    Phase::DropForeignKeys,
+                    format!(
+                        "ALTER TABLE {} DROP CONSTRAINT {};",
+                        qualified_name(&schema_name, &table_name),
+                        quote_identifier(&fk.name)
+                    ),
+                    false,
+                );
+            }
+        }
+        for k in ordered.iter().chain(&cyclic) {
+            let table = target_tables[k];
+            let name = qualified_name(&table.schema_name, &table.table_name);
+            self.difference(
+                DiffObjectType::Table,
+                ChangeKind::Removed,
+                &table.schema_name,
+                &table.table_name,
+                None,
+                vec![format!(
+                    "{} column(s) and their data will be dropped",
+                    table.columns.len()
+                )],
+                true,
+            );
+            self.step(Phase::DropTables, format!("DROP TABLE {};", name), true);
+        }
+
+        for (k, table) in &source_tables {
+            let name = qualified_name(&table.schema_name, &table.table_name);
+            let Some(existing) = target_tables.get(k) else {
+                self.difference(
+                    DiffObjectType::Table,
+                    ChangeKind::Added,
+                    &table.schema_name,
+                    &table.table_name,
+                    None,
+                    Vec::new(),
+                    false,
+                );
+                let mut columns: Vec<&ColumnInfo> = table.columns.iter().collect();
+                columns.sort_by_key(|c| c.ordinal_position);
+                let lines: Vec<String> = columns
+                    .iter()
+                    .map(|c| format!("    {}", column_declaration(c)))
+                    .collect();
+                self.step(
+                    Phase::CreateTables,
+                    format!("CREATE TABLE {} (\n{}\n);", name, lines.join(",\n")),
+                    false,
+                );
+                continue;
+            };
+            self.columns(table, existing, &name);
+        }
+    }
+
+    fn columns(&mut self, source: &TableInfo, target: &TableInfo, table_name: &str) {
+        let target_columns: BTreeMap<String, &ColumnInfo> = target
+            .columns
+            .iter()
+            .map(|c| (c.name.to_lowercase(), c))
+            .collect();
+        let source_columns: BTreeMap<String, &ColumnInfo> = source
+            .columns
+            .iter()
+            .map(|c| (c.name.to_lowercase(), c))
+            .collect();
+        let (schema_name, object_name) = (source.schema_name.as_str(), source.table_name.as_str());
+
+        for (k, column) in &target_columns {
+            if source_columns.contains_key(k) {
+                continue;
+            }
+            self.difference(
+                DiffObjectType::Column,
+                ChangeKind::Removed,
+                schema_name,
+                object_name,
+                Some(&column.name),
+                Vec::new(),
+                true,
+            );
+            if column.column_default.is_some() {
+                self.step(
+                    Phase::AlterColumns,
+                    drop_default_statement(table_name, &column.name),
+                    false,
+                );
+            }
+            self.step(
+                Phase::AlterColumns,
+                format!(
+                    "ALTER TABLE {} DROP COLUMN {};",
+                    table_name,
+                    quote_identifier(&column.name)
+                ),
+                true,
+            );
+        }
+
+        let mut added: Vec<&ColumnInfo> = source_columns
+            .iter()
+            .filter(|(k, _)| !target_columns.contains_key(*k))
+            .map(|(_, c)| *c)
+            .collect();
+        added.sort_by_key(|c| c.ordinal_position);
+        for column in added {
+            let mut details = Vec::new();
+            if column.is_computed {
+                details.push(
+                    "computed column: its expression is not part of the snapshot".to_string(),
+                );
+            } else if !column.is_nullable && column.column_default.is_none() && !column.is_identity
+            {
+                details.push("NOT NULL without a default fails if the table has rows".to_string());
+            }
+            self.difference(
+                DiffObjectType::Column,
+                ChangeKind::Added,
+                schema_name,
+                object_name,
+                Some(&column.name),
+                details,
+                false,
+            );
+            if column.is_computed {
+                self.step(
+                    Phase::AlterColumns,
+                    format!(
+                        "-- Add computed column {} to {} manually: its expression is not part of the snapshot",
+                        quote_identifier(&column.name),
+                        table_name
+                    ),
+                    false,
+                );
+            } else {
+                self.step(
+                    Phase::AlterColumns,
+                    format!(
+                        "ALTER TABLE {} ADD {};",
+                        table_name,
+                        column_declaration(column)
+                    ),
+                    false,
+                );
+            }
+        }
+
+        for (k, column) in &source_columns {
+            let Some(existing) = target_columns.get(k) else {
+                continue;
+            };
+            self.column_changes(column, existing, schema_name, object_name, table_name);
+        }
+    }
+
+    fn column_changes(
+        &mut self,
+        column: &ColumnInfo,
+        existing: &ColumnInfo,
+        schema_name: &str,
+        object_name: &str,
+        table_name: &str,
+    ) {
+        let column_name = quote_identifier(&column.name);
+        let (new_type, old_type) = (column_type(column), column_type(existing));
+        let mut details = Vec::new();
+        let mut data_loss = false;
+
+        if column.is_identity != existing.is_identity || column.is_computed != existing.is_computed
+        {
+            details
+                .push("identity or computed status differs: the table must be rebuilt".to_string());
+            self.step(
+                Phase::AlterColumns,
+                format!(
+                    "-- {}.{} differs in identity or computed status and needs a table rebuild",
+                    table_name, column_name
+                ),
+                false,
+            );
+        }
+
+        let type_changed = new_type != old_type;
+        let nullability_changed = column.is_nullable != existing.is_nullable;
+        if type_changed {
+            details.push(format!("type: {} -> {}", old_type, new_type));
+            data_loss = !is_widening(existing, column);
+        }
+        if nullability_changed {
+            details.push(if column.is_nullable {
+                "nullability: NOT NULL -> NULL".to_string()
+            } else {
+                "nullability: NULL -> NOT NULL (fails if NULLs exist)".to_string()
+            });
+        }
+        if type_changed || nullability_changed {
+            let rebuilt = self.rebuild_dependents(schema_name, object_name, &column.name);
+            if rebuilt > 0 {
+                details.push(format!(
+                    "{} dependent index(es), key(s) or foreign key(s) are dropped and re-created",
+                    rebuilt
+                ));
+            }
+            self.step(
+                Phase::AlterColumns,
+                format!(
+                    "ALTER TABLE {} ALTER COLUMN {} {} {};",
+                    table_name,
+                    column_name,
+                    new_type,
+                    if column.is_nullable {
+                        "NULL"
+                    } else {
+                        "NOT NULL"
+                    }
+                ),
+                data_loss,
+            );
+        }
+
+        if column.column_default != existing.column_default {
+            details.push(format!(
+                "default: {} -> {}",
+                existing.column_default.as_deref().unwrap_or("none"),
+                column.column_default.as_deref().unwrap_or("none")
+            ));
+            if existing.column_default.is_some() {
+                self.step(
+                    Phase::AlterColumns,
+                    drop_default_statement(table_name, &column.name),
+                    false,
+                );
+            }
+            if let Some(default) = &column.column_default {
+                self.step(
+                    Phase::AlterColumns,
+                    format!(
+                        "ALTER TABLE {} ADD DEFAULT {} FOR {};",
+                        table_name, default, column_name
+                    ),
+                    false,
+                );
+            }
+        }
+
+        if !details.is_empty() {
+            self.difference(
+                DiffObjectType::Column,
+                ChangeKind::Changed,
+                schema_name,
+                object_name,
+                Some(&column.name),
+                details,
+                data_loss,
+            );
+        }
+    }
+
+    /// Mark the target indexes, keys and foreign keys using a column for
+    /// rebuilding around its ALTER COLUMN; returns how many there are
+    fn rebuild_dependents(&mut self, schema_name: &str, table_name: &str, column: &str) -> usize {
+        let table_key = key(schema_name, table_name);
+        let uses = |name: &str| name.eq_ignore_ascii_case(column);
+        let mut count = 0;
+
+        for index in &self.target.indexes {
+            let index_uses = index.index.key_columns.iter().any(|c| uses(&c.name))
+                || index.index.included_columns.iter().any(|c| uses(c));
+            if key(&index.schema_name, &index.table_name) == table_key
+                && index_uses
+                && self
+                    .rebuild_indexes
+                    .insert((table_key.clone(), index.index.name.to_lowercase()))
+            {
+                count += 1;
+            }
+        }
+        for r in &self.target.relationships {
+            let referencing = key(&r.source_schema_name, &r.source_table_name);
+            let uses_column = (referencing == table_key && uses(&r.source_column_name))
+                || (key(&r.target_schema_name, &r.target_table_name) == table_key
+                    && uses(&r.target_column_name));
+            if uses_column
+                && self
+                    .rebuild_foreign_keys
+                    .insert((referencing, r.constraint_name.to_lowercase()))
+            {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    fn indexes(&mut self, source: &[IndexInfo], target: &[IndexInfo]) {
+        let source_indexes = indexes_by_name(source);
+        let target_indexes = indexes_by_name(target);
+
+        for (k, index) in &target_indexes {
+            let changed = match source_indexes.get(k) {
+                None => false,
+                Some(new) => new.index != index.index,
+            };
+            let rebuild = self.rebuild_indexes.contains(k);
+            if source_indexes.contains_key(k) && !changed && !rebuild {
+                continue;
+            }
+            let table_exists = self.table_in_both(&k.0);
+            if !table_exists {
+                // Dropped along with its table
+                continue;
+            }
+            if !source_indexes.contains_key(k) {
+                self.index_difference(index, ChangeKind::Removed, Vec::new());
+            }
+            let table_name = qualified_name(&index.schema_name, &index.table_name);
+            let sql = if index.index.is_constraint() {
+                format!(
+                    "ALTER TABLE {} DROP CONSTRAINT {};",
+                    table_name,
+                    quote_identifier(&index.index.name)
+                )
+            } else {
+                format!(
+                    "DROP INDEX {} ON {};",
+                    quote_identifier(&index.index.name),
+                    table_name
+                )
+            };
+            self.step(Phase::DropIndexes, sql, false);
+        }
+
+        for (k, index) in &source_indexes {
+            let table_name = qualified_name(&index.schema_name, &index.table_name);
+            match target_indexes.get(k) {
+                Some(existing)
+                    if existing.index == index.index && !self.rebuild_indexes.contains(k) =>
+                {
+                    continue
+                }
+                // Unchanged, but re-created around an ALTER COLUMN
+                Some(existing) if existing.index == index.index => {}
+                Some(existing) => {
+                    let details = vec![format!(
+                        "definition changed (was {})",
+                        describe_index(existing)
+                    )];
+                    self.index_difference(index, ChangeKind::Changed, details);
+                }
+                None if self.target_tables.contains_key(&k.0) => {
+                    self.index_difference(index, ChangeKind::Added, Vec::new());
+                }
+                // New table: reported once as a table
+                None => {}
+            }
+            let sql = if index.index.is_constraint() {
+                format!(
+                    "ALTER TABLE {} ADD {};",
+                    table_name,
+                    key_constraint_clause(&index.index)
+                )
+            } else {
+                index_statement(&table_name, &index.index)
+            };
+            self.step(Phase::CreateIndexes, sql, false);
+        }
+    }
+
+    fn index_difference(&mut self, index: &IndexInfo, change: ChangeKind, details: Vec<String>) {
+        let object_type = if index.index.is_constraint() {
+            DiffObjectType::Key
+        } else {
+            DiffObjectType::Index
+        };
+        self.difference(
+            object_type,
+            change,
+            &index.schema_name,
+            &index.table_name,
+            Some(&index.index.name),
+            details,
+            false,
+        );
+    }
+
+    fn foreign_keys(&mut self, source: &[RelationshipInfo], target: &[RelationshipInfo]) {
+        let source_fks = foreign_keys(source);
+        let target_fks = foreign_keys(target);
+
+        for (k, (schema_name, table_name, fk)) in &target_fks {
+            let changed = source_fks.get(k).is_some_and(|(_, _, new)| new != fk);
+            let rebuild = self.rebuild_foreign_keys.contains(k);
+            if source_fks.contains_key(k) && !changed && !rebuild {
+                continue;
+            }
+            if !self.table_in_both(&k.0) {
+                continue;
+            }
+            if !source_fks.contains_key(k) {
+                self.difference(
+                    DiffObjectType::ForeignKey,
+                    ChangeKind::Removed,
+                    schema_name,
+                    table_name,
+                    Some(&fk.name),
+                    Vec::new(),
+                    false,
+                );
+            }
+            self.step(
+                Phase::DropForeignKeys,
+                format!(
+                    "ALTER TABLE {} DROP CONSTRAINT {};",
+                    qualified_name(schema_name, table_name),
+                    quote_identifier(&fk.name)
+                ),
+                false,
+            );
+        }
+
+        for (k, (schema_name, table_name, fk)) in &source_fks {
+            match target_fks.get(k) {
+                Some((_, _, existing))
+                    if existing == fk && !self.rebuild_foreign_keys.contains(k) =>
+                {
+                    continue
+                }
+                // Unchanged, but re-created around an ALTER COLUMN
+                Some((_, _, existing)) if existing == fk => {}
+                Some((_, _, existing)) => self.difference(
+                    DiffObjectType::ForeignKey,
+                    ChangeKind::Changed,
+                    schema_name,
+                    table_name,
+                    Some(&fk.name),
+                    foreign_key_changes(existing, fk),
+                    false,
+                ),
+                None if self.target_tables.contains_key(&k.0) => self.difference(
+                    DiffObjectType::ForeignKey,
+                    ChangeKind::Added,
+                    schema_name,
+                    table_name,
+                    Some(&fk.name),
+                    Vec::new(),
+                    false,
+                ),
+                None => {}
+            }
+            self.step(
+                Phase::CreateForeignKeys,
+                foreign_key_statement(&qualified_name(schema_name, table_name), fk),
+                false,
+            );
+        }
+    }
+
+    /// Views and routines, compared by their bodies
+    fn modules(&mut self, source: &[Module], target: &[Module]) {
+        let source_modules: BTreeMap<Key, &Module> = source
+            .iter()
+            .map(|m| (key(&m.schema_name, &m.name), m))
+            .collect();
+        let target_modules: BTreeMap<Key, &Module> = target
+            .iter()
+            .map(|m| (key(&m.schema_name, &m.name), m))
+            .collect();
+
+        for (k, module) in &target_modules {
+            if source_modules.contains_key(k) {
+                continue;
+            }
+            self.difference(
+                module.object_type,
+                ChangeKind::Removed,
+                &module.schema_name,
+                &module.name,
+                None,
+                Vec::new(),
+                false,
+            );
+            self.step(
+                Phase::DropModules,
+                format!(
+                    "DROP {} IF EXISTS {};",
+                    module.keyword,
+                    qualified_name(&module.schema_name, &module.name)
+                ),
+                false,
+            );
+        }
+
+        for (k, module) in &source_modules {
+            let phase = if module.object_type == DiffObjectType::View {
+                Phase::CreateViews
+            } else {
+                Phase::CreateRoutines
+            };
+            let (change, action) = match target_modules.get(k) {
+                None => (ChangeKind::Added, ScriptAction::Create),
+                Some(existing) => {
+                    match (&module.definition, &existing.definition) {
+                        (Some(new), Some(old))
+                            if normalize_definition(new) != normalize_definition(old) => {}
+                        _ => continue,
+                    }
+                    (ChangeKind::Changed, ScriptAction::CreateOrAlter)
+                }
+            };
+            self.difference(
+                module.object_type,
+                change,
+                &module.schema_name,
+                &module.name,
+                None,
+                Vec::new(),
+                false,
+            );
+
+            let sql = module
+                .definition
+                .as_deref()
+                .and_then(|d| module_header(d.trim(), action))
+                .unwrap_or_else(|| {
+                    format!(
+                        "-- No definition available for {} (encrypted or not cached)",
+                        qualified_name(&module.schema_name, &module.name)
+                    )
+                });
+            self.step(phase, sql, false);
+        }
+    }
+
+    fn script(mut self, source: &SchemaInfo, target: &SchemaInfo) -> SchemaComparison {
+        self.steps.sort_by_key(|s| s.phase);
+        let loss_count = self.steps.iter().filter(|s| s.data_loss).count();
+
+        let mut script = format!(
+            "-- Migration script: brings [{}] in line with [{}]\n-- Review before running.\n",
+            target.database_name, source.database_name
+        );
+        if loss_count > 0 {
+            script.push_str(&format!(
+                "-- WARNING: {} statement(s) may lose data; they are marked below.\n",
+                loss_count
+            ));
+        }
+        if self.steps.is_empty() {
+            script.push_str("-- No differences found.\n");
+        }
+        for step in &self.steps {
+            script.push('\n');
+            if step.data_loss {
+                script.push_str("-- WARNING: possible data loss\n");
+            }
+            script.push_str(&step.sql);
+            script.push_str("\nGO\n");
+        }
+
+        SchemaComparison {
+            source_database: source.database_name.clone(),
+            target_database: target.database_name.clone(),
+            has_data_loss: self.differences.iter().any(|d| d.data_loss),
+            differences: self.differences,
+            script,
+        }
+    }
+}
+
+fn foreign_key_changes(old: &ForeignKeyDefinition, new: &ForeignKeyDefinition) -> Vec<String> {
+    let mut details = Vec::new();
+    if old.columns != new.columns
+        || old.referenced_columns != new.referenced_columns
+        || !old
+            .referenced_schema
+            .eq_ignore_ascii_case(&new.referenced_schema)
+        || !old
+            .referenced_table
+            .eq_ignore_ascii_case(&new.referenced_table)
+    {
+        details.push("columns or referenced table changed".to_string());
+    }
+    for (event, old_action, new_action) in [
+        ("ON DELETE", &old.delete_action, &new.delete_action),
+        ("ON UPDATE", &old.update_action, &new.update_action),
+    ] {
+        if old_action != new_action {
+            details.push(format!("{}: {} -> {}", event, old_action, new_action));
+        }
+    }
+    details
+}
+
+fn indexes_by_name(indexes: &[IndexInfo]) -> BTreeMap<(Key, String), &IndexInfo> {
+    indexes
+        .iter()
+        .map(|i| {
+            (
+                (
+                    key(&i.schema_name, &i.table_name),
+                    i.index.name.to_lowercase(),
+                ),
+                i,
+            )
+        })
+        .collect()
+}
+
+fn describe_index(index: &IndexInfo) -> String {
+    let columns: Vec<&str> = index
+        .index
+        .key_columns
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect();
+    format!(
+        "{}{} on ({})",
+        if index.index.is_unique { "UNIQUE " } else { "" },
+        index.index.type_desc,
+        columns.join(", ")
+    )
+}
+
+/// A view or routine with its body
+struct Module {
+    schema_name: String,
+    name: String,
+    object_type: DiffObjectType,
+    keyword: &'static str,
+    definition: Option<String>,
+}
+
+fn modules(schema: &SchemaInfo) -> Vec<Module> {
+    let views = schema
+        .tables
+        .iter()
+        .filter(|t| t.table_type == "VIEW")
+        .map(|t| Module {
+            schema_name: t.schema_name.clone(),
+            name: t.table_name.clone(),
+            object_type: DiffObjectType::View,
+            keyword: "VIEW",
+            definition: t.definition.clone(),
+        });
+    let routines = schema.routines.iter().map(|r| Module {
+        schema_name: r.schema_name.clone(),
+        name: r.routine_name.clone(),
+        object_type: DiffObjectType::Routine,
+        keyword: if r.routine_type == "PROCEDURE" {
+            "PROCEDURE"
+        } else {
+            "FUNCTION"
+        },
+        definition: r.definition.clone(),
+    });
+    views.chain(routines).collect()
+}
+
+/// Compare `target` against `source` and script the changes that make the
+/// target match the source
+pub fn compare_schemas(source: &SchemaInfo, target: &SchemaInfo) -> SchemaComparison {
+    let mut comparer = Comparer::new(source, target);
+    comparer.tables();
+    comparer.indexes(&source.indexes, &target.indexes);
+    comparer.foreign_keys(&source.relationships, &target.relationships);
+    comparer.modules(&modules(source), &modules(target));
+    comparer.script(source, target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema::RoutineInfo;
+
+    fn column(
+        name: &str,
+        data_type: &str,
+        max_length: Option<i32>,
+        is_nullable: bool,
+    ) -> ColumnInfo {
+        ColumnInfo {
+            max_length,
+            is_nullable,
+            ..ColumnInfo::for_test(name, data_type)
+        }
+    }
+
+    fn table(name: &str, columns: Vec<ColumnInfo>) -> TableInfo {
+        TableInfo::for_test(name, columns)
+    }
+
+    fn schema(
+        database_name: &str,
+        tables: Vec<TableInfo>,
+        routines: Vec<RoutineInfo>,
+    ) -> SchemaInfo {
+        SchemaInfo {
+            routines,
+            ..SchemaInfo::for_test(database_name, tables)
+        }
+    }
+
+    fn procedure(name: &str, body: &str) -> RoutineInfo {
+        RoutineInfo {
+            schema_name: "dbo".to_string(),
+            routine_name: name.to_string(),
+            routine_type: "PROCEDURE".to_string(),
+            return_type: None,
+            parameters: Vec::new(),
+            definition: Some(body.to_string()),
+            modify_date: None,
+            extended_properties: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn identical_schemas_have_no_differences() {
+        let tables = vec![table("Customers", vec![column("Id", "int", None, false)])];
+        let result = compare_schemas(
+            &schema("a", tables.clone(), vec![]),
+            &schema("b", tables, vec![]),
+        );
+        assert!(result.differences.is_empty());
+        assert!(!result.has_data_loss);
+        assert!(result.script.contains("No differences"));
+    }
+
+    #[test]
+    fn column_changes_are_classified_and_ordered() {
+        let source = schema(
+            "dev",
+            vec![
+                table(
+                    "Customers",
+                    vec![
+                        column("Id", "int", None, false),
+                        column("Name", "nvarchar", Some(200), false),
+                        column("Email", "varchar", Some(100), true),
+                    ],
+                ),
+                table("Orders", vec![column("Id", "int", None, false)]),
+            ],
+            vec![],
+        );
+        let target = schema(
+            "prod",
+            vec![
+                table(
+                    "Customers",
+                    vec![
+                        column("Id", "int", None, false),
+                        column("Name", "nvarchar", Some(100), false),
+                        column("Email", "varchar", Some(255), true),
+                        column("Legacy", "int", None, true),
+                    ],
+                ),
+                table("Audit", vec![column("Id", "int", None, false)]),
+            ],
+            vec![],
+        );
+
+        let result = compare_schemas(&source, &target);
+        let find = |child: &str| {
+            result
+                .differences
+                .iter()
+                .find(|d| d.child_name.as_deref() == Some(child))
+                .unwrap()
+        };
+        assert!(!find("Name").data_loss, "widening nvarchar is safe");
+        assert!(find("Email").data_loss, "narrowing varchar may truncate");
+        assert!(find("Legacy").data_loss);
+        assert!(result.has_data_loss);
+
+        let script = &result.script;
+        let drop_table = script.find("DROP TABLE [dbo].[Audit];").unwrap();
+        let create_table = script.find("CREATE TABLE [dbo].[Orders]").unwrap();
+        let alter = script
+            .find("ALTER TABLE [dbo].[Customers] ALTER COLUMN [Name] nvarchar(200) NOT NULL;")
+            .unwrap();
+        assert!(drop_table < create_table && create_table < alter);
+        assert!(script.contains(
+            "-- WARNING: possible data loss\nALTER TABLE [dbo].[Customers] DROP COLUMN [Legacy];"
+        ));
+    }
+
+    #[test]
+    fn changed_routines_use_create_or_alter() {
+        let source = schema(
+            "dev",
+            vec![],
+            vec![
+                procedure("GetOrders", "CREATE PROCEDURE dbo.GetOrders AS SELECT 2"),
+                procedure("NewProc", "CREATE PROCEDURE dbo.NewProc AS SELECT 1"),
+            ],
+        );
+        let target = schema(
+            "prod",
+            vec![],
+            vec![
+                procedure("GetOrders", "CREATE PROCEDURE dbo.GetOrders AS SELECT 1"),
+                procedure("OldProc", "CREATE PROCEDURE dbo.OldProc AS SELECT 1"),
+            ],
+        );
+
+        let result = compare_schemas(&source, &target);
+        assert_eq!(result.differences.len(), 3);
+        assert!(result
+            .script
+            .contains("DROP PROCEDURE IF EXISTS [dbo].[OldProc];"));
+        assert!(result
+            .script
+            .contains("CREATE OR ALTER PROCEDURE dbo.GetOrders AS SELECT 2"));
+        assert!(result
+            .script
+            .contains("CREATE PROCEDURE dbo.NewProc AS SELECT 1"));
+    }
+
+    fn relationship(name: &str, source: &str, column: &str, target: &str) -> RelationshipInfo {
+        RelationshipInfo::for_test(name, (source, column), (target, "Id"))
+    }
+
+    fn primary_key(table: &str, column: &str) -> IndexInfo {
+        IndexInfo {
+            schema_name: "dbo".to_string(),
+            table_name: table.to_string(),
+            index: crate::db::scripting::IndexDefinition {
+                name: format!("PK_{}", table),
+                type_desc: "CLUSTERED".to_string(),
+                is_unique: true,
+                is_primary_key: true,
+                is_unique_constraint: false,
+                key_columns: vec![crate::db::scripting::IndexColumn {
+                    name: column.to_string(),
+                    is_descending: false,
+                }],
+                included_columns: Vec::new(),
+                filter_definition: None,
+            },
+        }
+    }
+
+    #[test]
+    fn foreign_key_actions_are_compared_and_scripted() {
+        let tables = vec![
+            table("Customers", vec![column("Id", "int", None, false)]),
+            table("Orders", vec![column("CustomerId", "int", None, false)]),
+        ];
+        let mut source = schema("dev", tables.clone(), vec![]);
+        let mut target = schema("prod", tables, vec![]);
+        let mut cascade = relationship("FK_Orders_Customers", "Orders", "CustomerId", "Customers");
+        cascade.delete_action = "CASCADE".to_string();
+        cascade.update_action = "SET_NULL".to_string();
+        source.relationships = vec![cascade];
+        target.relationships = vec![relationship(
+            "FK_Orders_Customers",
+            "Orders",
+            "CustomerId",
+            "Customers",
+        )];
+
+        let result = compare_schemas(&source, &target);
+        assert_eq!(
+            result.differences[0].details,
+            vec![
+                "ON DELETE: NO_ACTION -> CASCADE".to_string(),
+                "ON UPDATE: NO_ACTION -> SET_NULL".to_string(),
+            ]
+        );
+        assert!(result
+            .script
+            .contains("REFERENCES [dbo].[Customers] ([Id]) ON DELETE CASCADE ON UPDATE SET NULL;"));
+    }
+
+    #[test]
+    fn declarations_keep_identity_seed_and_fractional_scale() {
+        let mut id = column("Id", "bigint", None, false);
+        id.is_identity = true;
+        id.identity_seed = Some("1000".to_string());
+        id.identity_increment = Some("5".to_string());
+        assert_eq!(
+            column_declaration(&id),
+            "[Id] bigint IDENTITY(1000, 5) NOT NULL"
+        );
+
+        let mut created = column("CreatedAt", "datetime2", None, false);
+        created.scale = Some(3);
+        assert_eq!(column_type(&created), "datetime2(3)");
+        let mut precise = created.clone();
+        precise.scale = Some(7);
+        assert!(is_widening(&created, &precise));
+        assert!(!is_widening(&precise, &created));
+    }
+
+    #[test]
+    fn altered_columns_rebuild_dependent_keys() {
+        let source = schema(
+            "dev",
+            vec![
+                table("Customers", vec![column("Id", "bigint", None, false)]),
+                table("Orders", vec![column("CustomerId", "bigint", None, false)]),
+            ],
+            vec![],
+        );
+        let target = schema(
+            "prod",
+            vec![
+                table("Customers", vec![column("Id", "int", None, false)]),
+                table("Orders", vec![column("CustomerId", "bigint", None, false)]),
+            ],
+            vec![],
+        );
+        let (mut source, mut target) = (source, target);
+        for side in [&mut source, &mut target] {
+            side.indexes = vec![primary_key("Customers", "Id")];
+            side.relationships = vec![relationship(
+                "FK_Orders_Customers",
+                "Orders",
+                "CustomerId",
+                "Customers",
+            )];
+        }
+
+        let result = compare_schemas(&source, &target);
+        assert_eq!(result.differences.len(), 1, "{:?}", result.differences);
+        let script = &result.script;
+        let drop_fk = script
+            .find("ALTER TABLE [dbo].[Orders] DROP CONSTRAINT [FK_Orders_Customers];")
+            .unwrap();
+        let drop_pk = script
+            .find("ALTER TABLE [dbo].[Customers] DROP CONSTRAINT [PK_Customers];")
+            .unwrap();
+        let alter = script
+            .find("ALTER TABLE [dbo].[Customers] ALTER COLUMN [Id] bigint NOT NULL;")
+            .unwrap();
+        let add_pk = script
+            .find("ALTER TABLE [dbo].[Customers] ADD CONSTRAINT [PK_Customers] PRIMARY KEY")
+            .unwrap();
+        let add_fk = script
+            .find("ALTER TABLE [dbo].[Orders] ADD CONSTRAINT [FK_Orders_Customers]")
+            .unwrap();
+        assert!(drop_fk < drop_pk && drop_pk < alter && alter < add_pk && add_pk < add_fk);
+    }
+
+    #[test]
+    fn removed_tables_drop_referencing_tables_first() {
+        let mut target = schema(
+            "prod",
+            vec![
+                table("Customers", vec![column("Id", "int", None, false)]),
+                table("Lines", vec![column("OrderId", "int", None, false)]),
+                table("Orders", vec![column("CustomerId", "int", None, false)]),
+            ],
+            vec![],
+        );
+        target.relationships = vec![
+            relationship("FK_Orders_Customers", "Orders", "CustomerId", "Customers"),
+            relationship("FK_Lines_Orders", "Lines", "OrderId", "Orders"),
+        ];
+        let script = compare_schemas(&schema("dev", vec![], vec![]), &target).script;
+        let position = |table: &str| {
+            script
+                .find(&format!("DROP TABLE [dbo].[{}];", table))
+                .unwrap()
+        };
+        assert!(position("Lines") < position("Orders"));
+        assert!(position("Orders") < position("Customers"));
+
+        // A cycle drops its foreign keys first
+        target.relationships.push(relationship(
+            "FK_Customers_Lines",
+            "Customers",
+            "LastLineId",
+            "Lines",
+        ));
+        let script = compare_schemas(&schema("dev", vec![], vec![]), &target).script;
+        let drop_fk = script
+            .find("ALTER TABLE [dbo].[Customers] DROP CONSTRAINT [FK_Customers_Lines];")
+            .unwrap();
+        assert!(drop_fk < script.find("DROP TABLE").unwrap());
+    }
+
+    #[test]
+    fn widening_rules() {
+        let int = column("A", "int", None, false);
+        let bigint = column("A", "bigint", None, false);
+        assert!(is_widening(&int, &bigint));
+        assert!(!is_widening(&bigint, &int));
+        let varchar = column("A", "varchar", Some(50), false);
+        assert!(is_widening(
+            &varchar,
+            &column("A", "nvarchar", Some(50), false)
+        ));
+        assert!(is_widening(
+            &varchar,
+            &column("A", "varchar", Some(-1), false)
+        ));
+        assert!(!is_widening(
+            &column("A", "varchar", Some(-1), false),
+            &varchar
+        ));
+    }
+}
@@ -0,0 +1,591 @@
+// Data Search
+// Find a value in any type-compatible column of every table in a database
+
+use crate::db::connection::ConnectionError;
+use crate::db::schema::{SchemaInfo, SchemaMetadataManager, TableInfo};
+use crate::sql::quote_identifier;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio_util::compat::Compat;
+
+type Client = tiberius::Client<Compat<TcpStream>>;
+
+/// What to look for and how hard to look
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DataSearch {
+    pub value: String,
+    /// Require whole-value equality in text columns instead of a substring match
+    pub exact_match: bool,
+    /// Only search tables in this schema
+    pub schema_name: Option<String>,
+    /// Tables searched at the same time, each on its own connection
+    pub max_concurrency: usize,
+    pub table_timeout_seconds: u64,
+    /// Primary keys of matching rows returned per hit
+    pub sample_size: usize,
+}
+
+impl Default for DataSearch {
+    fn default() -> Self {
+        Self {
+            value: String::new(),
+            exact_match: false,
+            schema_name: None,
+            max_concurrency: 4,
+            table_timeout_seconds: 30,
+            sample_size: 5,
+        }
+    }
+}
+
+/// The search value interpreted as each SQL type it can be compared with
+#[derive(Debug, Clone, PartialEq)]
+struct SearchValue {
+    text: String,
+    integer: Option<i64>,
+    decimal: Option<String>,
+    guid: Option<uuid::Uuid>,
+    date: Option<chrono::NaiveDate>,
+    datetime: Option<chrono::NaiveDateTime>,
+}
+
+impl SearchValue {
+    fn parse(value: &str) -> Self {
+        let trimmed = value.trim();
+        let is_decimal = trimmed.parse::<f64>().is_ok()
+            && trimmed
+                .chars()
+                .all(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.'));
+        let datetime = [
+            "%Y-%m-%d %H:%M:%S%.f",
+            "%Y-%m-%dT%H:%M:%S%.f",
+            "%Y-%m-%d %H:%M",
+        ]
+        .iter()
+        .find_map(|format| chrono::NaiveDateTime::parse_from_str(trimmed, format).ok());
+        Self {
+            text: value.to_string(),
+            integer: trimmed.parse().ok(),
+            decimal: is_decimal.then(|| trimmed.to_string()),
+            guid: uuid::Uuid::parse_str(trimmed.trim_matches(['{', '}'])).ok(),
+            date: chrono::NaiveDate::parse_from_str(trimmed, "%Y-%m-%d").ok(),
+            datetime,
+        }
+    }
+}
+
+/// WHERE predicate comparing `column` with the value, or None when the
+/// column's type cannot hold it
+fn column_predicate(
+    column: &str,
+    data_type: &str,
+    value: &SearchValue,
+    exact: bool,
+) -> Option<String> {
+    let column = quote_identifier(column);
+    let literal = |s: &str| format!("N'{}'", s.replace('\'', "''"));
+    let data_type = data_type.to_lowercase();
+    match data_type.as_str() {
+        "char" | "varchar" | "nchar" | "nvarchar" | "sysname" | "text" | "ntext" => {
+            // text/ntext do not support =, so they always use LIKE
+            if exact && !matches!(data_type.as_str(), "text" | "ntext") {
+                Some(format!("{} = {}", column, literal(&value.text)))
+            } else {
+                let pattern = value
+                    .text
+                    .replace('\\', "\\\\")
+                    .replace('%', "\\%")
+                    .replace('_', "\\_")
+                    .replace('[', "\\[");
+                let pattern = if exact {
+                    pattern
+                } else {
+                    format!("%{}%", pattern)
+                };
+                Some(format!("{} LIKE {} ESCAPE '\\'", column, literal(&pattern)))
+            }
+        }
+        "tinyint" | "smallint" | "int" | "bigint" => {
+            value.integer.map(|n| format!("{} = {}", column, n))
+        }
+        "decimal" | "numeric" | "money" | "smallmoney" | "float" | "real" => value
+            .decimal
+            .as_ref()
+            .map(|n| format!("{} = {}", column, n)),
+        "uniqueidentifier" => value.guid.map(|guid| format!("{} = '{}'", column, guid)),
+        "date" => value
+            .date
+            .or(value.datetime.map(|dt| dt.date()))
+            .map(|d| format!("{} = '{}'", column, d.format("%Y-%m-%d"))),
+        "datetime" | "datetime2" | "smalldatetime" | "datetimeoffset" => {
+            if let Some(dt) = value.datetime {
+                Some(format!(
+                    "{} = '{}'",
+                    column,
+                    dt.format("%Y-%m-%dT%H:%M:%S%.f")
+                ))
+            } else {
+                // A bare date matches the whole day
+                value.date.map(|d| {
+                    format!(
+                        "{0} >= '{1}' AND {0} < DATEADD(day, 1, CAST('{1}' AS date))",
+                        column,
+                        d.format("%Y-%m-%d")
+                    )
+                })
+            }
+        }
+        _ => None,
+    }
+}
+
+/// A table to search with the predicate for each compatible column
+#[derive(Debug, Clone, Serialize)]
+pub struct TableSearchPlan {
+    pub schema_name: String,
+    pub table_name: String,
+    /// (column, predicate)
+    pub columns: Vec<(String, String)>,
+    pub key_columns: Vec<String>,
+}
+
+impl TableSearchPlan {
+    fn table(&self) -> String {
+        format!(
+            "{}.{}",
+            quote_identifier(&self.schema_name),
+            quote_identifier(&self.table_name)
+        )
+    }
+
+    /// One scan counting matches per column
+    fn count_query(&self) -> String {
+        let counts: Vec<String> = self
+            .columns
+            .iter()
+            .map(|(_, predicate)| format!("COUNT_BIG(CASE WHEN {} THEN 1 END)", predicate))
+            .collect();
+        let filter: Vec<String> = self
+            .columns
+            .iter()
+            .map(|(_, predicate)| format!("({})", predicate))
+            .collect();
+        format!(
+            "SELECT {} FROM {} WHERE {}",
+            counts.join(", "),
+            self.table(),
+            filter.join(" OR ")
+        )
+    }
+
+    /// Primary keys of up to `limit` rows matching `predicate`
+    fn sample_query(&self, predicate: &str, limit: usize) -> Option<String> {
+        if self.key_columns.is_empty() {
+            return None;
+        }
+        let keys: Vec<String> = self
+            .key_columns
+            .iter()
+            .map(|k| quote_identifier(k))
+            .collect();
+        let select: Vec<String> = keys
+            .iter()
+            .map(|k| format!("CONVERT(nvarchar(4000), {})", k))
+            .collect();
+        Some(format!(
+            "SELECT TOP ({}) {} FROM {} WHERE {} ORDER BY {}",
+            limit,
+            select.join(", "),
+            self.table(),
+            predicate,
+            keys.join(", ")
+        ))
+    }
+}
+
+/// Plan the search over every base table with at least one compatible column
+pub fn plan_data_search(schema: &SchemaInfo, search: &DataSearch) -> Vec<TableSearchPlan> {
+    let value = SearchValue::parse(&search.value);
+    schema
+        .tables
+        .iter()
+        .filter(|t| t.table_type == "BASE TABLE")
+        .filter(|t| {
+            search
+                .schema_name
+                .as_ref()
+                .is_none_or(|s| s.eq_ignore_ascii_case(&t.schema_name))
+        })
+        .filter_map(|table| plan_table(table, &value, search.exact_match))
+        .collect()
+}
+
+fn plan_table(table: &TableInfo, value: &SearchValue, exact: bool) -> Option<TableSearchPlan> {
+    let columns: Vec<(String, String)> = table
+        .columns
+        .iter()
+        .filter_map(|c| {
+            column_predicate(&c.name, &c.data_type, value, exact).map(|p| (c.name.clone(), p))
+        })
+        .collect();
+    if columns.is_empty() {
+        return None;
+    }
+    Some(TableSearchPlan {
+        schema_name: table.schema_name.clone(),
+        table_name: table.table_name.clone(),
+        columns,
+        key_columns: table
+            .columns
+            .iter()
+            .filter(|c| c.is_primary_key)
+            .map(|c| c.name.clone())
+            .collect(),
+    })
+}
+
+/// A column containing the value
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataSearchHit {
+    pub schema_name: String,
+    pub table_name: String,
+    pub column_name: String,
+    pub row_count: i64,
+    pub key_columns: Vec<String>,
+    /// Primary key values of some matching rows (empty for heaps without a key)
+    pub sample_keys: Vec<Vec<Option<String>>>,
+}
+
+/// Streamed while a data search runs
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DataSearchEvent {
+    Hit(DataSearchHit),
+    Progress {
+        tables_searched: usize,
+        tables_total: usize,
+    },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DataSearchSummary {
+    pub tables_total: usize,
+    pub tables_searched: usize,
+    pub hits: usize,
+    /// `schema.table` names that exceeded the per-table timeout
+    pub timed_out: Vec<String>,
+    /// (`schema.table`, error)
+    pub errors: Vec<(String, String)>,
+    pub cancelled: bool,
+}
+
+/// State shared by the search workers
+struct SearchRun<'a> {
+    connection_id: &'a str,
+    database: &'a str,
+    search: &'a DataSearch,
+    queue: Mutex<VecDeque<TableSearchPlan>>,
+    summary: Mutex<DataSearchSummary>,
+    cancel: &'a AtomicBool,
+    events: &'a mpsc::Sender<DataSearchEvent>,
+}
+
+impl SchemaMetadataManager {
+    /// Run planned table searches with bounded concurrency, sending hits and
+    /// progress to `events` until done or `cancel` is set
+    pub async fn search_data(
+        &self,
+        connection_id: &str,
+        database: &str,
+        plans: Vec<TableSearchPlan>,
+        search: &DataSearch,
+        cancel: &AtomicBool,
+        events: &mpsc::Sender<DataSearchEvent>,
+    ) -> DataSearchSummary {
+        let run = SearchRun {
+            connection_id,
+            database,
+            search,
+            summary: Mutex::new(DataSearchSummary {
+                tables_total: plans.len(),
+                ..DataSearchSummary::default()
+            }),
+            queue: Mutex::new(plans.into()),
+            cancel,
+            events,
+        };
+        let workers = (0..search.max_concurrency.max(1)).map(|_| self.data_search_worker(&run));
+        futures::future::join_all(workers).await;
+
+        let mut summary = run.summary.into_inner().unwrap_or_else(|e| e.into_inner());
+        summary.cancelled = cancel.load(Ordering::Relaxed);
+        summary
+    }
+
+    async fn data_search_worker(&self, run: &SearchRun<'_>) {
+        let mut conn: Option<Client> = None;
+        let timeout = Duration::from_secs(run.search.table_timeout_seconds.max(1));
+
+        while !run.cancel.load(Ordering::Relaxed) {
+            let Some(plan) = run.queue.lock().ok().and_then(|mut q| q.pop_front()) else {
+                break;
+            };
+            let table = format!("{}.{}", plan.schema_name, plan.table_name);
+
+            if conn.is_none() {
+                match self
+                    .data_search_connection(run.connection_id, run.database)
+                    .await
+                {
+                    Ok(client) => conn = Some(client),
+                    Err(e) => {
+                        if let Ok(mut summary) = run.summary.lock() {
+                            summary.errors.push((table.clone(), e.to_string()));
+                        }
+                    }
+                }
+            }
+
+            let hits = match conn.as_mut() {
+                // Still counted as searched so progress reaches the total
+                None => Vec::new(),
+                Some(client) => {
+                    // Dedicated connections are dropped on timeout or cancel,
+                    // which aborts the running query
+                    let outcome = tokio::select! {
+                        result = tokio::time::timeout(timeout, search_table(client, &plan, run.search.sample_size)) => Some(result),
+                        _ = wait_for_cancel(run.cancel) => None,
+                    };
+                    match outcome {
+                        None => break,
+                        Some(Ok(Ok(hits))) => hits,
+                        Some(Ok(Err(e))) => {
+                            conn = None;
+                            if let Ok(mut summary) = run.summary.lock() {
+                                summary.errors.push((table, e.to_string()));
+                            }
+                            Vec::new()
+                        }
+                        Some(Err(_)) => {
+                            conn = None;
+                            if let Ok(mut summary) = run.summary.lock() {
+                                summary.timed_out.push(table);
+                            }
+                            Vec::new()
+                        }
+                    }
+                }
+            };
+
+            let progress = {
+                let Ok(mut summary) = run.summary.lock() else {
+                    break;
+                };
+                summary.tables_searched += 1;
+                summary.hits += hits.len();
+                DataSearchEvent::Progress {
+                    tables_searched: summary.tables_searched,
+                    tables_total: summary.tables_total,
+                }
+            };
+            for hit in hits {
+                let _ = run.events.send(DataSearchEvent::Hit(hit)).await;
+            }
+            let _ = run.events.send(progress).await;
+        }
+    }
+
+    async fn data_search_connection(
+        &self,
+        connection_id: &str,
+        database: &str,
+    ) -> Result<Client, ConnectionError> {
+        let mut client = self
+            .connection_manager
+            .create_dedicated_connection(connection_id)
+            .await?;
+        client
+            .simple_query(&format!("USE {}", quote_identifier(database)))
+            .await?;
+        Ok(client)
+    }
+}
+
+async fn search_table(
+    conn: &mut Client,
+    plan: &TableSearchPlan,
+    sample_size: usize,
+) -> Result<Vec<DataSearchHit>, ConnectionError> {
+    let counts = conn
+        .simple_query(&plan.count_query())
+        .await?
+        .into_row()
+        .await?;
+    let Some(counts) = counts else {
+        return Ok(Vec::new());
+    };
+
+    let mut hits = Vec::new();
+    for (i, (column, predicate)) in plan.columns.iter().enumerate() {
+        let row_count = counts.get::<i64, _>(i).unwrap_or(0);
+        if row_count == 0 {
+            continue;
+        }
+        let mut sample_keys = Vec::new();
+        if let Some(query) = plan
+            .sample_query(predicate, sample_size)
+            .filter(|_| sample_size > 0)
+        {
+            let rows = conn.simple_query(&query).await?.into_first_result().await?;
+            for row in rows.iter() {
+                sample_keys.push(
+                    (0..plan.key_columns.len())
+                        .map(|k| row.get::<&str, _>(k).map(str::to_string))
+                        .collect(),
+                );
+            }
+        }
+        hits.push(DataSearchHit {
+            schema_name: plan.schema_name.clone(),
+            table_name: plan.table_name.clone(),
+            column_name: column.clone(),
+            row_count,
+            key_columns: plan.key_columns.clone(),
+            sample_keys,
+        });
+    }
+    Ok(hits)
+}
+
+async fn wait_for_cancel(cancel: &AtomicBool) {
+    while !cancel.load(Ordering::Relaxed) {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema::ColumnInfo;
+
+    fn column(name: &str, data_type: &str, is_primary_key: bool) -> ColumnInfo {
+        ColumnInfo {
+            is_nullable: !is_primary_key,
+            is_primary_key,
+            ..ColumnInfo::for_test(name, data_type)
+        }
+    }
+
+    fn schema() -> SchemaInfo {
+        let table = |name: &str, table_type: &str, columns: Vec<ColumnInfo>| TableInfo {
+            table_type: table_type.to_string(),
+            ..TableInfo::for_test(name, columns)
+        };
+        SchemaInfo::for_test(
+            "Shop",
+            vec![
+                table(
+                    "Customers",
+                    "BASE TABLE",
+                    vec![
+                        column("Id", "int", true),
+                        column("Code", "nvarchar", false),
+                        column("RowGuid", "uniqueidentifier", false),
+                        column("CreatedAt", "datetime2", false),
+                    ],
+                ),
+                table(
+                    "Flags",
+                    "BASE TABLE",
+                    vec![column("IsActive", "bit", false)],
+                ),
+                table(
+                    "CustomerView",
+                    "VIEW",
+                    vec![column("Code", "nvarchar", false)],
+                ),
+            ],
+        )
+    }
+
+    fn search(value: &str) -> DataSearch {
+        DataSearch {
+            value: value.to_string(),
+            ..DataSearch::default()
+        }
+    }
+
+    #[test]
+    fn text_values_only_search_text_columns() {
+        let plans = plan_data_search(&schema(), &search("X0_42%"));
+        assert_eq!(plans.len(), 1);
+        assert_eq!(plans[0].table_name, "Customers");
+        assert_eq!(plans[0].key_columns, vec!["Id".to_string()]);
+        assert_eq!(
+            plans[0].columns,
+            vec![(
+                "Code".to_string(),
+                r"[Code] LIKE N'%X0\_42\%%' ESCAPE '\'".to_string()
+            )]
+        );
+
+        let exact = DataSearch {
+            exact_match: true,
+            ..search("O'Brien")
+        };
+        assert_eq!(
+            plan_data_search(&schema(), &exact)[0].columns[0].1,
+            "[Code] = N'O''Brien'"
+        );
+    }
+
+    #[test]
+    fn typed_values_add_compatible_columns() {
+        let columns = |value: &str| -> Vec<String> {
+            plan_data_search(&schema(), &search(value))[0]
+                .columns
+                .iter()
+                .map(|(_, p)| p.clone())
+                .collect()
+        };
+        assert_eq!(
+            columns("42"),
+            vec!["[Id] = 42", r"[Code] LIKE N'%42%' ESCAPE '\'"]
+        );
+
+        let guid = "{6F9619FF-8B86-D011-B42D-00C04FC964FF}";
+        assert_eq!(
+            columns(guid)[1],
+            "[RowGuid] = '6f9619ff-8b86-d011-b42d-00c04fc964ff'"
+        );
+
+        assert_eq!(
+            columns("2024-03-01")[1],
+            "[CreatedAt] >= '2024-03-01' AND [CreatedAt] < DATEADD(day, 1, CAST('2024-03-01' AS date))"
+        );
+        assert_eq!(
+            columns("2024-03-01 10:30:00")[1],
+            "[CreatedAt] = '2024-03-01T10:30:00'"
+        );
+    }
+
+    #[test]
+    fn queries_count_all_columns_in_one_scan() {
+        let plans = plan_data_search(&schema(), &search("42"));
+        assert_eq!(
+            plans[0].count_query(),
+            "SELECT COUNT_BIG(CASE WHEN [Id] = 42 THEN 1 END), COUNT_BIG(CASE WHEN [Code] LIKE N'%42%' ESCAPE '\\' THEN 1 END) \
+             FROM [dbo].[Customers] WHERE ([Id] = 42) OR ([Code] LIKE N'%42%' ESCAPE '\\')"
+        );
+        assert_eq!(
+            plans[0].sample_query("[Id] = 42", 5).unwrap(),
+            "SELECT TOP (5) CONVERT(nvarchar(4000), [Id]) FROM [dbo].[Customers] WHERE [Id] = 42 ORDER BY [Id]"
+        );
+    }
+}
@@ -0,0 +1,547 @@
+// Object Dependencies
+// "What uses this" / "what does this use" graphs from sys.sql_expression_dependencies, foreign keys and virtual references
+
+use crate::db::connection::ConnectionError;
+use crate::db::schema::SchemaMetadataManager;
+use crate::db::scripting::drop_order;
+use crate::sql::quote_identifier;
+use crate::storage::VirtualReference;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// The table, column, view or routine to analyse
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DependencyTarget {
+    pub schema_name: String,
+    pub object_name: String,
+    pub column_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyDirection {
+    /// What does this use
+    Uses,
+    /// What uses this
+    UsedBy,
+    Both,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyKind {
+    /// A view, routine, trigger, computed column or constraint body
+    Expression,
+    ForeignKey,
+    Virtual,
+}
+
+/// One end of a reference, optionally narrowed to a column
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DependencyObject {
+    pub server_name: Option<String>,
+    /// Set only for references into another database
+    pub database_name: Option<String>,
+    pub schema_name: String,
+    pub object_name: String,
+    pub column_name: Option<String>,
+}
+
+impl DependencyObject {
+    fn local(schema_name: &str, object_name: &str, column_name: Option<&str>) -> Self {
+        Self {
+            server_name: None,
+            database_name: None,
+            schema_name: schema_name.to_string(),
+            object_name: object_name.to_string(),
+            column_name: column_name.map(str::to_string),
+        }
+    }
+
+    /// Case-insensitive identity, e.g. `[otherdb].[dbo].[orders].[id]`
+    fn id(&self) -> String {
+        let mut parts: Vec<&str> = Vec::new();
+        parts.extend(self.server_name.as_deref());
+        parts.extend(self.database_name.as_deref());
+        parts.push(&self.schema_name);
+        parts.push(&self.object_name);
+        parts.extend(self.column_name.as_deref());
+        parts
+            .iter()
+            .map(|p| quote_identifier(&p.to_lowercase()))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    fn object(&self) -> Self {
+        Self {
+            column_name: None,
+            ..self.clone()
+        }
+    }
+
+    fn is_local(&self) -> bool {
+        self.server_name.is_none() && self.database_name.is_none()
+    }
+}
+
+/// A single referencing -> referenced edge as read from the catalog
+#[derive(Debug, Clone)]
+pub struct ObjectReference {
+    pub referencing: DependencyObject,
+    pub referencing_type: Option<String>,
+    pub referenced: DependencyObject,
+    pub referenced_type: Option<String>,
+    pub kind: DependencyKind,
+    /// Constraint name for foreign keys
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DependencyNode {
+    pub id: String,
+    #[serde(flatten)]
+    pub object: DependencyObject,
+    /// `sys.objects.type_desc`, when known
+    pub object_type: Option<String>,
+    /// Hops from the root; 0 for the root itself
+    pub depth: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DependencyEdge {
+    /// Referencing node id
+    pub from: String,
+    /// Referenced node id
+    pub to: String,
+    pub kind: DependencyKind,
+    pub name: Option<String>,
+    /// The referencer uses the table but SQL Server did not record which
+    /// columns, so it may not touch the requested column
+    pub column_unknown: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DependencyGraph {
+    pub root: String,
+    pub nodes: Vec<DependencyNode>,
+    pub edges: Vec<DependencyEdge>,
+    /// Local objects in the graph, dependents first
+    pub drop_order: Vec<String>,
+    /// Local objects in the graph, dependencies first
+    pub create_order: Vec<String>,
+}
+
+/// Walk `references` from `target` in the given direction(s), up to `max_depth` hops
+pub fn dependency_graph(
+    references: &[ObjectReference],
+    target: &DependencyTarget,
+    direction: DependencyDirection,
+    max_depth: Option<u32>,
+) -> DependencyGraph {
+    let root = DependencyObject::local(
+        &target.schema_name,
+        &target.object_name,
+        target.column_name.as_deref(),
+    );
+    let root_id = root.id();
+
+    let mut types: HashMap<String, String> = HashMap::new();
+    for r in references {
+        if let Some(t) = &r.referencing_type {
+            types.insert(r.referencing.object().id(), t.clone());
+        }
+        if let Some(t) = &r.referenced_type {
+            types.insert(r.referenced.object().id(), t.clone());
+        }
+    }
+
+    let mut nodes: Vec<DependencyNode> = Vec::new();
+    let mut node_ids: HashSet<String> = HashSet::new();
+    let mut edges: Vec<DependencyEdge> = Vec::new();
+    let mut edge_ids: HashSet<(String, String, Option<String>)> = HashSet::new();
+
+    let mut add_node = |object: &DependencyObject, depth: u32, nodes: &mut Vec<DependencyNode>| {
+        let id = object.id();
+        if node_ids.insert(id.clone()) {
+            nodes.push(DependencyNode {
+                id,
+                object: object.clone(),
+                object_type: types.get(&object.object().id()).cloned(),
+                depth,
+            });
+        }
+    };
+    add_node(&root, 0, &mut nodes);
+
+    let walks: &[bool] = match direction {
+        DependencyDirection::Uses => &[true],
+        DependencyDirection::UsedBy => &[false],
+        DependencyDirection::Both => &[true, false],
+    };
+    for &uses in walks {
+        let mut queue = VecDeque::from([(root.clone(), 0u32)]);
+        let mut visited: HashSet<String> = HashSet::from([root_id.clone()]);
+        while let Some((current, depth)) = queue.pop_front() {
+            if max_depth.is_some_and(|max| depth >= max) {
+                continue;
+            }
+            let current_object = current.object().id();
+            for r in references {
+                let (near, far) = if uses {
+                    (&r.referencing, &r.referenced)
+                } else {
+                    (&r.referenced, &r.referencing)
+                };
+                if near.object().id() != current_object {
+                    continue;
+                }
+                // Narrow to the current column when one is known on both sides
+                let column_unknown = match (&current.column_name, &near.column_name) {
+                    (Some(a), Some(b)) if !a.eq_ignore_ascii_case(b) => continue,
+                    (Some(_), None) => true,
+                    _ => false,
+                };
+
+                let (from, to) = if uses {
+                    (&current, far)
+                } else {
+                    (far, &current)
+                };
+                if edge_ids.insert((from.id(), to.id(), r.name.clone())) {
+                    edges.push(DependencyEdge {
+                        from: from.id(),
+                        to: to.id(),
+                        kind: r.kind,
+                        name: r.name.clone(),
+                        column_unknown,
+                    });
+                }
+                add_node(far, depth + 1, &mut nodes);
+                if far.is_local() && visited.insert(far.id()) {
+                    queue.push_back((far.clone(), depth + 1));
+                }
+            }
+        }
+    }
+
+    // Script ordering works on whole local objects
+    let mut objects: Vec<String> = Vec::new();
+    for node in nodes.iter().filter(|n| n.object.is_local()) {
+        let id = node.object.object().id();
+        if !objects.contains(&id) {
+            objects.push(id);
+        }
+    }
+    let positions: HashMap<&str, usize> = objects
+        .iter()
+        .enumerate()
+        .map(|(i, id)| (id.as_str(), i))
+        .collect();
+    let object_of: HashMap<&str, String> = nodes
+        .iter()
+        .map(|n| (n.id.as_str(), n.object.object().id()))
+        .collect();
+    let pairs: Vec<(usize, usize)> = edges
+        .iter()
+        .filter_map(|e| {
+            Some((
+                *positions.get(object_of.get(e.from.as_str())?.as_str())?,
+                *positions.get(object_of.get(e.to.as_str())?.as_str())?,
+            ))
+        })
+        .collect();
+    let drop_order: Vec<String> = drop_order(objects.len(), &pairs)
+        .into_iter()
+        .map(|i| objects[i].clone())
+        .collect();
+    let create_order = drop_order.iter().rev().cloned().collect();
+
+    DependencyGraph {
+        root: root_id,
+        nodes,
+        edges,
+        drop_order,
+        create_order,
+    }
+}
+
+impl SchemaMetadataManager {
+    /// Build the dependency graph for an object or column in `database`
+    pub async fn object_dependencies(
+        &self,
+        connection_id: &str,
+        database: &str,
+        virtual_references: &[VirtualReference],
+        target: &DependencyTarget,
+        direction: DependencyDirection,
+        max_depth: Option<u32>,
+    ) -> Result<DependencyGraph, ConnectionError> {
+        let mut references = self.fetch_references(connection_id, database).await?;
+        references.extend(virtual_references.iter().flat_map(|v| {
+            v.column_pairs()
+                .into_iter()
+                .map(move |c| ObjectReference {
+                    referencing: DependencyObject::local(
+                        &v.source_schema,
+                        &v.source_table,
+                        Some(&c.source_column),
+                    ),
+                    referencing_type: None,
+                    referenced: DependencyObject::local(
+                        &v.target_schema,
+                        &v.target_table,
+                        Some(&c.target_column),
+                    ),
+                    referenced_type: None,
+                    kind: DependencyKind::Virtual,
+                    name: None,
+                })
+        }));
+        Ok(dependency_graph(&references, target, direction, max_depth))
+    }
+
+    /// Read expression dependencies and foreign key columns for the whole database
+    async fn fetch_references(
+        &self,
+        connection_id: &str,
+        database: &str,
+    ) -> Result<Vec<ObjectReference>, ConnectionError> {
+        let pool = self.connection_manager.connect(connection_id).await?;
+        let mut conn = pool.get().await?;
+        conn.simple_query(&format!("USE {}", quote_identifier(database)))
+            .await?;
+
+        // Unqualified names SQL Server could not resolve are assumed to be in dbo
+        let expression_query = r#"
+            SELECT
+                OBJECT_SCHEMA_NAME(d.referencing_id),
+                OBJECT_NAME(d.referencing_id),
+                CASE WHEN d.referencing_minor_id > 0 THEN COL_NAME(d.referencing_id, d.referencing_minor_id) END,
+                o.type_desc,
+                d.referenced_server_name,
+                d.referenced_database_name,
+                COALESCE(d.referenced_schema_name, OBJECT_SCHEMA_NAME(d.referenced_id), 'dbo'),
+                d.referenced_entity_name,
+                CASE WHEN d.referenced_minor_id > 0 THEN COL_NAME(d.referenced_id, d.referenced_minor_id) END,
+                ro.type_desc
+            FROM sys.sql_expression_dependencies d
+            JOIN sys.objects o ON o.object_id = d.referencing_id
+            LEFT JOIN sys.objects ro ON ro.object_id = d.referenced_id
+            WHERE d.referencing_class = 1 AND d.referenced_class = 1
+        "#;
+        let rows = conn
+            .simple_query(expression_query)
+            .await?
+            .into_first_result()
+            .await?;
+
+        let mut references: Vec<ObjectReference> = rows
+            .iter()
+            .filter_map(|row| {
+                let database_name = row
+                    .get::<&str, _>(5)
+                    .filter(|db| !db.eq_ignore_ascii_case(database))
+                    .map(str::to_string);
+                Some(ObjectReference {
+                    referencing: DependencyObject::local(
+                        row.get::<&str, _>(0)?,
+                        row.get::<&str, _>(1)?,
+                        row.get::<&str, _>(2),
+                    ),
+                    referencing_type: row.get::<&str, _>(3).map(str::to_string),
+                    referenced: DependencyObject {
+                        server_name: row.get::<&str, _>(4).map(str::to_string),
+                        database_name,
+                        schema_name: row.get::<&str, _>(6)?.to_string(),
+                        object_name: row.get::<&str, _>(7)?.to_string(),
+                        column_name: row.get::<&str, _>(8).map(str::to_string),
+                    },
+                    referenced_type: row.get::<&str, _>(9).map(str::to_string),
+                    kind: DependencyKind::Expression,
+                    name: None,
+                })
+            })
+            .collect();
+
+        let foreign_key_query = r#"
+            SELECT
+                OBJECT_SCHEMA_NAME(fkc.parent_object_id),
+                OBJECT_NAME(fkc.parent_object_id),
+                COL_NAME(fkc.parent_object_id, fkc.parent_column_id),
+                OBJECT_SCHEMA_NAME(fkc.referenced_object_id),
+                OBJECT_NAME(fkc.referenced_object_id),
+                COL_NAME(fkc.referenced_object_id, fkc.referenced_column_id),
+                fk.name
+            FROM sys.foreign_key_columns fkc
+            JOIN sys.foreign_keys fk ON fk.object_id = fkc.constraint_object_id
+        "#;
+        let rows = conn
+            .simple_query(foreign_key_query)
+            .await?
+            .into_first_result()
+            .await?;
+
+        references.extend(rows.iter().filter_map(|row| {
+            Some(ObjectReference {
+                referencing: DependencyObject::local(
+                    row.get::<&str, _>(0)?,
+                    row.get::<&str, _>(1)?,
+                    row.get::<&str, _>(2),
+                ),
+                referencing_type: Some("USER_TABLE".to_string()),
+                referenced: DependencyObject::local(
+                    row.get::<&str, _>(3)?,
+                    row.get::<&str, _>(4)?,
+                    row.get::<&str, _>(5),
+                ),
+                referenced_type: Some("USER_TABLE".to_string()),
+                kind: DependencyKind::ForeignKey,
+                name: row.get::<&str, _>(6).map(str::to_string),
+            })
+        }));
+
+        Ok(references)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reference(
+        referencing: (&str, Option<&str>),
+        referenced: (&str, Option<&str>),
+        kind: DependencyKind,
+    ) -> ObjectReference {
+        ObjectReference {
+            referencing: DependencyObject::local("dbo", referencing.0, referencing.1),
+            referencing_type: None,
+            referenced: DependencyObject::local("dbo", referenced.0, referenced.1),
+            referenced_type: None,
+            kind,
+            name: None,
+        }
+    }
+
+    fn target(object_name: &str, column_name: Option<&str>) -> DependencyTarget {
+        DependencyTarget {
+            schema_name: "dbo".to_string(),
+            object_name: object_name.to_string(),
+            column_name: column_name.map(str::to_string),
+        }
+    }
+
+    fn sample() -> Vec<ObjectReference> {
+        vec![
+            // vOrders selects Orders.Total (schema-bound, so the column is recorded)
+            reference(
+                ("vOrders", None),
+                ("Orders", Some("Total")),
+                DependencyKind::Expression,
+            ),
+            // GetOrders uses the view
+            reference(
+                ("GetOrders", None),
+                ("vOrders", None),
+                DependencyKind::Expression,
+            ),
+            // Report uses Orders without column detail
+            reference(
+                ("Report", None),
+                ("Orders", None),
+                DependencyKind::Expression,
+            ),
+            reference(
+                ("OrderLines", Some("OrderId")),
+                ("Orders", Some("Id")),
+                DependencyKind::ForeignKey,
+            ),
+        ]
+    }
+
+    #[test]
+    fn used_by_is_transitive_and_ordered_for_scripts() {
+        let graph = dependency_graph(
+            &sample(),
+            &target("Orders", None),
+            DependencyDirection::UsedBy,
+            None,
+        );
+        let ids: Vec<&str> = graph.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert!(ids.contains(&"[dbo].[getorders]"));
+        assert!(ids.contains(&"[dbo].[orderlines].[orderid]"));
+        let depth = |id: &str| graph.nodes.iter().find(|n| n.id == id).unwrap().depth;
+        assert_eq!(depth("[dbo].[vorders]"), 1);
+        assert_eq!(depth("[dbo].[getorders]"), 2);
+
+        let position = |id: &str| graph.drop_order.iter().position(|o| o == id).unwrap();
+        assert!(position("[dbo].[getorders]") < position("[dbo].[vorders]"));
+        assert!(position("[dbo].[vorders]") < position("[dbo].[orders]"));
+        assert_eq!(
+            graph.create_order.first().map(String::as_str),
+            Some("[dbo].[orders]")
+        );
+    }
+
+    #[test]
+    fn column_targets_skip_other_columns_and_flag_unknown_ones() {
+        let graph = dependency_graph(
+            &sample(),
+            &target("Orders", Some("Total")),
+            DependencyDirection::UsedBy,
+            Some(1),
+        );
+        let ids: Vec<&str> = graph.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "[dbo].[orders].[total]",
+                "[dbo].[vorders]",
+                "[dbo].[report]"
+            ]
+        );
+        assert!(!graph.edges[0].column_unknown);
+        assert!(graph.edges[1].column_unknown);
+    }
+
+    #[test]
+    fn uses_follows_references_outwards() {
+        let mut references = sample();
+        references.push(ObjectReference {
+            referencing: DependencyObject::local("dbo", "GetOrders", None),
+            referencing_type: Some("SQL_STORED_PROCEDURE".to_string()),
+            referenced: DependencyObject {
+                server_name: None,
+                database_name: Some("Archive".to_string()),
+                schema_name: "dbo".to_string(),
+                object_name: "OldOrders".to_string(),
+                column_name: None,
+            },
+            referenced_type: None,
+            kind: DependencyKind::Expression,
+            name: None,
+        });
+        let graph = dependency_graph(
+            &references,
+            &target("GetOrders", None),
+            DependencyDirection::Uses,
+            None,
+        );
+        let ids: Vec<&str> = graph.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "[dbo].[getorders]",
+                "[dbo].[vorders]",
+                "[archive].[dbo].[oldorders]",
+                "[dbo].[orders].[total]"
+            ]
+        );
+        assert_eq!(
+            graph.nodes[0].object_type.as_deref(),
+            Some("SQL_STORED_PROCEDURE")
+        );
+        assert!(!graph.drop_order.iter().any(|id| id.contains("archive")));
+    }
+}
@@ -0,0 +1,740 @@
+// ER Diagrams
+// Renders tables, key columns and relationships (declared and virtual) as Mermaid, DOT, PlantUML or SVG
+
+use crate::db::schema::{SchemaInfo, TableInfo};
+use crate::db::scripting::ObjectRef;
+use crate::storage::VirtualReference;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagramFormat {
+    Mermaid,
+    Dot,
+    PlantUml,
+    /// DOT-equivalent graph laid out in Rust and rendered as SVG
+    Svg,
+}
+
+/// Which part of the model to draw
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiagramOptions {
+    pub schema_name: Option<String>,
+    /// Start from this table and follow relationships in either direction
+    pub seed: Option<ObjectRef>,
+    /// How many relationships away from the seed to include
+    pub hops: u32,
+    pub include_virtual: bool,
+    /// Draw every column instead of only key columns
+    pub all_columns: bool,
+}
+
+impl Default for DiagramOptions {
+    fn default() -> Self {
+        Self {
+            schema_name: None,
+            seed: None,
+            hops: 1,
+            include_virtual: true,
+            all_columns: false,
+        }
+    }
+}
+
+/// How many rows on one side of a relationship match a row on the other
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cardinality {
+    ZeroOrOne,
+    ExactlyOne,
+    ZeroOrMany,
+}
+
+type TableKey = (String, String);
+
+fn key(schema_name: &str, table_name: &str) -> TableKey {
+    (schema_name.to_lowercase(), table_name.to_lowercase())
+}
+
+#[derive(Debug, Clone)]
+struct Entity<'a> {
+    table: &'a TableInfo,
+    /// (column, data type, marker) rows to draw
+    columns: Vec<(String, String, &'static str)>,
+}
+
+impl Entity<'_> {
+    fn name(&self) -> String {
+        format!("{}.{}", self.table.schema_name, self.table.table_name)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Edge {
+    from: TableKey,
+    to: TableKey,
+    label: String,
+    is_virtual: bool,
+    /// Rows in `from` per row in `to`
+    from_cardinality: Cardinality,
+    /// Rows in `to` per row in `from`
+    to_cardinality: Cardinality,
+}
+
+struct Diagram<'a> {
+    entities: BTreeMap<TableKey, Entity<'a>>,
+    edges: Vec<Edge>,
+}
+
+/// Render the diagram for `schema` plus the database's virtual references
+pub fn generate_er_diagram(
+    schema: &SchemaInfo,
+    virtual_references: &[VirtualReference],
+    options: &DiagramOptions,
+    format: DiagramFormat,
+) -> String {
+    let diagram = build_diagram(schema, virtual_references, options);
+    match format {
+        DiagramFormat::Mermaid => render_mermaid(&diagram),
+        DiagramFormat::Dot => render_dot(&diagram),
+        DiagramFormat::PlantUml => render_plantuml(&diagram),
+        DiagramFormat::Svg => render_svg(&diagram),
+    }
+}
+
+fn build_diagram<'a>(
+    schema: &'a SchemaInfo,
+    virtual_references: &[VirtualReference],
+    options: &DiagramOptions,
+) -> Diagram<'a> {
+    let tables: HashMap<TableKey, &TableInfo> = schema
+        .tables
+        .iter()
+        .filter(|t| t.table_type != "VIEW")
+        .filter(|t| {
+            options
+                .schema_name
+                .as_ref()
+                .is_none_or(|s| t.schema_name.eq_ignore_ascii_case(s))
+        })
+        .map(|t| (key(&t.schema_name, &t.table_name), t))
+        .collect();
+
+    // (from, to, label, from columns, is_virtual)
+    let mut links: Vec<(TableKey, TableKey, String, Vec<String>, bool)> = Vec::new();
+    let mut declared: BTreeMap<(TableKey, String), (TableKey, Vec<String>)> = BTreeMap::new();
+    let mut relationships: Vec<_> = schema.relationships.iter().collect();
+    relationships.sort_by_key(|r| r.ordinal_position);
+    for r in relationships {
+        let entry = declared
+            .entry((
+                key(&r.source_schema_name, &r.source_table_name),
+                r.constraint_name.clone(),
+            ))
+            .or_insert_with(|| (key(&r.target_schema_name, &r.target_table_name), Vec::new()));
+        entry.1.push(r.source_column_name.clone());
+    }
+    for ((from, name), (to, columns)) in declared {
+        links.push((from, to, name, columns, false));
+    }
+    if options.include_virtual {
+        for v in virtual_references {
+            let columns: Vec<String> = v
+                .column_pairs()
+                .into_iter()
+                .map(|c| c.source_column)
+                .collect();
+            links.push((
+                key(&v.source_schema, &v.source_table),
+                key(&v.target_schema, &v.target_table),
+                columns.join(", "),
+                columns,
+                true,
+            ));
+        }
+    }
+    links.retain(|(from, to, ..)| tables.contains_key(from) && tables.contains_key(to));
+
+    let included: BTreeSet<TableKey> = match &options.seed {
+        Some(seed) => {
+            let mut seen = BTreeSet::new();
+            let start = key(&seed.schema_name, &seed.object_name);
+            if tables.contains_key(&start) {
+                let mut queue = VecDeque::from([(start.clone(), 0)]);
+                seen.insert(start);
+                while let Some((table, depth)) = queue.pop_front() {
+                    if depth >= options.hops {
+                        continue;
+                    }
+                    for (from, to, ..) in &links {
+                        let next = if *from == table {
+                            to
+                        } else if *to == table {
+                            from
+                        } else {
+                            continue;
+                        };
+                        if seen.insert(next.clone()) {
+                            queue.push_back((next.clone(), depth + 1));
+                        }
+                    }
+                }
+            }
+            seen
+        }
+        None => tables.keys().cloned().collect(),
+    };
+    links.retain(|(from, to, ..)| included.contains(from) && included.contains(to));
+
+    // Key columns: primary key plus anything referencing another table
+    let mut fk_columns: HashMap<TableKey, BTreeSet<String>> = HashMap::new();
+    for (from, _, _, columns, _) in &links {
+        fk_columns
+            .entry(from.clone())
+            .or_default()
+            .extend(columns.iter().map(|c| c.to_lowercase()));
+    }
+
+    let entities = included
+        .iter()
+        .map(|k| {
+            let table = tables[k];
+            let mut columns: Vec<_> = table.columns.iter().collect();
+            columns.sort_by_key(|c| c.ordinal_position);
+            let fks = fk_columns.get(k);
+            let rows = columns
+                .into_iter()
+                .filter_map(|c| {
+                    let is_fk = fks.is_some_and(|f| f.contains(&c.name.to_lowercase()));
+                    let marker = match (c.is_primary_key, is_fk) {
+                        (true, true) => "PK,FK",
+                        (true, false) => "PK",
+                        (false, true) => "FK",
+                        (false, false) if options.all_columns => "",
+                        _ => return None,
+                    };
+                    Some((c.name.clone(), c.data_type.clone(), marker))
+                })
+                .collect();
+            (
+                k.clone(),
+                Entity {
+                    table,
+                    columns: rows,
+                },
+            )
+        })
+        .collect();
+
+    let edges = links
+        .into_iter()
+        .map(|(from, to, label, columns, is_virtual)| {
+            let table = tables[&from];
+            let source_columns: Vec<_> = columns
+                .iter()
+                .filter_map(|name| {
+                    table
+                        .columns
+                        .iter()
+                        .find(|c| c.name.eq_ignore_ascii_case(name))
+                })
+                .collect();
+            let optional = source_columns.iter().any(|c| c.is_nullable);
+            let primary_key: BTreeSet<String> = table
+                .columns
+                .iter()
+                .filter(|c| c.is_primary_key)
+                .map(|c| c.name.to_lowercase())
+                .collect();
+            let referencing: BTreeSet<String> = columns.iter().map(|c| c.to_lowercase()).collect();
+            let unique = (!primary_key.is_empty() && primary_key == referencing)
+                || schema.indexes.iter().any(|i| {
+                    i.index.is_unique
+                        && key(&i.schema_name, &i.table_name) == from
+                        && i.index
+                            .key_columns
+                            .iter()
+                            .map(|c| c.name.to_lowercase())
+                            .collect::<BTreeSet<_>>()
+                            == referencing
+                });
+            Edge {
+                from,
+                to,
+                label,
+                is_virtual,
+                from_cardinality: if unique {
+                    Cardinality::ZeroOrOne
+                } else {
+                    Cardinality::ZeroOrMany
+                },
+                to_cardinality: if optional {
+                    Cardinality::ZeroOrOne
+                } else {
+                    Cardinality::ExactlyOne
+                },
+            }
+        })
+        .collect();
+
+    Diagram { entities, edges }
+}
+
+/// Identifier safe for Mermaid, PlantUML aliases and DOT node ids
+fn node_id(k: &TableKey, diagram: &Diagram) -> String {
+    diagram.entities[k]
+        .name()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+fn render_mermaid(diagram: &Diagram) -> String {
+    let mut out = String::from("erDiagram\n");
+    for (k, entity) in &diagram.entities {
+        out.push_str(&format!("    {} {{\n", node_id(k, diagram)));
+        for (name, data_type, marker) in &entity.columns {
+            let data_type: String = data_type.chars().filter(|c| !c.is_whitespace()).collect();
+            out.push_str(&format!("        {} {}", data_type, name.replace(' ', "_")));
+            if !marker.is_empty() {
+                out.push_str(&format!(" {}", marker));
+            }
+            out.push('\n');
+        }
+        out.push_str("    }\n");
+    }
+    for edge in &diagram.edges {
+        let left = match edge.from_cardinality {
+            Cardinality::ZeroOrOne => "|o",
+            Cardinality::ExactlyOne => "||",
+            Cardinality::ZeroOrMany => "}o",
+        };
+        let right = match edge.to_cardinality {
+            Cardinality::ZeroOrOne => "o|",
+            _ => "||",
+        };
+        out.push_str(&format!(
+            "    {} {}{}{} {} : \"{}{}\"\n",
+            node_id(&edge.from, diagram),
+            left,
+            if edge.is_virtual { ".." } else { "--" },
+            right,
+            node_id(&edge.to, diagram),
+            edge.label.replace('"', "'"),
+            if edge.is_virtual { " (virtual)" } else { "" }
+        ));
+    }
+    out
+}
+
+fn dot_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '{' | '}' | '|' | '<' | '>' | '"' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn render_dot(diagram: &Diagram) -> String {
+    let mut out = String::from(
+        "digraph ER {\n    rankdir=LR;\n    node [shape=record, fontname=\"Helvetica\", fontsize=10];\n    edge [fontname=\"Helvetica\", fontsize=9];\n",
+    );
+    for (k, entity) in &diagram.entities {
+        let rows: Vec<String> = entity
+            .columns
+            .iter()
+            .map(|(name, data_type, marker)| {
+                let prefix = if marker.is_empty() {
+                    String::new()
+                } else {
+                    format!("{} ", marker)
+                };
+                dot_escape(&format!("{}{} : {}", prefix, name, data_type)) + "\\l"
+            })
+            .collect();
+        out.push_str(&format!(
+            "    {} [label=\"{{{}|{}}}\"];\n",
+            node_id(k, diagram),
+            dot_escape(&entity.name()),
+            rows.concat()
+        ));
+    }
+    for edge in &diagram.edges {
+        let tail = match edge.from_cardinality {
+            Cardinality::ZeroOrMany => "crowodot",
+            _ => "teeodot",
+        };
+        let head = match edge.to_cardinality {
+            Cardinality::ZeroOrOne => "teeodot",
+            _ => "teetee",
+        };
+        out.push_str(&format!(
+            "    {} -> {} [label=\"{}\", dir=both, arrowtail={}, arrowhead={}{}];\n",
+            node_id(&edge.from, diagram),
+            node_id(&edge.to, diagram),
+            dot_escape(&edge.label),
+            tail,
+            head,
+            if edge.is_virtual {
+                ", style=dashed"
+            } else {
+                ""
+            }
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn render_plantuml(diagram: &Diagram) -> String {
+    let mut out = String::from("@startuml\nhide circle\nskinparam linetype ortho\n\n");
+    for (k, entity) in &diagram.entities {
+        out.push_str(&format!(
+            "entity \"{}\" as {} {{\n",
+            entity.name(),
+            node_id(k, diagram)
+        ));
+        let (keys, others): (Vec<_>, Vec<_>) = entity
+            .columns
+            .iter()
+            .partition(|(_, _, m)| m.starts_with("PK"));
+        for (name, data_type, marker) in &keys {
+            out.push_str(&format!("  * {} : {} <<{}>>\n", name, data_type, marker));
+        }
+        if !keys.is_empty() && !others.is_empty() {
+            out.push_str("  --\n");
+        }
+        for (name, data_type, marker) in &others {
+            out.push_str(&format!("  {} : {}", name, data_type));
+            if !marker.is_empty() {
+                out.push_str(&format!(" <<{}>>", marker));
+            }
+            out.push('\n');
+        }
+        out.push_str("}\n");
+    }
+    out.push('\n');
+    for edge in &diagram.edges {
+        let left = match edge.from_cardinality {
+            Cardinality::ZeroOrOne => "|o",
+            Cardinality::ExactlyOne => "||",
+            Cardinality::ZeroOrMany => "}o",
+        };
+        let right = match edge.to_cardinality {
+            Cardinality::ZeroOrOne => "o|",
+            _ => "||",
+        };
+        out.push_str(&format!(
+            "{} {}{}{} {} : {}{}\n",
+            node_id(&edge.from, diagram),
+            left,
+            if edge.is_virtual { ".." } else { "--" },
+            right,
+            node_id(&edge.to, diagram),
+            edge.label,
+            if edge.is_virtual { " (virtual)" } else { "" }
+        ));
+    }
+    out.push_str("@enduml\n");
+    out
+}
+
+const CHAR_WIDTH: f64 = 7.0;
+const ROW_HEIGHT: f64 = 18.0;
+const H_GAP: f64 = 40.0;
+const V_GAP: f64 = 70.0;
+const MARGIN: f64 = 20.0;
+
+pub(crate) fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Layered layout: referenced tables above the tables that reference them,
+/// ordered within each layer by the average position of their parents
+fn render_svg(diagram: &Diagram) -> String {
+    let keys: Vec<&TableKey> = diagram.entities.keys().collect();
+    let index: HashMap<&TableKey, usize> = keys.iter().enumerate().map(|(i, k)| (*k, i)).collect();
+    let n = keys.len();
+
+    // Longest path from a referenced table; bounded by n passes so cycles terminate
+    let mut layer = vec![0usize; n];
+    for _ in 0..n {
+        let mut changed = false;
+        for edge in diagram.edges.iter().filter(|e| e.from != e.to) {
+            let (from, to) = (index[&edge.from], index[&edge.to]);
+            if layer[from] < layer[to] + 1 && layer[to] + 1 < n {
+                layer[from] = layer[to] + 1;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let layer_count = layer.iter().max().map_or(0, |m| m + 1);
+    let mut layers: Vec<Vec<usize>> = vec![Vec::new(); layer_count];
+    for (i, &l) in layer.iter().enumerate() {
+        layers[l].push(i);
+    }
+
+    let sizes: Vec<(f64, f64)> = keys
+        .iter()
+        .map(|k| {
+            let entity = &diagram.entities[*k];
+            let widest = entity
+                .columns
+                .iter()
+                .map(|(name, data_type, marker)| name.len() + data_type.len() + marker.len() + 4)
+                .chain(std::iter::once(entity.name().len()))
+                .max()
+                .unwrap_or(0);
+            (
+                widest as f64 * CHAR_WIDTH + 16.0,
+                (entity.columns.len() + 1) as f64 * ROW_HEIGHT + 10.0,
+            )
+        })
+        .collect();
+
+    let mut positions = vec![(0.0, 0.0); n];
+    let mut y = MARGIN;
+    let mut width: f64 = 0.0;
+    for (l, members) in layers.iter_mut().enumerate() {
+        if l > 0 {
+            let centre = |i: usize| {
+                let parents: Vec<f64> = diagram
+                    .edges
+                    .iter()
+                    .filter(|e| index[&e.from] == i && layer[index[&e.to]] < l)
+                    .map(|e| positions[index[&e.to]].0 + sizes[index[&e.to]].0 / 2.0)
+                    .collect();
+                if parents.is_empty() {
+                    f64::MAX
+                } else {
+                    parents.iter().sum::<f64>() / parents.len() as f64
+                }
+            };
+            members.sort_by(|a, b| centre(*a).total_cmp(&centre(*b)));
+        }
+        let mut x = MARGIN;
+        let mut height: f64 = 0.0;
+        for &i in members.iter() {
+            positions[i] = (x, y);
+            x += sizes[i].0 + H_GAP;
+            height = height.max(sizes[i].1);
+        }
+        width = width.max(x - H_GAP + MARGIN);
+        y += height + V_GAP;
+    }
+    let height = y - V_GAP + MARGIN;
+
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w:.0}\" height=\"{h:.0}\" viewBox=\"0 0 {w:.0} {h:.0}\" font-family=\"Helvetica, Arial, sans-serif\" font-size=\"11\">\n",
+        w = width.max(MARGIN * 2.0),
+        h = height.max(MARGIN * 2.0)
+    );
+    out.push_str("  <defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"8\" markerHeight=\"8\" orient=\"auto-start-reverse\"><path d=\"M 0 0 L 10 5 L 0 10 z\" fill=\"#555\"/></marker></defs>\n");
+
+    for edge in &diagram.edges {
+        let (from, to) = (index[&edge.from], index[&edge.to]);
+        let ((fx, fy), (fw, fh)) = (positions[from], sizes[from]);
+        let ((tx, ty), (tw, th)) = (positions[to], sizes[to]);
+        let (x1, y1, x2, y2) = if from == to {
+            (fx + fw, fy + ROW_HEIGHT, fx + fw, fy + fh - 6.0)
+        } else if fy > ty {
+            (fx + fw / 2.0, fy, tx + tw / 2.0, ty + th)
+        } else if fy < ty {
+            (fx + fw / 2.0, fy + fh, tx + tw / 2.0, ty)
+        } else if fx < tx {
+            (fx + fw, fy + fh / 2.0, tx, ty + th / 2.0)
+        } else {
+            (fx, fy + fh / 2.0, tx + tw, ty + th / 2.0)
+        };
+        out.push_str(&format!(
+            "  <line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"#555\"{} marker-end=\"url(#arrow)\"><title>{}</title></line>\n",
+            x1,
+            y1,
+            x2,
+            y2,
+            if edge.is_virtual { " stroke-dasharray=\"5,4\"" } else { "" },
+            xml_escape(&edge.label)
+        ));
+        out.push_str(&format!(
+            "  <text x=\"{:.1}\" y=\"{:.1}\" fill=\"#555\" font-size=\"9\" text-anchor=\"middle\">{}</text>\n",
+            (x1 + x2) / 2.0,
+            (y1 + y2) / 2.0 - 3.0,
+            xml_escape(&edge.label)
+        ));
+    }
+
+    for (i, k) in keys.iter().enumerate() {
+        let entity = &diagram.entities[*k];
+        let ((x, y), (w, h)) = (positions[i], sizes[i]);
+        out.push_str(&format!(
+            "  <g>\n    <rect x=\"{x:.1}\" y=\"{y:.1}\" width=\"{w:.1}\" height=\"{h:.1}\" fill=\"#fff\" stroke=\"#333\"/>\n    <rect x=\"{x:.1}\" y=\"{y:.1}\" width=\"{w:.1}\" height=\"{r:.1}\" fill=\"#e8eef7\" stroke=\"#333\"/>\n    <text x=\"{tx:.1}\" y=\"{ty:.1}\" font-weight=\"bold\">{name}</text>\n",
+            r = ROW_HEIGHT + 4.0,
+            tx = x + 8.0,
+            ty = y + ROW_HEIGHT - 3.0,
+            name = xml_escape(&entity.name())
+        ));
+        for (row, (name, data_type, marker)) in entity.columns.iter().enumerate() {
+            let prefix = if marker.is_empty() {
+                String::new()
+            } else {
+                format!("{} ", marker)
+            };
+            out.push_str(&format!(
+                "    <text x=\"{:.1}\" y=\"{:.1}\">{}</text>\n",
+                x + 8.0,
+                y + (row + 2) as f64 * ROW_HEIGHT,
+                xml_escape(&format!("{}{} : {}", prefix, name, data_type))
+            ));
+        }
+        out.push_str("  </g>\n");
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema::{ColumnInfo, RelationshipInfo};
+
+    fn column(
+        name: &str,
+        is_primary_key: bool,
+        is_nullable: bool,
+        ordinal_position: i32,
+    ) -> ColumnInfo {
+        ColumnInfo {
+            is_nullable,
+            is_primary_key,
+            ordinal_position,
+            ..ColumnInfo::for_test(name, "int")
+        }
+    }
+
+    fn table(name: &str, columns: Vec<ColumnInfo>) -> TableInfo {
+        TableInfo::for_test(name, columns)
+    }
+
+    fn relationship(name: &str, source: &str, column: &str, target: &str) -> RelationshipInfo {
+        RelationshipInfo::for_test(name, (source, column), (target, "Id"))
+    }
+
+    fn sample() -> (SchemaInfo, Vec<VirtualReference>) {
+        let schema = SchemaInfo {
+            tables: vec![
+                table(
+                    "Customers",
+                    vec![column("Id", true, false, 1), column("Name", false, true, 2)],
+                ),
+                table(
+                    "Orders",
+                    vec![
+                        column("Id", true, false, 1),
+                        column("CustomerId", false, false, 2),
+                        column("RegionCode", false, true, 3),
+                    ],
+                ),
+                table(
+                    "OrderLines",
+                    vec![
+                        column("Id", true, false, 1),
+                        column("OrderId", false, false, 2),
+                    ],
+                ),
+                table("Regions", vec![column("Id", true, false, 1)]),
+            ],
+            relationships: vec![
+                relationship("FK_Orders_Customers", "Orders", "CustomerId", "Customers"),
+                relationship("FK_OrderLines_Orders", "OrderLines", "OrderId", "Orders"),
+            ],
+            ..SchemaInfo::for_test("shop", Vec::new())
+        };
+        let virtual_references = vec![VirtualReference {
+            id: "v1".to_string(),
+            connection_id: "c".to_string(),
+            database_name: "shop".to_string(),
+            source_schema: "dbo".to_string(),
+            source_table: "Orders".to_string(),
+            source_column: "RegionCode".to_string(),
+            target_schema: "dbo".to_string(),
+            target_table: "Regions".to_string(),
+            target_column: "Id".to_string(),
+            columns: Vec::new(),
+            created_at: String::new(),
+            updated_at: String::new(),
+        }];
+        (schema, virtual_references)
+    }
+
+    #[test]
+    fn mermaid_distinguishes_declared_and_virtual_references() {
+        let (schema, virtual_references) = sample();
+        let out = generate_er_diagram(
+            &schema,
+            &virtual_references,
+            &DiagramOptions::default(),
+            DiagramFormat::Mermaid,
+        );
+
+        assert!(out.starts_with("erDiagram\n"));
+        assert!(out.contains("        int CustomerId FK\n"));
+        assert!(
+            !out.contains("Name"),
+            "non-key columns are hidden by default"
+        );
+        assert!(out.contains("dbo_Orders }o--|| dbo_Customers : \"FK_Orders_Customers\""));
+        assert!(out.contains("dbo_Orders }o..o| dbo_Regions : \"RegionCode (virtual)\""));
+    }
+
+    #[test]
+    fn seed_limits_tables_by_hops() {
+        let (schema, virtual_references) = sample();
+        let options = DiagramOptions {
+            seed: Some(ObjectRef {
+                schema_name: "dbo".to_string(),
+                object_name: "OrderLines".to_string(),
+            }),
+            hops: 1,
+            include_virtual: false,
+            ..DiagramOptions::default()
+        };
+        let out = generate_er_diagram(
+            &schema,
+            &virtual_references,
+            &options,
+            DiagramFormat::PlantUml,
+        );
+
+        assert!(out.contains("entity \"dbo.OrderLines\""));
+        assert!(out.contains("entity \"dbo.Orders\""));
+        assert!(!out.contains("dbo.Customers"));
+        assert!(!out.contains("Regions"));
+        assert!(out.contains("dbo_OrderLines }o--|| dbo_Orders : FK_OrderLines_Orders"));
+    }
+
+    #[test]
+    fn dot_and_svg_render_every_table() {
+        let (schema, virtual_references) = sample();
+        let options = DiagramOptions::default();
+
+        let dot = generate_er_diagram(&schema, &virtual_references, &options, DiagramFormat::Dot);
+        assert!(dot.contains("dbo_Customers [label=\"{dbo.Customers|PK Id : int\\l}\"];"));
+        assert!(dot.contains("dbo_Orders -> dbo_Regions [label=\"RegionCode\", dir=both, arrowtail=crowodot, arrowhead=teeodot, style=dashed];"));
+
+        let svg = generate_er_diagram(&schema, &virtual_references, &options, DiagramFormat::Svg);
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<g>").count(), 4);
+        assert_eq!(svg.matches("stroke-dasharray").count(), 1);
+    }
+}
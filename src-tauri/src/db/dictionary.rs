@@ -0,0 +1,789 @@
+// Data Dictionary
+// Object descriptions (MS_Description) and Markdown / HTML data dictionary generation
+
+use crate::db::compare::column_type;
+use crate::db::connection::ConnectionError;
+use crate::db::diagram::xml_escape;
+use crate::db::schema::{
+    description, ColumnInfo, ExtendedProperty, RoutineInfo, SchemaInfo, SchemaMetadataManager,
+    TableInfo, DESCRIPTION_PROPERTY,
+};
+use crate::sql::quote_identifier;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DictionaryFormat {
+    Markdown,
+    Html,
+}
+
+/// Object kinds that can carry a description, as named by
+/// `sp_addextendedproperty`'s `@level1type`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DescribedObjectType {
+    Table,
+    View,
+    Procedure,
+    Function,
+}
+
+impl DescribedObjectType {
+    fn level1type(self) -> &'static str {
+        match self {
+            Self::Table => "TABLE",
+            Self::View => "VIEW",
+            Self::Procedure => "PROCEDURE",
+            Self::Function => "FUNCTION",
+        }
+    }
+}
+
+/// An object, or a column of a table or view, whose description is edited
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DescriptionTarget {
+    pub schema_name: String,
+    pub object_name: String,
+    pub object_type: DescribedObjectType,
+    #[serde(default)]
+    pub column_name: Option<String>,
+}
+
+fn literal(value: &str) -> String {
+    format!("N'{}'", value.replace('\'', "''"))
+}
+
+/// Script setting (or, for None/empty, dropping) the `MS_Description` of
+/// `target`. Updates the property when it exists and adds it otherwise.
+pub fn description_script(target: &DescriptionTarget, text: Option<&str>) -> String {
+    let mut levels = vec![
+        ("SCHEMA", target.schema_name.as_str()),
+        (target.object_type.level1type(), target.object_name.as_str()),
+    ];
+    if let Some(column) = &target.column_name {
+        levels.push(("COLUMN", column.as_str()));
+    }
+
+    let mut list_args = vec![literal(DESCRIPTION_PROPERTY)];
+    let mut level_args = Vec::new();
+    for (i, (level_type, name)) in levels.iter().enumerate() {
+        list_args.push(literal(level_type));
+        list_args.push(literal(name));
+        level_args.push(format!(
+            "@level{0}type = {1}, @level{0}name = {2}",
+            i,
+            literal(level_type),
+            literal(name)
+        ));
+    }
+    if target.column_name.is_none() {
+        list_args.extend(["NULL".to_string(), "NULL".to_string()]);
+    }
+
+    let exists = format!(
+        "IF EXISTS (SELECT 1 FROM sys.fn_listextendedproperty({}))",
+        list_args.join(", ")
+    );
+    let levels = level_args.join(", ");
+    let name = literal(DESCRIPTION_PROPERTY);
+    match text.filter(|t| !t.trim().is_empty()) {
+        Some(text) => format!(
+            "{exists}\n    EXEC sys.sp_updateextendedproperty @name = {name}, @value = {value}, {levels};\n\
+             ELSE\n    EXEC sys.sp_addextendedproperty @name = {name}, @value = {value}, {levels};\n",
+            value = literal(text)
+        ),
+        None => format!("{exists}\n    EXEC sys.sp_dropextendedproperty @name = {name}, {levels};\n"),
+    }
+}
+
+/// Update the description of `target` in a cached schema. Returns false when
+/// the object or column is not in the schema.
+pub fn apply_description(
+    schema: &mut SchemaInfo,
+    target: &DescriptionTarget,
+    text: Option<&str>,
+) -> bool {
+    let properties = match target.object_type {
+        DescribedObjectType::Table | DescribedObjectType::View => {
+            let Some(table) = schema.tables.iter_mut().find(|t| {
+                t.schema_name.eq_ignore_ascii_case(&target.schema_name)
+                    && t.table_name.eq_ignore_ascii_case(&target.object_name)
+            }) else {
+                return false;
+            };
+            match &target.column_name {
+                Some(column) => match table
+                    .columns
+                    .iter_mut()
+                    .find(|c| c.name.eq_ignore_ascii_case(column))
+                {
+                    Some(column) => &mut column.extended_properties,
+                    None => return false,
+                },
+                None => &mut table.extended_properties,
+            }
+        }
+        DescribedObjectType::Procedure | DescribedObjectType::Function => {
+            match schema.routines.iter_mut().find(|r| {
+                r.schema_name.eq_ignore_ascii_case(&target.schema_name)
+                    && r.routine_name.eq_ignore_ascii_case(&target.object_name)
+            }) {
+                Some(routine) => &mut routine.extended_properties,
+                None => return false,
+            }
+        }
+    };
+
+    properties.retain(|p| p.name != DESCRIPTION_PROPERTY);
+    if let Some(text) = text.filter(|t| !t.trim().is_empty()) {
+        properties.push(ExtendedProperty {
+            name: DESCRIPTION_PROPERTY.to_string(),
+            value: text.to_string(),
+        });
+    }
+    true
+}
+
+impl SchemaMetadataManager {
+    /// Set or drop a description on the server, then mirror it into the
+    /// cached schema so it shows without waiting for the next refresh (which
+    /// re-reads all extended properties anyway). Returns the executed script.
+    pub async fn set_description(
+        &self,
+        connection_id: &str,
+        database: &str,
+        target: &DescriptionTarget,
+        text: Option<&str>,
+    ) -> Result<String, ConnectionError> {
+        let script = description_script(target, text);
+
+        let pool = self.connection_manager.connect(connection_id).await?;
+        let mut conn = pool.get().await?;
+        conn.simple_query(&format!("USE {}", quote_identifier(database)))
+            .await?;
+        conn.simple_query(&script).await?.into_results().await?;
+
+        if let Ok(Some(mut cached)) = self.db_manager.get_schema(connection_id, database) {
+            if apply_description(&mut cached, target, text) {
+                let _ = self
+                    .db_manager
+                    .save_schema(connection_id, database, &cached);
+            }
+        }
+        Ok(script)
+    }
+}
+
+/// A foreign key with its column pairs in order
+struct ForeignKey<'a> {
+    name: &'a str,
+    source: (&'a str, &'a str),
+    target: (&'a str, &'a str),
+    source_columns: Vec<&'a str>,
+    target_columns: Vec<&'a str>,
+}
+
+fn foreign_keys(schema: &SchemaInfo) -> Vec<ForeignKey<'_>> {
+    let mut keys: BTreeMap<(&str, &str, &str), ForeignKey> = BTreeMap::new();
+    let mut relationships: Vec<_> = schema.relationships.iter().collect();
+    relationships.sort_by_key(|r| r.ordinal_position);
+    for r in relationships {
+        let key = keys
+            .entry((
+                &r.source_schema_name,
+                &r.source_table_name,
+                &r.constraint_name,
+            ))
+            .or_insert_with(|| ForeignKey {
+                name: &r.constraint_name,
+                source: (&r.source_schema_name, &r.source_table_name),
+                target: (&r.target_schema_name, &r.target_table_name),
+                source_columns: Vec::new(),
+                target_columns: Vec::new(),
+            });
+        key.source_columns.push(&r.source_column_name);
+        key.target_columns.push(&r.target_column_name);
+    }
+    keys.into_values().collect()
+}
+
+/// Everything the dictionary says about one table or view
+struct TableEntry<'a> {
+    table: &'a TableInfo,
+    /// (name, columns) of the primary key and unique keys
+    primary_key: Option<(&'a str, Vec<&'a str>)>,
+    unique_keys: Vec<(&'a str, Vec<&'a str>)>,
+    references: Vec<&'a ForeignKey<'a>>,
+    referenced_by: Vec<&'a ForeignKey<'a>>,
+}
+
+impl TableEntry<'_> {
+    fn is_foreign_key(&self, column: &str) -> bool {
+        self.references
+            .iter()
+            .any(|fk| fk.source_columns.contains(&column))
+    }
+}
+
+fn anchor(schema_name: &str, name: &str) -> String {
+    format!("{}-{}", schema_name, name)
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+fn table_entries<'a>(
+    schema: &'a SchemaInfo,
+    keys: &'a [ForeignKey<'a>],
+    schema_filter: Option<&str>,
+) -> Vec<TableEntry<'a>> {
+    let mut tables: Vec<&TableInfo> = schema
+        .tables
+        .iter()
+        .filter(|t| schema_filter.is_none_or(|s| s.eq_ignore_ascii_case(&t.schema_name)))
+        .collect();
+    tables.sort_by(|a, b| (&a.schema_name, &a.table_name).cmp(&(&b.schema_name, &b.table_name)));
+
+    tables
+        .into_iter()
+        .map(|table| {
+            let is_table = |schema_name: &str, table_name: &str| {
+                schema_name == table.schema_name && table_name == table.table_name
+            };
+            let indexes: Vec<_> = schema
+                .indexes
+                .iter()
+                .filter(|i| is_table(&i.schema_name, &i.table_name))
+                .map(|i| {
+                    let columns = i
+                        .index
+                        .key_columns
+                        .iter()
+                        .map(|c| c.name.as_str())
+                        .collect();
+                    (&i.index, (i.index.name.as_str(), columns))
+                })
+                .collect();
+            let primary_key = indexes
+                .iter()
+                .find(|(index, _)| index.is_primary_key)
+                .map(|(_, key)| key.clone())
+                .or_else(|| {
+                    let columns: Vec<&str> = table
+                        .columns
+                        .iter()
+                        .filter(|c| c.is_primary_key)
+                        .map(|c| c.name.as_str())
+                        .collect();
+                    (!columns.is_empty()).then_some(("", columns))
+                });
+            TableEntry {
+                table,
+                primary_key,
+                unique_keys: indexes
+                    .iter()
+                    .filter(|(index, _)| index.is_unique && !index.is_primary_key)
+                    .map(|(_, key)| key.clone())
+                    .collect(),
+                references: keys
+                    .iter()
+                    .filter(|fk| is_table(fk.source.0, fk.source.1))
+                    .collect(),
+                referenced_by: keys
+                    .iter()
+                    .filter(|fk| is_table(fk.target.0, fk.target.1))
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+fn routines<'a>(schema: &'a SchemaInfo, schema_filter: Option<&str>) -> Vec<&'a RoutineInfo> {
+    let mut routines: Vec<&RoutineInfo> = schema
+        .routines
+        .iter()
+        .filter(|r| schema_filter.is_none_or(|s| s.eq_ignore_ascii_case(&r.schema_name)))
+        .collect();
+    routines
+        .sort_by(|a, b| (&a.schema_name, &a.routine_name).cmp(&(&b.schema_name, &b.routine_name)));
+    routines
+}
+
+fn key_label(entry: &TableEntry, column: &ColumnInfo) -> String {
+    let mut labels = Vec::new();
+    if entry
+        .primary_key
+        .as_ref()
+        .is_some_and(|(_, columns)| columns.contains(&column.name.as_str()))
+    {
+        labels.push("PK");
+    }
+    if entry.is_foreign_key(&column.name) {
+        labels.push("FK");
+    }
+    labels.join(", ")
+}
+
+fn key_text(name: &str, columns: &[&str]) -> String {
+    if name.is_empty() {
+        format!("({})", columns.join(", "))
+    } else {
+        format!("{} ({})", name, columns.join(", "))
+    }
+}
+
+fn reference_text(fk: &ForeignKey, outgoing: bool) -> String {
+    if outgoing {
+        format!(
+            "{}: ({}) → {}.{} ({})",
+            fk.name,
+            fk.source_columns.join(", "),
+            fk.target.0,
+            fk.target.1,
+            fk.target_columns.join(", ")
+        )
+    } else {
+        format!(
+            "{}: {}.{} ({}) → ({})",
+            fk.name,
+            fk.source.0,
+            fk.source.1,
+            fk.source_columns.join(", "),
+            fk.target_columns.join(", ")
+        )
+    }
+}
+
+/// Generate a data dictionary of tables, views and routines with their
+/// columns, keys, relationships and descriptions
+pub fn generate_data_dictionary(
+    schema: &SchemaInfo,
+    format: DictionaryFormat,
+    schema_filter: Option<&str>,
+) -> String {
+    let keys = foreign_keys(schema);
+    let tables = table_entries(schema, &keys, schema_filter);
+    let routines = routines(schema, schema_filter);
+    match format {
+        DictionaryFormat::Markdown => render_markdown(schema, &tables, &routines),
+        DictionaryFormat::Html => render_html(schema, &tables, &routines),
+    }
+}
+
+fn md_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
+
+fn render_markdown(
+    schema: &SchemaInfo,
+    tables: &[TableEntry],
+    routines: &[&RoutineInfo],
+) -> String {
+    let mut md = format!("# Data Dictionary: {}\n\n", schema.database_name);
+    md.push_str(&format!(
+        "Generated from schema fetched at {}.\n\n",
+        schema.fetched_at
+    ));
+
+    md.push_str("## Contents\n\n");
+    for entry in tables {
+        let t = entry.table;
+        md.push_str(&format!(
+            "- [{}.{}](#{}) ({})\n",
+            t.schema_name,
+            t.table_name,
+            anchor(&t.schema_name, &t.table_name),
+            t.table_type.to_lowercase()
+        ));
+    }
+    for r in routines {
+        md.push_str(&format!(
+            "- [{}.{}](#{}) ({})\n",
+            r.schema_name,
+            r.routine_name,
+            anchor(&r.schema_name, &r.routine_name),
+            r.routine_type.to_lowercase()
+        ));
+    }
+
+    for entry in tables {
+        let t = entry.table;
+        md.push_str(&format!(
+            "\n<a id=\"{}\"></a>\n\n## {}.{}\n\n",
+            anchor(&t.schema_name, &t.table_name),
+            t.schema_name,
+            t.table_name
+        ));
+        md.push_str(&format!("*{}*\n\n", t.table_type.to_lowercase()));
+        if let Some(text) = description(&t.extended_properties) {
+            md.push_str(&format!("{}\n\n", text));
+        }
+
+        md.push_str("| Column | Type | Nullable | Key | Default | Description |\n");
+        md.push_str("|---|---|---|---|---|---|\n");
+        for c in &t.columns {
+            md.push_str(&format!(
+                "| {} | {} | {} | {} | {} | {} |\n",
+                md_cell(&c.name),
+                column_type(c),
+                if c.is_nullable { "YES" } else { "NO" },
+                key_label(entry, c),
+                md_cell(c.column_default.as_deref().unwrap_or("")),
+                md_cell(description(&c.extended_properties).unwrap_or(""))
+            ));
+        }
+
+        let mut keys = Vec::new();
+        if let Some((name, columns)) = &entry.primary_key {
+            keys.push(format!("- **Primary key:** {}", key_text(name, columns)));
+        }
+        for (name, columns) in &entry.unique_keys {
+            keys.push(format!("- **Unique:** {}", key_text(name, columns)));
+        }
+        for fk in &entry.references {
+            keys.push(format!("- **References:** {}", reference_text(fk, true)));
+        }
+        for fk in &entry.referenced_by {
+            keys.push(format!(
+                "- **Referenced by:** {}",
+                reference_text(fk, false)
+            ));
+        }
+        if !keys.is_empty() {
+            md.push('\n');
+            md.push_str(&keys.join("\n"));
+            md.push('\n');
+        }
+    }
+
+    for r in routines {
+        md.push_str(&format!(
+            "\n<a id=\"{}\"></a>\n\n## {}.{}\n\n*{}*",
+            anchor(&r.schema_name, &r.routine_name),
+            r.schema_name,
+            r.routine_name,
+            r.routine_type.to_lowercase()
+        ));
+        if let Some(return_type) = &r.return_type {
+            md.push_str(&format!(" returning `{}`", return_type));
+        }
+        md.push_str("\n\n");
+        if let Some(text) = description(&r.extended_properties) {
+            md.push_str(&format!("{}\n\n", text));
+        }
+        if !r.parameters.is_empty() {
+            md.push_str("| Parameter | Type | Direction |\n|---|---|---|\n");
+            for p in &r.parameters {
+                md.push_str(&format!(
+                    "| {} | {} | {} |\n",
+                    p.name, p.data_type, p.parameter_mode
+                ));
+            }
+        }
+    }
+    md
+}
+
+const HTML_STYLE: &str = "body{margin:0;font:14px/1.5 system-ui,sans-serif;color:#222;display:flex}\
+nav{width:260px;height:100vh;overflow:auto;position:sticky;top:0;border-right:1px solid #ddd;padding:12px;box-sizing:border-box;background:#fafafa}\
+nav input{width:100%;margin-bottom:8px;padding:4px}nav a{display:block;color:#0366d6;text-decoration:none;white-space:nowrap;overflow:hidden;text-overflow:ellipsis}\
+main{flex:1;padding:0 24px 24px;max-width:1100px}section{border-bottom:1px solid #eee;padding-bottom:12px}\
+table{border-collapse:collapse;width:100%}th,td{border:1px solid #ddd;padding:4px 8px;text-align:left;vertical-align:top}\
+th{background:#f3f3f3}.kind{color:#777;font-style:italic}code{background:#f3f3f3;padding:0 3px}";
+
+const HTML_SCRIPT: &str = "document.getElementById('filter').addEventListener('input',function(e){\
+var q=e.target.value.toLowerCase();document.querySelectorAll('nav a').forEach(function(a){\
+a.style.display=a.textContent.toLowerCase().indexOf(q)<0?'none':'';});});";
+
+fn render_html(schema: &SchemaInfo, tables: &[TableEntry], routines: &[&RoutineInfo]) -> String {
+    let e = xml_escape;
+    let title = format!("Data Dictionary: {}", e(&schema.database_name));
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n",
+        title, HTML_STYLE
+    );
+
+    html.push_str("<nav>\n<input id=\"filter\" placeholder=\"Filter objects\">\n");
+    for entry in tables {
+        let t = entry.table;
+        html.push_str(&format!(
+            "<a href=\"#{}\">{}.{}</a>\n",
+            anchor(&t.schema_name, &t.table_name),
+            e(&t.schema_name),
+            e(&t.table_name)
+        ));
+    }
+    for r in routines {
+        html.push_str(&format!(
+            "<a href=\"#{}\">{}.{}</a>\n",
+            anchor(&r.schema_name, &r.routine_name),
+            e(&r.schema_name),
+            e(&r.routine_name)
+        ));
+    }
+    html.push_str("</nav>\n<main>\n");
+    html.push_str(&format!(
+        "<h1>{}</h1>\n<p>Generated from schema fetched at {}.</p>\n",
+        title,
+        e(&schema.fetched_at)
+    ));
+
+    for entry in tables {
+        let t = entry.table;
+        html.push_str(&format!(
+            "<section id=\"{}\">\n<h2>{}.{}</h2>\n<p class=\"kind\">{}</p>\n",
+            anchor(&t.schema_name, &t.table_name),
+            e(&t.schema_name),
+            e(&t.table_name),
+            e(&t.table_type.to_lowercase())
+        ));
+        if let Some(text) = description(&t.extended_properties) {
+            html.push_str(&format!("<p>{}</p>\n", e(text)));
+        }
+        html.push_str(
+            "<table>\n<tr><th>Column</th><th>Type</th><th>Nullable</th><th>Key</th><th>Default</th><th>Description</th></tr>\n",
+        );
+        for c in &t.columns {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                e(&c.name),
+                e(&column_type(c)),
+                if c.is_nullable { "YES" } else { "NO" },
+                key_label(entry, c),
+                e(c.column_default.as_deref().unwrap_or("")),
+                e(description(&c.extended_properties).unwrap_or(""))
+            ));
+        }
+        html.push_str("</table>\n");
+
+        let mut keys = Vec::new();
+        if let Some((name, columns)) = &entry.primary_key {
+            keys.push(format!(
+                "<li><b>Primary key:</b> {}</li>",
+                e(&key_text(name, columns))
+            ));
+        }
+        for (name, columns) in &entry.unique_keys {
+            keys.push(format!(
+                "<li><b>Unique:</b> {}</li>",
+                e(&key_text(name, columns))
+            ));
+        }
+        for fk in &entry.references {
+            keys.push(format!(
+                "<li><b>References:</b> <a href=\"#{}\">{}</a></li>",
+                anchor(fk.target.0, fk.target.1),
+                e(&reference_text(fk, true))
+            ));
+        }
+        for fk in &entry.referenced_by {
+            keys.push(format!(
+                "<li><b>Referenced by:</b> <a href=\"#{}\">{}</a></li>",
+                anchor(fk.source.0, fk.source.1),
+                e(&reference_text(fk, false))
+            ));
+        }
+        if !keys.is_empty() {
+            html.push_str(&format!("<ul>\n{}\n</ul>\n", keys.join("\n")));
+        }
+        html.push_str("</section>\n");
+    }
+
+    for r in routines {
+        html.push_str(&format!(
+            "<section id=\"{}\">\n<h2>{}.{}</h2>\n<p class=\"kind\">{}{}</p>\n",
+            anchor(&r.schema_name, &r.routine_name),
+            e(&r.schema_name),
+            e(&r.routine_name),
+            e(&r.routine_type.to_lowercase()),
+            r.return_type
+                .as_ref()
+                .map(|t| format!(" returning <code>{}</code>", e(t)))
+                .unwrap_or_default()
+        ));
+        if let Some(text) = description(&r.extended_properties) {
+            html.push_str(&format!("<p>{}</p>\n", e(text)));
+        }
+        if !r.parameters.is_empty() {
+            html.push_str("<table>\n<tr><th>Parameter</th><th>Type</th><th>Direction</th></tr>\n");
+            for p in &r.parameters {
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    e(&p.name),
+                    e(&p.data_type),
+                    e(&p.parameter_mode)
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+        html.push_str("</section>\n");
+    }
+
+    html.push_str(&format!(
+        "</main>\n<script>{}</script>\n</body>\n</html>\n",
+        HTML_SCRIPT
+    ));
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema::{IndexInfo, RelationshipInfo};
+    use crate::db::scripting::{IndexColumn, IndexDefinition};
+
+    fn column(name: &str, is_primary_key: bool, description: Option<&str>) -> ColumnInfo {
+        ColumnInfo {
+            is_nullable: !is_primary_key,
+            is_primary_key,
+            extended_properties: description
+                .map(|d| {
+                    vec![ExtendedProperty {
+                        name: DESCRIPTION_PROPERTY.to_string(),
+                        value: d.to_string(),
+                    }]
+                })
+                .unwrap_or_default(),
+            ..ColumnInfo::for_test(name, "int")
+        }
+    }
+
+    fn table(name: &str, columns: Vec<ColumnInfo>) -> TableInfo {
+        TableInfo::for_test(name, columns)
+    }
+
+    fn schema() -> SchemaInfo {
+        let mut customers = table("Customers", vec![column("Id", true, None)]);
+        customers.extended_properties.push(ExtendedProperty {
+            name: DESCRIPTION_PROPERTY.to_string(),
+            value: "People who place orders".to_string(),
+        });
+        let tables = vec![
+            table(
+                "Orders",
+                vec![
+                    column("Id", true, None),
+                    column("CustomerId", false, Some("Who | ordered")),
+                ],
+            ),
+            customers,
+        ];
+        SchemaInfo {
+            relationships: vec![RelationshipInfo::for_test(
+                "FK_Orders_Customers",
+                ("Orders", "CustomerId"),
+                ("Customers", "Id"),
+            )],
+            indexes: vec![IndexInfo {
+                schema_name: "dbo".to_string(),
+                table_name: "Orders".to_string(),
+                index: IndexDefinition {
+                    name: "PK_Orders".to_string(),
+                    type_desc: "CLUSTERED".to_string(),
+                    is_unique: true,
+                    is_primary_key: true,
+                    is_unique_constraint: false,
+                    key_columns: vec![IndexColumn {
+                        name: "Id".to_string(),
+                        is_descending: false,
+                    }],
+                    included_columns: vec![],
+                    filter_definition: None,
+                },
+            }],
+            fetched_at: "2024-01-01T00:00:00Z".to_string(),
+            ..SchemaInfo::for_test("Shop", tables)
+        }
+    }
+
+    #[test]
+    fn description_script_updates_or_adds() {
+        let target = DescriptionTarget {
+            schema_name: "dbo".to_string(),
+            object_name: "Orders".to_string(),
+            object_type: DescribedObjectType::Table,
+            column_name: Some("CustomerId".to_string()),
+        };
+        assert_eq!(
+            description_script(&target, Some("Customer's id")),
+            "IF EXISTS (SELECT 1 FROM sys.fn_listextendedproperty(N'MS_Description', N'SCHEMA', N'dbo', N'TABLE', N'Orders', N'COLUMN', N'CustomerId'))\n    \
+             EXEC sys.sp_updateextendedproperty @name = N'MS_Description', @value = N'Customer''s id', \
+             @level0type = N'SCHEMA', @level0name = N'dbo', @level1type = N'TABLE', @level1name = N'Orders', @level2type = N'COLUMN', @level2name = N'CustomerId';\n\
+             ELSE\n    \
+             EXEC sys.sp_addextendedproperty @name = N'MS_Description', @value = N'Customer''s id', \
+             @level0type = N'SCHEMA', @level0name = N'dbo', @level1type = N'TABLE', @level1name = N'Orders', @level2type = N'COLUMN', @level2name = N'CustomerId';\n"
+        );
+
+        let procedure = DescriptionTarget {
+            schema_name: "dbo".to_string(),
+            object_name: "GetOrders".to_string(),
+            object_type: DescribedObjectType::Procedure,
+            column_name: None,
+        };
+        assert_eq!(
+            description_script(&procedure, Some("  ")),
+            "IF EXISTS (SELECT 1 FROM sys.fn_listextendedproperty(N'MS_Description', N'SCHEMA', N'dbo', N'PROCEDURE', N'GetOrders', NULL, NULL))\n    \
+             EXEC sys.sp_dropextendedproperty @name = N'MS_Description', @level0type = N'SCHEMA', @level0name = N'dbo', @level1type = N'PROCEDURE', @level1name = N'GetOrders';\n"
+        );
+    }
+
+    #[test]
+    fn cached_descriptions_are_replaced() {
+        let mut schema = schema();
+        let target = DescriptionTarget {
+            schema_name: "dbo".to_string(),
+            object_name: "orders".to_string(),
+            object_type: DescribedObjectType::Table,
+            column_name: Some("CustomerId".to_string()),
+        };
+        assert!(apply_description(&mut schema, &target, Some("Buyer")));
+        assert_eq!(
+            description(&schema.tables[0].columns[1].extended_properties),
+            Some("Buyer")
+        );
+        assert!(apply_description(&mut schema, &target, None));
+        assert!(schema.tables[0].columns[1].extended_properties.is_empty());
+
+        let missing = DescriptionTarget {
+            column_name: Some("Nope".to_string()),
+            ..target
+        };
+        assert!(!apply_description(&mut schema, &missing, Some("x")));
+    }
+
+    #[test]
+    fn markdown_lists_columns_keys_and_relationships() {
+        let md = generate_data_dictionary(&schema(), DictionaryFormat::Markdown, None);
+        assert!(md.starts_with("# Data Dictionary: Shop\n"));
+        assert!(md.contains("- [dbo.Customers](#dbo-customers) (base table)\n- [dbo.Orders](#dbo-orders) (base table)\n"));
+        assert!(md.contains("## dbo.Customers\n\n*base table*\n\nPeople who place orders\n"));
+        assert!(md.contains(
+            "| Id | int | NO | PK |  |  |\n| CustomerId | int | YES | FK |  | Who \\| ordered |\n"
+        ));
+        assert!(md.contains("- **Primary key:** PK_Orders (Id)\n- **References:** FK_Orders_Customers: (CustomerId) → dbo.Customers (Id)\n"));
+        assert!(md.contains("- **Primary key:** (Id)\n- **Referenced by:** FK_Orders_Customers: dbo.Orders (CustomerId) → (Id)\n"));
+    }
+
+    #[test]
+    fn html_is_escaped_and_linked() {
+        let html = generate_data_dictionary(&schema(), DictionaryFormat::Html, Some("dbo"));
+        assert!(html.contains("<a href=\"#dbo-orders\">dbo.Orders</a>"));
+        assert!(html.contains("<section id=\"dbo-customers\">"));
+        assert!(html.contains(
+            "<li><b>References:</b> <a href=\"#dbo-customers\">FK_Orders_Customers: (CustomerId) → dbo.Customers (Id)</a></li>"
+        ));
+        assert!(
+            generate_data_dictionary(&schema(), DictionaryFormat::Html, Some("sales"))
+                .contains("<nav>\n<input")
+        );
+        assert!(
+            !generate_data_dictionary(&schema(), DictionaryFormat::Html, Some("sales"))
+                .contains("<section")
+        );
+    }
+}
@@ -0,0 +1,453 @@
+// Fan-Out Queries
+// Run one query against many space/database targets and merge the result sets
+
+use crate::db::connection::{ConnectionConfig, MssqlConnectionManager};
+use crate::db::query::{CellValue, ColumnInfo, QueryEngine, QueryResult};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+/// Columns prepended to every merged row
+pub const SERVER_COLUMN: &str = "_server";
+pub const DATABASE_COLUMN: &str = "_database";
+
+/// Targets running at once when the caller doesn't say
+pub const DEFAULT_PARALLELISM: usize = 4;
+
+/// How often running targets check the cancel flag
+const CANCEL_POLL: Duration = Duration::from_millis(100);
+
+/// A space to run against, optionally on another database than its default
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FanOutTarget {
+    pub space_id: String,
+    pub database: Option<String>,
+}
+
+/// A target resolved to a connection config
+#[derive(Debug, Clone)]
+pub struct PreparedTarget {
+    pub space_id: String,
+    /// `host` or `host,port`, as shown in the `_server` column
+    pub server: String,
+    pub database: String,
+    pub config: ConnectionConfig,
+}
+
+/// How one target went
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TargetOutcome {
+    pub space_id: String,
+    pub server: String,
+    pub database: String,
+    /// Rows the target contributed to the merged result
+    pub row_count: usize,
+    pub execution_time_ms: u64,
+    pub error: Option<String>,
+    /// Cancelled before or while it ran
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FanOutResult {
+    pub result: QueryResult,
+    pub targets: Vec<TargetOutcome>,
+}
+
+/// One target's result set, as input to `merge_results`
+pub struct TargetRows<'a> {
+    pub server: &'a str,
+    pub database: &'a str,
+    pub result: &'a QueryResult,
+}
+
+/// The result set a target contributes: its last one with columns, or its last
+/// one when no statement returned rows
+fn result_set(results: &[QueryResult]) -> Option<&QueryResult> {
+    results
+        .iter()
+        .rev()
+        .find(|r| !r.columns.is_empty())
+        .or(results.last())
+}
+
+/// Types whose values can share a merged column
+fn type_family(data_type: &str) -> String {
+    let base = data_type
+        .split('(')
+        .next()
+        .unwrap_or(data_type)
+        .to_ascii_lowercase();
+    let family = match base.as_str() {
+        "tinyint" | "smallint" | "int" | "bigint" => "integer",
+        "decimal" | "numeric" | "money" | "smallmoney" => "decimal",
+        "real" | "float" => "float",
+        "char" | "varchar" | "nchar" | "nvarchar" | "text" | "ntext" => "string",
+        "binary" | "varbinary" | "image" => "binary",
+        "datetime" | "datetime2" | "smalldatetime" => "datetime",
+        other => other,
+    };
+    family.to_string()
+}
+
+/// Why `columns` can't be stacked under `shape`, if they can't: names must
+/// match ignoring case and types must be of the same family. An all-NULL
+/// column (type `null`) fits anything.
+fn shape_mismatch(shape: &[ColumnInfo], columns: &[ColumnInfo]) -> Option<String> {
+    let names_match = shape.len() == columns.len()
+        && shape
+            .iter()
+            .zip(columns)
+            .all(|(a, b)| a.name.eq_ignore_ascii_case(&b.name));
+    if !names_match {
+        return Some(format!(
+            "Columns ({}) don't match the first target ({})",
+            describe_columns(columns),
+            describe_columns(shape)
+        ));
+    }
+    shape.iter().zip(columns).find_map(|(a, b)| {
+        let compatible = a.data_type.eq_ignore_ascii_case("null")
+            || b.data_type.eq_ignore_ascii_case("null")
+            || type_family(&a.data_type) == type_family(&b.data_type);
+        (!compatible).then(|| {
+            format!(
+                "Column {} is {} here but {} on the first target",
+                b.name, b.data_type, a.data_type
+            )
+        })
+    })
+}
+
+fn describe_columns(columns: &[ColumnInfo]) -> String {
+    if columns.is_empty() {
+        return "no columns".to_string();
+    }
+    columns
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Stack the target result sets under `_server`/`_database` columns. The first
+/// part sets the shape; a part whose columns differ in name or type family is
+/// left out, with the reason at its index in the returned list
+pub fn merge_results(query_id: &str, parts: &[TargetRows]) -> (QueryResult, Vec<Option<String>>) {
+    let mut merged = QueryResult::new(query_id.to_string());
+    merged.is_complete = true;
+    let mut rejected = vec![None; parts.len()];
+
+    let Some(shape) = parts.first().map(|p| &p.result.columns) else {
+        return (merged, rejected);
+    };
+    if !shape.is_empty() {
+        merged.columns = [SERVER_COLUMN, DATABASE_COLUMN]
+            .iter()
+            .map(|name| ColumnInfo {
+                name: name.to_string(),
+                data_type: "nvarchar".to_string(),
+                nullable: false,
+            })
+            .chain(shape.iter().cloned())
+            .collect();
+    }
+
+    for (index, part) in parts.iter().enumerate() {
+        if let Some(reason) = shape_mismatch(shape, &part.result.columns) {
+            rejected[index] = Some(reason);
+            continue;
+        }
+        merged.truncated |= part.result.truncated;
+        merged.limit_applied = merged.limit_applied.or(part.result.limit_applied);
+        if shape.is_empty() {
+            // Nothing to stack; report the total rows affected
+            merged.row_count += part.result.row_count;
+            continue;
+        }
+        merged.rows.extend(part.result.rows.iter().map(|row| {
+            [
+                CellValue::String(part.server.to_string()),
+                CellValue::String(part.database.to_string()),
+            ]
+            .into_iter()
+            .chain(row.iter().cloned())
+            .collect::<Vec<_>>()
+        }));
+    }
+    if !shape.is_empty() {
+        merged.row_count = merged.rows.len();
+    }
+    (merged, rejected)
+}
+
+/// Run `query` on every target, at most `parallelism` at a time, and merge what
+/// succeeded. Each target gets its own connection so setting `cancel_flag`
+/// stops exactly this fan-out's queries; targets not yet started are skipped
+#[allow(clippy::too_many_arguments)]
+pub async fn run_fan_out(
+    manager: &MssqlConnectionManager,
+    engine: &QueryEngine,
+    fan_out_id: &str,
+    query: &str,
+    targets: Vec<PreparedTarget>,
+    max_rows: Option<usize>,
+    parallelism: usize,
+    cancel_flag: &AtomicBool,
+) -> FanOutResult {
+    let started = Instant::now();
+    let semaphore = Semaphore::new(parallelism.max(1));
+
+    let runs = targets.into_iter().enumerate().map(|(index, target)| {
+        let semaphore = &semaphore;
+        async move {
+            let mut outcome = TargetOutcome {
+                space_id: target.space_id.clone(),
+                server: target.server.clone(),
+                database: target.database.clone(),
+                row_count: 0,
+                execution_time_ms: 0,
+                error: None,
+                cancelled: false,
+            };
+            let _permit = semaphore.acquire().await;
+            if cancel_flag.load(Ordering::Relaxed) {
+                outcome.cancelled = true;
+                return (outcome, Vec::new());
+            }
+
+            let connection_id = format!("fanout:{}:{}", fan_out_id, index);
+            let mut config = target.config;
+            config.id = connection_id.clone();
+            if let Err(e) = manager.add_connection(config).await {
+                outcome.error = Some(e.to_string());
+                return (outcome, Vec::new());
+            }
+
+            let target_started = Instant::now();
+            let run = engine.execute_query(
+                &connection_id,
+                query,
+                Some(target.database.as_str()).filter(|d| !d.is_empty()),
+                false,
+                max_rows,
+            );
+            tokio::pin!(run);
+            // Keep cancelling until the batch returns, so a statement that
+            // starts after the first cancel is stopped too
+            let results = loop {
+                tokio::select! {
+                    results = &mut run => break results,
+                    _ = tokio::time::sleep(CANCEL_POLL) => {
+                        if cancel_flag.load(Ordering::Relaxed) {
+                            outcome.cancelled = true;
+                            engine.cancel_all_for_connection(&connection_id).await;
+                        }
+                    }
+                }
+            };
+            outcome.execution_time_ms = target_started.elapsed().as_millis() as u64;
+            let _ = manager.remove_connection(&connection_id).await;
+
+            match results {
+                Ok(results) => {
+                    outcome.error = results.iter().find_map(|r| r.error.clone());
+                    (outcome, results)
+                }
+                Err(e) => {
+                    outcome.error = Some(e.to_string());
+                    (outcome, Vec::new())
+                }
+            }
+        }
+    });
+    let finished = futures::future::join_all(runs).await;
+
+    let mut targets: Vec<TargetOutcome> = finished.iter().map(|(o, _)| o.clone()).collect();
+    let (indexes, parts): (Vec<usize>, Vec<TargetRows>) = finished
+        .iter()
+        .enumerate()
+        .filter(|(_, (outcome, _))| outcome.error.is_none() && !outcome.cancelled)
+        .filter_map(|(index, (outcome, results))| {
+            let result = result_set(results)?;
+            Some((
+                index,
+                TargetRows {
+                    server: &outcome.server,
+                    database: &outcome.database,
+                    result,
+                },
+            ))
+        })
+        .unzip();
+
+    let (mut result, rejected) = merge_results(fan_out_id, &parts);
+    for ((index, part), error) in indexes.into_iter().zip(&parts).zip(rejected) {
+        match error {
+            Some(error) => targets[index].error = Some(error),
+            None if part.result.columns.is_empty() => {
+                targets[index].row_count = part.result.row_count
+            }
+            None => targets[index].row_count = part.result.rows.len(),
+        }
+    }
+
+    result.execution_time_ms = started.elapsed().as_millis() as u64;
+    result.statement_text = Some(query.to_string());
+    if !targets.is_empty() && targets.iter().all(|t| t.error.is_some() || t.cancelled) {
+        result.error = Some(if cancel_flag.load(Ordering::Relaxed) {
+            "Fan-out cancelled".to_string()
+        } else {
+            format!("All {} targets failed", targets.len())
+        });
+    }
+    FanOutResult { result, targets }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(columns: &[&str], rows: Vec<Vec<CellValue>>) -> QueryResult {
+        let mut result = QueryResult::new("q".to_string());
+        result.columns = columns
+            .iter()
+            .map(|name| ColumnInfo {
+                name: name.to_string(),
+                data_type: "int".to_string(),
+                nullable: true,
+            })
+            .collect();
+        result.row_count = rows.len();
+        result.rows = rows;
+        result
+    }
+
+    #[test]
+    fn merges_compatible_targets_under_server_and_database_columns() {
+        let first = result(
+            &["Id", "Name"],
+            vec![vec![CellValue::Int(1), CellValue::Null]],
+        );
+        let mut second = result(
+            &["id", "NAME"],
+            vec![
+                vec![CellValue::Int(2), CellValue::Null],
+                vec![CellValue::Int(3), CellValue::Null],
+            ],
+        );
+        second.truncated = true;
+        let mismatched = result(&["Id"], vec![vec![CellValue::Int(4)]]);
+
+        let parts = [
+            TargetRows {
+                server: "sql1",
+                database: "TenantA",
+                result: &first,
+            },
+            TargetRows {
+                server: "sql2,1444",
+                database: "TenantB",
+                result: &second,
+            },
+            TargetRows {
+                server: "sql3",
+                database: "TenantC",
+                result: &mismatched,
+            },
+        ];
+        let (merged, rejected) = merge_results("f", &parts);
+
+        let names: Vec<&str> = merged.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["_server", "_database", "Id", "Name"]);
+        assert_eq!(merged.columns[0].data_type, "nvarchar");
+        assert_eq!(merged.row_count, 3);
+        assert!(merged.truncated);
+        assert!(matches!(&merged.rows[1][0], CellValue::String(s) if s == "sql2,1444"));
+        assert!(matches!(&merged.rows[1][1], CellValue::String(s) if s == "TenantB"));
+        assert!(matches!(merged.rows[2][2], CellValue::Int(3)));
+
+        assert_eq!(rejected[..2], [None, None]);
+        assert_eq!(
+            rejected[2].as_deref(),
+            Some("Columns (Id) don't match the first target (Id, Name)")
+        );
+    }
+
+    #[test]
+    fn rejects_targets_whose_column_types_differ() {
+        let mut ints = result(
+            &["Id", "Code"],
+            vec![vec![CellValue::Int(1), CellValue::Int(7)]],
+        );
+        ints.columns[0].data_type = "bigint".to_string();
+        let mut strings = result(
+            &["Id", "Code"],
+            vec![vec![CellValue::Int(2), CellValue::String("A7".to_string())]],
+        );
+        strings.columns[1].data_type = "nvarchar".to_string();
+        let mut nulls = result(
+            &["Id", "Code"],
+            vec![vec![CellValue::Int(3), CellValue::Null]],
+        );
+        nulls.columns[1].data_type = "null".to_string();
+
+        let (merged, rejected) = merge_results(
+            "f",
+            &[
+                TargetRows {
+                    server: "a",
+                    database: "A",
+                    result: &ints,
+                },
+                TargetRows {
+                    server: "b",
+                    database: "B",
+                    result: &strings,
+                },
+                TargetRows {
+                    server: "c",
+                    database: "C",
+                    result: &nulls,
+                },
+            ],
+        );
+        assert_eq!(
+            merged.row_count, 2,
+            "int and bigint share a family, NULL fits"
+        );
+        assert_eq!(
+            rejected[1].as_deref(),
+            Some("Column Code is nvarchar here but int on the first target")
+        );
+        assert_eq!(rejected[2], None);
+    }
+
+    #[test]
+    fn picks_the_last_result_set_with_columns() {
+        let mut update = result(&[], Vec::new());
+        update.row_count = 5;
+        let select = result(&["Total"], vec![vec![CellValue::Int(5)]]);
+        let results = [select, update.clone()];
+        assert_eq!(result_set(&results).unwrap().columns[0].name, "Total");
+
+        let (merged, _) = merge_results(
+            "f",
+            &[
+                TargetRows {
+                    server: "a",
+                    database: "A",
+                    result: &update,
+                },
+                TargetRows {
+                    server: "b",
+                    database: "B",
+                    result: &update,
+                },
+            ],
+        );
+        assert!(merged.columns.is_empty());
+        assert_eq!(merged.row_count, 10);
+    }
+}
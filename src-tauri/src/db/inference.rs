@@ -0,0 +1,557 @@
+// Reference Inference
+// Suggest virtual references from column naming conventions, optionally confirmed by data overlap
+
+use crate::db::connection::ConnectionError;
+use crate::db::schema::{ColumnInfo, SchemaInfo, SchemaMetadataManager, TableInfo};
+use crate::sql::quote_identifier;
+use crate::storage::{VirtualReference, VirtualReferenceColumn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InferenceOptions {
+    /// Only suggest references from tables in this schema
+    pub schema_name: Option<String>,
+    pub min_confidence: f64,
+    /// Check on the server that sampled source values exist in the target
+    pub verify_data: bool,
+    /// Distinct source values sampled per candidate
+    pub sample_size: usize,
+    /// Most candidates verified, highest confidence first
+    pub max_verified: usize,
+}
+
+impl Default for InferenceOptions {
+    fn default() -> Self {
+        Self {
+            schema_name: None,
+            min_confidence: 0.4,
+            verify_data: false,
+            sample_size: 1000,
+            max_verified: 200,
+        }
+    }
+}
+
+/// How the source column name relates to the target key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NameMatch {
+    /// `CustomerId` -> Customers.Id, or the same name as the target key
+    Exact,
+    /// `BillingCustomerId` -> Customers.Id
+    Role,
+    /// `CustId` -> Customers.Id
+    Abbreviation,
+}
+
+impl NameMatch {
+    fn score(self) -> f64 {
+        match self {
+            Self::Exact => 0.9,
+            Self::Role => 0.75,
+            Self::Abbreviation => 0.55,
+        }
+    }
+}
+
+/// Result of the sampled containment check
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DataOverlap {
+    pub sampled_values: i64,
+    pub matched_values: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReferenceCandidate {
+    pub source_schema: String,
+    pub source_table: String,
+    pub source_column: String,
+    pub target_schema: String,
+    pub target_table: String,
+    pub target_column: String,
+    pub name_match: NameMatch,
+    /// 0.0 - 1.0
+    pub confidence: f64,
+    pub overlap: Option<DataOverlap>,
+}
+
+impl ReferenceCandidate {
+    pub fn to_virtual_reference(&self, connection_id: &str, database: &str) -> VirtualReference {
+        let now = chrono::Utc::now().to_rfc3339();
+        VirtualReference {
+            id: uuid::Uuid::new_v4().to_string(),
+            connection_id: connection_id.to_string(),
+            database_name: database.to_string(),
+            source_schema: self.source_schema.clone(),
+            source_table: self.source_table.clone(),
+            source_column: self.source_column.clone(),
+            target_schema: self.target_schema.clone(),
+            target_table: self.target_table.clone(),
+            target_column: self.target_column.clone(),
+            columns: vec![VirtualReferenceColumn {
+                source_column: self.source_column.clone(),
+                target_column: self.target_column.clone(),
+            }],
+            created_at: now.clone(),
+            updated_at: now,
+        }
+    }
+
+    /// Blend the name score with the share of sampled values found in the
+    /// target. Columns without data cannot confirm anything and lose a little.
+    fn apply_overlap(&mut self, overlap: DataOverlap) {
+        self.confidence = if overlap.sampled_values == 0 {
+            self.confidence * 0.8
+        } else {
+            let containment = overlap.matched_values as f64 / overlap.sampled_values as f64;
+            0.4 * self.confidence + 0.6 * containment
+        };
+        self.overlap = Some(overlap);
+    }
+}
+
+/// Lowercase with separators removed: `customer_id` and `CustomerID` both
+/// become `customerid`
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Entity name of a table: normalized, without a `tbl` prefix, singular
+fn entity_name(table_name: &str) -> String {
+    let name = normalize(table_name);
+    let name = name
+        .strip_prefix("tbl")
+        .filter(|n| !n.is_empty())
+        .unwrap_or(&name);
+    if let Some(stem) = name.strip_suffix("ies") {
+        format!("{}y", stem)
+    } else if ["sses", "xes", "ches", "shes"]
+        .iter()
+        .any(|s| name.ends_with(s))
+    {
+        name[..name.len() - 2].to_string()
+    } else if name.ends_with('s') && !name.ends_with("ss") && !name.ends_with("us") {
+        name[..name.len() - 1].to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+fn is_subsequence(short: &str, long: &str) -> bool {
+    let mut chars = long.chars();
+    short.chars().all(|c| chars.any(|l| l == c))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TypeFamily {
+    Integer,
+    Text,
+    Guid,
+    Other,
+}
+
+fn type_family(data_type: &str) -> TypeFamily {
+    match data_type.to_lowercase().as_str() {
+        "tinyint" | "smallint" | "int" | "bigint" => TypeFamily::Integer,
+        "char" | "varchar" | "nchar" | "nvarchar" => TypeFamily::Text,
+        "uniqueidentifier" => TypeFamily::Guid,
+        _ => TypeFamily::Other,
+    }
+}
+
+/// A table with a single-column primary key that columns may point at
+struct TargetKey<'a> {
+    table: &'a TableInfo,
+    column: &'a ColumnInfo,
+    entity: String,
+    /// Normalized key name
+    key: String,
+    /// What follows the entity in referencing column names, e.g. `id` or `code`
+    suffix: String,
+}
+
+impl<'a> TargetKey<'a> {
+    fn new(table: &'a TableInfo) -> Option<Self> {
+        let mut keys = table.columns.iter().filter(|c| c.is_primary_key);
+        let column = keys.next()?;
+        if keys.next().is_some() {
+            return None;
+        }
+        let entity = entity_name(&table.table_name);
+        let key = normalize(&column.name);
+        let suffix = key
+            .strip_prefix(entity.as_str())
+            .filter(|s| !s.is_empty())
+            .unwrap_or(&key)
+            .to_string();
+        Some(Self {
+            table,
+            column,
+            entity,
+            key,
+            suffix,
+        })
+    }
+
+    fn name_match(&self, column: &str) -> Option<NameMatch> {
+        let full = format!("{}{}", self.entity, self.suffix);
+        if column == full || (column == self.key && self.key != self.suffix) {
+            return Some(NameMatch::Exact);
+        }
+        if column.len() > full.len() && column.ends_with(&full) {
+            return Some(NameMatch::Role);
+        }
+        let prefix = column.strip_suffix(self.suffix.as_str())?;
+        let abbreviates = self.entity.len() >= 4
+            && prefix.len() >= 2
+            && prefix.len() < self.entity.len()
+            && prefix.chars().next() == self.entity.chars().next()
+            && is_subsequence(prefix, &self.entity);
+        abbreviates.then_some(NameMatch::Abbreviation)
+    }
+}
+
+/// Rank columns that look like references to another table's primary key.
+/// Columns already covered by a declared or virtual reference are skipped.
+pub fn infer_reference_candidates(
+    schema: &SchemaInfo,
+    existing: &[VirtualReference],
+    options: &InferenceOptions,
+) -> Vec<ReferenceCandidate> {
+    let key = |schema_name: &str, table: &str, column: &str| {
+        (
+            schema_name.to_lowercase(),
+            table.to_lowercase(),
+            column.to_lowercase(),
+        )
+    };
+    let covered: HashSet<_> = schema
+        .relationships
+        .iter()
+        .map(|r| {
+            key(
+                &r.source_schema_name,
+                &r.source_table_name,
+                &r.source_column_name,
+            )
+        })
+        .chain(existing.iter().flat_map(|r| {
+            r.column_pairs()
+                .into_iter()
+                .map(|c| key(&r.source_schema, &r.source_table, &c.source_column))
+        }))
+        .collect();
+
+    let tables: Vec<&TableInfo> = schema
+        .tables
+        .iter()
+        .filter(|t| t.table_type == "BASE TABLE")
+        .collect();
+    let targets: Vec<TargetKey> = tables.iter().filter_map(|t| TargetKey::new(t)).collect();
+
+    let mut candidates = Vec::new();
+    for table in &tables {
+        if options
+            .schema_name
+            .as_ref()
+            .is_some_and(|s| !s.eq_ignore_ascii_case(&table.schema_name))
+        {
+            continue;
+        }
+        for column in &table.columns {
+            if covered.contains(&key(&table.schema_name, &table.table_name, &column.name)) {
+                continue;
+            }
+            let normalized = normalize(&column.name);
+            let mut matches: Vec<(&TargetKey, NameMatch)> = targets
+                .iter()
+                .filter(|t| !(std::ptr::eq(t.table, *table) && std::ptr::eq(t.column, column)))
+                .filter(|t| {
+                    let family = type_family(&column.data_type);
+                    family == type_family(&t.column.data_type)
+                        && (family != TypeFamily::Other
+                            || column.data_type.eq_ignore_ascii_case(&t.column.data_type))
+                })
+                .filter_map(|t| t.name_match(&normalized).map(|m| (t, m)))
+                .collect();
+            // A guessed abbreviation is noise next to a real name match, and a
+            // primary key only references another table under the same name
+            if matches.iter().any(|(_, m)| *m != NameMatch::Abbreviation) {
+                matches.retain(|(_, m)| *m != NameMatch::Abbreviation);
+            }
+            if column.is_primary_key {
+                matches.retain(|(_, m)| *m == NameMatch::Exact);
+            }
+
+            for (target, name_match) in matches {
+                let mut confidence = name_match.score();
+                if !column
+                    .data_type
+                    .eq_ignore_ascii_case(&target.column.data_type)
+                {
+                    confidence -= 0.1;
+                }
+                candidates.push(ReferenceCandidate {
+                    source_schema: table.schema_name.clone(),
+                    source_table: table.table_name.clone(),
+                    source_column: column.name.clone(),
+                    target_schema: target.table.schema_name.clone(),
+                    target_table: target.table.table_name.clone(),
+                    target_column: target.column.name.clone(),
+                    name_match,
+                    confidence,
+                    overlap: None,
+                });
+            }
+        }
+    }
+
+    rank(&mut candidates, options.min_confidence);
+    candidates
+}
+
+fn rank(candidates: &mut Vec<ReferenceCandidate>, min_confidence: f64) {
+    candidates.retain(|c| c.confidence >= min_confidence);
+    candidates.sort_by(|a, b| {
+        b.confidence
+            .total_cmp(&a.confidence)
+            .then_with(|| {
+                (&a.source_schema, &a.source_table, &a.source_column).cmp(&(
+                    &b.source_schema,
+                    &b.source_table,
+                    &b.source_column,
+                ))
+            })
+            .then_with(|| {
+                (&a.target_schema, &a.target_table).cmp(&(&b.target_schema, &b.target_table))
+            })
+    });
+}
+
+impl SchemaMetadataManager {
+    /// Sample distinct non-null source values of the top candidates and count
+    /// how many exist in the target key, then re-rank. Candidates whose check
+    /// fails (e.g. conversion errors) keep their name-based confidence.
+    pub async fn verify_reference_candidates(
+        &self,
+        connection_id: &str,
+        database: &str,
+        candidates: &mut Vec<ReferenceCandidate>,
+        options: &InferenceOptions,
+    ) -> Result<(), ConnectionError> {
+        let pool = self.connection_manager.connect(connection_id).await?;
+        let mut conn = pool.get().await?;
+        conn.simple_query(&format!("USE {}", quote_identifier(database)))
+            .await?;
+
+        for candidate in candidates.iter_mut().take(options.max_verified) {
+            let query = format!(
+                r#"
+                SELECT COUNT_BIG(*), COUNT_BIG(t.{target_column})
+                FROM (
+                    SELECT DISTINCT TOP ({sample}) {source_column} AS v
+                    FROM {source_schema}.{source_table}
+                    WHERE {source_column} IS NOT NULL
+                ) s
+                LEFT JOIN {target_schema}.{target_table} t ON t.{target_column} = s.v
+            "#,
+                sample = options.sample_size.max(1),
+                source_schema = quote_identifier(&candidate.source_schema),
+                source_table = quote_identifier(&candidate.source_table),
+                source_column = quote_identifier(&candidate.source_column),
+                target_schema = quote_identifier(&candidate.target_schema),
+                target_table = quote_identifier(&candidate.target_table),
+                target_column = quote_identifier(&candidate.target_column),
+            );
+            let row = match conn.simple_query(&query).await {
+                Ok(stream) => stream.into_row().await,
+                Err(e) => Err(e),
+            };
+            match row {
+                Ok(Some(row)) => candidate.apply_overlap(DataOverlap {
+                    sampled_values: row.get::<i64, _>(0).unwrap_or(0),
+                    matched_values: row.get::<i64, _>(1).unwrap_or(0),
+                }),
+                Ok(None) => {}
+                Err(e) => eprintln!(
+                    "[Inference] Overlap check of {}.{}.{} failed: {}",
+                    candidate.source_schema, candidate.source_table, candidate.source_column, e
+                ),
+            }
+        }
+
+        rank(candidates, options.min_confidence);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(name: &str, data_type: &str, is_primary_key: bool) -> ColumnInfo {
+        ColumnInfo {
+            is_nullable: !is_primary_key,
+            is_primary_key,
+            ..ColumnInfo::for_test(name, data_type)
+        }
+    }
+
+    fn table(name: &str, columns: Vec<ColumnInfo>) -> TableInfo {
+        TableInfo::for_test(name, columns)
+    }
+
+    fn schema(tables: Vec<TableInfo>) -> SchemaInfo {
+        SchemaInfo::for_test("Legacy", tables)
+    }
+
+    fn summary(candidates: &[ReferenceCandidate]) -> Vec<(String, String, NameMatch)> {
+        candidates
+            .iter()
+            .map(|c| {
+                (
+                    format!("{}.{}", c.source_table, c.source_column),
+                    format!("{}.{}", c.target_table, c.target_column),
+                    c.name_match,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn entity_names_are_singular_and_unprefixed() {
+        assert_eq!(entity_name("Customers"), "customer");
+        assert_eq!(entity_name("tblCategories"), "category");
+        assert_eq!(entity_name("Addresses"), "address");
+        assert_eq!(entity_name("order_status"), "orderstatus");
+        assert_eq!(entity_name("Class"), "class");
+    }
+
+    #[test]
+    fn naming_conventions_are_ranked() {
+        let schema = schema(vec![
+            table(
+                "Customers",
+                vec![column("Id", "int", true), column("Name", "nvarchar", false)],
+            ),
+            table(
+                "Orders",
+                vec![
+                    column("OrderId", "int", true),
+                    column("customer_id", "int", false),
+                    column("BillingCustomerId", "bigint", false),
+                    column("CustId", "int", false),
+                    column("Notes", "nvarchar", false),
+                ],
+            ),
+            table(
+                "OrderLines",
+                vec![
+                    column("Id", "int", true),
+                    column("OrderId", "uniqueidentifier", false),
+                ],
+            ),
+            table(
+                "Shipments",
+                vec![column("Id", "int", true), column("OrderId", "int", false)],
+            ),
+        ]);
+
+        let candidates = infer_reference_candidates(&schema, &[], &InferenceOptions::default());
+        assert_eq!(
+            summary(&candidates),
+            vec![
+                (
+                    "Orders.customer_id".to_string(),
+                    "Customers.Id".to_string(),
+                    NameMatch::Exact
+                ),
+                (
+                    "Shipments.OrderId".to_string(),
+                    "Orders.OrderId".to_string(),
+                    NameMatch::Exact
+                ),
+                (
+                    "Orders.BillingCustomerId".to_string(),
+                    "Customers.Id".to_string(),
+                    NameMatch::Role
+                ),
+                (
+                    "Orders.CustId".to_string(),
+                    "Customers.Id".to_string(),
+                    NameMatch::Abbreviation
+                ),
+            ]
+        );
+        assert!(
+            (candidates[2].confidence - 0.65).abs() < 1e-9,
+            "type mismatch costs confidence"
+        );
+    }
+
+    #[test]
+    fn covered_columns_are_skipped() {
+        let schema = schema(vec![
+            table("Customers", vec![column("Id", "int", true)]),
+            table(
+                "Orders",
+                vec![
+                    column("Id", "int", true),
+                    column("CustomerId", "int", false),
+                ],
+            ),
+        ]);
+        let existing = ReferenceCandidate {
+            source_schema: "dbo".to_string(),
+            source_table: "orders".to_string(),
+            source_column: "customerid".to_string(),
+            target_schema: "dbo".to_string(),
+            target_table: "Customers".to_string(),
+            target_column: "Id".to_string(),
+            name_match: NameMatch::Exact,
+            confidence: 1.0,
+            overlap: None,
+        }
+        .to_virtual_reference("space1", "Legacy");
+
+        assert_eq!(
+            infer_reference_candidates(&schema, &[], &InferenceOptions::default()).len(),
+            1
+        );
+        assert!(
+            infer_reference_candidates(&schema, &[existing], &InferenceOptions::default())
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn data_overlap_adjusts_confidence() {
+        let schema = schema(vec![
+            table("Customers", vec![column("Id", "int", true)]),
+            table(
+                "Orders",
+                vec![column("Id", "int", true), column("CustId", "int", false)],
+            ),
+        ]);
+        let mut candidate =
+            infer_reference_candidates(&schema, &[], &InferenceOptions::default()).remove(0);
+        let mut empty = candidate.clone();
+
+        candidate.apply_overlap(DataOverlap {
+            sampled_values: 200,
+            matched_values: 200,
+        });
+        assert!((candidate.confidence - 0.82).abs() < 1e-9);
+
+        empty.apply_overlap(DataOverlap {
+            sampled_values: 0,
+            matched_values: 0,
+        });
+        assert!((empty.confidence - 0.44).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,529 @@
+// Join Path Finding
+// Shortest join paths between tables over declared foreign keys and virtual references,
+// rendered as a ready-to-insert FROM ... JOIN ... ON ... clause
+
+use crate::db::navigation::{all_references, ResolvedReference};
+use crate::db::schema::SchemaInfo;
+use crate::db::scripting::ObjectRef;
+use crate::sql::{is_reserved, quote_identifier};
+use crate::storage::VirtualReference;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JoinPathOptions {
+    /// Plans returned when several paths of the same length exist
+    pub max_alternatives: usize,
+    /// Longest path, in joins, searched between two tables
+    pub max_joins: usize,
+    pub include_virtual: bool,
+}
+
+impl Default for JoinPathOptions {
+    fn default() -> Self {
+        Self {
+            max_alternatives: 3,
+            max_joins: 6,
+            include_virtual: true,
+        }
+    }
+}
+
+/// A table in a join plan with its generated alias
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JoinedTable {
+    pub schema_name: String,
+    pub table_name: String,
+    pub alias: String,
+    /// False for intermediate tables added to connect the requested ones
+    pub requested: bool,
+}
+
+/// One JOIN: `alias` joins `on_alias` through `reference`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JoinStep {
+    pub alias: String,
+    pub on_alias: String,
+    pub reference: ResolvedReference,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JoinPlan {
+    pub tables: Vec<JoinedTable>,
+    pub joins: Vec<JoinStep>,
+    pub clause: String,
+}
+
+type TableKey = (String, String);
+
+fn key(schema_name: &str, table_name: &str) -> TableKey {
+    (schema_name.to_lowercase(), table_name.to_lowercase())
+}
+
+/// (reference index, table already joined, table being joined)
+type Hop = (usize, TableKey, TableKey);
+
+struct Graph {
+    references: Vec<ResolvedReference>,
+    adjacency: BTreeMap<TableKey, Vec<(usize, TableKey)>>,
+}
+
+impl Graph {
+    fn new(references: Vec<ResolvedReference>) -> Self {
+        let mut adjacency: BTreeMap<TableKey, Vec<(usize, TableKey)>> = BTreeMap::new();
+        for (i, r) in references.iter().enumerate() {
+            let source = key(&r.source_schema, &r.source_table);
+            let target = key(&r.target_schema, &r.target_table);
+            if source == target {
+                continue;
+            }
+            adjacency
+                .entry(source.clone())
+                .or_default()
+                .push((i, target.clone()));
+            adjacency.entry(target).or_default().push((i, source));
+        }
+        Self {
+            references,
+            adjacency,
+        }
+    }
+
+    /// Shortest paths from any table in `joined` to `target`, up to `limit`
+    fn shortest_paths(
+        &self,
+        joined: &[TableKey],
+        target: &TableKey,
+        max_joins: usize,
+        limit: usize,
+    ) -> Vec<Vec<Hop>> {
+        if joined.contains(target) {
+            return vec![Vec::new()];
+        }
+
+        // Breadth-first by level, keeping every predecessor on a shortest path
+        let mut depth: BTreeMap<TableKey, usize> = joined.iter().map(|t| (t.clone(), 0)).collect();
+        let mut predecessors: BTreeMap<TableKey, Vec<(usize, TableKey)>> = BTreeMap::new();
+        let mut level: Vec<TableKey> = joined.to_vec();
+        for d in 1..=max_joins {
+            let mut next = Vec::new();
+            for table in &level {
+                for (edge, other) in self.adjacency.get(table).into_iter().flatten() {
+                    match depth.get(other) {
+                        Some(&found) if found < d => continue,
+                        Some(_) => {}
+                        None => {
+                            depth.insert(other.clone(), d);
+                            next.push(other.clone());
+                        }
+                    }
+                    predecessors
+                        .entry(other.clone())
+                        .or_default()
+                        .push((*edge, table.clone()));
+                }
+            }
+            if depth.contains_key(target) || next.is_empty() {
+                break;
+            }
+            level = next;
+        }
+        if !depth.contains_key(target) {
+            return Vec::new();
+        }
+
+        let mut paths = Vec::new();
+        let mut suffix = Vec::new();
+        collect_paths(&predecessors, target, &mut suffix, &mut paths, limit);
+        paths
+    }
+}
+
+/// Walk predecessors back to the joined set, emitting hops in join order
+fn collect_paths(
+    predecessors: &BTreeMap<TableKey, Vec<(usize, TableKey)>>,
+    table: &TableKey,
+    suffix: &mut Vec<Hop>,
+    paths: &mut Vec<Vec<Hop>>,
+    limit: usize,
+) {
+    let Some(previous) = predecessors.get(table) else {
+        paths.push(suffix.iter().rev().cloned().collect());
+        return;
+    };
+    for (edge, from) in previous {
+        if paths.len() >= limit {
+            return;
+        }
+        suffix.push((*edge, from.clone(), table.clone()));
+        collect_paths(predecessors, from, suffix, paths, limit);
+        suffix.pop();
+    }
+}
+
+/// Initials of the words in a table name: OrderLines -> ol, tbl_customer -> tc
+fn base_alias(table_name: &str) -> String {
+    let mut alias = String::new();
+    let mut previous: Option<char> = None;
+    for c in table_name.chars() {
+        let starts_word = match previous {
+            None => c.is_alphabetic(),
+            Some(p) => {
+                c.is_alphabetic()
+                    && (!p.is_alphanumeric() || (c.is_uppercase() && p.is_lowercase()))
+            }
+        };
+        if starts_word {
+            alias.extend(c.to_lowercase());
+        }
+        previous = Some(c);
+    }
+    if alias.is_empty() {
+        alias.push('t');
+    }
+    alias
+}
+
+/// A unique alias that is not a keyword
+pub(crate) fn unique_alias(table_name: &str, used: &mut BTreeSet<String>) -> String {
+    let base = base_alias(table_name);
+    let mut alias = base.clone();
+    let mut n = 2;
+    while used.contains(&alias) || is_reserved(&alias.to_uppercase()) {
+        alias = format!("{}{}", base, n);
+        n += 1;
+    }
+    used.insert(alias.clone());
+    alias
+}
+
+/// Find the shortest join paths connecting `tables`, joined in the order
+/// given. Each table after the first is reached from the tables already in
+/// the plan, so intermediate tables are added as needed. Plans with fewer
+/// joins come first.
+pub fn find_join_paths(
+    schema: &SchemaInfo,
+    virtual_references: &[VirtualReference],
+    tables: &[ObjectRef],
+    options: &JoinPathOptions,
+) -> Result<Vec<JoinPlan>, String> {
+    let Some((first, rest)) = tables.split_first() else {
+        return Err("No tables given".to_string());
+    };
+
+    let virtual_references = if options.include_virtual {
+        virtual_references
+    } else {
+        &[]
+    };
+    let graph = Graph::new(all_references(schema, virtual_references));
+
+    // Display names per table, preferring the schema's casing
+    let mut names: BTreeMap<TableKey, (String, String)> = BTreeMap::new();
+    for r in &graph.references {
+        for (schema_name, table_name) in [
+            (&r.source_schema, &r.source_table),
+            (&r.target_schema, &r.target_table),
+        ] {
+            names.insert(
+                key(schema_name, table_name),
+                (schema_name.clone(), table_name.clone()),
+            );
+        }
+    }
+    for t in &schema.tables {
+        names.insert(
+            key(&t.schema_name, &t.table_name),
+            (t.schema_name.clone(), t.table_name.clone()),
+        );
+    }
+    let requested: Vec<TableKey> = tables
+        .iter()
+        .map(|t| {
+            let k = key(&t.schema_name, &t.object_name);
+            if names.contains_key(&k) {
+                Ok(k)
+            } else {
+                Err(format!(
+                    "Table not found: {}.{}",
+                    t.schema_name, t.object_name
+                ))
+            }
+        })
+        .collect::<Result<_, _>>()?;
+
+    let limit = options.max_alternatives.max(1);
+    let mut plans: Vec<(Vec<TableKey>, Vec<Hop>)> = vec![(vec![requested[0].clone()], Vec::new())];
+    for (table, target) in rest.iter().zip(&requested[1..]) {
+        let mut extended = Vec::new();
+        for (joined, hops) in &plans {
+            for path in graph.shortest_paths(joined, target, options.max_joins, limit) {
+                let mut joined = joined.clone();
+                let mut hops = hops.clone();
+                for hop in path {
+                    joined.push(hop.2.clone());
+                    hops.push(hop);
+                }
+                extended.push((joined, hops));
+            }
+        }
+        if extended.is_empty() {
+            return Err(format!(
+                "No relationship path connects {}.{} to {}.{}",
+                table.schema_name, table.object_name, first.schema_name, first.object_name
+            ));
+        }
+
+        extended.sort_by_key(|(_, hops)| hops.len());
+        let mut seen = BTreeSet::new();
+        extended.retain(|(_, hops)| {
+            let edges: BTreeSet<usize> = hops.iter().map(|h| h.0).collect();
+            seen.insert(edges.into_iter().collect::<Vec<_>>())
+        });
+        extended.truncate(limit);
+        plans = extended;
+    }
+
+    Ok(plans
+        .into_iter()
+        .map(|(joined, hops)| render_plan(&graph, &names, &requested, &joined, &hops))
+        .collect())
+}
+
+fn render_plan(
+    graph: &Graph,
+    names: &BTreeMap<TableKey, (String, String)>,
+    requested: &[TableKey],
+    joined: &[TableKey],
+    hops: &[Hop],
+) -> JoinPlan {
+    let mut used = BTreeSet::new();
+    let mut aliases: BTreeMap<&TableKey, String> = BTreeMap::new();
+    let tables: Vec<JoinedTable> = joined
+        .iter()
+        .map(|k| {
+            let (schema_name, table_name) = names[k].clone();
+            let alias = unique_alias(&table_name, &mut used);
+            aliases.insert(k, alias.clone());
+            JoinedTable {
+                schema_name,
+                table_name,
+                alias,
+                requested: requested.contains(k),
+            }
+        })
+        .collect();
+
+    let qualified = |t: &JoinedTable| {
+        format!(
+            "{}.{} AS {}",
+            quote_identifier(&t.schema_name),
+            quote_identifier(&t.table_name),
+            t.alias
+        )
+    };
+    let mut clause = format!("FROM {}", qualified(&tables[0]));
+    let mut joins = Vec::with_capacity(hops.len());
+    for (edge, from, to) in hops {
+        let reference = graph.references[*edge].clone();
+        let alias = aliases[to].clone();
+        let on_alias = aliases[from].clone();
+        let joining_source = key(&reference.source_schema, &reference.source_table) == *to;
+        let conditions: Vec<String> = reference
+            .columns
+            .iter()
+            .map(|c| {
+                let (mine, theirs) = if joining_source {
+                    (&c.source_column, &c.target_column)
+                } else {
+                    (&c.target_column, &c.source_column)
+                };
+                format!(
+                    "{}.{} = {}.{}",
+                    alias,
+                    quote_identifier(mine),
+                    on_alias,
+                    quote_identifier(theirs)
+                )
+            })
+            .collect();
+        let table = tables.iter().find(|t| t.alias == alias).unwrap();
+        clause.push_str(&format!(
+            "\nJOIN {} ON {}",
+            qualified(table),
+            conditions.join(" AND ")
+        ));
+        joins.push(JoinStep {
+            alias,
+            on_alias,
+            reference,
+        });
+    }
+
+    JoinPlan {
+        tables,
+        joins,
+        clause,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema::RelationshipInfo;
+
+    fn fk(
+        name: &str,
+        ordinal: i32,
+        source: (&str, &str),
+        target: (&str, &str),
+    ) -> RelationshipInfo {
+        RelationshipInfo {
+            ordinal_position: ordinal,
+            ..RelationshipInfo::for_test(name, source, target)
+        }
+    }
+
+    fn schema(relationships: Vec<RelationshipInfo>) -> SchemaInfo {
+        SchemaInfo {
+            relationships,
+            ..SchemaInfo::for_test("shop", Vec::new())
+        }
+    }
+
+    fn table(name: &str) -> ObjectRef {
+        ObjectRef {
+            schema_name: "dbo".to_string(),
+            object_name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn joins_through_intermediate_tables_with_composite_keys() {
+        let schema = schema(vec![
+            fk(
+                "FK_Orders_Customers",
+                1,
+                ("Orders", "CustomerId"),
+                ("Customers", "Id"),
+            ),
+            fk(
+                "FK_Lines_Orders",
+                1,
+                ("OrderLines", "TenantId"),
+                ("Orders", "TenantId"),
+            ),
+            fk(
+                "FK_Lines_Orders",
+                2,
+                ("OrderLines", "OrderNo"),
+                ("Orders", "OrderNo"),
+            ),
+            fk(
+                "FK_Lines_Products",
+                1,
+                ("OrderLines", "ProductId"),
+                ("Products", "Id"),
+            ),
+        ]);
+        let plans = find_join_paths(
+            &schema,
+            &[],
+            &[table("customers"), table("Products")],
+            &JoinPathOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(plans.len(), 1);
+        assert_eq!(
+            plans[0].clause,
+            "FROM [dbo].[Customers] AS c\n\
+             JOIN [dbo].[Orders] AS o ON o.[CustomerId] = c.[Id]\n\
+             JOIN [dbo].[OrderLines] AS ol ON ol.[TenantId] = o.[TenantId] AND ol.[OrderNo] = o.[OrderNo]\n\
+             JOIN [dbo].[Products] AS p ON p.[Id] = ol.[ProductId]"
+        );
+        let requested: Vec<_> = plans[0].tables.iter().map(|t| t.requested).collect();
+        assert_eq!(requested, [true, false, false, true]);
+    }
+
+    #[test]
+    fn parallel_relationships_are_alternatives() {
+        let schema = schema(vec![
+            fk(
+                "FK_Orders_BillTo",
+                1,
+                ("Orders", "BillToId"),
+                ("Addresses", "Id"),
+            ),
+            fk(
+                "FK_Orders_ShipTo",
+                1,
+                ("Orders", "ShipToId"),
+                ("Addresses", "Id"),
+            ),
+        ]);
+        let tables = [table("Orders"), table("Addresses")];
+        let plans = find_join_paths(&schema, &[], &tables, &JoinPathOptions::default()).unwrap();
+        assert_eq!(plans.len(), 2);
+        assert!(plans[0].clause.ends_with("ON a.[Id] = o.[BillToId]"));
+        assert!(plans[1].clause.ends_with("ON a.[Id] = o.[ShipToId]"));
+
+        let one = JoinPathOptions {
+            max_alternatives: 1,
+            ..JoinPathOptions::default()
+        };
+        assert_eq!(
+            find_join_paths(&schema, &[], &tables, &one).unwrap().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn virtual_references_connect_tables() {
+        let schema = schema(Vec::new());
+        let reference = VirtualReference {
+            id: "v1".to_string(),
+            connection_id: "c".to_string(),
+            database_name: "shop".to_string(),
+            source_schema: "dbo".to_string(),
+            source_table: "Payments".to_string(),
+            source_column: "InvoiceNo".to_string(),
+            target_schema: "dbo".to_string(),
+            target_table: "Invoices".to_string(),
+            target_column: "Number".to_string(),
+            columns: Vec::new(),
+            created_at: String::new(),
+            updated_at: String::new(),
+        };
+        let tables = [table("Invoices"), table("Payments")];
+        let plans = find_join_paths(
+            &schema,
+            std::slice::from_ref(&reference),
+            &tables,
+            &JoinPathOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            plans[0].clause,
+            "FROM [dbo].[Invoices] AS i\nJOIN [dbo].[Payments] AS p ON p.[InvoiceNo] = i.[Number]"
+        );
+        assert!(plans[0].joins[0].reference.is_virtual);
+
+        let declared_only = JoinPathOptions {
+            include_virtual: false,
+            ..JoinPathOptions::default()
+        };
+        assert!(find_join_paths(&schema, &[reference], &tables, &declared_only).is_err());
+    }
+
+    #[test]
+    fn aliases_are_unique_and_not_keywords() {
+        let mut used = BTreeSet::new();
+        assert_eq!(unique_alias("OrderLines", &mut used), "ol");
+        assert_eq!(unique_alias("order_logs", &mut used), "ol2");
+        assert_eq!(unique_alias("InvoiceNotes", &mut used), "in2");
+        assert_eq!(unique_alias("tbl_customer", &mut used), "tc");
+        assert_eq!(unique_alias("_2024", &mut used), "t");
+    }
+}
@@ -1,17 +1,46 @@
 // MS-SQL Connection & Query Execution (T015, T016, T017, T024)
 // This module handles database connections, query operations, and schema metadata
 
+pub mod compare;
 pub mod connection;
+pub mod data_search;
+pub mod dependencies;
+pub mod diagram;
+pub mod dictionary;
+pub mod fanout;
+pub mod inference;
+pub mod joins;
+pub mod navigation;
 pub mod query;
 pub mod schema;
+pub mod scripting;
+pub mod search;
+pub mod storage_stats;
 pub mod management;
 
+pub use compare::{SchemaComparison, SchemaSource};
 pub use connection::{
     ConnectionConfig, ConnectionConfigUpdate, ConnectionError, ConnectionInfo,
     MssqlConnectionManager, MssqlPool,
 };
-pub use query::{CellValue, ColumnInfo, QueryEngine, QueryInfo, QueryResult, QueryStatus};
+pub use data_search::{DataSearch, DataSearchEvent, DataSearchSummary};
+pub use dependencies::{DependencyDirection, DependencyGraph, DependencyTarget};
+pub use diagram::{DiagramFormat, DiagramOptions};
+pub use dictionary::{DescriptionTarget, DictionaryFormat};
+pub use fanout::{FanOutResult, FanOutTarget, PreparedTarget, TargetOutcome};
+pub use inference::{InferenceOptions, ReferenceCandidate};
+pub use joins::{JoinPathOptions, JoinPlan};
+pub use navigation::{ReferenceRows, ResolvedReference};
+pub use query::{
+    set_query_logging, CellValue, ColumnInfo, QueryEngine, QueryInfo, QueryResult, QueryStatus,
+};
 pub use schema::{
-    ColumnInfo as SchemaColumnInfo, RelationshipInfo as SchemaRelationshipInfo, RoutineInfo,
-    SchemaInfo, SchemaMetadataManager, TableInfo,
+    ColumnInfo as SchemaColumnInfo, ConstraintInfo, ConstraintType, ExtendedProperty, IndexInfo,
+    RelationshipInfo as SchemaRelationshipInfo, RoutineInfo, SchemaInfo, SchemaMetadataManager,
+    SequenceInfo, SynonymInfo, TableInfo, TriggerInfo, UserTypeInfo,
+};
+pub use scripting::{ObjectRef, ScriptAction};
+pub use search::{DefinitionSearch, DefinitionSearchResult};
+pub use storage_stats::{
+    DatabaseStorageSummary, FragmentationScanMode, IndexFragmentationInfo, TableStorageInfo,
 };
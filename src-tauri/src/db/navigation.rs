@@ -0,0 +1,493 @@
+// Reference Navigation
+// Resolve the declared or virtual reference behind a cell and fetch the parent row it points at,
+// or the child rows in other tables that point at the current row
+
+use crate::db::connection::ConnectionError;
+use crate::db::query::{CellValue, ColumnInfo};
+use crate::db::schema::{SchemaInfo, SchemaMetadataManager};
+use crate::sql::quote_identifier;
+use crate::storage::{VirtualReference, VirtualReferenceColumn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use tiberius::{ColumnType, Query};
+
+/// A declared foreign key or virtual reference, with every column pair in key order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResolvedReference {
+    /// Constraint name for a declared foreign key; None for a virtual reference
+    pub constraint_name: Option<String>,
+    pub source_schema: String,
+    pub source_table: String,
+    pub target_schema: String,
+    pub target_table: String,
+    pub columns: Vec<VirtualReferenceColumn>,
+    pub is_virtual: bool,
+    pub virtual_reference_id: Option<String>,
+}
+
+/// Rows fetched through a reference
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReferenceRows {
+    pub reference: ResolvedReference,
+    pub columns: Vec<ColumnInfo>,
+    pub rows: Vec<Vec<CellValue>>,
+    /// Rows matching the key, which may exceed the rows returned
+    pub row_count: i64,
+    pub truncated: bool,
+    /// Why the lookup was skipped, e.g. a NULL or missing key value
+    pub skipped: Option<String>,
+}
+
+fn eq(a: &str, b: &str) -> bool {
+    a.eq_ignore_ascii_case(b)
+}
+
+fn declared_references(schema: &SchemaInfo) -> Vec<ResolvedReference> {
+    let mut constraints: BTreeMap<(String, String, String), Vec<_>> = BTreeMap::new();
+    for r in &schema.relationships {
+        constraints
+            .entry((
+                r.source_schema_name.clone(),
+                r.source_table_name.clone(),
+                r.constraint_name.clone(),
+            ))
+            .or_default()
+            .push(r);
+    }
+    constraints
+        .into_values()
+        .map(|mut columns| {
+            columns.sort_by_key(|r| r.ordinal_position);
+            let first = columns[0];
+            ResolvedReference {
+                constraint_name: Some(first.constraint_name.clone()),
+                source_schema: first.source_schema_name.clone(),
+                source_table: first.source_table_name.clone(),
+                target_schema: first.target_schema_name.clone(),
+                target_table: first.target_table_name.clone(),
+                columns: columns
+                    .iter()
+                    .map(|r| VirtualReferenceColumn {
+                        source_column: r.source_column_name.clone(),
+                        target_column: r.target_column_name.clone(),
+                    })
+                    .collect(),
+                is_virtual: false,
+                virtual_reference_id: None,
+            }
+        })
+        .collect()
+}
+
+fn virtual_reference(v: &VirtualReference) -> ResolvedReference {
+    ResolvedReference {
+        constraint_name: None,
+        source_schema: v.source_schema.clone(),
+        source_table: v.source_table.clone(),
+        target_schema: v.target_schema.clone(),
+        target_table: v.target_table.clone(),
+        columns: v.column_pairs(),
+        is_virtual: true,
+        virtual_reference_id: Some(v.id.clone()),
+    }
+}
+
+/// All declared and virtual references, declared first
+pub(crate) fn all_references(
+    schema: &SchemaInfo,
+    virtual_references: &[VirtualReference],
+) -> Vec<ResolvedReference> {
+    let mut references = declared_references(schema);
+    references.extend(virtual_references.iter().map(virtual_reference));
+    references
+}
+
+/// The reference covering `column` of a table. A declared foreign key wins
+/// over a virtual one, and among either the one with the fewest columns is
+/// the most direct lookup.
+pub fn resolve_reference(
+    schema: &SchemaInfo,
+    virtual_references: &[VirtualReference],
+    schema_name: &str,
+    table_name: &str,
+    column: &str,
+) -> Option<ResolvedReference> {
+    all_references(schema, virtual_references)
+        .into_iter()
+        .filter(|r| {
+            eq(&r.source_schema, schema_name)
+                && eq(&r.source_table, table_name)
+                && r.columns.iter().any(|c| eq(&c.source_column, column))
+        })
+        .min_by_key(|r| (r.is_virtual, r.columns.len()))
+}
+
+/// Every declared and virtual reference pointing at a table
+pub fn referencing_references(
+    schema: &SchemaInfo,
+    virtual_references: &[VirtualReference],
+    schema_name: &str,
+    table_name: &str,
+) -> Vec<ResolvedReference> {
+    all_references(schema, virtual_references)
+        .into_iter()
+        .filter(|r| eq(&r.target_schema, schema_name) && eq(&r.target_table, table_name))
+        .collect()
+}
+
+/// Look up the key values in `row` (keyed by column name, any case), in
+/// order. Errors name the first missing or NULL column.
+fn key_values<'a, 'c>(
+    row: &'a HashMap<String, CellValue>,
+    columns: impl Iterator<Item = &'c str>,
+) -> Result<Vec<&'a CellValue>, String> {
+    columns
+        .map(|column| {
+            let value = row
+                .iter()
+                .find(|(name, _)| eq(name, column))
+                .map(|(_, value)| value)
+                .ok_or_else(|| format!("Column {} is not in the row", column))?;
+            match value {
+                CellValue::Null => Err(format!("{} is NULL", column)),
+                value => Ok(value),
+            }
+        })
+        .collect()
+}
+
+/// `WHERE` clause comparing `columns` with parameters @P1, @P2, ...
+fn key_predicate<'a>(columns: impl Iterator<Item = &'a str>) -> String {
+    columns
+        .enumerate()
+        .map(|(i, column)| format!("{} = @P{}", quote_identifier(column), i + 1))
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+/// Row and count queries selecting rows of `schema_name.table_name` whose
+/// `columns` equal the bound key
+pub fn lookup_queries<'a>(
+    schema_name: &str,
+    table_name: &str,
+    columns: impl Iterator<Item = &'a str>,
+    row_limit: usize,
+) -> (String, String) {
+    let table = format!(
+        "{}.{}",
+        quote_identifier(schema_name),
+        quote_identifier(table_name)
+    );
+    let predicate = key_predicate(columns);
+    (
+        format!(
+            "SELECT TOP ({}) * FROM {} WHERE {}",
+            row_limit + 1,
+            table,
+            predicate
+        ),
+        format!("SELECT COUNT_BIG(*) FROM {} WHERE {}", table, predicate),
+    )
+}
+
+fn bind<'a>(query: &mut Query<'a>, value: &CellValue) {
+    match value {
+        CellValue::Null => query.bind(Option::<String>::None),
+        CellValue::Bool(v) => query.bind(*v),
+        CellValue::Int(v) => query.bind(*v),
+        CellValue::Float(v) => query.bind(*v),
+        CellValue::String(v) | CellValue::DateTime(v) => query.bind(v.clone()),
+        CellValue::Binary(v) => query.bind(v.clone()),
+    }
+}
+
+impl SchemaMetadataManager {
+    /// Fetch the parent row a cell points at through `reference`. `row` holds
+    /// the current row's values, which must include every source column.
+    pub async fn fetch_parent_rows(
+        &self,
+        connection_id: &str,
+        database: &str,
+        reference: ResolvedReference,
+        row: &HashMap<String, CellValue>,
+        row_limit: usize,
+    ) -> Result<ReferenceRows, ConnectionError> {
+        let values = key_values(
+            row,
+            reference.columns.iter().map(|c| c.source_column.as_str()),
+        );
+        let target_columns = reference.columns.iter().map(|c| c.target_column.as_str());
+        let (rows_query, count_query) = lookup_queries(
+            &reference.target_schema,
+            &reference.target_table,
+            target_columns,
+            row_limit,
+        );
+        self.fetch_reference_rows(
+            connection_id,
+            database,
+            reference,
+            values,
+            &rows_query,
+            Some(&count_query),
+            row_limit,
+        )
+        .await
+    }
+
+    /// Count the rows in every table referencing the current row, and fetch
+    /// up to `row_limit` of them when `include_rows` is set. `row` must
+    /// include the referenced key columns of each relationship to look it up.
+    pub async fn fetch_child_rows(
+        &self,
+        connection_id: &str,
+        database: &str,
+        references: Vec<ResolvedReference>,
+        row: &HashMap<String, CellValue>,
+        include_rows: bool,
+        row_limit: usize,
+    ) -> Result<Vec<ReferenceRows>, ConnectionError> {
+        let mut children = Vec::with_capacity(references.len());
+        for reference in references {
+            let values = key_values(
+                row,
+                reference.columns.iter().map(|c| c.target_column.as_str()),
+            );
+            let source_columns = reference.columns.iter().map(|c| c.source_column.as_str());
+            let (rows_query, count_query) = lookup_queries(
+                &reference.source_schema,
+                &reference.source_table,
+                source_columns,
+                row_limit,
+            );
+            let (query, count_query) = if include_rows {
+                (rows_query.as_str(), Some(count_query.as_str()))
+            } else {
+                (count_query.as_str(), None)
+            };
+            let child = self
+                .fetch_reference_rows(
+                    connection_id,
+                    database,
+                    reference,
+                    values,
+                    query,
+                    count_query,
+                    row_limit,
+                )
+                .await?;
+            children.push(child);
+        }
+        Ok(children)
+    }
+
+    /// Run `query` with the key bound as parameters. Without `count_query`,
+    /// `query` is itself the count and no rows are returned.
+    #[allow(clippy::too_many_arguments)]
+    async fn fetch_reference_rows(
+        &self,
+        connection_id: &str,
+        database: &str,
+        reference: ResolvedReference,
+        values: Result<Vec<&CellValue>, String>,
+        query: &str,
+        count_query: Option<&str>,
+        row_limit: usize,
+    ) -> Result<ReferenceRows, ConnectionError> {
+        let mut result = ReferenceRows {
+            reference,
+            columns: Vec::new(),
+            rows: Vec::new(),
+            row_count: 0,
+            truncated: false,
+            skipped: None,
+        };
+        let values = match values {
+            Ok(values) => values,
+            Err(reason) => {
+                result.skipped = Some(reason);
+                return Ok(result);
+            }
+        };
+
+        let pool = self.connection_manager.connect(connection_id).await?;
+        let mut conn = pool.get().await?;
+        conn.simple_query(&format!("USE {}", quote_identifier(database)))
+            .await?;
+
+        let run = |sql: &str| {
+            let mut query = Query::new(sql.to_string());
+            for value in &values {
+                bind(&mut query, value);
+            }
+            query
+        };
+
+        if let Some(count_query) = count_query {
+            let rows = run(query)
+                .query(&mut *conn)
+                .await?
+                .into_first_result()
+                .await?;
+            if let Some(first) = rows.first() {
+                result.columns = first.columns().iter().map(ColumnInfo::from).collect();
+                let types: Vec<ColumnType> =
+                    first.columns().iter().map(|c| c.column_type()).collect();
+                result.rows = rows
+                    .iter()
+                    .take(row_limit)
+                    .map(|row| {
+                        (0..types.len())
+                            .map(|idx| CellValue::from_row(row, idx, &types[idx]))
+                            .collect()
+                    })
+                    .collect();
+            }
+            result.truncated = rows.len() > row_limit;
+            result.row_count = if result.truncated {
+                count(&mut conn, run(count_query)).await?
+            } else {
+                rows.len() as i64
+            };
+        } else {
+            result.row_count = count(&mut conn, run(query)).await?;
+        }
+        Ok(result)
+    }
+}
+
+async fn count(
+    conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
+    query: Query<'_>,
+) -> Result<i64, ConnectionError> {
+    let row = query.query(&mut **conn).await?.into_row().await?;
+    Ok(row.and_then(|r| r.get::<i64, _>(0)).unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema::RelationshipInfo;
+
+    fn relationship(
+        name: &str,
+        ordinal: i32,
+        source: (&str, &str),
+        target: (&str, &str),
+    ) -> RelationshipInfo {
+        RelationshipInfo {
+            ordinal_position: ordinal,
+            ..RelationshipInfo::for_test(name, source, target)
+        }
+    }
+
+    fn schema() -> SchemaInfo {
+        SchemaInfo {
+            relationships: vec![
+                relationship(
+                    "FK_Lines_Orders",
+                    2,
+                    ("Lines", "OrderNo"),
+                    ("Orders", "OrderNo"),
+                ),
+                relationship(
+                    "FK_Lines_Orders",
+                    1,
+                    ("Lines", "TenantId"),
+                    ("Orders", "TenantId"),
+                ),
+                relationship(
+                    "FK_Lines_Tenants",
+                    1,
+                    ("Lines", "TenantId"),
+                    ("Tenants", "Id"),
+                ),
+            ],
+            ..SchemaInfo::for_test("shop", Vec::new())
+        }
+    }
+
+    fn virtual_ref(source_column: &str, target_table: &str) -> VirtualReference {
+        VirtualReference {
+            id: format!("v-{}", source_column),
+            connection_id: "c".to_string(),
+            database_name: "shop".to_string(),
+            source_schema: "dbo".to_string(),
+            source_table: "Lines".to_string(),
+            source_column: source_column.to_string(),
+            target_schema: "dbo".to_string(),
+            target_table: target_table.to_string(),
+            target_column: "Id".to_string(),
+            columns: Vec::new(),
+            created_at: String::new(),
+            updated_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn declared_keys_win_and_fewest_columns_first() {
+        let schema = schema();
+        let virtual_references = vec![
+            virtual_ref("tenantid", "Clients"),
+            virtual_ref("ProductCode", "Products"),
+        ];
+
+        let tenant =
+            resolve_reference(&schema, &virtual_references, "DBO", "lines", "TenantId").unwrap();
+        assert_eq!(tenant.constraint_name.as_deref(), Some("FK_Lines_Tenants"));
+
+        let order =
+            resolve_reference(&schema, &virtual_references, "dbo", "Lines", "OrderNo").unwrap();
+        let columns: Vec<_> = order
+            .columns
+            .iter()
+            .map(|c| c.source_column.as_str())
+            .collect();
+        assert_eq!(columns, ["TenantId", "OrderNo"]);
+
+        let product =
+            resolve_reference(&schema, &virtual_references, "dbo", "Lines", "productcode").unwrap();
+        assert!(product.is_virtual);
+        assert_eq!(
+            product.virtual_reference_id.as_deref(),
+            Some("v-ProductCode")
+        );
+        assert!(resolve_reference(&schema, &virtual_references, "dbo", "Lines", "Qty").is_none());
+
+        let children = referencing_references(&schema, &virtual_references, "dbo", "orders");
+        assert_eq!(children.len(), 1);
+        assert_eq!(children[0].source_table, "Lines");
+    }
+
+    #[test]
+    fn lookups_are_parameterized() {
+        let (rows, count) =
+            lookup_queries("dbo", "Order]s", ["TenantId", "OrderNo"].into_iter(), 200);
+        assert_eq!(
+            rows,
+            "SELECT TOP (201) * FROM [dbo].[Order]]s] WHERE [TenantId] = @P1 AND [OrderNo] = @P2"
+        );
+        assert_eq!(
+            count,
+            "SELECT COUNT_BIG(*) FROM [dbo].[Order]]s] WHERE [TenantId] = @P1 AND [OrderNo] = @P2"
+        );
+    }
+
+    #[test]
+    fn key_values_skip_nulls_and_missing_columns() {
+        let row = HashMap::from([
+            ("tenantid".to_string(), CellValue::Int(7)),
+            ("OrderNo".to_string(), CellValue::Null),
+        ]);
+        let values = key_values(&row, ["TenantId"].into_iter()).unwrap();
+        assert!(matches!(values[0], CellValue::Int(7)));
+        assert_eq!(
+            key_values(&row, ["TenantId", "OrderNo"].into_iter()).unwrap_err(),
+            "OrderNo is NULL"
+        );
+        assert_eq!(
+            key_values(&row, ["LineNo"].into_iter()).unwrap_err(),
+            "Column LineNo is not in the row"
+        );
+    }
+}
@@ -2,21 +2,35 @@
 // Handles non-blocking query execution with result streaming
 
 use crate::db::connection::{ConnectionError, MssqlConnectionManager};
+use crate::sql::sqlcmd::{preprocess, OnError, SqlcmdOptions};
+use crate::sql::{
+    line_of, parse_statements, quote_identifier, significant_tokens, split_batches,
+    split_terminated, Batch, Diagnostic, Severity, Span, Statement, TokenKind,
+};
 use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use tiberius::{Column, ColumnType, Row, ToSql};
 use tiberius::numeric::Numeric;
 use tokio::sync::{RwLock, oneshot};
 use uuid::Uuid;
 
+/// Whether the engine logs its progress; the CLI turns this off
+static LOGGING: AtomicBool = AtomicBool::new(true);
+
+/// Turn the engine's progress logging on or off for the whole process
+pub fn set_query_logging(enabled: bool) {
+    LOGGING.store(enabled, Ordering::Relaxed);
+}
+
 // Logging macros using println for simplicity (no trailing semicolon for use in match arms)
 macro_rules! log_info {
-    ($($arg:tt)*) => {{ println!("[INFO] {}", format!($($arg)*)) }};
+    ($($arg:tt)*) => {{ if LOGGING.load(Ordering::Relaxed) { println!("[INFO] {}", format!($($arg)*)) } }};
 }
 macro_rules! log_warn {
-    ($($arg:tt)*) => {{ println!("[WARN] {}", format!($($arg)*)) }};
+    ($($arg:tt)*) => {{ if LOGGING.load(Ordering::Relaxed) { println!("[WARN] {}", format!($($arg)*)) } }};
 }
 
 /// Represents a single cell value in the result set
@@ -407,19 +421,21 @@ fn auto_wrap_procedure(stmt: String) -> String {
 
 /// Parse SQL text into individual statements.
 ///
-/// Splitting strategy:
-/// 1. Split on the `GO` batch separator (line-level, case-insensitive, only
-///    when not inside a string or block comment). `GO` is a true batch boundary
-///    in T-SQL — variables and temp tables do not survive across it.
+/// Splitting strategy (boundaries come from the shared `crate::sql` parser, so
+/// strings, comments and bracketed identifiers are never mistaken for
+/// separators):
+/// 1. Split on the `GO` batch separator (`GO` or `GO n` on its own line,
+///    case-insensitive). `GO` is a true batch boundary in T-SQL — variables
+///    and temp tables do not survive across it.
 /// 2. Within each `GO` batch, decide whether to also split on semicolons:
-///    - If the batch needs scope preservation (contains `DECLARE`, a
-///      statement-start `SET`, or `#`/`##` temp-table references outside
-///      strings/comments/brackets), keep the WHOLE batch as a single statement
-///      so it is sent to the server in one TDS request. Splitting such a batch
-///      would either lose variable/temp-table scope or produce spurious empty
-///      result tabs (one per non-rowset statement).
-///    - Otherwise split on `;` so independent statements each get their own
-///      result tab.
+///    - If the batch needs scope preservation (contains a `DECLARE` or `SET`
+///      statement, or `#`/`##` temp-table references), keep the WHOLE batch
+///      as a single statement so it is sent to the server in one TDS request.
+///      Splitting such a batch would either lose variable/temp-table scope or
+///      produce spurious empty result tabs (one per non-rowset statement).
+///    - Otherwise split on top-level `;` so independent statements each get
+///      their own result tab. Semicolons inside `BEGIN...END` blocks, IF/ELSE
+///      bodies and procedure/function/trigger/view definitions never split.
 ///
 /// Auto-wrapping: if a statement is just a procedure name (e.g., "sp_who2"),
 /// it's automatically wrapped with EXEC to allow execution without an explicit
@@ -436,21 +452,7 @@ fn parse_sql_statements(sql: &str) -> Vec<String> {
     // Phase 2: per batch, either keep whole (scope-sensitive) or split on `;`.
     let mut statements = Vec::new();
     for batch in go_batches {
-        let batch = batch.trim();
-        if batch.is_empty() {
-            continue;
-        }
-
-        if batch_needs_scope_preservation(batch) {
-            statements.push(auto_wrap_procedure(batch.to_string()));
-        } else {
-            for s in split_on_semicolon(batch) {
-                let stmt = s.trim();
-                if !stmt.is_empty() && stmt != ";" {
-                    statements.push(auto_wrap_procedure(s));
-                }
-            }
-        }
+        statements.extend(split_batch_statements(&batch));
     }
 
     // If nothing parsed, return the original as a single statement.
@@ -461,276 +463,77 @@ fn parse_sql_statements(sql: &str) -> Vec<String> {
     statements
 }
 
-/// Split SQL on the `GO` batch separator (`GO` on its own line,
-/// case-insensitive), ignoring `GO` inside strings or block comments.
-fn split_on_go(sql: &str) -> Vec<String> {
-    let mut batches = Vec::new();
-    let mut current = String::new();
-    let mut in_string = false;
-    let mut in_block_comment = false;
-    let mut in_line_comment = false;
-    let mut prev_char = '\0';
-
-    for line in sql.lines() {
-        let trimmed = line.trim();
-
-        // GO is a separator only when not inside a string/block comment/line
-        // comment at the start of the line.
-        let go_separates = !in_string
-            && !in_block_comment
-            && !in_line_comment
-            && trimmed.eq_ignore_ascii_case("go");
-
-        if go_separates {
-            let batch = current.trim().to_string();
-            if !batch.is_empty() {
-                batches.push(batch);
-            }
-            current.clear();
-            // Reset state for the next batch — GO cannot be mid-string/comment.
-            in_string = false;
-            in_block_comment = false;
-            in_line_comment = false;
-            prev_char = '\0';
-            continue;
-        }
-
-        // Scan the line to keep string/comment state accurate.
-        for ch in line.chars() {
-            if ch == '\'' && prev_char != '\\' && !in_block_comment && !in_line_comment {
-                in_string = !in_string;
-            }
-            if !in_string {
-                if ch == '-' && prev_char == '-' && !in_block_comment {
-                    in_line_comment = true;
-                } else if ch == '*' && prev_char == '/' && !in_line_comment {
-                    in_block_comment = true;
-                } else if ch == '/' && prev_char == '*' && in_block_comment {
-                    in_block_comment = false;
-                }
-            }
-            prev_char = ch;
-        }
-
-        in_line_comment = false;
-        if !current.is_empty() {
-            current.push('\n');
-        }
-        current.push_str(line);
+/// Split one `GO` batch into the statements to execute: the whole batch when
+/// it needs scope preservation, otherwise one statement per top-level `;`.
+fn split_batch_statements(batch: &str) -> Vec<String> {
+    let batch = batch.trim();
+    if batch.is_empty() {
+        return Vec::new();
     }
 
-    let final_batch = current.trim().to_string();
-    if !final_batch.is_empty() {
-        batches.push(final_batch);
+    if batch_needs_scope_preservation(batch) {
+        return vec![auto_wrap_procedure(batch.to_string())];
     }
 
+    split_on_semicolon(batch)
+        .into_iter()
+        .filter(|s| {
+            let stmt = s.trim();
+            !stmt.is_empty() && stmt != ";"
+        })
+        .map(auto_wrap_procedure)
+        .collect()
+}
+
+/// Split SQL on the `GO` batch separator (`GO` or `GO n` on its own line,
+/// case-insensitive), ignoring `GO` inside strings or comments.
+fn split_on_go(sql: &str) -> Vec<String> {
+    let batches: Vec<String> = split_batches(sql)
+        .iter()
+        .map(|b| b.text(sql).trim().to_string())
+        .filter(|b| !b.is_empty())
+        .collect();
+
     if batches.is_empty() && !sql.trim().is_empty() {
-        batches.push(sql.trim().to_string());
+        return vec![sql.trim().to_string()];
     }
 
     batches
 }
 
-/// Split a SQL batch on semicolons that are outside strings, line comments, and
-/// block comments. Returns each non-empty statement (terminator retained on the
-/// statement text to match historical behavior).
+/// Split a SQL batch on top-level semicolons (outside strings, comments,
+/// blocks and module bodies). Returns each non-empty statement (terminator
+/// retained on the statement text to match historical behavior).
 fn split_on_semicolon(sql: &str) -> Vec<String> {
-    let mut statements = Vec::new();
-    let mut current = String::new();
-    let mut in_string = false;
-    let mut in_block_comment = false;
-    let mut in_line_comment = false;
-    let mut prev_char = '\0';
-
-    for line in sql.lines() {
-        for ch in line.chars() {
-            if ch == '\'' && prev_char != '\\' && !in_block_comment && !in_line_comment {
-                in_string = !in_string;
-            }
-            if !in_string {
-                if ch == '-' && prev_char == '-' && !in_block_comment {
-                    in_line_comment = true;
-                } else if ch == '*' && prev_char == '/' && !in_line_comment {
-                    in_block_comment = true;
-                } else if ch == '/' && prev_char == '*' && in_block_comment {
-                    in_block_comment = false;
-                }
-            }
-
-            if ch == ';' && !in_string && !in_block_comment && !in_line_comment {
-                current.push(ch);
-                let stmt = current.trim().to_string();
-                if !stmt.is_empty() && stmt != ";" {
-                    statements.push(stmt);
-                }
-                current.clear();
-                prev_char = ch;
-                continue;
-            }
-
-            current.push(ch);
-            prev_char = ch;
-        }
-        in_line_comment = false;
-        current.push('\n');
-    }
-
-    let final_stmt = current.trim().to_string();
-    if !final_stmt.is_empty() && final_stmt != ";" {
-        statements.push(final_stmt);
-    }
-
-    statements
+    split_terminated(sql)
+        .iter()
+        .map(|span| span.text(sql).to_string())
+        .filter(|stmt| !stmt.is_empty() && stmt != ";")
+        .collect()
 }
 
 /// Detect whether a SQL batch must be sent to the server as a single statement
-/// to preserve scope. Returns true when the batch (outside strings, comments,
-/// and `[...]` bracketed identifiers) contains any of:
+/// to preserve scope. Returns true when the batch contains any of:
 ///
-/// - `DECLARE` at the start of a statement (after `;` or at batch start)
-/// - `SET` at the start of a statement (covers session SETs like NOCOUNT as
-///   well as variable assignments — splitting those off otherwise yields
-///   spurious empty result tabs)
-/// - A `#` or `##` temp-table reference (`#` followed by an identifier char)
+/// - A `DECLARE` statement
+/// - A `SET` statement (covers session SETs like NOCOUNT as well as variable
+///   assignments — splitting those off otherwise yields spurious empty result
+///   tabs). The `SET` clause of an `UPDATE` does not count.
+/// - A `#` or `##` temp-table reference (a `#` inside `[...]`, a string or a
+///   comment is not one)
 ///
 /// Each signals that the batch carries state (variable or temp-table scope, or
 /// a session SET that should not produce its own empty result tab) across `;`
 /// boundaries within the batch.
 fn batch_needs_scope_preservation(sql: &str) -> bool {
-    let bytes = sql.as_bytes();
-    let mut i = 0usize;
-    let mut in_string = false;
-    let mut in_block_comment = false;
-    let mut in_line_comment = false;
-    let mut statement_start = true;
-
-    while i < bytes.len() {
-        let b = bytes[i];
-        let next = bytes.get(i + 1).copied();
-
-        if in_line_comment {
-            if b == b'\n' {
-                in_line_comment = false;
-            }
-            i += 1;
-            continue;
-        }
-        if in_block_comment {
-            if b == b'*' && next == Some(b'/') {
-                in_block_comment = false;
-                i += 2;
-            } else {
-                i += 1;
-            }
-            continue;
-        }
-        if in_string {
-            if b == b'\'' {
-                if next == Some(b'\'') {
-                    i += 2;
-                } else {
-                    in_string = false;
-                    i += 1;
-                }
-            } else {
-                i += 1;
-            }
-            continue;
-        }
-
-        // Not in string/comment — detect comment starts.
-        if b == b'-' && next == Some(b'-') {
-            in_line_comment = true;
-            i += 2;
-            continue;
-        }
-        if b == b'/' && next == Some(b'*') {
-            in_block_comment = true;
-            i += 2;
-            continue;
-        }
-        if b == b'\'' {
-            in_string = true;
-            i += 1;
-            continue;
-        }
-
-        // Semicolons end a statement; whitespace doesn't change statement_start.
-        if b == b';' {
-            statement_start = true;
-            i += 1;
-            continue;
-        }
-        if b.is_ascii_whitespace() {
-            i += 1;
-            continue;
-        }
-
-        // `[...]` bracketed identifiers — skip contents (a `#` inside brackets
-        // is part of an identifier name, e.g. `[#count]` column alias, not a
-        // temp table reference).
-        if b == b'[' {
-            i += 1;
-            while i < bytes.len() && bytes[i] != b']' {
-                i += 1;
-            }
-            if i < bytes.len() {
-                i += 1; // consume `]`
-            }
-            statement_start = false;
-            continue;
-        }
-
-        // Temp-table reference: `#` or `##` followed by an identifier char,
-        // outside strings/comments/brackets.
-        if b == b'#' {
-            let after_hash = if next == Some(b'#') {
-                bytes.get(i + 2).copied()
-            } else {
-                next
-            };
-            if let Some(c) = after_hash {
-                if c.is_ascii_alphanumeric() || c == b'_' {
-                    return true;
-                }
-            }
-            statement_start = false;
-            i += 1;
-            continue;
-        }
-
-        // Identifier-start at a statement boundary — read the keyword.
-        if statement_start && (b.is_ascii_alphabetic() || b == b'_') {
-            let start = i;
-            i += 1;
-            while i < bytes.len() {
-                let c = bytes[i];
-                if c.is_ascii_alphanumeric() || c == b'_' {
-                    i += 1;
-                } else {
-                    break;
-                }
-            }
-            let upper = sql[start..i].to_uppercase();
-            if upper == "DECLARE" {
-                return true;
-            }
-            if upper == "SET" {
-                // Any statement-start SET: covers session SETs (NOCOUNT,
-                // ROWCOUNT) that would otherwise produce spurious empty
-                // tabs, and SET @var assignments.
-                return true;
-            }
-            statement_start = false;
-            continue;
-        }
-
-        // Any other non-whitespace token ends the statement-start window.
-        statement_start = false;
-        i += 1;
+    if significant_tokens(sql).iter().any(|t| t.is_temp_table()) {
+        return true;
     }
 
-    false
+    parse_statements(sql)
+        .iter()
+        .flat_map(|s| s.walk())
+        .any(|s| s.is("DECLARE") || s.is("SET"))
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -739,142 +542,150 @@ enum StatementKind {
     Other,
 }
 
-fn starts_with_keyword(input: &str, keyword: &str) -> bool {
-    if !input.starts_with(keyword) {
-        return false;
-    }
-
-    match input.as_bytes().get(keyword.len()) {
-        None => true,
-        Some(next) => !next.is_ascii_alphanumeric() && *next != b'_',
-    }
-}
-
+/// Classify a statement (or script) by its last executable statement:
+/// `INSERT`/`UPDATE`/`DELETE`/`MERGE` make it DML, while row-returning or DDL
+/// statements make it `Other`. Statements that produce neither (DECLARE, SET,
+/// PRINT, ...) don't change the classification. Nested statements inside
+/// blocks and IF/WHILE bodies are considered in order.
 fn infer_statement_kind(sql: &str) -> StatementKind {
     let mut last_kind = StatementKind::Other;
 
-    let mut in_string = false;
-    let mut in_line_comment = false;
-    let mut in_block_comment = false;
-    let mut statement_start = true;
-
-    let bytes = sql.as_bytes();
-    let mut i = 0usize;
-
-    while i < bytes.len() {
-        let b = bytes[i];
-        let next = bytes.get(i + 1).copied();
-
-        if in_line_comment {
-            if b == b'\n' {
-                in_line_comment = false;
-            }
-            i += 1;
-            continue;
-        }
-
-        if in_block_comment {
-            if b == b'*' && next == Some(b'/') {
-                in_block_comment = false;
-                i += 2;
-            } else {
-                i += 1;
-            }
-            continue;
+    for stmt in parse_statements(sql).iter().flat_map(|s| s.walk()) {
+        match stmt.keyword.as_deref() {
+            Some("UPDATE" | "INSERT" | "DELETE" | "MERGE") => last_kind = StatementKind::Dml,
+            Some(
+                "SELECT" | "EXEC" | "EXECUTE" | "CREATE" | "ALTER" | "DROP" | "TRUNCATE" | "WITH",
+            ) => last_kind = StatementKind::Other,
+            _ => {}
         }
+    }
 
-        if in_string {
-            if b == b'\'' {
-                if next == Some(b'\'') {
-                    i += 2;
-                } else {
-                    in_string = false;
-                    i += 1;
-                }
-            } else {
-                i += 1;
-            }
-            continue;
-        }
+    last_kind
+}
 
-        if b == b'-' && next == Some(b'-') {
-            in_line_comment = true;
-            i += 2;
-            continue;
+/// Map a server error, whose line number is relative to the batch that was
+/// sent, onto the trimmed text of the matching line in the full script.
+fn server_error_diagnostic(source: &str, batch: &Batch, error_line: u32, code: u32, message: &str) -> Diagnostic {
+    let mut start = batch.span.start;
+    for _ in 1..error_line.max(1) {
+        match source[start..batch.span.end].find('\n') {
+            Some(pos) => start += pos + 1,
+            None => break,
         }
+    }
+    let end = source[start..batch.span.end]
+        .find('\n')
+        .map_or(batch.span.end, |pos| start + pos);
+    let line_text = &source[start..end];
+    let trimmed_start = start + (line_text.len() - line_text.trim_start().len());
+    let trimmed_end = start + line_text.trim_end().len();
+
+    Diagnostic::new(
+        source,
+        Span::new(trimmed_start, trimmed_end.max(trimmed_start)),
+        Severity::Error,
+        &format!("MSSQL{}", code),
+        message,
+    )
+}
 
-        if b == b'/' && next == Some(b'*') {
-            in_block_comment = true;
-            i += 2;
-            continue;
-        }
+type DedicatedClient = tiberius::Client<tokio_util::compat::Compat<tokio::net::TcpStream>>;
 
-        if b == b'\'' {
-            in_string = true;
-            i += 1;
-            continue;
-        }
+/// Most times one batch is re-sent while collecting its validation errors
+const MAX_VALIDATION_ROUNDS: usize = 50;
 
-        if b == b';' {
-            statement_start = true;
-            i += 1;
-            continue;
-        }
+/// `SET NOEXEC`/`SET PARSEONLY` in a script being validated. Turning either off
+/// would let the rest of the script really run, so such scripts are refused.
+fn validation_guard_toggles(source: &str) -> Vec<Diagnostic> {
+    significant_tokens(source)
+        .iter()
+        .filter(|t| {
+            t.kind == TokenKind::Word
+                && (t.text.eq_ignore_ascii_case("NOEXEC") || t.text.eq_ignore_ascii_case("PARSEONLY"))
+        })
+        .map(|t| {
+            Diagnostic::new(
+                source,
+                t.span,
+                Severity::Error,
+                "validate-guard",
+                format!("Scripts that change {} can't be validated", t.text.to_ascii_uppercase()),
+            )
+        })
+        .collect()
+}
 
-        if b.is_ascii_whitespace() {
-            i += 1;
-            continue;
-        }
+/// Replace the innermost statement covering 1-based `line` with spaces, keeping
+/// line breaks so later server line numbers still match. None when no
+/// statement covers the line.
+fn blank_statement_at(text: &str, line: u32) -> Option<String> {
+    fn innermost(text: &str, statements: &[Statement], line: usize) -> Option<Span> {
+        let stmt = statements
+            .iter()
+            .find(|s| line_of(text, s.span.start) <= line && line <= line_of(text, s.span.end))?;
+        innermost(text, &stmt.children, line).or(Some(stmt.span))
+    }
 
-        if statement_start {
-            if b.is_ascii_alphabetic() || b == b'_' {
-                let start = i;
-                i += 1;
-                while i < bytes.len() {
-                    let c = bytes[i];
-                    if c.is_ascii_alphanumeric() || c == b'_' {
-                        i += 1;
-                    } else {
-                        break;
-                    }
-                }
+    let span = innermost(text, &parse_statements(text), line.max(1) as usize)?;
+    let blank: String = text[span.start..span.end]
+        .chars()
+        .map(|c| if c == '\n' || c == '\r' { c } else { ' ' })
+        .collect();
+    Some(format!("{}{}{}", &text[..span.start], blank, &text[span.end..]))
+}
 
-                let upper = sql[start..i].to_uppercase();
+async fn run_guard(conn: &mut DedicatedClient, statements: &[&str]) -> Result<(), ConnectionError> {
+    for statement in statements {
+        conn.simple_query(*statement)
+            .await
+            .map_err(|e| ConnectionError::QueryError(e.to_string()))?
+            .into_results()
+            .await
+            .map_err(|e| ConnectionError::QueryError(e.to_string()))?;
+    }
+    Ok(())
+}
 
-                if starts_with_keyword(&upper, "UPDATE")
-                    || starts_with_keyword(&upper, "INSERT")
-                    || starts_with_keyword(&upper, "DELETE")
-                    || starts_with_keyword(&upper, "MERGE")
-                {
-                    last_kind = StatementKind::Dml;
-                } else if starts_with_keyword(&upper, "SELECT")
-                    || starts_with_keyword(&upper, "EXEC")
-                    || starts_with_keyword(&upper, "EXECUTE")
-                    || starts_with_keyword(&upper, "CREATE")
-                    || starts_with_keyword(&upper, "ALTER")
-                    || starts_with_keyword(&upper, "DROP")
-                    || starts_with_keyword(&upper, "TRUNCATE")
-                    || starts_with_keyword(&upper, "WITH")
-                {
-                    last_kind = StatementKind::Other;
+/// Send one batch under `guard` and collect every server error it raises. Only
+/// the first error of a round comes back, so the failing statement is blanked
+/// out and the batch sent again until it passes.
+async fn collect_batch_errors(
+    conn: &mut DedicatedClient,
+    source: &str,
+    batch: &Batch,
+    guard: &[&str],
+) -> Result<Vec<Diagnostic>, ConnectionError> {
+    let mut diagnostics = Vec::new();
+    let mut text = batch.text(source).to_string();
+    for _ in 0..MAX_VALIDATION_ROUNDS {
+        if significant_tokens(&text).is_empty() {
+            break;
+        }
+        run_guard(conn, guard).await?;
+        let outcome = match conn.simple_query(text.as_str()).await {
+            Ok(stream) => stream.into_results().await.map(|_| ()),
+            Err(e) => Err(e),
+        };
+        match outcome {
+            Ok(()) => break,
+            Err(tiberius::error::Error::Server(e)) => {
+                diagnostics.push(server_error_diagnostic(source, batch, e.line(), e.code(), e.message()));
+                match blank_statement_at(&text, e.line()) {
+                    Some(next) => text = next,
+                    None => break,
                 }
-            } else {
-                i += 1;
             }
-
-            statement_start = false;
-            continue;
+            Err(e) => return Err(ConnectionError::QueryError(e.to_string())),
         }
-
-        i += 1;
     }
-
-    last_kind
+    Ok(diagnostics)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{infer_statement_kind, parse_sql_statements, batch_needs_scope_preservation, StatementKind};
+    use super::{infer_statement_kind, parse_sql_statements, batch_needs_scope_preservation, server_error_diagnostic, StatementKind,
+        blank_statement_at, validation_guard_toggles};
+    use crate::sql::split_batches;
 
     #[test]
     fn infer_kind_for_declare_then_update_is_dml() {
@@ -1052,6 +863,98 @@ UPDATE ProductQuestion_Rule SET EffectiveFrom = '2026-05-01' WHERE ProductQuesti
     fn scope_returns_false_for_plain_independent_selects() {
         assert!(!batch_needs_scope_preservation("SELECT 1; SELECT 2;"));
     }
+
+    // --- parser-backed splitting ---
+
+    #[test]
+    fn accepts_go_with_repeat_count_as_separator() {
+        let stmts = parse_sql_statements("SELECT 1\nGO 5\nSELECT 2");
+        assert_eq!(stmts, vec!["SELECT 1", "SELECT 2"]);
+    }
+
+    #[test]
+    fn does_not_split_inside_begin_end_block() {
+        let sql = "IF 1 = 1\nBEGIN\n  SELECT 1;\n  SELECT 2;\nEND;\nSELECT 3;";
+        let stmts = parse_sql_statements(sql);
+        assert_eq!(stmts.len(), 2);
+        assert!(stmts[0].contains("SELECT 2;"));
+        assert!(stmts[0].trim_end().ends_with("END;"));
+    }
+
+    #[test]
+    fn keeps_procedure_definition_as_one_statement() {
+        let sql = "CREATE PROCEDURE dbo.p AS\nSELECT 1;\nSELECT 2;";
+        let stmts = parse_sql_statements(sql);
+        assert_eq!(stmts.len(), 1);
+    }
+
+    #[test]
+    fn infer_kind_for_cte_update_is_dml() {
+        let sql = "WITH c AS (SELECT id FROM T) UPDATE c SET id = 1";
+        assert_eq!(infer_statement_kind(sql), StatementKind::Dml);
+    }
+
+    #[test]
+    fn infer_kind_for_cte_select_is_other() {
+        let sql = "WITH c AS (SELECT id FROM T) SELECT * FROM c";
+        assert_eq!(infer_statement_kind(sql), StatementKind::Other);
+    }
+
+    #[test]
+    fn infer_kind_for_declare_then_update_without_semicolons_is_dml() {
+        let sql = "DECLARE @x INT = 1\nUPDATE T SET v = @x WHERE id = 1";
+        assert_eq!(infer_statement_kind(sql), StatementKind::Dml);
+    }
+
+    #[test]
+    fn infer_kind_for_insert_select_is_dml() {
+        let sql = "INSERT INTO T (a) SELECT a FROM S";
+        assert_eq!(infer_statement_kind(sql), StatementKind::Dml);
+    }
+
+    #[test]
+    fn server_errors_map_to_script_lines() {
+        let sql = "SELECT 1\nGO\n\nSELECT *\n  FROM missing_table\nGO\n";
+        let batches = split_batches(sql);
+        // The batch text starts with the newline that ends the GO line
+        assert_eq!(batches[1].text(sql).lines().nth(3), Some("  FROM missing_table"));
+        let diag = server_error_diagnostic(sql, &batches[1], 4, 208, "Invalid object name 'missing_table'.");
+        assert_eq!(diag.code, "MSSQL208");
+        assert_eq!((diag.line, diag.column, diag.end_line), (5, 3, 5));
+        assert_eq!(diag.span.text(sql), "FROM missing_table");
+    }
+
+    #[test]
+    fn validation_refuses_scripts_that_toggle_the_guard() {
+        let diags = validation_guard_toggles("SELECT 1\nSET NOEXEC OFF\nDELETE FROM t\nset parseonly off");
+        assert_eq!(diags.len(), 2);
+        assert_eq!((diags[0].line, diags[0].column), (2, 5));
+        assert!(diags[1].message.contains("PARSEONLY"));
+        assert!(validation_guard_toggles("SELECT 'SET NOEXEC OFF' -- NOEXEC").is_empty());
+    }
+
+    #[test]
+    fn blanking_a_failing_statement_keeps_line_numbers() {
+        let text = "SELECT 1\nSELEC 2\nIF 1 = 1\nBEGIN\n  SELECT * FROM\nEND";
+        let blanked = blank_statement_at(text, 2).unwrap();
+        assert_eq!(blanked.lines().nth(1), Some("       "));
+        assert_eq!(blanked.lines().count(), text.lines().count());
+
+        // Only the innermost statement on the line goes
+        let blanked = blank_statement_at(text, 5).unwrap();
+        assert_eq!(blanked.lines().nth(3), Some("BEGIN"));
+        assert!(blanked.lines().nth(4).unwrap().trim().is_empty());
+        assert!(blank_statement_at(text, 40).is_none());
+    }
+
+    #[test]
+    fn server_error_lines_past_the_batch_stay_inside_it() {
+        let sql = "SELECT 1\nGO\nSELECT 2";
+        let batches = split_batches(sql);
+        let diag = server_error_diagnostic(sql, &batches[1], 9, 102, "Incorrect syntax");
+        assert_eq!(diag.line, 3);
+        assert_eq!(diag.span.text(sql), "SELECT 2");
+    }
 }
 
 impl QueryEngine {
@@ -1097,6 +1000,69 @@ impl QueryEngine {
         self.execute_batch(connection_id, statements, database, is_selection, max_rows).await
     }
 
+    /// Execute a script in sqlcmd mode: directives are expanded first, each
+    /// batch runs `GO n` times, and `:on error exit` stops the script at the
+    /// first failing statement. `statement_text` carries the expanded SQL.
+    pub async fn execute_sqlcmd_script(
+        &self,
+        connection_id: &str,
+        script: &str,
+        database: Option<&str>,
+        is_selection: bool,
+        max_rows: Option<usize>,
+        options: &SqlcmdOptions,
+    ) -> Result<Vec<QueryResult>, ConnectionError> {
+        let batches = match preprocess(script, options) {
+            Ok(batches) => batches,
+            Err(e) => {
+                return Ok(vec![QueryResult::with_error(Uuid::new_v4().to_string(), e.to_string())]);
+            }
+        };
+
+        let mut results = Vec::new();
+        let mut index = 0;
+
+        'script: for batch in &batches {
+            let statements = split_batch_statements(&batch.text);
+            for _ in 0..batch.repeat.max(1) {
+                for statement in &statements {
+                    let query_result = self.execute_single_statement(
+                        connection_id,
+                        statement,
+                        database,
+                        is_selection,
+                        max_rows,
+                        Some(index),
+                        Some(statement.clone()),
+                    ).await;
+                    index += 1;
+
+                    match query_result {
+                        Ok(mut statement_results) => {
+                            let cancelled = statement_results
+                                .iter()
+                                .any(|r| r.error.as_ref().is_some_and(|e| e.contains("cancelled")));
+                            let failed = statement_results.iter().any(|r| r.error.is_some());
+                            results.append(&mut statement_results);
+                            if cancelled || (failed && batch.on_error == OnError::Exit) {
+                                break 'script;
+                            }
+                        }
+                        Err(e) => {
+                            results.push(QueryResult::with_error(
+                                Uuid::new_v4().to_string(),
+                                e.to_string()
+                            ));
+                            break 'script;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(results)
+    }
+
     /// Execute a batch of SQL statements sequentially
     /// Note: If statements contains variable declarations (DECLARE/SET), they should
     /// already be combined into a single statement by parse_sql_statements
@@ -1147,6 +1113,56 @@ impl QueryEngine {
         Ok(results)
     }
 
+    /// Validate a script on the server without running it. Each GO batch is
+    /// sent on a dedicated connection under `SET PARSEONLY ON`, which reports
+    /// syntax errors only; with `compile` set, batches that parse are then
+    /// compiled under `SET NOEXEC ON` so bad column or object names surface.
+    /// The guard is re-sent before every batch, and scripts that change NOEXEC
+    /// or PARSEONLY themselves are refused. Diagnostics carry line numbers of
+    /// the whole script, not of the batch.
+    pub async fn validate_query(
+        &self,
+        connection_id: &str,
+        query: &str,
+        database: Option<&str>,
+        compile: bool,
+    ) -> Result<Vec<Diagnostic>, ConnectionError> {
+        let refused = validation_guard_toggles(query);
+        if !refused.is_empty() {
+            return Ok(refused);
+        }
+
+        // Session settings must not leak into pooled connections
+        let mut conn = self.connection_manager.create_dedicated_connection(connection_id).await?;
+
+        if let Some(db) = database {
+            run_guard(&mut conn, &[&format!("USE {}", quote_identifier(db))]).await?;
+        }
+        // NOEXEC is honoured at run time, so it goes in before PARSEONLY stops
+        // statements from running at all
+        if compile {
+            run_guard(&mut conn, &["SET NOEXEC ON"]).await?;
+        }
+
+        let mut diagnostics = Vec::new();
+        for batch in split_batches(query) {
+            if significant_tokens(batch.text(query)).is_empty() {
+                continue;
+            }
+
+            let syntax = collect_batch_errors(&mut conn, query, &batch, &["SET PARSEONLY ON"]).await?;
+            let parsed = syntax.is_empty();
+            diagnostics.extend(syntax);
+            if compile && parsed {
+                let guard = ["SET PARSEONLY OFF", "SET NOEXEC ON"];
+                diagnostics.extend(collect_batch_errors(&mut conn, query, &batch, &guard).await?);
+            }
+        }
+
+        log_info!("[VALIDATE] {} diagnostic(s), compile={}", diagnostics.len(), compile);
+        Ok(diagnostics)
+    }
+
     /// Execute a single SQL statement and return result
     async fn execute_single_statement(
         &self,
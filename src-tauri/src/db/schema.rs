@@ -2,10 +2,12 @@
 // Queries SQL Server system catalogs for database schema information
 
 use crate::db::connection::{ConnectionError, MssqlConnectionManager};
+use crate::db::scripting::{format_column_type, object_condition, IndexColumn, IndexDefinition};
+use crate::sql::quote_identifier;
 use crate::storage::DatabaseManager;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 
 /// Represents a column in a table or view
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,9 +20,36 @@ pub struct ColumnInfo {
     pub is_nullable: bool,
     pub is_primary_key: bool,
     pub is_identity: bool,
+    /// `IDENTITY(seed, increment)` of identity columns, as text since a
+    /// decimal(38,0) identity can go past the i64 range
+    #[serde(default)]
+    pub identity_seed: Option<String>,
+    #[serde(default)]
+    pub identity_increment: Option<String>,
     pub is_computed: bool,
     pub column_default: Option<String>,
     pub ordinal_position: i32,
+    /// Extended properties such as `MS_Description`
+    #[serde(default)]
+    pub extended_properties: Vec<ExtendedProperty>,
+}
+
+/// An extended property (`sys.extended_properties`) attached to an object or column
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExtendedProperty {
+    pub name: String,
+    pub value: String,
+}
+
+/// Name of the extended property holding an object's description
+pub const DESCRIPTION_PROPERTY: &str = "MS_Description";
+
+/// Value of the `MS_Description` property, if any
+pub fn description(properties: &[ExtendedProperty]) -> Option<&str> {
+    properties
+        .iter()
+        .find(|p| p.name == DESCRIPTION_PROPERTY)
+        .map(|p| p.value.as_str())
 }
 
 /// Represents a table or view in the database
@@ -30,6 +59,14 @@ pub struct TableInfo {
     pub table_name: String,
     pub table_type: String, // "BASE TABLE" or "VIEW"
     pub columns: Vec<ColumnInfo>,
+    /// View body from OBJECT_DEFINITION (None for tables and encrypted views)
+    #[serde(default)]
+    pub definition: Option<String>,
+    /// `sys.objects.modify_date` when fetched, used for incremental refresh
+    #[serde(default)]
+    pub modify_date: Option<String>,
+    #[serde(default)]
+    pub extended_properties: Vec<ExtendedProperty>,
 }
 
 /// Represents a foreign key relationship between two table columns
@@ -43,6 +80,16 @@ pub struct RelationshipInfo {
     pub target_schema_name: String,
     pub target_table_name: String,
     pub target_column_name: String,
+    /// `sys.foreign_keys` referential actions: NO_ACTION, CASCADE, SET_NULL or
+    /// SET_DEFAULT
+    #[serde(default = "no_action")]
+    pub delete_action: String,
+    #[serde(default = "no_action")]
+    pub update_action: String,
+}
+
+fn no_action() -> String {
+    "NO_ACTION".to_string()
 }
 
 /// Represents a parameter of a stored procedure or function
@@ -66,6 +113,100 @@ pub struct RoutineInfo {
     pub routine_type: String, // "PROCEDURE" or "FUNCTION"
     pub return_type: Option<String>,
     pub parameters: Vec<ParameterInfo>,
+    /// Routine body from OBJECT_DEFINITION (None when encrypted)
+    #[serde(default)]
+    pub definition: Option<String>,
+    /// `sys.objects.modify_date` when fetched, used for incremental refresh
+    #[serde(default)]
+    pub modify_date: Option<String>,
+    #[serde(default)]
+    pub extended_properties: Vec<ExtendedProperty>,
+}
+
+/// Represents an index on a table, including those backing PRIMARY KEY and
+/// UNIQUE constraints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexInfo {
+    pub schema_name: String,
+    pub table_name: String,
+    #[serde(flatten)]
+    pub index: IndexDefinition,
+}
+
+/// Kind of a table-level constraint not already covered by indexes or
+/// relationships
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConstraintType {
+    Check,
+    Default,
+}
+
+/// A CHECK or DEFAULT constraint. PRIMARY KEY and UNIQUE constraints are in
+/// `SchemaInfo::indexes`, foreign keys in `SchemaInfo::relationships`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConstraintInfo {
+    pub schema_name: String,
+    pub table_name: String,
+    pub constraint_name: String,
+    pub constraint_type: ConstraintType,
+    /// Column for defaults and column-level checks
+    pub column_name: Option<String>,
+    pub definition: String,
+    pub is_disabled: bool,
+}
+
+/// A DML trigger on a table or view, or a database-level DDL trigger
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TriggerInfo {
+    /// None for database triggers
+    pub schema_name: Option<String>,
+    pub trigger_name: String,
+    /// Table or view the trigger is on; None for database triggers
+    pub parent_name: Option<String>,
+    pub is_instead_of: bool,
+    pub is_disabled: bool,
+    /// INSERT, UPDATE, DELETE or DDL event names
+    pub events: Vec<String>,
+}
+
+/// A sequence object; values are rendered as text since they may exceed i64
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SequenceInfo {
+    pub schema_name: String,
+    pub sequence_name: String,
+    pub data_type: String,
+    pub start_value: String,
+    pub increment: String,
+    pub minimum_value: String,
+    pub maximum_value: String,
+    pub is_cycling: bool,
+    pub current_value: Option<String>,
+}
+
+/// A synonym and the parts of the object it points to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SynonymInfo {
+    pub schema_name: String,
+    pub synonym_name: String,
+    pub base_object_name: String,
+    pub base_server: Option<String>,
+    pub base_database: Option<String>,
+    pub base_schema: Option<String>,
+    pub base_object: String,
+}
+
+/// A user-defined alias (scalar) type or table type
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserTypeInfo {
+    pub schema_name: String,
+    pub type_name: String,
+    pub is_table_type: bool,
+    /// Underlying system type for alias types, e.g. `nvarchar(20)`
+    pub base_type: Option<String>,
+    pub is_nullable: bool,
+    /// Columns of a table type
+    pub columns: Vec<ColumnInfo>,
 }
 
 /// Complete schema information for a database
@@ -77,13 +218,381 @@ pub struct SchemaInfo {
     #[serde(default)]
     pub relationships: Vec<RelationshipInfo>,
     pub routines: Vec<RoutineInfo>,
+    #[serde(default)]
+    pub indexes: Vec<IndexInfo>,
+    #[serde(default)]
+    pub constraints: Vec<ConstraintInfo>,
+    #[serde(default)]
+    pub triggers: Vec<TriggerInfo>,
+    #[serde(default)]
+    pub sequences: Vec<SequenceInfo>,
+    #[serde(default)]
+    pub synonyms: Vec<SynonymInfo>,
+    #[serde(default)]
+    pub user_types: Vec<UserTypeInfo>,
     pub fetched_at: String,
+    /// Seconds since `fetched_at` when served from the cache
+    #[serde(default)]
+    pub cache_age_seconds: Option<i64>,
+}
+
+// Minimal values for tests; callers override fields with struct update syntax
+#[cfg(test)]
+impl ColumnInfo {
+    /// A nullable column with no key, identity, default or properties
+    pub(crate) fn for_test(name: &str, data_type: &str) -> Self {
+        ColumnInfo {
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            max_length: None,
+            precision: None,
+            scale: None,
+            is_nullable: true,
+            is_primary_key: false,
+            is_identity: false,
+            identity_seed: None,
+            identity_increment: None,
+            is_computed: false,
+            column_default: None,
+            ordinal_position: 0,
+            extended_properties: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl TableInfo {
+    /// A `dbo` base table
+    pub(crate) fn for_test(name: &str, columns: Vec<ColumnInfo>) -> Self {
+        TableInfo {
+            schema_name: "dbo".to_string(),
+            table_name: name.to_string(),
+            table_type: "BASE TABLE".to_string(),
+            columns,
+            definition: None,
+            modify_date: None,
+            extended_properties: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl RelationshipInfo {
+    /// A single-column `dbo` foreign key with no referential actions
+    pub(crate) fn for_test(name: &str, source: (&str, &str), target: (&str, &str)) -> Self {
+        RelationshipInfo {
+            constraint_name: name.to_string(),
+            ordinal_position: 1,
+            source_schema_name: "dbo".to_string(),
+            source_table_name: source.0.to_string(),
+            source_column_name: source.1.to_string(),
+            target_schema_name: "dbo".to_string(),
+            target_table_name: target.0.to_string(),
+            target_column_name: target.1.to_string(),
+            delete_action: no_action(),
+            update_action: no_action(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl SchemaInfo {
+    /// A database with only the `dbo` schema and the given tables
+    pub(crate) fn for_test(database: &str, tables: Vec<TableInfo>) -> Self {
+        SchemaInfo {
+            database_name: database.to_string(),
+            schemas: vec!["dbo".to_string()],
+            tables,
+            relationships: Vec::new(),
+            routines: Vec::new(),
+            indexes: Vec::new(),
+            constraints: Vec::new(),
+            triggers: Vec::new(),
+            sequences: Vec::new(),
+            synonyms: Vec::new(),
+            user_types: Vec::new(),
+            fetched_at: String::new(),
+            cache_age_seconds: None,
+        }
+    }
 }
 
 /// Manages schema metadata caching per connection/database
 pub struct SchemaMetadataManager {
-    connection_manager: Arc<MssqlConnectionManager>,
-    db_manager: Arc<DatabaseManager>,
+    pub(crate) connection_manager: Arc<MssqlConnectionManager>,
+    pub(crate) db_manager: Arc<DatabaseManager>,
+    /// (connection_id, database) pairs with a background refresh in flight
+    refreshing: Mutex<HashSet<(String, String)>>,
+}
+
+/// Incremental refreshes touching more objects than this re-fetch everything
+const MAX_INCREMENTAL_OBJECTS: usize = 500;
+
+/// Identity and last DDL change of a catalog object
+#[derive(Debug, Clone)]
+struct ObjectVersion {
+    object_id: i32,
+    modify_date: String,
+}
+
+/// Everything in SchemaInfo that is fetched per object
+struct SchemaObjects {
+    tables: Vec<TableInfo>,
+    relationships: Vec<RelationshipInfo>,
+    routines: Vec<RoutineInfo>,
+    indexes: Vec<IndexInfo>,
+    constraints: Vec<ConstraintInfo>,
+}
+
+/// Objects whose changes don't show in a table's modify_date; small enough
+/// to always re-read in full
+struct StandaloneObjects {
+    triggers: Vec<TriggerInfo>,
+    sequences: Vec<SequenceInfo>,
+    synonyms: Vec<SynonymInfo>,
+    user_types: Vec<UserTypeInfo>,
+    /// Extended properties keyed by (schema, object)
+    extended_properties: HashMap<(String, String), ObjectProperties>,
+}
+
+/// Extended properties of an object and its columns
+#[derive(Debug, Default)]
+struct ObjectProperties {
+    object: Vec<ExtendedProperty>,
+    columns: HashMap<String, Vec<ExtendedProperty>>,
+}
+
+impl StandaloneObjects {
+    fn apply(mut self, schema: &mut SchemaInfo) {
+        schema.triggers = self.triggers;
+        schema.sequences = self.sequences;
+        schema.synonyms = self.synonyms;
+        schema.user_types = self.user_types;
+
+        for table in &mut schema.tables {
+            let mut properties = self
+                .extended_properties
+                .remove(&(table.schema_name.clone(), table.table_name.clone()))
+                .unwrap_or_default();
+            table.extended_properties = properties.object;
+            for column in &mut table.columns {
+                column.extended_properties = properties.columns.remove(&column.name).unwrap_or_default();
+            }
+        }
+        for routine in &mut schema.routines {
+            routine.extended_properties = self
+                .extended_properties
+                .remove(&(routine.schema_name.clone(), routine.routine_name.clone()))
+                .map(|p| p.object)
+                .unwrap_or_default();
+        }
+    }
+}
+
+/// What an incremental refresh has to re-read and discard
+#[derive(Debug, Default)]
+struct SchemaDelta {
+    /// Object ids of new or altered objects
+    changed: Vec<i32>,
+    /// (schema, name) of cached objects that were altered or dropped
+    stale: HashSet<(String, String)>,
+}
+
+impl SchemaDelta {
+    fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.stale.is_empty()
+    }
+}
+
+/// Compare cached modify dates against the catalog
+fn schema_delta(cached: &SchemaInfo, versions: &HashMap<(String, String), ObjectVersion>) -> SchemaDelta {
+    let cached_dates: HashMap<(String, String), Option<&str>> = cached
+        .tables
+        .iter()
+        .map(|t| ((t.schema_name.clone(), t.table_name.clone()), t.modify_date.as_deref()))
+        .chain(
+            cached
+                .routines
+                .iter()
+                .map(|r| ((r.schema_name.clone(), r.routine_name.clone()), r.modify_date.as_deref())),
+        )
+        .collect();
+
+    let mut delta = SchemaDelta::default();
+    for (key, version) in versions {
+        if cached_dates.get(key).copied().flatten() != Some(version.modify_date.as_str()) {
+            delta.changed.push(version.object_id);
+            if cached_dates.contains_key(key) {
+                delta.stale.insert(key.clone());
+            }
+        }
+    }
+    for key in cached_dates.keys() {
+        if !versions.contains_key(key) {
+            delta.stale.insert(key.clone());
+        }
+    }
+    delta.changed.sort_unstable();
+    delta
+}
+
+/// Replace stale cached objects with freshly fetched ones
+fn merge_schema(cached: &mut SchemaInfo, delta: &SchemaDelta, fresh: SchemaObjects) {
+    let is_stale = |schema_name: &str, name: &str| delta.stale.contains(&(schema_name.to_string(), name.to_string()));
+
+    cached.tables.retain(|t| !is_stale(&t.schema_name, &t.table_name));
+    cached.tables.extend(fresh.tables);
+    cached
+        .tables
+        .sort_by(|a, b| (&a.schema_name, &a.table_name).cmp(&(&b.schema_name, &b.table_name)));
+
+    cached.routines.retain(|r| !is_stale(&r.schema_name, &r.routine_name));
+    cached.routines.extend(fresh.routines);
+    cached
+        .routines
+        .sort_by(|a, b| (&a.schema_name, &a.routine_name).cmp(&(&b.schema_name, &b.routine_name)));
+
+    // Foreign keys, indexes and constraints belong to their table, whose
+    // modify_date changes with them
+    cached
+        .relationships
+        .retain(|r| !is_stale(&r.source_schema_name, &r.source_table_name));
+    cached.relationships.extend(fresh.relationships);
+
+    cached.indexes.retain(|i| !is_stale(&i.schema_name, &i.table_name));
+    cached.indexes.extend(fresh.indexes);
+
+    cached.constraints.retain(|c| !is_stale(&c.schema_name, &c.table_name));
+    cached.constraints.extend(fresh.constraints);
+}
+
+/// A database's claim on `SchemaMetadataManager::refreshing`, released on
+/// drop so a failed or cancelled refresh does not block later ones
+struct RefreshClaim<'a> {
+    refreshing: &'a Mutex<HashSet<(String, String)>>,
+    key: (String, String),
+}
+
+impl Drop for RefreshClaim<'_> {
+    fn drop(&mut self) {
+        let mut refreshing = self.refreshing.lock().unwrap_or_else(|e| e.into_inner());
+        refreshing.remove(&self.key);
+    }
+}
+
+/// Fractional-second digits of datetime2, time and datetimeoffset columns,
+/// which INFORMATION_SCHEMA reports as DATETIME_PRECISION rather than scale
+fn fractional_scale(data_type: &str, datetime_precision: Option<i16>) -> Option<i32> {
+    match data_type.to_ascii_lowercase().as_str() {
+        "datetime2" | "time" | "datetimeoffset" => datetime_precision.map(i32::from),
+        _ => None,
+    }
+}
+
+/// Fetch rowstore and columnstore indexes with their key and included columns,
+/// ordered by table and then index_id. Shared with object scripting.
+pub(crate) async fn fetch_indexes(
+    conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
+    schema_filter: Option<&str>,
+    object_ids: Option<&[i32]>,
+) -> Result<Vec<IndexInfo>, ConnectionError> {
+    let schema_condition = schema_filter
+        .map(|s| format!("AND s.name = '{}'", s.replace('\'', "''")))
+        .unwrap_or_default();
+
+    let indexes_query = format!(
+        r#"
+        SELECT
+            i.object_id,
+            i.index_id,
+            s.name AS schema_name,
+            t.name AS table_name,
+            i.name AS index_name,
+            i.type_desc,
+            i.is_unique,
+            i.is_primary_key,
+            i.is_unique_constraint,
+            i.filter_definition
+        FROM sys.indexes i
+        JOIN sys.tables t ON t.object_id = i.object_id
+        JOIN sys.schemas s ON s.schema_id = t.schema_id
+        WHERE i.type IN (1, 2, 5, 6) AND i.is_hypothetical = 0 AND t.is_ms_shipped = 0 {} {}
+        ORDER BY s.name, t.name, i.index_id
+    "#,
+        schema_condition,
+        object_condition("t.object_id", object_ids)
+    );
+
+    let stream = conn.simple_query(&indexes_query).await?;
+    let index_rows = stream.into_first_result().await?;
+
+    let mut positions: HashMap<(i32, i32), usize> = HashMap::new();
+    let mut indexes: Vec<IndexInfo> = Vec::new();
+    for row in index_rows.iter() {
+        let (Some(object_id), Some(index_id), Some(schema_name), Some(table_name), Some(index_name)) = (
+            row.get::<i32, _>(0),
+            row.get::<i32, _>(1),
+            row.get::<&str, _>(2),
+            row.get::<&str, _>(3),
+            row.get::<&str, _>(4),
+        ) else {
+            continue;
+        };
+        positions.insert((object_id, index_id), indexes.len());
+        indexes.push(IndexInfo {
+            schema_name: schema_name.to_string(),
+            table_name: table_name.to_string(),
+            index: IndexDefinition {
+                name: index_name.to_string(),
+                type_desc: row.get::<&str, _>(5).unwrap_or("NONCLUSTERED").to_string(),
+                is_unique: row.get::<bool, _>(6).unwrap_or(false),
+                is_primary_key: row.get::<bool, _>(7).unwrap_or(false),
+                is_unique_constraint: row.get::<bool, _>(8).unwrap_or(false),
+                key_columns: vec![],
+                included_columns: vec![],
+                filter_definition: row.get::<&str, _>(9).map(|s| s.to_string()),
+            },
+        });
+    }
+
+    let columns_query = format!(
+        r#"
+        SELECT ic.object_id, ic.index_id, c.name, ic.is_descending_key, ic.is_included_column
+        FROM sys.index_columns ic
+        JOIN sys.columns c ON c.object_id = ic.object_id AND c.column_id = ic.column_id
+        JOIN sys.tables t ON t.object_id = ic.object_id
+        JOIN sys.schemas s ON s.schema_id = t.schema_id
+        WHERE t.is_ms_shipped = 0 {} {}
+        ORDER BY ic.object_id, ic.index_id, ic.key_ordinal, ic.index_column_id
+    "#,
+        schema_condition,
+        object_condition("ic.object_id", object_ids)
+    );
+
+    let stream = conn.simple_query(&columns_query).await?;
+    let column_rows = stream.into_first_result().await?;
+
+    for row in column_rows.iter() {
+        let (Some(object_id), Some(index_id), Some(column_name)) =
+            (row.get::<i32, _>(0), row.get::<i32, _>(1), row.get::<&str, _>(2))
+        else {
+            continue;
+        };
+        let Some(&position) = positions.get(&(object_id, index_id)) else {
+            continue;
+        };
+        let index = &mut indexes[position].index;
+        if row.get::<bool, _>(4).unwrap_or(false) {
+            index.included_columns.push(column_name.to_string());
+        } else {
+            index.key_columns.push(IndexColumn {
+                name: column_name.to_string(),
+                is_descending: row.get::<bool, _>(3).unwrap_or(false),
+            });
+        }
+    }
+
+    Ok(indexes)
 }
 
 impl SchemaMetadataManager {
@@ -91,12 +600,17 @@ impl SchemaMetadataManager {
         Self {
             connection_manager,
             db_manager,
+            refreshing: Mutex::new(HashSet::new()),
         }
     }
 
     /// Get schema info from cache
     pub async fn get_cached_schema(&self, connection_id: &str, database: &str) -> Option<SchemaInfo> {
-        self.db_manager.get_schema(connection_id, database).unwrap_or(None)
+        let mut schema = self.db_manager.get_schema(connection_id, database).unwrap_or(None)?;
+        schema.cache_age_seconds = chrono::DateTime::parse_from_rfc3339(&schema.fetched_at)
+            .ok()
+            .map(|fetched_at| (chrono::Utc::now() - fetched_at.with_timezone(&chrono::Utc)).num_seconds());
+        Some(schema)
     }
 
     /// Clear schema cache for a connection/database
@@ -122,28 +636,561 @@ impl SchemaMetadataManager {
         // Fetch schemas
         let schemas = self.fetch_schemas(&mut conn).await?;
 
+        let versions = self.fetch_object_versions(&mut conn, schema_filter).await?;
+        let objects = self.fetch_objects(&mut conn, schema_filter, None, &versions).await?;
+
+        let mut schema_info = SchemaInfo {
+            database_name: database.to_string(),
+            schemas,
+            tables: objects.tables,
+            relationships: objects.relationships,
+            routines: objects.routines,
+            indexes: objects.indexes,
+            constraints: objects.constraints,
+            triggers: vec![],
+            sequences: vec![],
+            synonyms: vec![],
+            user_types: vec![],
+            fetched_at: chrono::Utc::now().to_rfc3339(),
+            cache_age_seconds: Some(0),
+        };
+        self.fetch_standalone_objects(&mut conn, schema_filter)
+            .await?
+            .apply(&mut schema_info);
+
+        // Cache the result
+        let _ = self.db_manager.save_schema(connection_id, database, &schema_info);
+
+        Ok(schema_info)
+    }
+
+    /// Bring the cached schema up to date, re-reading only objects created,
+    /// altered or dropped since it was fetched. Falls back to a full fetch when
+    /// nothing is cached or too much has changed.
+    pub async fn refresh_schema(
+        &self,
+        connection_id: &str,
+        database: &str,
+    ) -> Result<SchemaInfo, ConnectionError> {
+        let Some(mut cached) = self.db_manager.get_schema(connection_id, database).unwrap_or(None) else {
+            return self.fetch_schema(connection_id, database, None).await;
+        };
+
+        {
+            let pool = self.connection_manager.connect(connection_id).await?;
+            let mut conn = pool.get().await?;
+            conn.simple_query(&format!("USE {}", quote_identifier(database))).await?;
+
+            let versions = self.fetch_object_versions(&mut conn, None).await?;
+            let delta = schema_delta(&cached, &versions);
+            if delta.changed.len() <= MAX_INCREMENTAL_OBJECTS {
+                if !delta.is_empty() {
+                    let objects = self
+                        .fetch_objects(&mut conn, None, Some(&delta.changed), &versions)
+                        .await?;
+                    merge_schema(&mut cached, &delta, objects);
+                }
+                cached.schemas = self.fetch_schemas(&mut conn).await?;
+                self.fetch_standalone_objects(&mut conn, None)
+                    .await?
+                    .apply(&mut cached);
+                cached.fetched_at = chrono::Utc::now().to_rfc3339();
+                cached.cache_age_seconds = Some(0);
+
+                let _ = self.db_manager.save_schema(connection_id, database, &cached);
+                return Ok(cached);
+            }
+        }
+
+        self.fetch_schema(connection_id, database, None).await
+    }
+
+    /// Refresh the cached schema if it is older than the configured TTL.
+    /// Returns None when nothing is cached, the cache is fresh, or a refresh
+    /// of the same database is already running.
+    pub async fn refresh_if_stale(
+        &self,
+        connection_id: &str,
+        database: &str,
+    ) -> Result<Option<SchemaInfo>, ConnectionError> {
+        let Some(cached) = self.get_cached_schema(connection_id, database).await else {
+            return Ok(None);
+        };
+        if !self.is_stale(&cached) {
+            return Ok(None);
+        }
+
+        let key = (connection_id.to_string(), database.to_string());
+        let claimed = self
+            .refreshing
+            .lock()
+            .map_err(|e| ConnectionError::QueryError(e.to_string()))?
+            .insert(key.clone());
+        if !claimed {
+            return Ok(None);
+        }
+        let _claim = RefreshClaim { refreshing: &self.refreshing, key };
+        self.refresh_schema(connection_id, database).await.map(Some)
+    }
+
+    /// Whether a cached schema is older than the schema cache TTL (0 disables expiry)
+    pub fn is_stale(&self, schema: &SchemaInfo) -> bool {
+        let ttl_minutes = self.db_manager.get_schema_cache_ttl_minutes().unwrap_or(60);
+        ttl_minutes > 0 && schema.cache_age_seconds.unwrap_or(0) >= i64::from(ttl_minutes) * 60
+    }
+
+    /// Fetch tables, relationships, routines, indexes and constraints,
+    /// optionally limited to the given object ids, stamped with their modify dates
+    async fn fetch_objects(
+        &self,
+        conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
+        schema_filter: Option<&str>,
+        object_ids: Option<&[i32]>,
+        versions: &HashMap<(String, String), ObjectVersion>,
+    ) -> Result<SchemaObjects, ConnectionError> {
         // Fetch tables and views
-        let tables = self.fetch_tables_and_views(&mut conn, schema_filter).await?;
+        let mut tables = self.fetch_tables_and_views(conn, schema_filter, object_ids).await?;
 
         // Fetch foreign key relationships for join suggestions
-        let relationships = self.fetch_relationships(&mut conn, schema_filter).await?;
+        let relationships = self.fetch_relationships(conn, schema_filter, object_ids).await?;
 
         // Fetch routines (stored procedures and functions)
-        let routines = self.fetch_routines(&mut conn, schema_filter).await?;
+        let mut routines = self.fetch_routines(conn, schema_filter, object_ids).await?;
 
-        let schema_info = SchemaInfo {
-            database_name: database.to_string(),
-            schemas,
+        // Fetch indexes and key constraints
+        let indexes = fetch_indexes(conn, schema_filter, object_ids).await?;
+
+        // Fetch check and default constraints
+        let constraints = self.fetch_constraints(conn, schema_filter, object_ids).await?;
+
+        // Attach view and routine bodies
+        let mut definitions = self.fetch_definitions(conn, schema_filter, object_ids).await?;
+        for table in &mut tables {
+            let key = (table.schema_name.clone(), table.table_name.clone());
+            if table.table_type == "VIEW" {
+                table.definition = definitions.remove(&key);
+            }
+            table.modify_date = versions.get(&key).map(|v| v.modify_date.clone());
+        }
+        for routine in &mut routines {
+            let key = (routine.schema_name.clone(), routine.routine_name.clone());
+            routine.definition = definitions.remove(&key);
+            routine.modify_date = versions.get(&key).map(|v| v.modify_date.clone());
+        }
+
+        Ok(SchemaObjects {
             tables,
             relationships,
             routines,
-            fetched_at: chrono::Utc::now().to_rfc3339(),
-        };
+            indexes,
+            constraints,
+        })
+    }
 
-        // Cache the result
-        let _ = self.db_manager.save_schema(connection_id, database, &schema_info);
+    /// Fetch triggers, sequences, synonyms and user-defined types
+    async fn fetch_standalone_objects(
+        &self,
+        conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
+        schema_filter: Option<&str>,
+    ) -> Result<StandaloneObjects, ConnectionError> {
+        Ok(StandaloneObjects {
+            triggers: self.fetch_triggers(conn, schema_filter).await?,
+            sequences: self.fetch_sequences(conn, schema_filter).await?,
+            synonyms: self.fetch_synonyms(conn, schema_filter).await?,
+            user_types: self.fetch_user_types(conn, schema_filter).await?,
+            extended_properties: self.fetch_extended_properties(conn, schema_filter).await?,
+        })
+    }
 
-        Ok(schema_info)
+    /// Fetch CHECK and DEFAULT constraints
+    async fn fetch_constraints(
+        &self,
+        conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
+        schema_filter: Option<&str>,
+        object_ids: Option<&[i32]>,
+    ) -> Result<Vec<ConstraintInfo>, ConnectionError> {
+        let condition = format!(
+            "{} {}",
+            schema_filter
+                .map(|s| format!("AND s.name = '{}'", s.replace('\'', "''")))
+                .unwrap_or_default(),
+            object_condition("t.object_id", object_ids)
+        );
+
+        let query = format!(
+            r#"
+            SELECT s.name, t.name, cc.name, 'CHECK', col.name, cc.definition, cc.is_disabled
+            FROM sys.check_constraints cc
+            JOIN sys.tables t ON t.object_id = cc.parent_object_id
+            JOIN sys.schemas s ON s.schema_id = t.schema_id
+            LEFT JOIN sys.columns col
+                ON col.object_id = cc.parent_object_id AND col.column_id = cc.parent_column_id
+            WHERE t.is_ms_shipped = 0 {0}
+            UNION ALL
+            SELECT s.name, t.name, dc.name, 'DEFAULT', col.name, dc.definition, CAST(0 AS bit)
+            FROM sys.default_constraints dc
+            JOIN sys.tables t ON t.object_id = dc.parent_object_id
+            JOIN sys.schemas s ON s.schema_id = t.schema_id
+            JOIN sys.columns col
+                ON col.object_id = dc.parent_object_id AND col.column_id = dc.parent_column_id
+            WHERE t.is_ms_shipped = 0 {0}
+            ORDER BY 1, 2, 3
+        "#,
+            condition
+        );
+
+        let stream = conn.simple_query(&query).await?;
+        let rows = stream.into_first_result().await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                Some(ConstraintInfo {
+                    schema_name: row.get::<&str, _>(0)?.to_string(),
+                    table_name: row.get::<&str, _>(1)?.to_string(),
+                    constraint_name: row.get::<&str, _>(2)?.to_string(),
+                    constraint_type: if row.get::<&str, _>(3)? == "CHECK" {
+                        ConstraintType::Check
+                    } else {
+                        ConstraintType::Default
+                    },
+                    column_name: row.get::<&str, _>(4).map(|s| s.to_string()),
+                    definition: row.get::<&str, _>(5)?.to_string(),
+                    is_disabled: row.get::<bool, _>(6).unwrap_or(false),
+                })
+            })
+            .collect())
+    }
+
+    /// Fetch DML triggers (filtered by their parent's schema) and, without a
+    /// schema filter, database-level DDL triggers
+    async fn fetch_triggers(
+        &self,
+        conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
+        schema_filter: Option<&str>,
+    ) -> Result<Vec<TriggerInfo>, ConnectionError> {
+        let schema_condition = schema_filter
+            .map(|s| format!("AND OBJECT_SCHEMA_NAME(tr.parent_id) = '{}'", s.replace('\'', "''")))
+            .unwrap_or_default();
+
+        let query = format!(
+            r#"
+            SELECT
+                tr.object_id,
+                CASE WHEN tr.parent_class = 1 THEN OBJECT_SCHEMA_NAME(tr.parent_id) END,
+                tr.name,
+                CASE WHEN tr.parent_class = 1 THEN OBJECT_NAME(tr.parent_id) END,
+                tr.is_instead_of_trigger,
+                tr.is_disabled
+            FROM sys.triggers tr
+            WHERE tr.is_ms_shipped = 0 AND tr.parent_class IN (0, 1) {}
+            ORDER BY 2, 4, 3
+        "#,
+            schema_condition
+        );
+
+        let stream = conn.simple_query(&query).await?;
+        let rows = stream.into_first_result().await?;
+
+        let mut positions: HashMap<i32, usize> = HashMap::new();
+        let mut triggers: Vec<TriggerInfo> = Vec::new();
+        for row in rows.iter() {
+            let (Some(object_id), Some(trigger_name)) = (row.get::<i32, _>(0), row.get::<&str, _>(2)) else {
+                continue;
+            };
+            positions.insert(object_id, triggers.len());
+            triggers.push(TriggerInfo {
+                schema_name: row.get::<&str, _>(1).map(|s| s.to_string()),
+                trigger_name: trigger_name.to_string(),
+                parent_name: row.get::<&str, _>(3).map(|s| s.to_string()),
+                is_instead_of: row.get::<bool, _>(4).unwrap_or(false),
+                is_disabled: row.get::<bool, _>(5).unwrap_or(false),
+                events: vec![],
+            });
+        }
+
+        let events_query = r#"
+            SELECT te.object_id, te.type_desc
+            FROM sys.trigger_events te
+            JOIN sys.triggers tr ON tr.object_id = te.object_id
+            WHERE tr.is_ms_shipped = 0
+            ORDER BY te.object_id, te.type
+        "#;
+
+        let stream = conn.simple_query(events_query).await?;
+        let event_rows = stream.into_first_result().await?;
+
+        for row in event_rows.iter() {
+            let (Some(object_id), Some(event)) = (row.get::<i32, _>(0), row.get::<&str, _>(1)) else {
+                continue;
+            };
+            if let Some(&position) = positions.get(&object_id) {
+                triggers[position].events.push(event.to_string());
+            }
+        }
+
+        Ok(triggers)
+    }
+
+    /// Fetch sequences with their current values
+    async fn fetch_sequences(
+        &self,
+        conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
+        schema_filter: Option<&str>,
+    ) -> Result<Vec<SequenceInfo>, ConnectionError> {
+        let query = format!(
+            r#"
+            SELECT
+                s.name,
+                seq.name,
+                TYPE_NAME(seq.user_type_id),
+                CAST(seq.start_value AS nvarchar(40)),
+                CAST(seq.increment AS nvarchar(40)),
+                CAST(seq.minimum_value AS nvarchar(40)),
+                CAST(seq.maximum_value AS nvarchar(40)),
+                seq.is_cycling,
+                CAST(seq.current_value AS nvarchar(40))
+            FROM sys.sequences seq
+            JOIN sys.schemas s ON s.schema_id = seq.schema_id
+            WHERE 1=1 {}
+            ORDER BY s.name, seq.name
+        "#,
+            schema_filter
+                .map(|s| format!("AND s.name = '{}'", s.replace('\'', "''")))
+                .unwrap_or_default()
+        );
+
+        let stream = conn.simple_query(&query).await?;
+        let rows = stream.into_first_result().await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                Some(SequenceInfo {
+                    schema_name: row.get::<&str, _>(0)?.to_string(),
+                    sequence_name: row.get::<&str, _>(1)?.to_string(),
+                    data_type: row.get::<&str, _>(2).unwrap_or("bigint").to_string(),
+                    start_value: row.get::<&str, _>(3).unwrap_or_default().to_string(),
+                    increment: row.get::<&str, _>(4).unwrap_or_default().to_string(),
+                    minimum_value: row.get::<&str, _>(5).unwrap_or_default().to_string(),
+                    maximum_value: row.get::<&str, _>(6).unwrap_or_default().to_string(),
+                    is_cycling: row.get::<bool, _>(7).unwrap_or(false),
+                    current_value: row.get::<&str, _>(8).map(|s| s.to_string()),
+                })
+            })
+            .collect())
+    }
+
+    /// Fetch synonyms with their base object split into name parts
+    async fn fetch_synonyms(
+        &self,
+        conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
+        schema_filter: Option<&str>,
+    ) -> Result<Vec<SynonymInfo>, ConnectionError> {
+        let query = format!(
+            r#"
+            SELECT
+                s.name,
+                sn.name,
+                sn.base_object_name,
+                PARSENAME(sn.base_object_name, 4),
+                PARSENAME(sn.base_object_name, 3),
+                PARSENAME(sn.base_object_name, 2),
+                PARSENAME(sn.base_object_name, 1)
+            FROM sys.synonyms sn
+            JOIN sys.schemas s ON s.schema_id = sn.schema_id
+            WHERE 1=1 {}
+            ORDER BY s.name, sn.name
+        "#,
+            schema_filter
+                .map(|s| format!("AND s.name = '{}'", s.replace('\'', "''")))
+                .unwrap_or_default()
+        );
+
+        let stream = conn.simple_query(&query).await?;
+        let rows = stream.into_first_result().await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                let base_object_name = row.get::<&str, _>(2)?.to_string();
+                Some(SynonymInfo {
+                    schema_name: row.get::<&str, _>(0)?.to_string(),
+                    synonym_name: row.get::<&str, _>(1)?.to_string(),
+                    base_server: row.get::<&str, _>(3).map(|s| s.to_string()),
+                    base_database: row.get::<&str, _>(4).map(|s| s.to_string()),
+                    base_schema: row.get::<&str, _>(5).map(|s| s.to_string()),
+                    base_object: row
+                        .get::<&str, _>(6)
+                        .map(|s| s.to_string())
+                        .unwrap_or_else(|| base_object_name.clone()),
+                    base_object_name,
+                })
+            })
+            .collect())
+    }
+
+    /// Fetch user-defined alias types and table types (with their columns)
+    async fn fetch_user_types(
+        &self,
+        conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
+        schema_filter: Option<&str>,
+    ) -> Result<Vec<UserTypeInfo>, ConnectionError> {
+        let schema_condition = schema_filter
+            .map(|s| format!("AND s.name = '{}'", s.replace('\'', "''")))
+            .unwrap_or_default();
+
+        let types_query = format!(
+            r#"
+            SELECT
+                s.name,
+                t.name,
+                t.is_table_type,
+                TYPE_NAME(t.system_type_id),
+                t.max_length,
+                t.precision,
+                t.scale,
+                t.is_nullable,
+                tt.type_table_object_id
+            FROM sys.types t
+            JOIN sys.schemas s ON s.schema_id = t.schema_id
+            LEFT JOIN sys.table_types tt ON tt.user_type_id = t.user_type_id
+            WHERE t.is_user_defined = 1 {}
+            ORDER BY s.name, t.name
+        "#,
+            schema_condition
+        );
+
+        let stream = conn.simple_query(&types_query).await?;
+        let type_rows = stream.into_first_result().await?;
+
+        let mut positions: HashMap<i32, usize> = HashMap::new();
+        let mut user_types: Vec<UserTypeInfo> = Vec::new();
+        for row in type_rows.iter() {
+            let (Some(schema_name), Some(type_name)) = (row.get::<&str, _>(0), row.get::<&str, _>(1)) else {
+                continue;
+            };
+            let is_table_type = row.get::<bool, _>(2).unwrap_or(false);
+            if let Some(table_object_id) = row.get::<i32, _>(8) {
+                positions.insert(table_object_id, user_types.len());
+            }
+            user_types.push(UserTypeInfo {
+                schema_name: schema_name.to_string(),
+                type_name: type_name.to_string(),
+                is_table_type,
+                base_type: if is_table_type {
+                    None
+                } else {
+                    row.get::<&str, _>(3).map(|base| {
+                        format_column_type(
+                            base,
+                            row.get::<i16, _>(4).unwrap_or(0),
+                            row.get::<u8, _>(5).unwrap_or(0),
+                            row.get::<u8, _>(6).unwrap_or(0),
+                        )
+                    })
+                },
+                is_nullable: row.get::<bool, _>(7).unwrap_or(true),
+                columns: vec![],
+            });
+        }
+
+        let columns_query = format!(
+            r#"
+            SELECT
+                tt.type_table_object_id,
+                c.name,
+                TYPE_NAME(c.user_type_id),
+                CASE
+                    WHEN c.max_length = -1 THEN -1
+                    WHEN TYPE_NAME(c.system_type_id) IN ('nchar', 'nvarchar') THEN c.max_length / 2
+                    WHEN TYPE_NAME(c.system_type_id) IN ('char', 'varchar', 'binary', 'varbinary') THEN c.max_length
+                END,
+                CAST(c.precision AS int),
+                CAST(c.scale AS int),
+                c.is_nullable,
+                c.is_identity,
+                c.is_computed,
+                OBJECT_DEFINITION(c.default_object_id),
+                c.column_id,
+                CASE WHEN EXISTS (
+                    SELECT 1
+                    FROM sys.indexes i
+                    JOIN sys.index_columns ic ON ic.object_id = i.object_id AND ic.index_id = i.index_id
+                    WHERE i.object_id = c.object_id AND i.is_primary_key = 1 AND ic.column_id = c.column_id
+                ) THEN 1 ELSE 0 END
+            FROM sys.table_types tt
+            JOIN sys.schemas s ON s.schema_id = tt.schema_id
+            JOIN sys.columns c ON c.object_id = tt.type_table_object_id
+            WHERE 1=1 {}
+            ORDER BY tt.type_table_object_id, c.column_id
+        "#,
+            schema_condition
+        );
+
+        let stream = conn.simple_query(&columns_query).await?;
+        let column_rows = stream.into_first_result().await?;
+
+        for row in column_rows.iter() {
+            let (Some(table_object_id), Some(name)) = (row.get::<i32, _>(0), row.get::<&str, _>(1)) else {
+                continue;
+            };
+            let Some(&position) = positions.get(&table_object_id) else {
+                continue;
+            };
+            user_types[position].columns.push(ColumnInfo {
+                name: name.to_string(),
+                data_type: row.get::<&str, _>(2).unwrap_or("unknown").to_string(),
+                max_length: row.get::<i32, _>(3),
+                precision: row.get::<i32, _>(4),
+                scale: row.get::<i32, _>(5),
+                is_nullable: row.get::<bool, _>(6).unwrap_or(true),
+                is_primary_key: row.get::<i32, _>(11).map(|v| v == 1).unwrap_or(false),
+                is_identity: row.get::<bool, _>(7).unwrap_or(false),
+                identity_seed: None,
+                identity_increment: None,
+                is_computed: row.get::<bool, _>(8).unwrap_or(false),
+                column_default: row.get::<&str, _>(9).map(|s| s.to_string()),
+                ordinal_position: row.get::<i32, _>(10).unwrap_or(0),
+                extended_properties: vec![],
+            });
+        }
+
+        Ok(user_types)
+    }
+
+    /// Fetch object ids and modify dates for every table, view and routine
+    async fn fetch_object_versions(
+        &self,
+        conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
+        schema_filter: Option<&str>,
+    ) -> Result<HashMap<(String, String), ObjectVersion>, ConnectionError> {
+        let query = format!(
+            r#"
+            SELECT SCHEMA_NAME(o.schema_id), o.name, o.object_id, CONVERT(varchar(23), o.modify_date, 126)
+            FROM sys.objects o
+            WHERE o.type IN ('U', 'V', 'P', 'PC', 'FN', 'IF', 'TF', 'FS', 'FT') {}
+        "#,
+            schema_filter
+                .map(|s| format!("AND SCHEMA_NAME(o.schema_id) = '{}'", s.replace('\'', "''")))
+                .unwrap_or_default()
+        );
+
+        let stream = conn.simple_query(&query).await?;
+        let rows = stream.into_first_result().await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                Some((
+                    (row.get::<&str, _>(0)?.to_string(), row.get::<&str, _>(1)?.to_string()),
+                    ObjectVersion {
+                        object_id: row.get::<i32, _>(2)?,
+                        modify_date: row.get::<&str, _>(3)?.to_string(),
+                    },
+                ))
+            })
+            .collect())
     }
 
     /// Fetch foreign key relationships between tables
@@ -151,6 +1198,7 @@ impl SchemaMetadataManager {
         &self,
         conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
         schema_filter: Option<&str>,
+        object_ids: Option<&[i32]>,
     ) -> Result<Vec<RelationshipInfo>, ConnectionError> {
         let schema_condition = schema_filter
             .map(|s| format!(
@@ -170,7 +1218,9 @@ impl SchemaMetadataManager {
                 src_column.name AS source_column_name,
                 tgt_schema.name AS target_schema_name,
                 tgt_table.name AS target_table_name,
-                tgt_column.name AS target_column_name
+                tgt_column.name AS target_column_name,
+                fk.delete_referential_action_desc,
+                fk.update_referential_action_desc
             FROM sys.foreign_keys fk
             JOIN sys.foreign_key_columns fkc
                 ON fk.object_id = fkc.constraint_object_id
@@ -188,10 +1238,11 @@ impl SchemaMetadataManager {
             JOIN sys.columns tgt_column
                 ON fkc.referenced_object_id = tgt_column.object_id
                 AND fkc.referenced_column_id = tgt_column.column_id
-            WHERE 1=1 {}
+            WHERE 1=1 {} {}
             ORDER BY fk.name, fkc.constraint_column_id
         "#,
-            schema_condition
+            schema_condition,
+            object_condition("fk.parent_object_id", object_ids)
         );
 
         let stream = conn.simple_query(&query).await?;
@@ -209,6 +1260,8 @@ impl SchemaMetadataManager {
                     target_schema_name: row.get::<&str, _>(5)?.to_string(),
                     target_table_name: row.get::<&str, _>(6)?.to_string(),
                     target_column_name: row.get::<&str, _>(7)?.to_string(),
+                    delete_action: row.get::<&str, _>(8).unwrap_or("NO_ACTION").to_string(),
+                    update_action: row.get::<&str, _>(9).unwrap_or("NO_ACTION").to_string(),
                 })
             })
             .collect();
@@ -216,6 +1269,88 @@ impl SchemaMetadataManager {
         Ok(relationships)
     }
 
+    /// Fetch view and routine bodies keyed by (schema, name)
+    async fn fetch_definitions(
+        &self,
+        conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
+        schema_filter: Option<&str>,
+        object_ids: Option<&[i32]>,
+    ) -> Result<HashMap<(String, String), String>, ConnectionError> {
+        let query = format!(
+            r#"
+            SELECT SCHEMA_NAME(o.schema_id), o.name, OBJECT_DEFINITION(o.object_id)
+            FROM sys.sql_modules m
+            JOIN sys.objects o ON o.object_id = m.object_id
+            WHERE o.type IN ('V', 'P', 'FN', 'IF', 'TF') AND o.is_ms_shipped = 0 {} {}
+        "#,
+            schema_filter
+                .map(|s| format!("AND SCHEMA_NAME(o.schema_id) = '{}'", s.replace('\'', "''")))
+                .unwrap_or_default(),
+            object_condition("o.object_id", object_ids)
+        );
+
+        let stream = conn.simple_query(&query).await?;
+        let rows = stream.into_first_result().await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                Some((
+                    (row.get::<&str, _>(0)?.to_string(), row.get::<&str, _>(1)?.to_string()),
+                    row.get::<&str, _>(2)?.to_string(),
+                ))
+            })
+            .collect())
+    }
+
+    /// Fetch extended properties of tables, views, routines and columns
+    async fn fetch_extended_properties(
+        &self,
+        conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
+        schema_filter: Option<&str>,
+    ) -> Result<HashMap<(String, String), ObjectProperties>, ConnectionError> {
+        let query = format!(
+            r#"
+            SELECT SCHEMA_NAME(o.schema_id), o.name, c.name, ep.name, CAST(ep.value AS nvarchar(max))
+            FROM sys.extended_properties ep
+            JOIN sys.objects o ON o.object_id = ep.major_id
+            LEFT JOIN sys.columns c ON c.object_id = ep.major_id AND c.column_id = ep.minor_id
+            WHERE ep.class = 1
+                AND o.type IN ('U', 'V', 'P', 'FN', 'IF', 'TF')
+                AND o.is_ms_shipped = 0
+                AND (ep.minor_id = 0 OR c.column_id IS NOT NULL) {}
+            ORDER BY 1, 2, 3, ep.name
+        "#,
+            schema_filter
+                .map(|s| format!("AND SCHEMA_NAME(o.schema_id) = '{}'", s.replace('\'', "''")))
+                .unwrap_or_default()
+        );
+
+        let stream = conn.simple_query(&query).await?;
+        let rows = stream.into_first_result().await?;
+
+        let mut properties: HashMap<(String, String), ObjectProperties> = HashMap::new();
+        for row in rows.iter() {
+            let (Some(schema_name), Some(object_name), Some(name)) =
+                (row.get::<&str, _>(0), row.get::<&str, _>(1), row.get::<&str, _>(3))
+            else {
+                continue;
+            };
+            let property = ExtendedProperty {
+                name: name.to_string(),
+                value: row.get::<&str, _>(4).unwrap_or_default().to_string(),
+            };
+            let entry = properties
+                .entry((schema_name.to_string(), object_name.to_string()))
+                .or_default();
+            match row.get::<&str, _>(2) {
+                Some(column) => entry.columns.entry(column.to_string()).or_default().push(property),
+                None => entry.object.push(property),
+            }
+        }
+        Ok(properties)
+    }
+
     /// Fetch all schema names in the database
     async fn fetch_schemas(
         &self,
@@ -244,6 +1379,7 @@ impl SchemaMetadataManager {
         &self,
         conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
         schema_filter: Option<&str>,
+        object_ids: Option<&[i32]>,
     ) -> Result<Vec<TableInfo>, ConnectionError> {
         // First, fetch all tables and views
         let schema_condition = schema_filter
@@ -258,10 +1394,11 @@ impl SchemaMetadataManager {
                 t.TABLE_TYPE
             FROM INFORMATION_SCHEMA.TABLES t
             WHERE t.TABLE_TYPE IN ('BASE TABLE', 'VIEW')
-            {}
+            {} {}
             ORDER BY t.TABLE_SCHEMA, t.TABLE_NAME
         "#,
-            schema_condition
+            schema_condition,
+            object_condition("OBJECT_ID(QUOTENAME(t.TABLE_SCHEMA) + '.' + QUOTENAME(t.TABLE_NAME))", object_ids)
         );
 
         let stream = conn.simple_query(&tables_query).await?;
@@ -279,6 +1416,9 @@ impl SchemaMetadataManager {
                     table_name,
                     table_type,
                     columns: vec![],
+                    definition: None,
+                    modify_date: None,
+                    extended_properties: vec![],
                 })
             })
             .collect();
@@ -299,7 +1439,10 @@ impl SchemaMetadataManager {
                 c.ORDINAL_POSITION,
                 CASE WHEN pk.COLUMN_NAME IS NOT NULL THEN 1 ELSE 0 END AS IS_PRIMARY_KEY,
                 COLUMNPROPERTY(OBJECT_ID(c.TABLE_SCHEMA + '.' + c.TABLE_NAME), c.COLUMN_NAME, 'IsIdentity') AS IS_IDENTITY,
-                COLUMNPROPERTY(OBJECT_ID(c.TABLE_SCHEMA + '.' + c.TABLE_NAME), c.COLUMN_NAME, 'IsComputed') AS IS_COMPUTED
+                COLUMNPROPERTY(OBJECT_ID(c.TABLE_SCHEMA + '.' + c.TABLE_NAME), c.COLUMN_NAME, 'IsComputed') AS IS_COMPUTED,
+                c.DATETIME_PRECISION,
+                CAST(IDENT_SEED(QUOTENAME(c.TABLE_SCHEMA) + '.' + QUOTENAME(c.TABLE_NAME)) AS nvarchar(40)) AS IDENT_SEED,
+                CAST(IDENT_INCR(QUOTENAME(c.TABLE_SCHEMA) + '.' + QUOTENAME(c.TABLE_NAME)) AS nvarchar(40)) AS IDENT_INCR
             FROM INFORMATION_SCHEMA.COLUMNS c
             LEFT JOIN (
                 SELECT 
@@ -314,12 +1457,13 @@ impl SchemaMetadataManager {
             ) pk ON c.TABLE_SCHEMA = pk.TABLE_SCHEMA 
                 AND c.TABLE_NAME = pk.TABLE_NAME 
                 AND c.COLUMN_NAME = pk.COLUMN_NAME
-            WHERE 1=1 {}
+            WHERE 1=1 {} {}
             ORDER BY c.TABLE_SCHEMA, c.TABLE_NAME, c.ORDINAL_POSITION
         "#,
             schema_filter
                 .map(|s| format!("AND c.TABLE_SCHEMA = '{}'", s))
-                .unwrap_or_default()
+                .unwrap_or_default(),
+            object_condition("OBJECT_ID(QUOTENAME(c.TABLE_SCHEMA) + '.' + QUOTENAME(c.TABLE_NAME))", object_ids)
         );
 
         let stream = conn.simple_query(&columns_query).await?;
@@ -360,6 +1504,7 @@ impl SchemaMetadataManager {
             let is_primary_key = row.get::<i32, _>(10).map(|v| v == 1).unwrap_or(false);
             let is_identity = row.get::<i32, _>(11).map(|v| v == 1).unwrap_or(false);
             let is_computed = row.get::<i32, _>(12).map(|v| v == 1).unwrap_or(false);
+            let scale = fractional_scale(&data_type, row.get::<i16, _>(13)).or(scale);
 
             let column = ColumnInfo {
                 name: column_name,
@@ -370,9 +1515,12 @@ impl SchemaMetadataManager {
                 is_nullable,
                 is_primary_key,
                 is_identity,
+                identity_seed: row.get::<&str, _>(14).filter(|_| is_identity).map(str::to_string),
+                identity_increment: row.get::<&str, _>(15).filter(|_| is_identity).map(str::to_string),
                 is_computed,
                 column_default,
                 ordinal_position,
+                extended_properties: vec![],
             };
 
             columns_by_table
@@ -396,6 +1544,7 @@ impl SchemaMetadataManager {
         &self,
         conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
         schema_filter: Option<&str>,
+        object_ids: Option<&[i32]>,
     ) -> Result<Vec<RoutineInfo>, ConnectionError> {
         let schema_condition = schema_filter
             .map(|s| format!("AND ROUTINE_SCHEMA = '{}'", s))
@@ -411,10 +1560,11 @@ impl SchemaMetadataManager {
                 DATA_TYPE
             FROM INFORMATION_SCHEMA.ROUTINES
             WHERE ROUTINE_TYPE IN ('PROCEDURE', 'FUNCTION')
-            {}
+            {} {}
             ORDER BY ROUTINE_SCHEMA, ROUTINE_NAME
         "#,
-            schema_condition
+            schema_condition,
+            object_condition("OBJECT_ID(QUOTENAME(ROUTINE_SCHEMA) + '.' + QUOTENAME(ROUTINE_NAME))", object_ids)
         );
 
         let stream = conn.simple_query(&routines_query).await?;
@@ -433,6 +1583,9 @@ impl SchemaMetadataManager {
                     routine_type,
                     return_type,
                     parameters: vec![],
+                    definition: None,
+                    modify_date: None,
+                    extended_properties: vec![],
                 })
             })
             .collect();
@@ -452,12 +1605,13 @@ impl SchemaMetadataManager {
                 ORDINAL_POSITION
             FROM INFORMATION_SCHEMA.PARAMETERS
             WHERE PARAMETER_NAME IS NOT NULL
-            {}
+            {} {}
             ORDER BY SPECIFIC_SCHEMA, SPECIFIC_NAME, ORDINAL_POSITION
         "#,
             schema_filter
                 .map(|s| format!("AND SPECIFIC_SCHEMA = '{}'", s))
-                .unwrap_or_default()
+                .unwrap_or_default(),
+            object_condition("OBJECT_ID(QUOTENAME(SPECIFIC_SCHEMA) + '.' + QUOTENAME(SPECIFIC_NAME))", object_ids)
         );
 
         let stream = conn.simple_query(&params_query).await?;
@@ -558,7 +1712,10 @@ impl SchemaMetadataManager {
                 c.ORDINAL_POSITION,
                 CASE WHEN pk.COLUMN_NAME IS NOT NULL THEN 1 ELSE 0 END AS IS_PRIMARY_KEY,
                 COLUMNPROPERTY(OBJECT_ID('{}.{}'), c.COLUMN_NAME, 'IsIdentity') AS IS_IDENTITY,
-                COLUMNPROPERTY(OBJECT_ID('{}.{}'), c.COLUMN_NAME, 'IsComputed') AS IS_COMPUTED
+                COLUMNPROPERTY(OBJECT_ID('{}.{}'), c.COLUMN_NAME, 'IsComputed') AS IS_COMPUTED,
+                c.DATETIME_PRECISION,
+                CAST(IDENT_SEED(QUOTENAME(c.TABLE_SCHEMA) + '.' + QUOTENAME(c.TABLE_NAME)) AS nvarchar(40)) AS IDENT_SEED,
+                CAST(IDENT_INCR(QUOTENAME(c.TABLE_SCHEMA) + '.' + QUOTENAME(c.TABLE_NAME)) AS nvarchar(40)) AS IDENT_INCR
             FROM INFORMATION_SCHEMA.COLUMNS c
             LEFT JOIN (
                 SELECT ku.COLUMN_NAME
@@ -599,6 +1756,7 @@ impl SchemaMetadataManager {
                 let is_primary_key = row.get::<i32, _>(8).map(|v| v == 1).unwrap_or(false);
                 let is_identity = row.get::<i32, _>(9).map(|v| v == 1).unwrap_or(false);
                 let is_computed = row.get::<i32, _>(10).map(|v| v == 1).unwrap_or(false);
+                let scale = fractional_scale(&data_type, row.get::<i16, _>(11)).or(scale);
 
                 Some(ColumnInfo {
                     name,
@@ -609,9 +1767,12 @@ impl SchemaMetadataManager {
                     is_nullable,
                     is_primary_key,
                     is_identity,
+                    identity_seed: row.get::<&str, _>(12).filter(|_| is_identity).map(str::to_string),
+                    identity_increment: row.get::<&str, _>(13).filter(|_| is_identity).map(str::to_string),
                     is_computed,
                     column_default,
                     ordinal_position,
+                    extended_properties: vec![],
                 })
             })
             .collect();
@@ -625,3 +1786,152 @@ impl Default for SchemaMetadataManager {
         panic!("SchemaMetadataManager requires a connection manager to be initialized")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(name: &str, modify_date: Option<&str>) -> TableInfo {
+        TableInfo {
+            modify_date: modify_date.map(str::to_string),
+            ..TableInfo::for_test(name, vec![])
+        }
+    }
+
+    fn version(object_id: i32, modify_date: &str) -> ObjectVersion {
+        ObjectVersion {
+            object_id,
+            modify_date: modify_date.to_string(),
+        }
+    }
+
+    fn cached_schema() -> SchemaInfo {
+        let tables = vec![
+            table("Customers", Some("2024-01-01T00:00:00")),
+            table("Orders", Some("2024-01-01T00:00:00")),
+            table("Legacy", Some("2024-01-01T00:00:00")),
+        ];
+        SchemaInfo {
+            relationships: vec![RelationshipInfo::for_test(
+                "FK_Orders_Customers",
+                ("Orders", "CustomerId"),
+                ("Customers", "Id"),
+            )],
+            ..SchemaInfo::for_test("db", tables)
+        }
+    }
+
+    #[test]
+    fn delta_finds_altered_new_and_dropped_objects() {
+        let versions: HashMap<_, _> = [
+            (("dbo".to_string(), "Customers".to_string()), version(1, "2024-01-01T00:00:00")),
+            (("dbo".to_string(), "Orders".to_string()), version(2, "2024-02-01T00:00:00")),
+            (("dbo".to_string(), "Invoices".to_string()), version(4, "2024-02-01T00:00:00")),
+        ]
+        .into_iter()
+        .collect();
+
+        let delta = schema_delta(&cached_schema(), &versions);
+        assert_eq!(delta.changed, vec![2, 4]);
+        let mut stale: Vec<_> = delta.stale.iter().map(|(_, name)| name.as_str()).collect();
+        stale.sort();
+        assert_eq!(stale, vec!["Legacy", "Orders"]);
+
+        let unchanged = schema_delta(
+            &cached_schema(),
+            &cached_schema()
+                .tables
+                .iter()
+                .enumerate()
+                .map(|(i, t)| ((t.schema_name.clone(), t.table_name.clone()), version(i as i32, "2024-01-01T00:00:00")))
+                .collect(),
+        );
+        assert!(unchanged.is_empty());
+    }
+
+    #[test]
+    fn merge_replaces_stale_objects_and_their_children() {
+        let mut cached = cached_schema();
+        let delta = SchemaDelta {
+            changed: vec![2],
+            stale: [("dbo".to_string(), "Orders".to_string()), ("dbo".to_string(), "Legacy".to_string())]
+                .into_iter()
+                .collect(),
+        };
+        let fresh = SchemaObjects {
+            tables: vec![table("Orders", Some("2024-02-01T00:00:00"))],
+            relationships: vec![],
+            routines: vec![],
+            indexes: vec![],
+            constraints: vec![],
+        };
+        cached.constraints.push(ConstraintInfo {
+            schema_name: "dbo".to_string(),
+            table_name: "Orders".to_string(),
+            constraint_name: "DF_Orders_Status".to_string(),
+            constraint_type: ConstraintType::Default,
+            column_name: Some("Status".to_string()),
+            definition: "('new')".to_string(),
+            is_disabled: false,
+        });
+
+        merge_schema(&mut cached, &delta, fresh);
+        let names: Vec<_> = cached.tables.iter().map(|t| t.table_name.as_str()).collect();
+        assert_eq!(names, vec!["Customers", "Orders"]);
+        assert_eq!(cached.tables[1].modify_date.as_deref(), Some("2024-02-01T00:00:00"));
+        assert!(cached.relationships.is_empty(), "FK of the altered table is re-read");
+        assert!(cached.constraints.is_empty());
+    }
+
+    #[test]
+    fn standalone_objects_attach_extended_properties() {
+        let mut schema = cached_schema();
+        schema.tables[1].columns.push(ColumnInfo {
+            max_length: Some(10),
+            is_nullable: false,
+            ordinal_position: 1,
+            extended_properties: vec![ExtendedProperty {
+                name: "Stale".to_string(),
+                value: "dropped since".to_string(),
+            }],
+            ..ColumnInfo::for_test("Status", "varchar")
+        });
+        let property = |value: &str| ExtendedProperty {
+            name: DESCRIPTION_PROPERTY.to_string(),
+            value: value.to_string(),
+        };
+        let mut orders = ObjectProperties {
+            object: vec![property("Customer orders")],
+            ..ObjectProperties::default()
+        };
+        orders.columns.insert("Status".to_string(), vec![property("Order state")]);
+
+        StandaloneObjects {
+            triggers: vec![],
+            sequences: vec![],
+            synonyms: vec![],
+            user_types: vec![],
+            extended_properties: [(("dbo".to_string(), "Orders".to_string()), orders)]
+                .into_iter()
+                .collect(),
+        }
+        .apply(&mut schema);
+
+        let orders = &schema.tables[1];
+        assert_eq!(orders.table_name, "Orders");
+        assert_eq!(description(&orders.extended_properties), Some("Customer orders"));
+        assert_eq!(orders.columns[0].extended_properties, vec![property("Order state")]);
+        assert!(schema.tables[0].extended_properties.is_empty());
+    }
+
+    #[test]
+    fn refresh_claims_release_on_drop() {
+        let refreshing = Mutex::new(HashSet::new());
+        let key = ("conn".to_string(), "db".to_string());
+        refreshing.lock().unwrap().insert(key.clone());
+        {
+            let _claim = RefreshClaim { refreshing: &refreshing, key: key.clone() };
+        }
+        assert!(refreshing.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,1081 @@
+// Object Scripting
+// Generates CREATE / ALTER / DROP T-SQL for tables, views and modules from sys.* catalogs
+
+use crate::db::connection::ConnectionError;
+use crate::db::schema::{fetch_indexes, SchemaMetadataManager};
+use crate::sql::{quote_identifier, significant_tokens};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+type PooledConnection<'a> = bb8::PooledConnection<'a, bb8_tiberius::ConnectionManager>;
+
+/// What kind of script to generate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptAction {
+    Create,
+    CreateOrAlter,
+    Alter,
+    Drop,
+}
+
+/// A schema-qualified object name
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ObjectRef {
+    pub schema_name: String,
+    pub object_name: String,
+}
+
+/// Scriptable object kinds, from `sys.objects.type`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ObjectType {
+    Table,
+    View,
+    Procedure,
+    Function,
+    Trigger,
+}
+
+impl ObjectType {
+    pub fn from_sys_type(code: &str) -> Option<Self> {
+        match code.trim() {
+            "U" => Some(Self::Table),
+            "V" => Some(Self::View),
+            "P" => Some(Self::Procedure),
+            "FN" | "IF" | "TF" => Some(Self::Function),
+            "TR" => Some(Self::Trigger),
+            _ => None,
+        }
+    }
+
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Self::Table => "TABLE",
+            Self::View => "VIEW",
+            Self::Procedure => "PROCEDURE",
+            Self::Function => "FUNCTION",
+            Self::Trigger => "TRIGGER",
+        }
+    }
+}
+
+/// An object resolved against the catalog
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptObject {
+    pub object_id: i32,
+    pub schema_name: String,
+    pub object_name: String,
+    pub object_type: ObjectType,
+}
+
+impl ScriptObject {
+    pub fn qualified_name(&self) -> String {
+        qualified_name(&self.schema_name, &self.object_name)
+    }
+}
+
+/// A column as declared in CREATE TABLE
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnDefinition {
+    pub name: String,
+    /// Rendered type, e.g. `nvarchar(50)` or `[dbo].[Phone]`
+    pub data_type: String,
+    pub is_nullable: bool,
+    /// Only set when it differs from the database default
+    pub collation: Option<String>,
+    /// (seed, increment)
+    pub identity: Option<(String, String)>,
+    pub computed_definition: Option<String>,
+    pub is_persisted: bool,
+    pub default_name: Option<String>,
+    pub default_definition: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexColumn {
+    pub name: String,
+    pub is_descending: bool,
+}
+
+/// A rowstore or columnstore index; primary keys and unique constraints are
+/// indexes flagged as constraints
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexDefinition {
+    pub name: String,
+    /// CLUSTERED, NONCLUSTERED, CLUSTERED COLUMNSTORE or NONCLUSTERED COLUMNSTORE
+    pub type_desc: String,
+    pub is_unique: bool,
+    pub is_primary_key: bool,
+    pub is_unique_constraint: bool,
+    pub key_columns: Vec<IndexColumn>,
+    pub included_columns: Vec<String>,
+    pub filter_definition: Option<String>,
+}
+
+impl IndexDefinition {
+    pub fn is_constraint(&self) -> bool {
+        self.is_primary_key || self.is_unique_constraint
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckConstraintDefinition {
+    pub name: String,
+    pub definition: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForeignKeyDefinition {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub referenced_schema: String,
+    pub referenced_table: String,
+    pub referenced_columns: Vec<String>,
+    /// NO_ACTION, CASCADE, SET_NULL or SET_DEFAULT
+    pub delete_action: String,
+    pub update_action: String,
+}
+
+/// Everything needed to recreate a table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableDefinition {
+    pub schema_name: String,
+    pub table_name: String,
+    pub columns: Vec<ColumnDefinition>,
+    pub indexes: Vec<IndexDefinition>,
+    pub check_constraints: Vec<CheckConstraintDefinition>,
+    pub foreign_keys: Vec<ForeignKeyDefinition>,
+}
+
+impl TableDefinition {
+    pub fn qualified_name(&self) -> String {
+        qualified_name(&self.schema_name, &self.table_name)
+    }
+}
+
+/// Source of a view, procedure, function or trigger
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModuleDefinition {
+    pub schema_name: String,
+    pub object_name: String,
+    pub object_type: ObjectType,
+    pub definition: String,
+    pub uses_ansi_nulls: bool,
+    pub uses_quoted_identifier: bool,
+}
+
+pub fn qualified_name(schema_name: &str, object_name: &str) -> String {
+    format!(
+        "{}.{}",
+        quote_identifier(schema_name),
+        quote_identifier(object_name)
+    )
+}
+
+/// Render a column type from `sys.columns` sizes (max_length is in bytes)
+pub fn format_column_type(type_name: &str, max_length: i16, precision: u8, scale: u8) -> String {
+    let lower = type_name.to_lowercase();
+    match lower.as_str() {
+        "varchar" | "char" | "varbinary" | "binary" => {
+            if max_length == -1 {
+                format!("{}(max)", lower)
+            } else {
+                format!("{}({})", lower, max_length)
+            }
+        }
+        "nvarchar" | "nchar" => {
+            if max_length == -1 {
+                format!("{}(max)", lower)
+            } else {
+                format!("{}({})", lower, max_length / 2)
+            }
+        }
+        "decimal" | "numeric" => format!("{}({}, {})", lower, precision, scale),
+        "datetime2" | "datetimeoffset" | "time" => format!("{}({})", lower, scale),
+        _ => lower,
+    }
+}
+
+fn index_column_list(columns: &[IndexColumn]) -> String {
+    columns
+        .iter()
+        .map(|c| {
+            format!(
+                "{} {}",
+                quote_identifier(&c.name),
+                if c.is_descending { "DESC" } else { "ASC" }
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn name_list(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|n| quote_identifier(n))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn column_line(column: &ColumnDefinition) -> String {
+    let name = quote_identifier(&column.name);
+    if let Some(expression) = &column.computed_definition {
+        let persisted = if column.is_persisted {
+            " PERSISTED"
+        } else {
+            ""
+        };
+        return format!("{} AS {}{}", name, expression, persisted);
+    }
+
+    let mut line = format!("{} {}", name, column.data_type);
+    if let Some(collation) = &column.collation {
+        line.push_str(&format!(" COLLATE {}", collation));
+    }
+    if let Some((seed, increment)) = &column.identity {
+        line.push_str(&format!(" IDENTITY({}, {})", seed, increment));
+    }
+    line.push_str(if column.is_nullable {
+        " NULL"
+    } else {
+        " NOT NULL"
+    });
+    if let Some(default) = &column.default_definition {
+        if let Some(name) = &column.default_name {
+            line.push_str(&format!(" CONSTRAINT {}", quote_identifier(name)));
+        }
+        line.push_str(&format!(" DEFAULT {}", default));
+    }
+    line
+}
+
+/// `CONSTRAINT [PK_x] PRIMARY KEY CLUSTERED (...)` for a key-backing index
+pub fn key_constraint_clause(index: &IndexDefinition) -> String {
+    format!(
+        "CONSTRAINT {} {} {} ({})",
+        quote_identifier(&index.name),
+        if index.is_primary_key {
+            "PRIMARY KEY"
+        } else {
+            "UNIQUE"
+        },
+        index.type_desc,
+        index_column_list(&index.key_columns)
+    )
+}
+
+/// `ALTER TABLE ... ADD CONSTRAINT ... FOREIGN KEY` statement for a quoted table name
+pub fn foreign_key_statement(table_name: &str, fk: &ForeignKeyDefinition) -> String {
+    let mut sql = format!(
+        "ALTER TABLE {} ADD CONSTRAINT {} FOREIGN KEY ({}) REFERENCES {} ({})",
+        table_name,
+        quote_identifier(&fk.name),
+        name_list(&fk.columns),
+        qualified_name(&fk.referenced_schema, &fk.referenced_table),
+        name_list(&fk.referenced_columns)
+    );
+    for (event, action) in [("DELETE", &fk.delete_action), ("UPDATE", &fk.update_action)] {
+        if action != "NO_ACTION" {
+            sql.push_str(&format!(" ON {} {}", event, action.replace('_', " ")));
+        }
+    }
+    sql.push(';');
+    sql
+}
+
+/// `CREATE INDEX` statement for an index that does not back a constraint,
+/// on a quoted table name
+pub fn index_statement(table_name: &str, index: &IndexDefinition) -> String {
+    let unique = if index.is_unique { "UNIQUE " } else { "" };
+    let mut sql = format!(
+        "CREATE {}{} INDEX {} ON {}",
+        unique,
+        index.type_desc,
+        quote_identifier(&index.name),
+        table_name
+    );
+    if index.type_desc == "CLUSTERED COLUMNSTORE" {
+        sql.push(';');
+        return sql;
+    }
+    if index.type_desc.ends_with("COLUMNSTORE") {
+        let columns: Vec<String> = index
+            .key_columns
+            .iter()
+            .map(|c| c.name.clone())
+            .chain(index.included_columns.iter().cloned())
+            .collect();
+        sql.push_str(&format!(" ({})", name_list(&columns)));
+    } else {
+        sql.push_str(&format!(" ({})", index_column_list(&index.key_columns)));
+        if !index.included_columns.is_empty() {
+            sql.push_str(&format!(
+                " INCLUDE ({})",
+                name_list(&index.included_columns)
+            ));
+        }
+    }
+    if let Some(filter) = &index.filter_definition {
+        sql.push_str(&format!(" WHERE {}", filter));
+    }
+    sql.push(';');
+    sql
+}
+
+/// Full CREATE TABLE script: columns with defaults and identity, key and
+/// check constraints inline, then foreign keys and indexes as separate batches
+pub fn script_table(table: &TableDefinition) -> String {
+    let mut lines: Vec<String> = table.columns.iter().map(column_line).collect();
+    lines.extend(
+        table
+            .indexes
+            .iter()
+            .filter(|i| i.is_constraint())
+            .map(key_constraint_clause),
+    );
+    lines.extend(table.check_constraints.iter().map(|c| {
+        format!(
+            "CONSTRAINT {} CHECK {}",
+            quote_identifier(&c.name),
+            c.definition
+        )
+    }));
+
+    let mut script = format!(
+        "CREATE TABLE {} (\n{}\n);\nGO\n",
+        table.qualified_name(),
+        lines
+            .iter()
+            .map(|l| format!("    {}", l))
+            .collect::<Vec<_>>()
+            .join(",\n")
+    );
+    for fk in &table.foreign_keys {
+        script.push_str(&format!("\n{}\nGO\n", foreign_key_statement(&table.qualified_name(), fk)));
+    }
+    for index in table.indexes.iter().filter(|i| !i.is_constraint()) {
+        script.push_str(&format!("\n{}\nGO\n", index_statement(&table.qualified_name(), index)));
+    }
+    script
+}
+
+/// Rewrite the leading `CREATE` (or `CREATE OR ALTER`) of a module definition
+/// for the requested action. Returns None for Drop or an unrecognised header.
+pub fn module_header(definition: &str, action: ScriptAction) -> Option<String> {
+    let tokens = significant_tokens(definition);
+    let create = tokens.iter().position(|t| t.is_keyword("CREATE"))?;
+    let or_alter = tokens.get(create + 1).is_some_and(|t| t.is_keyword("OR"))
+        && tokens
+            .get(create + 2)
+            .is_some_and(|t| t.is_keyword("ALTER"));
+    let header_end = if or_alter {
+        tokens[create + 2].span.end
+    } else {
+        tokens[create].span.end
+    };
+
+    let replacement = match action {
+        ScriptAction::Create => "CREATE",
+        ScriptAction::CreateOrAlter => "CREATE OR ALTER",
+        ScriptAction::Alter => "ALTER",
+        ScriptAction::Drop => return None,
+    };
+    Some(format!(
+        "{}{}{}",
+        &definition[..tokens[create].span.start],
+        replacement,
+        &definition[header_end..]
+    ))
+}
+
+/// Module script with the SET options it was created under
+pub fn script_module(module: &ModuleDefinition, action: ScriptAction) -> Option<String> {
+    let body = module_header(&module.definition, action)?;
+    Some(format!(
+        "SET ANSI_NULLS {}\nGO\nSET QUOTED_IDENTIFIER {}\nGO\n{}\nGO\n",
+        if module.uses_ansi_nulls { "ON" } else { "OFF" },
+        if module.uses_quoted_identifier {
+            "ON"
+        } else {
+            "OFF"
+        },
+        body.trim_end()
+    ))
+}
+
+/// Order objects so everything is dropped before the objects it depends on.
+/// `dependencies` holds (referencing, referenced) index pairs; objects caught
+/// in a cycle keep their input order at the end.
+pub fn drop_order(count: usize, dependencies: &[(usize, usize)]) -> Vec<usize> {
+    // An object can be dropped once nothing left references it
+    let mut referenced_by = vec![0usize; count];
+    for &(from, to) in dependencies {
+        if from != to {
+            referenced_by[to] += 1;
+        }
+    }
+
+    let mut order = Vec::with_capacity(count);
+    let mut done = vec![false; count];
+    loop {
+        let ready: Vec<usize> = (0..count)
+            .filter(|&i| !done[i] && referenced_by[i] == 0)
+            .collect();
+        if ready.is_empty() {
+            break;
+        }
+        for i in ready {
+            done[i] = true;
+            order.push(i);
+            for &(from, to) in dependencies {
+                if from == i && from != to {
+                    referenced_by[to] -= 1;
+                }
+            }
+        }
+    }
+    order.extend((0..count).filter(|&i| !done[i]));
+    order
+}
+
+/// `(column, ids)` as an `AND column IN (...)` condition, or nothing
+pub(crate) fn object_condition(column: &str, object_ids: Option<&[i32]>) -> String {
+    match object_ids {
+        Some(ids) if !ids.is_empty() => format!(
+            "AND {} IN ({})",
+            column,
+            ids.iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Some(_) => "AND 1 = 0".to_string(),
+        None => String::new(),
+    }
+}
+
+/// Resolve an object name to its catalog entry
+pub(crate) async fn resolve_object(
+    conn: &mut PooledConnection<'_>,
+    object: &ObjectRef,
+) -> Result<ScriptObject, ConnectionError> {
+    let query = r#"
+        SELECT o.object_id, o.type, s.name, o.name
+        FROM sys.objects o
+        JOIN sys.schemas s ON s.schema_id = o.schema_id
+        WHERE s.name = @P1 AND o.name = @P2
+    "#;
+    let rows = conn
+        .query(
+            query,
+            &[&object.schema_name.as_str(), &object.object_name.as_str()],
+        )
+        .await?
+        .into_first_result()
+        .await?;
+
+    rows.iter()
+        .find_map(|row| {
+            Some(ScriptObject {
+                object_id: row.get::<i32, _>(0)?,
+                object_type: ObjectType::from_sys_type(row.get::<&str, _>(1)?)?,
+                schema_name: row.get::<&str, _>(2)?.to_string(),
+                object_name: row.get::<&str, _>(3)?.to_string(),
+            })
+        })
+        .ok_or_else(|| {
+            ConnectionError::QueryError(format!(
+                "Object not found or not scriptable: {}",
+                qualified_name(&object.schema_name, &object.object_name)
+            ))
+        })
+}
+
+/// Fetch table definitions, for the given object IDs or all user tables
+pub(crate) async fn fetch_table_definitions(
+    conn: &mut PooledConnection<'_>,
+    object_ids: Option<&[i32]>,
+) -> Result<Vec<TableDefinition>, ConnectionError> {
+    let tables_query = format!(
+        r#"
+        SELECT t.object_id, s.name, t.name
+        FROM sys.tables t
+        JOIN sys.schemas s ON s.schema_id = t.schema_id
+        WHERE t.is_ms_shipped = 0 {}
+        ORDER BY s.name, t.name
+    "#,
+        object_condition("t.object_id", object_ids)
+    );
+    let rows = conn
+        .simple_query(&tables_query)
+        .await?
+        .into_first_result()
+        .await?;
+
+    let mut ids = Vec::new();
+    let mut tables: HashMap<i32, TableDefinition> = HashMap::new();
+    for row in rows.iter() {
+        let (Some(id), Some(schema_name), Some(table_name)) = (
+            row.get::<i32, _>(0),
+            row.get::<&str, _>(1),
+            row.get::<&str, _>(2),
+        ) else {
+            continue;
+        };
+        ids.push(id);
+        tables.insert(
+            id,
+            TableDefinition {
+                schema_name: schema_name.to_string(),
+                table_name: table_name.to_string(),
+                columns: Vec::new(),
+                indexes: Vec::new(),
+                check_constraints: Vec::new(),
+                foreign_keys: Vec::new(),
+            },
+        );
+    }
+    let condition = match object_ids {
+        Some(_) => object_condition("t.object_id", Some(&ids)),
+        None => "AND t.is_ms_shipped = 0".to_string(),
+    };
+
+    // Columns with identity, computed and default details
+    let columns_query = format!(
+        r#"
+        SELECT
+            c.object_id,
+            c.name,
+            ty.name,
+            ty.is_user_defined,
+            SCHEMA_NAME(ty.schema_id),
+            c.max_length,
+            c.precision,
+            c.scale,
+            c.is_nullable,
+            CASE WHEN c.collation_name <> CAST(DATABASEPROPERTYEX(DB_NAME(), 'Collation') AS sysname)
+                 THEN c.collation_name END,
+            CAST(ic.seed_value AS nvarchar(40)),
+            CAST(ic.increment_value AS nvarchar(40)),
+            cc.definition,
+            cc.is_persisted,
+            dc.name,
+            dc.definition
+        FROM sys.columns c
+        JOIN sys.tables t ON t.object_id = c.object_id
+        JOIN sys.types ty ON ty.user_type_id = c.user_type_id
+        LEFT JOIN sys.identity_columns ic ON ic.object_id = c.object_id AND ic.column_id = c.column_id
+        LEFT JOIN sys.computed_columns cc ON cc.object_id = c.object_id AND cc.column_id = c.column_id
+        LEFT JOIN sys.default_constraints dc ON dc.parent_object_id = c.object_id AND dc.parent_column_id = c.column_id
+        WHERE 1 = 1 {}
+        ORDER BY c.object_id, c.column_id
+    "#,
+        condition
+    );
+    let rows = conn
+        .simple_query(&columns_query)
+        .await?
+        .into_first_result()
+        .await?;
+    for row in rows.iter() {
+        let (Some(id), Some(name), Some(type_name)) = (
+            row.get::<i32, _>(0),
+            row.get::<&str, _>(1),
+            row.get::<&str, _>(2),
+        ) else {
+            continue;
+        };
+        let Some(table) = tables.get_mut(&id) else {
+            continue;
+        };
+
+        let data_type = if row.get::<bool, _>(3).unwrap_or(false) {
+            qualified_name(row.get::<&str, _>(4).unwrap_or("dbo"), type_name)
+        } else {
+            format_column_type(
+                type_name,
+                row.get::<i16, _>(5).unwrap_or(0),
+                row.get::<u8, _>(6).unwrap_or(0),
+                row.get::<u8, _>(7).unwrap_or(0),
+            )
+        };
+        let identity = match (row.get::<&str, _>(10), row.get::<&str, _>(11)) {
+            (Some(seed), Some(increment)) => Some((seed.to_string(), increment.to_string())),
+            _ => None,
+        };
+
+        table.columns.push(ColumnDefinition {
+            name: name.to_string(),
+            data_type,
+            is_nullable: row.get::<bool, _>(8).unwrap_or(true),
+            collation: row.get::<&str, _>(9).map(|s| s.to_string()),
+            identity,
+            computed_definition: row.get::<&str, _>(12).map(|s| s.to_string()),
+            is_persisted: row.get::<bool, _>(13).unwrap_or(false),
+            default_name: row.get::<&str, _>(14).map(|s| s.to_string()),
+            default_definition: row.get::<&str, _>(15).map(|s| s.to_string()),
+        });
+    }
+
+    // Indexes, including the ones backing PRIMARY KEY / UNIQUE constraints
+    let ids_by_name: HashMap<(String, String), i32> = tables
+        .iter()
+        .map(|(id, t)| ((t.schema_name.clone(), t.table_name.clone()), *id))
+        .collect();
+    let index_ids = object_ids.map(|_| ids.as_slice());
+    for info in fetch_indexes(conn, None, index_ids).await? {
+        let key = (info.schema_name, info.table_name);
+        if let Some(table) = ids_by_name.get(&key).and_then(|id| tables.get_mut(id)) {
+            table.indexes.push(info.index);
+        }
+    }
+
+    let checks_query = format!(
+        r#"
+        SELECT cc.parent_object_id, cc.name, cc.definition
+        FROM sys.check_constraints cc
+        JOIN sys.tables t ON t.object_id = cc.parent_object_id
+        WHERE 1 = 1 {}
+        ORDER BY cc.parent_object_id, cc.name
+    "#,
+        condition
+    );
+    let rows = conn
+        .simple_query(&checks_query)
+        .await?
+        .into_first_result()
+        .await?;
+    for row in rows.iter() {
+        let (Some(id), Some(name), Some(definition)) = (
+            row.get::<i32, _>(0),
+            row.get::<&str, _>(1),
+            row.get::<&str, _>(2),
+        ) else {
+            continue;
+        };
+        if let Some(table) = tables.get_mut(&id) {
+            table.check_constraints.push(CheckConstraintDefinition {
+                name: name.to_string(),
+                definition: definition.to_string(),
+            });
+        }
+    }
+
+    let foreign_keys_query = format!(
+        r#"
+        SELECT
+            fk.parent_object_id,
+            fk.name,
+            SCHEMA_NAME(rt.schema_id),
+            rt.name,
+            pc.name,
+            rc.name,
+            fk.delete_referential_action_desc,
+            fk.update_referential_action_desc
+        FROM sys.foreign_keys fk
+        JOIN sys.foreign_key_columns fkc ON fkc.constraint_object_id = fk.object_id
+        JOIN sys.tables t ON t.object_id = fk.parent_object_id
+        JOIN sys.tables rt ON rt.object_id = fk.referenced_object_id
+        JOIN sys.columns pc ON pc.object_id = fkc.parent_object_id AND pc.column_id = fkc.parent_column_id
+        JOIN sys.columns rc ON rc.object_id = fkc.referenced_object_id AND rc.column_id = fkc.referenced_column_id
+        WHERE 1 = 1 {}
+        ORDER BY fk.parent_object_id, fk.name, fkc.constraint_column_id
+    "#,
+        condition
+    );
+    let rows = conn
+        .simple_query(&foreign_keys_query)
+        .await?
+        .into_first_result()
+        .await?;
+    for row in rows.iter() {
+        let (Some(id), Some(name), Some(column), Some(referenced_column)) = (
+            row.get::<i32, _>(0),
+            row.get::<&str, _>(1),
+            row.get::<&str, _>(4),
+            row.get::<&str, _>(5),
+        ) else {
+            continue;
+        };
+        let Some(table) = tables.get_mut(&id) else {
+            continue;
+        };
+
+        if table.foreign_keys.last().map(|fk| fk.name.as_str()) != Some(name) {
+            table.foreign_keys.push(ForeignKeyDefinition {
+                name: name.to_string(),
+                columns: Vec::new(),
+                referenced_schema: row.get::<&str, _>(2).unwrap_or("dbo").to_string(),
+                referenced_table: row.get::<&str, _>(3).unwrap_or_default().to_string(),
+                referenced_columns: Vec::new(),
+                delete_action: row.get::<&str, _>(6).unwrap_or("NO_ACTION").to_string(),
+                update_action: row.get::<&str, _>(7).unwrap_or("NO_ACTION").to_string(),
+            });
+        }
+        if let Some(fk) = table.foreign_keys.last_mut() {
+            fk.columns.push(column.to_string());
+            fk.referenced_columns.push(referenced_column.to_string());
+        }
+    }
+
+    Ok(ids
+        .into_iter()
+        .filter_map(|id| tables.remove(&id))
+        .collect())
+}
+
+/// Fetch module definitions, for the given object IDs or all user modules
+pub(crate) async fn fetch_module_definitions(
+    conn: &mut PooledConnection<'_>,
+    object_ids: Option<&[i32]>,
+) -> Result<Vec<ModuleDefinition>, ConnectionError> {
+    let query = format!(
+        r#"
+        SELECT SCHEMA_NAME(o.schema_id), o.name, o.type, OBJECT_DEFINITION(o.object_id),
+               m.uses_ansi_nulls, m.uses_quoted_identifier
+        FROM sys.sql_modules m
+        JOIN sys.objects o ON o.object_id = m.object_id
+        WHERE o.is_ms_shipped = 0 AND o.type IN ('V', 'P', 'FN', 'IF', 'TF', 'TR') {}
+        ORDER BY SCHEMA_NAME(o.schema_id), o.name
+    "#,
+        object_condition("o.object_id", object_ids)
+    );
+    let rows = conn.simple_query(&query).await?.into_first_result().await?;
+
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            Some(ModuleDefinition {
+                schema_name: row.get::<&str, _>(0)?.to_string(),
+                object_name: row.get::<&str, _>(1)?.to_string(),
+                object_type: ObjectType::from_sys_type(row.get::<&str, _>(2)?)?,
+                // NULL for encrypted modules
+                definition: row.get::<&str, _>(3)?.to_string(),
+                uses_ansi_nulls: row.get::<bool, _>(4).unwrap_or(true),
+                uses_quoted_identifier: row.get::<bool, _>(5).unwrap_or(true),
+            })
+        })
+        .collect())
+}
+
+impl SchemaMetadataManager {
+    /// Generate a CREATE, CREATE OR ALTER, ALTER or DROP script for one object.
+    /// Tables only support CREATE and DROP.
+    pub async fn script_object(
+        &self,
+        connection_id: &str,
+        database: &str,
+        object: &ObjectRef,
+        action: ScriptAction,
+    ) -> Result<String, ConnectionError> {
+        if action == ScriptAction::Drop {
+            return self
+                .script_drop(connection_id, database, std::slice::from_ref(object))
+                .await;
+        }
+
+        let pool = self.connection_manager.connect(connection_id).await?;
+        let mut conn = pool.get().await?;
+        conn.simple_query(&format!("USE {}", quote_identifier(database))).await?;
+
+        let resolved = resolve_object(&mut conn, object).await?;
+        if resolved.object_type == ObjectType::Table {
+            if action != ScriptAction::Create {
+                return Err(ConnectionError::QueryError(
+                    "Tables can only be scripted as CREATE or DROP".to_string(),
+                ));
+            }
+            let tables = fetch_table_definitions(&mut conn, Some(&[resolved.object_id])).await?;
+            return tables.first().map(script_table).ok_or_else(|| {
+                ConnectionError::QueryError(format!(
+                    "Table not found: {}",
+                    resolved.qualified_name()
+                ))
+            });
+        }
+
+        let modules = fetch_module_definitions(&mut conn, Some(&[resolved.object_id])).await?;
+        modules
+            .first()
+            .and_then(|m| script_module(m, action))
+            .ok_or_else(|| {
+                ConnectionError::QueryError(format!(
+                    "No definition available for {} (it may be encrypted)",
+                    resolved.qualified_name()
+                ))
+            })
+    }
+
+    /// DROP script for several objects in dependency order. Foreign keys from
+    /// tables outside the set that reference tables being dropped go first.
+    pub async fn script_drop(
+        &self,
+        connection_id: &str,
+        database: &str,
+        objects: &[ObjectRef],
+    ) -> Result<String, ConnectionError> {
+        let pool = self.connection_manager.connect(connection_id).await?;
+        let mut conn = pool.get().await?;
+        conn.simple_query(&format!("USE {}", quote_identifier(database))).await?;
+
+        let mut resolved = Vec::with_capacity(objects.len());
+        for object in objects {
+            resolved.push(resolve_object(&mut conn, object).await?);
+        }
+        let ids: Vec<i32> = resolved.iter().map(|o| o.object_id).collect();
+        let id_list = ids
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        // (referencing, referenced) among the selected objects
+        let dependencies_query = format!(
+            r#"
+            SELECT d.referencing_id, d.referenced_id
+            FROM sys.sql_expression_dependencies d
+            WHERE d.referencing_id IN ({0}) AND d.referenced_id IN ({0})
+            UNION
+            SELECT fk.parent_object_id, fk.referenced_object_id
+            FROM sys.foreign_keys fk
+            WHERE fk.parent_object_id IN ({0}) AND fk.referenced_object_id IN ({0})
+            UNION
+            SELECT tr.object_id, tr.parent_id
+            FROM sys.triggers tr
+            WHERE tr.object_id IN ({0}) AND tr.parent_id IN ({0})
+        "#,
+            id_list
+        );
+        let rows = conn
+            .simple_query(&dependencies_query)
+            .await?
+            .into_first_result()
+            .await?;
+        let index_of = |id: i32| ids.iter().position(|&i| i == id);
+        let dependencies: Vec<(usize, usize)> = rows
+            .iter()
+            .filter_map(|row| {
+                Some((
+                    index_of(row.get::<i32, _>(0)?)?,
+                    index_of(row.get::<i32, _>(1)?)?,
+                ))
+            })
+            .collect();
+
+        let external_fks_query = format!(
+            r#"
+            SELECT SCHEMA_NAME(t.schema_id), t.name, fk.name
+            FROM sys.foreign_keys fk
+            JOIN sys.tables t ON t.object_id = fk.parent_object_id
+            WHERE fk.referenced_object_id IN ({0}) AND fk.parent_object_id NOT IN ({0})
+            ORDER BY SCHEMA_NAME(t.schema_id), t.name, fk.name
+        "#,
+            id_list
+        );
+        let rows = conn
+            .simple_query(&external_fks_query)
+            .await?
+            .into_first_result()
+            .await?;
+
+        let mut script = String::new();
+        for row in rows.iter() {
+            let (Some(schema_name), Some(table_name), Some(fk_name)) = (
+                row.get::<&str, _>(0),
+                row.get::<&str, _>(1),
+                row.get::<&str, _>(2),
+            ) else {
+                continue;
+            };
+            script.push_str(&format!(
+                "ALTER TABLE {} DROP CONSTRAINT {};\nGO\n",
+                qualified_name(schema_name, table_name),
+                quote_identifier(fk_name)
+            ));
+        }
+        for index in drop_order(resolved.len(), &dependencies) {
+            let object = &resolved[index];
+            script.push_str(&format!(
+                "DROP {} IF EXISTS {};\nGO\n",
+                object.object_type.keyword(),
+                object.qualified_name()
+            ));
+        }
+        Ok(script)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn orders_table() -> TableDefinition {
+        TableDefinition {
+            schema_name: "dbo".to_string(),
+            table_name: "Orders".to_string(),
+            columns: vec![
+                ColumnDefinition {
+                    name: "Id".to_string(),
+                    data_type: "int".to_string(),
+                    is_nullable: false,
+                    collation: None,
+                    identity: Some(("1".to_string(), "1".to_string())),
+                    computed_definition: None,
+                    is_persisted: false,
+                    default_name: None,
+                    default_definition: None,
+                },
+                ColumnDefinition {
+                    name: "CustomerId".to_string(),
+                    data_type: "int".to_string(),
+                    is_nullable: false,
+                    collation: None,
+                    identity: None,
+                    computed_definition: None,
+                    is_persisted: false,
+                    default_name: None,
+                    default_definition: None,
+                },
+                ColumnDefinition {
+                    name: "Code".to_string(),
+                    data_type: format_column_type("nvarchar", 40, 0, 0),
+                    is_nullable: true,
+                    collation: Some("Latin1_General_BIN2".to_string()),
+                    identity: None,
+                    computed_definition: None,
+                    is_persisted: false,
+                    default_name: Some("DF_Orders_Code".to_string()),
+                    default_definition: Some("(N'')".to_string()),
+                },
+                ColumnDefinition {
+                    name: "Total".to_string(),
+                    data_type: "decimal(18, 2)".to_string(),
+                    is_nullable: true,
+                    collation: None,
+                    identity: None,
+                    computed_definition: Some("([Id]*(2))".to_string()),
+                    is_persisted: true,
+                    default_name: None,
+                    default_definition: None,
+                },
+            ],
+            indexes: vec![
+                IndexDefinition {
+                    name: "PK_Orders".to_string(),
+                    type_desc: "CLUSTERED".to_string(),
+                    is_unique: true,
+                    is_primary_key: true,
+                    is_unique_constraint: false,
+                    key_columns: vec![IndexColumn {
+                        name: "Id".to_string(),
+                        is_descending: false,
+                    }],
+                    included_columns: vec![],
+                    filter_definition: None,
+                },
+                IndexDefinition {
+                    name: "IX_Orders_Customer".to_string(),
+                    type_desc: "NONCLUSTERED".to_string(),
+                    is_unique: false,
+                    is_primary_key: false,
+                    is_unique_constraint: false,
+                    key_columns: vec![IndexColumn {
+                        name: "CustomerId".to_string(),
+                        is_descending: true,
+                    }],
+                    included_columns: vec!["Code".to_string()],
+                    filter_definition: Some("([Code] IS NOT NULL)".to_string()),
+                },
+            ],
+            check_constraints: vec![CheckConstraintDefinition {
+                name: "CK_Orders_Id".to_string(),
+                definition: "([Id]>(0))".to_string(),
+            }],
+            foreign_keys: vec![ForeignKeyDefinition {
+                name: "FK_Orders_Customers".to_string(),
+                columns: vec!["CustomerId".to_string()],
+                referenced_schema: "dbo".to_string(),
+                referenced_table: "Customers".to_string(),
+                referenced_columns: vec!["Id".to_string()],
+                delete_action: "CASCADE".to_string(),
+                update_action: "NO_ACTION".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn column_types_use_character_lengths() {
+        assert_eq!(format_column_type("NVARCHAR", 100, 0, 0), "nvarchar(50)");
+        assert_eq!(format_column_type("varbinary", -1, 0, 0), "varbinary(max)");
+        assert_eq!(format_column_type("decimal", 9, 18, 4), "decimal(18, 4)");
+        assert_eq!(format_column_type("datetime2", 8, 27, 7), "datetime2(7)");
+        assert_eq!(format_column_type("int", 4, 10, 0), "int");
+    }
+
+    #[test]
+    fn table_script_includes_constraints_and_indexes() {
+        let script = script_table(&orders_table());
+        assert_eq!(
+            script,
+            "CREATE TABLE [dbo].[Orders] (\n\
+             \x20   [Id] int IDENTITY(1, 1) NOT NULL,\n\
+             \x20   [CustomerId] int NOT NULL,\n\
+             \x20   [Code] nvarchar(20) COLLATE Latin1_General_BIN2 NULL CONSTRAINT [DF_Orders_Code] DEFAULT (N''),\n\
+             \x20   [Total] AS ([Id]*(2)) PERSISTED,\n\
+             \x20   CONSTRAINT [PK_Orders] PRIMARY KEY CLUSTERED ([Id] ASC),\n\
+             \x20   CONSTRAINT [CK_Orders_Id] CHECK ([Id]>(0))\n\
+             );\nGO\n\n\
+             ALTER TABLE [dbo].[Orders] ADD CONSTRAINT [FK_Orders_Customers] FOREIGN KEY ([CustomerId]) \
+             REFERENCES [dbo].[Customers] ([Id]) ON DELETE CASCADE;\nGO\n\n\
+             CREATE NONCLUSTERED INDEX [IX_Orders_Customer] ON [dbo].[Orders] ([CustomerId] DESC) \
+             INCLUDE ([Code]) WHERE ([Code] IS NOT NULL);\nGO\n"
+        );
+    }
+
+    #[test]
+    fn module_header_rewrites_only_the_leading_create() {
+        let definition = "-- CREATE note\nCREATE   PROCEDURE dbo.p AS\nSELECT 'CREATE'";
+        assert_eq!(
+            module_header(definition, ScriptAction::Alter).unwrap(),
+            "-- CREATE note\nALTER   PROCEDURE dbo.p AS\nSELECT 'CREATE'"
+        );
+        assert_eq!(
+            module_header("create or alter view v as select 1", ScriptAction::Create).unwrap(),
+            "CREATE view v as select 1"
+        );
+        assert_eq!(
+            module_header("CREATE VIEW v AS SELECT 1", ScriptAction::CreateOrAlter).unwrap(),
+            "CREATE OR ALTER VIEW v AS SELECT 1"
+        );
+        assert!(module_header("CREATE VIEW v AS SELECT 1", ScriptAction::Drop).is_none());
+    }
+
+    #[test]
+    fn module_script_restores_set_options() {
+        let module = ModuleDefinition {
+            schema_name: "dbo".to_string(),
+            object_name: "v".to_string(),
+            object_type: ObjectType::View,
+            definition: "CREATE VIEW dbo.v AS SELECT 1 AS x\n".to_string(),
+            uses_ansi_nulls: true,
+            uses_quoted_identifier: false,
+        };
+        assert_eq!(
+            script_module(&module, ScriptAction::CreateOrAlter).unwrap(),
+            "SET ANSI_NULLS ON\nGO\nSET QUOTED_IDENTIFIER OFF\nGO\nCREATE OR ALTER VIEW dbo.v AS SELECT 1 AS x\nGO\n"
+        );
+    }
+
+    #[test]
+    fn drop_order_puts_referencing_objects_first() {
+        // 0: Customers, 1: Orders -> Customers, 2: view over Orders, 3: trigger on Orders
+        let order = drop_order(4, &[(1, 0), (2, 1), (3, 1)]);
+        assert_eq!(order, vec![2, 3, 1, 0]);
+
+        // A cycle keeps input order after everything else
+        let order = drop_order(3, &[(0, 1), (1, 0)]);
+        assert_eq!(order, vec![2, 0, 1]);
+    }
+}
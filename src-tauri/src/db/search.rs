@@ -0,0 +1,414 @@
+// Definition Search
+// Text / regex search over module definitions, object names and column names in one or more databases
+
+use crate::db::connection::ConnectionError;
+use crate::db::schema::SchemaMetadataManager;
+use crate::sql::quote_identifier;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+/// Matching lines kept per object
+const MAX_LINES_PER_OBJECT: usize = 20;
+/// Longest line snippet returned, in characters
+const MAX_SNIPPET_CHARS: usize = 200;
+
+/// What to search for and where
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DefinitionSearch {
+    pub pattern: String,
+    pub is_regex: bool,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    /// Databases to search; None searches every database the login can access
+    pub databases: Option<Vec<String>>,
+    pub include_definitions: bool,
+    pub include_object_names: bool,
+    pub include_column_names: bool,
+    pub max_results: usize,
+}
+
+impl Default for DefinitionSearch {
+    fn default() -> Self {
+        Self {
+            pattern: String::new(),
+            is_regex: false,
+            case_sensitive: false,
+            whole_word: false,
+            databases: None,
+            include_definitions: true,
+            include_object_names: true,
+            include_column_names: true,
+            max_results: 500,
+        }
+    }
+}
+
+impl DefinitionSearch {
+    /// Compile the pattern into the regex used for matching
+    pub fn matcher(&self) -> Result<Regex, String> {
+        if self.pattern.is_empty() {
+            return Err("Search pattern is empty".to_string());
+        }
+        let mut pattern = if self.is_regex {
+            self.pattern.clone()
+        } else {
+            regex::escape(&self.pattern)
+        };
+        if self.whole_word {
+            pattern = format!(r"\b(?:{})\b", pattern);
+        }
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!self.case_sensitive)
+            .build()
+            .map_err(|e| format!("Invalid search pattern: {}", e))
+    }
+
+    /// `AND <column> LIKE ...` narrowing rows server-side for plain-text searches
+    fn like_condition(&self, column: &str) -> String {
+        if self.is_regex {
+            return String::new();
+        }
+        let escaped = self
+            .pattern
+            .replace('\'', "''")
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+            .replace('[', "\\[");
+        format!(
+            "AND LOWER({}) LIKE LOWER(N'%{}%') ESCAPE '\\'",
+            column, escaped
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchMatchKind {
+    Definition,
+    ObjectName,
+    ColumnName,
+}
+
+/// A matching line; `match_start`/`match_end` are character offsets into `text`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LineMatch {
+    pub line_number: usize,
+    pub text: String,
+    pub match_start: usize,
+    pub match_end: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DefinitionSearchMatch {
+    pub database: String,
+    pub schema_name: String,
+    pub object_name: String,
+    /// `sys.objects.type_desc`, e.g. SQL_STORED_PROCEDURE or USER_TABLE
+    pub object_type: String,
+    pub kind: SearchMatchKind,
+    pub column_name: Option<String>,
+    /// Matching definition lines (definition matches only)
+    pub lines: Vec<LineMatch>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DefinitionSearchResult {
+    pub matches: Vec<DefinitionSearchMatch>,
+    pub databases_searched: Vec<String>,
+    /// (database, error) for databases that could not be searched
+    pub errors: Vec<(String, String)>,
+    /// More matches existed than `max_results`
+    pub truncated: bool,
+}
+
+/// Lines of `definition` matching `matcher`, numbered from 1
+pub fn match_lines(definition: &str, matcher: &Regex, limit: usize) -> Vec<LineMatch> {
+    definition
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let found = matcher.find(line)?;
+            let line = line.trim_end();
+            let indent = line.len() - line.trim_start().len();
+            let trimmed = &line[indent..];
+            // The match may lie partly or wholly in the trimmed whitespace
+            let found_start = found.start().clamp(indent, line.len());
+            let found_end = found.end().clamp(found_start, line.len());
+            let start = line[indent..found_start].chars().count();
+            let end = start + line[found_start..found_end].chars().count();
+
+            // Keep the match visible in long lines
+            let skip = start
+                .saturating_sub(MAX_SNIPPET_CHARS / 2)
+                .min(trimmed.chars().count().saturating_sub(MAX_SNIPPET_CHARS));
+            let text: String = trimmed.chars().skip(skip).take(MAX_SNIPPET_CHARS).collect();
+            let match_start = start - skip;
+            let match_end = (end - skip).min(text.chars().count());
+            Some(LineMatch {
+                line_number: i + 1,
+                text,
+                match_start,
+                match_end,
+            })
+        })
+        .take(limit)
+        .collect()
+}
+
+impl SchemaMetadataManager {
+    /// Search module definitions, object names and column names
+    pub async fn search_definitions(
+        &self,
+        connection_id: &str,
+        search: &DefinitionSearch,
+    ) -> Result<DefinitionSearchResult, ConnectionError> {
+        let matcher = search.matcher().map_err(ConnectionError::QueryError)?;
+
+        let databases = match &search.databases {
+            Some(databases) => databases.clone(),
+            None => self
+                .connection_manager
+                .get_databases_with_access(connection_id)
+                .await?
+                .into_iter()
+                .filter(|(_, has_access)| *has_access)
+                .map(|(name, _)| name)
+                .collect(),
+        };
+
+        let mut result = DefinitionSearchResult {
+            matches: Vec::new(),
+            databases_searched: Vec::new(),
+            errors: Vec::new(),
+            truncated: false,
+        };
+        for database in databases {
+            if result.matches.len() >= search.max_results {
+                result.truncated = true;
+                break;
+            }
+            match self
+                .search_database(connection_id, &database, search, &matcher)
+                .await
+            {
+                Ok(matches) => {
+                    let room = search.max_results - result.matches.len();
+                    result.truncated |= matches.len() > room;
+                    result.matches.extend(matches.into_iter().take(room));
+                    result.databases_searched.push(database);
+                }
+                Err(e) => result.errors.push((database, e.to_string())),
+            }
+        }
+        Ok(result)
+    }
+
+    async fn search_database(
+        &self,
+        connection_id: &str,
+        database: &str,
+        search: &DefinitionSearch,
+        matcher: &Regex,
+    ) -> Result<Vec<DefinitionSearchMatch>, ConnectionError> {
+        let pool = self.connection_manager.connect(connection_id).await?;
+        let mut conn = pool.get().await?;
+        conn.simple_query(&format!("USE {}", quote_identifier(database)))
+            .await?;
+
+        let mut matches = Vec::new();
+        let push = |matches: &mut Vec<DefinitionSearchMatch>,
+                    row: &tiberius::Row,
+                    kind: SearchMatchKind,
+                    column_name: Option<String>,
+                    lines: Vec<LineMatch>| {
+            if let (Some(schema_name), Some(object_name)) =
+                (row.get::<&str, _>(0), row.get::<&str, _>(1))
+            {
+                matches.push(DefinitionSearchMatch {
+                    database: database.to_string(),
+                    schema_name: schema_name.to_string(),
+                    object_name: object_name.to_string(),
+                    object_type: row.get::<&str, _>(2).unwrap_or_default().to_string(),
+                    kind,
+                    column_name,
+                    lines,
+                });
+            }
+        };
+
+        if search.include_object_names {
+            let query = format!(
+                r#"
+                SELECT SCHEMA_NAME(o.schema_id), o.name, o.type_desc
+                FROM sys.objects o
+                WHERE o.is_ms_shipped = 0 AND o.parent_object_id = 0 {}
+                ORDER BY 1, 2
+            "#,
+                search.like_condition("o.name")
+            );
+            let rows = conn.simple_query(&query).await?.into_first_result().await?;
+            for row in rows.iter() {
+                if row
+                    .get::<&str, _>(1)
+                    .is_some_and(|name| matcher.is_match(name))
+                {
+                    push(
+                        &mut matches,
+                        row,
+                        SearchMatchKind::ObjectName,
+                        None,
+                        Vec::new(),
+                    );
+                }
+            }
+        }
+
+        if search.include_column_names {
+            let query = format!(
+                r#"
+                SELECT SCHEMA_NAME(o.schema_id), o.name, o.type_desc, c.name
+                FROM sys.columns c
+                JOIN sys.objects o ON o.object_id = c.object_id
+                WHERE o.is_ms_shipped = 0 AND o.type IN ('U', 'V', 'IF', 'TF') {}
+                ORDER BY 1, 2, c.column_id
+            "#,
+                search.like_condition("c.name")
+            );
+            let rows = conn.simple_query(&query).await?.into_first_result().await?;
+            for row in rows.iter() {
+                let Some(column_name) = row.get::<&str, _>(3).filter(|c| matcher.is_match(c))
+                else {
+                    continue;
+                };
+                push(
+                    &mut matches,
+                    row,
+                    SearchMatchKind::ColumnName,
+                    Some(column_name.to_string()),
+                    Vec::new(),
+                );
+            }
+        }
+
+        if search.include_definitions {
+            let query = format!(
+                r#"
+                SELECT SCHEMA_NAME(o.schema_id), o.name, o.type_desc, m.definition
+                FROM sys.sql_modules m
+                JOIN sys.objects o ON o.object_id = m.object_id
+                WHERE o.is_ms_shipped = 0 AND m.definition IS NOT NULL {}
+                ORDER BY 1, 2
+            "#,
+                search.like_condition("m.definition")
+            );
+            let rows = conn.simple_query(&query).await?.into_first_result().await?;
+            for row in rows.iter() {
+                let Some(definition) = row.get::<&str, _>(3) else {
+                    continue;
+                };
+                let lines = match_lines(definition, matcher, MAX_LINES_PER_OBJECT);
+                if !lines.is_empty() {
+                    push(&mut matches, row, SearchMatchKind::Definition, None, lines);
+                }
+            }
+        }
+
+        Ok(matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(pattern: &str) -> DefinitionSearch {
+        DefinitionSearch {
+            pattern: pattern.to_string(),
+            ..DefinitionSearch::default()
+        }
+    }
+
+    #[test]
+    fn plain_text_is_escaped_and_case_insensitive() {
+        let matcher = search("o.Status").matcher().unwrap();
+        assert!(matcher.is_match("WHERE O.STATUS = 1"));
+        assert!(!matcher.is_match("WHERE oxStatus = 1"));
+
+        let whole_word = DefinitionSearch {
+            whole_word: true,
+            case_sensitive: true,
+            ..search("Status")
+        }
+        .matcher()
+        .unwrap();
+        assert!(whole_word.is_match("SET Status = 2"));
+        assert!(!whole_word.is_match("SET OrderStatus = 2"));
+        assert!(!whole_word.is_match("SET status = 2"));
+    }
+
+    #[test]
+    fn regex_patterns_are_used_verbatim() {
+        let regex = DefinitionSearch {
+            is_regex: true,
+            ..search(r"Orders\.(Status|State)")
+        };
+        assert!(regex.matcher().unwrap().is_match("SELECT Orders.State"));
+        assert!(DefinitionSearch {
+            is_regex: true,
+            ..search("(")
+        }
+        .matcher()
+        .is_err());
+        assert_eq!(search("").matcher().unwrap_err(), "Search pattern is empty");
+        assert!(regex.like_condition("m.definition").is_empty());
+    }
+
+    #[test]
+    fn like_condition_escapes_wildcards() {
+        assert_eq!(
+            search("50%_o'k").like_condition("m.definition"),
+            r"AND LOWER(m.definition) LIKE LOWER(N'%50\%\_o''k%') ESCAPE '\'"
+        );
+    }
+
+    #[test]
+    fn matching_lines_are_trimmed_with_offsets() {
+        let definition = "CREATE PROCEDURE dbo.GetOrders AS\n    SELECT *\n    FROM dbo.Orders o\n    WHERE o.Status = 1;";
+        let lines = match_lines(definition, &search("o.status").matcher().unwrap(), 10);
+        assert_eq!(
+            lines,
+            vec![LineMatch {
+                line_number: 4,
+                text: "WHERE o.Status = 1;".to_string(),
+                match_start: 6,
+                match_end: 14,
+            }]
+        );
+
+        let long_line = format!("{}Status{}", "x".repeat(300), "y".repeat(300));
+        let lines = match_lines(&long_line, &search("status").matcher().unwrap(), 10);
+        let line = &lines[0];
+        assert_eq!(line.text.chars().count(), MAX_SNIPPET_CHARS);
+        assert_eq!(&line.text[line.match_start..line.match_end], "Status");
+    }
+
+    #[test]
+    fn matches_in_trimmed_whitespace_stay_in_bounds() {
+        let trailing = DefinitionSearch {
+            is_regex: true,
+            ..search("  $")
+        };
+        let lines = match_lines("SELECT 1\n  a   \n", &trailing.matcher().unwrap(), 10);
+        assert_eq!(
+            lines,
+            vec![LineMatch {
+                line_number: 2,
+                text: "a".to_string(),
+                match_start: 1,
+                match_end: 1,
+            }]
+        );
+    }
+}
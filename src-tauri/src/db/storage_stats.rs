@@ -0,0 +1,363 @@
+// Storage Statistics
+// Table sizes and row counts, index fragmentation and database-level space usage
+
+use crate::db::connection::ConnectionError;
+use crate::db::schema::SchemaMetadataManager;
+use crate::db::scripting::ObjectRef;
+use crate::sql::quote_identifier;
+use serde::{Deserialize, Serialize};
+
+/// Indexes smaller than this are not worth defragmenting
+const MIN_MAINTENANCE_PAGES: i64 = 1000;
+
+/// Row count and space usage (in KB) of a table, summed over its partitions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableStorageInfo {
+    pub schema_name: String,
+    pub table_name: String,
+    pub row_count: i64,
+    pub reserved_kb: i64,
+    pub used_kb: i64,
+    pub data_kb: i64,
+    pub index_kb: i64,
+    pub unused_kb: i64,
+    /// Most recent update of any statistics on the table
+    pub stats_last_updated: Option<String>,
+}
+
+impl TableStorageInfo {
+    /// Split page counts the way `sp_spaceused` does: index space is what is
+    /// used beyond the heap/clustered data, unused is reserved but not used
+    fn from_pages(
+        schema_name: String,
+        table_name: String,
+        row_count: i64,
+        reserved_pages: i64,
+        used_pages: i64,
+        data_pages: i64,
+        stats_last_updated: Option<String>,
+    ) -> Self {
+        Self {
+            schema_name,
+            table_name,
+            row_count,
+            reserved_kb: reserved_pages * 8,
+            used_kb: used_pages * 8,
+            data_kb: data_pages * 8,
+            index_kb: (used_pages - data_pages).max(0) * 8,
+            unused_kb: (reserved_pages - used_pages).max(0) * 8,
+            stats_last_updated,
+        }
+    }
+}
+
+/// `sys.dm_db_index_physical_stats` scan mode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FragmentationScanMode {
+    /// Reads only parent-level pages; fast, no page density
+    #[default]
+    Limited,
+    /// Samples 1% of leaf pages; adds page density
+    Sampled,
+}
+
+impl FragmentationScanMode {
+    fn as_sql(self) -> &'static str {
+        match self {
+            Self::Limited => "LIMITED",
+            Self::Sampled => "SAMPLED",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexMaintenance {
+    None,
+    Reorganize,
+    Rebuild,
+}
+
+/// Usual guidance: leave small or lightly fragmented indexes alone, reorganize
+/// from 5% and rebuild above 30%
+pub fn recommended_maintenance(
+    avg_fragmentation_percent: f64,
+    page_count: i64,
+) -> IndexMaintenance {
+    if page_count < MIN_MAINTENANCE_PAGES || avg_fragmentation_percent < 5.0 {
+        IndexMaintenance::None
+    } else if avg_fragmentation_percent <= 30.0 {
+        IndexMaintenance::Reorganize
+    } else {
+        IndexMaintenance::Rebuild
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexFragmentationInfo {
+    pub schema_name: String,
+    pub table_name: String,
+    /// None for heaps
+    pub index_name: Option<String>,
+    pub index_type: String,
+    pub partition_number: i32,
+    pub avg_fragmentation_percent: f64,
+    pub page_count: i64,
+    pub fragment_count: Option<i64>,
+    /// Only available in SAMPLED mode
+    pub avg_page_space_used_percent: Option<f64>,
+    pub stats_last_updated: Option<String>,
+    pub recommendation: IndexMaintenance,
+}
+
+/// Size and configuration of a database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseStorageSummary {
+    pub database_name: String,
+    pub state: String,
+    pub recovery_model: String,
+    pub compatibility_level: u8,
+    pub data_size_mb: f64,
+    pub data_used_mb: Option<f64>,
+    pub log_size_mb: f64,
+    /// From `sys.dm_db_log_space_usage` (SQL Server 2012+)
+    pub log_used_mb: Option<f64>,
+    pub log_used_percent: Option<f64>,
+}
+
+impl SchemaMetadataManager {
+    /// Row counts and space usage of every user table, largest first
+    pub async fn table_storage_stats(
+        &self,
+        connection_id: &str,
+        database: &str,
+        schema_filter: Option<&str>,
+    ) -> Result<Vec<TableStorageInfo>, ConnectionError> {
+        let pool = self.connection_manager.connect(connection_id).await?;
+        let mut conn = pool.get().await?;
+        conn.simple_query(&format!("USE {}", quote_identifier(database)))
+            .await?;
+
+        let query = format!(
+            r#"
+            SELECT
+                s.name,
+                t.name,
+                SUM(CASE WHEN ps.index_id IN (0, 1) THEN ps.row_count ELSE 0 END),
+                SUM(ps.reserved_page_count),
+                SUM(ps.used_page_count),
+                SUM(CASE WHEN ps.index_id IN (0, 1)
+                    THEN ps.in_row_data_page_count + ps.lob_used_page_count + ps.row_overflow_used_page_count
+                    ELSE 0 END),
+                (SELECT CONVERT(varchar(23), MAX(STATS_DATE(st.object_id, st.stats_id)), 126)
+                 FROM sys.stats st WHERE st.object_id = t.object_id)
+            FROM sys.tables t
+            JOIN sys.schemas s ON s.schema_id = t.schema_id
+            JOIN sys.dm_db_partition_stats ps ON ps.object_id = t.object_id
+            WHERE t.is_ms_shipped = 0 {}
+            GROUP BY s.name, t.name, t.object_id
+            ORDER BY SUM(ps.reserved_page_count) DESC, s.name, t.name
+        "#,
+            schema_filter
+                .map(|s| format!("AND s.name = '{}'", s.replace('\'', "''")))
+                .unwrap_or_default()
+        );
+
+        let rows = conn.simple_query(&query).await?.into_first_result().await?;
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                Some(TableStorageInfo::from_pages(
+                    row.get::<&str, _>(0)?.to_string(),
+                    row.get::<&str, _>(1)?.to_string(),
+                    row.get::<i64, _>(2).unwrap_or(0),
+                    row.get::<i64, _>(3).unwrap_or(0),
+                    row.get::<i64, _>(4).unwrap_or(0),
+                    row.get::<i64, _>(5).unwrap_or(0),
+                    row.get::<&str, _>(6).map(|s| s.to_string()),
+                ))
+            })
+            .collect())
+    }
+
+    /// Leaf-level fragmentation of every index (or of one table's indexes),
+    /// most fragmented first
+    pub async fn index_fragmentation(
+        &self,
+        connection_id: &str,
+        database: &str,
+        table: Option<&ObjectRef>,
+        mode: FragmentationScanMode,
+    ) -> Result<Vec<IndexFragmentationInfo>, ConnectionError> {
+        let pool = self.connection_manager.connect(connection_id).await?;
+        let mut conn = pool.get().await?;
+        conn.simple_query(&format!("USE {}", quote_identifier(database)))
+            .await?;
+
+        let object_id = table
+            .map(|t| {
+                let name = format!(
+                    "{}.{}",
+                    quote_identifier(&t.schema_name),
+                    quote_identifier(&t.object_name)
+                );
+                format!("OBJECT_ID(N'{}')", name.replace('\'', "''"))
+            })
+            .unwrap_or_else(|| "NULL".to_string());
+        let query = format!(
+            r#"
+            SELECT
+                s.name,
+                t.name,
+                i.name,
+                i.type_desc,
+                ips.partition_number,
+                ips.avg_fragmentation_in_percent,
+                ips.page_count,
+                ips.fragment_count,
+                ips.avg_page_space_used_in_percent,
+                CONVERT(varchar(23), STATS_DATE(i.object_id, i.index_id), 126)
+            FROM sys.dm_db_index_physical_stats(DB_ID(), {}, NULL, NULL, '{}') ips
+            JOIN sys.indexes i ON i.object_id = ips.object_id AND i.index_id = ips.index_id
+            JOIN sys.tables t ON t.object_id = ips.object_id
+            JOIN sys.schemas s ON s.schema_id = t.schema_id
+            WHERE t.is_ms_shipped = 0 AND ips.index_level = 0 AND ips.alloc_unit_type_desc = 'IN_ROW_DATA'
+            ORDER BY ips.avg_fragmentation_in_percent DESC, ips.page_count DESC
+        "#,
+            object_id,
+            mode.as_sql()
+        );
+
+        let rows = conn.simple_query(&query).await?.into_first_result().await?;
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                let avg_fragmentation_percent = row.get::<f64, _>(5).unwrap_or(0.0);
+                let page_count = row.get::<i64, _>(6).unwrap_or(0);
+                Some(IndexFragmentationInfo {
+                    schema_name: row.get::<&str, _>(0)?.to_string(),
+                    table_name: row.get::<&str, _>(1)?.to_string(),
+                    index_name: row.get::<&str, _>(2).map(|s| s.to_string()),
+                    index_type: row.get::<&str, _>(3).unwrap_or_default().to_string(),
+                    partition_number: row.get::<i32, _>(4).unwrap_or(1),
+                    avg_fragmentation_percent,
+                    page_count,
+                    fragment_count: row.get::<i64, _>(7),
+                    avg_page_space_used_percent: row.get::<f64, _>(8),
+                    stats_last_updated: row.get::<&str, _>(9).map(|s| s.to_string()),
+                    recommendation: recommended_maintenance(avg_fragmentation_percent, page_count),
+                })
+            })
+            .collect())
+    }
+
+    /// Database size, log usage, recovery model and compatibility level
+    pub async fn database_storage_summary(
+        &self,
+        connection_id: &str,
+        database: &str,
+    ) -> Result<DatabaseStorageSummary, ConnectionError> {
+        let pool = self.connection_manager.connect(connection_id).await?;
+        let mut conn = pool.get().await?;
+        conn.simple_query(&format!("USE {}", quote_identifier(database)))
+            .await?;
+
+        let query = r#"
+            SELECT
+                d.name,
+                d.state_desc,
+                d.recovery_model_desc,
+                d.compatibility_level,
+                CAST(SUM(CASE WHEN f.type = 0 THEN f.size ELSE 0 END) * 8 / 1024.0 AS float),
+                CAST(SUM(CASE WHEN f.type = 0 THEN FILEPROPERTY(f.name, 'SpaceUsed') END) * 8 / 1024.0 AS float),
+                CAST(SUM(CASE WHEN f.type = 1 THEN f.size ELSE 0 END) * 8 / 1024.0 AS float)
+            FROM sys.database_files f
+            CROSS JOIN sys.databases d
+            WHERE d.database_id = DB_ID()
+            GROUP BY d.name, d.state_desc, d.recovery_model_desc, d.compatibility_level
+        "#;
+        let row = conn
+            .simple_query(query)
+            .await?
+            .into_row()
+            .await?
+            .ok_or_else(|| {
+                ConnectionError::QueryError(format!("Database {} not found", database))
+            })?;
+
+        let mut summary = DatabaseStorageSummary {
+            database_name: row.get::<&str, _>(0).unwrap_or(database).to_string(),
+            state: row.get::<&str, _>(1).unwrap_or_default().to_string(),
+            recovery_model: row.get::<&str, _>(2).unwrap_or_default().to_string(),
+            compatibility_level: row.get::<u8, _>(3).unwrap_or(0),
+            data_size_mb: row.get::<f64, _>(4).unwrap_or(0.0),
+            data_used_mb: row.get::<f64, _>(5),
+            log_size_mb: row.get::<f64, _>(6).unwrap_or(0.0),
+            log_used_mb: None,
+            log_used_percent: None,
+        };
+
+        // Not available before SQL Server 2012; leave log usage unknown there
+        let log_usage = r#"
+            SELECT
+                CAST(used_log_space_in_bytes / 1048576.0 AS float),
+                CAST(used_log_space_in_percent AS float)
+            FROM sys.dm_db_log_space_usage
+        "#;
+        if let Ok(stream) = conn.simple_query(log_usage).await {
+            if let Ok(Some(row)) = stream.into_row().await {
+                summary.log_used_mb = row.get::<f64, _>(0);
+                summary.log_used_percent = row.get::<f64, _>(1);
+            }
+        }
+
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn space_is_split_like_sp_spaceused() {
+        let info = TableStorageInfo::from_pages(
+            "dbo".to_string(),
+            "Orders".to_string(),
+            1200,
+            100,
+            90,
+            60,
+            None,
+        );
+        assert_eq!(
+            (
+                info.reserved_kb,
+                info.used_kb,
+                info.data_kb,
+                info.index_kb,
+                info.unused_kb
+            ),
+            (800, 720, 480, 240, 80)
+        );
+    }
+
+    #[test]
+    fn maintenance_follows_fragmentation_thresholds() {
+        assert_eq!(recommended_maintenance(80.0, 10), IndexMaintenance::None);
+        assert_eq!(recommended_maintenance(3.0, 50_000), IndexMaintenance::None);
+        assert_eq!(
+            recommended_maintenance(12.5, 50_000),
+            IndexMaintenance::Reorganize
+        );
+        assert_eq!(
+            recommended_maintenance(30.0, 1000),
+            IndexMaintenance::Reorganize
+        );
+        assert_eq!(
+            recommended_maintenance(45.0, 1000),
+            IndexMaintenance::Rebuild
+        );
+    }
+}
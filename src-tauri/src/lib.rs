@@ -1,16 +1,22 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 
 // Module declarations
+pub mod api;
+pub mod cli;
 pub mod commands;
 pub mod db;
 pub mod export;
+pub mod jsonrpc;
+pub mod lsp;
+pub mod scheduler;
+pub mod sql;
 pub mod storage;
 
 use commands::AppState;
 use db::{MssqlConnectionManager, QueryEngine, SchemaMetadataManager};
 use storage::{DatabaseManager, get_default_db_path};
 use std::sync::{Arc, Mutex};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use tokio::sync::RwLock;
 use tokio::time::{interval, Duration};
 
@@ -56,15 +62,23 @@ pub fn run() {
         query_engine,
         schema_manager,
         export_cancel_flags: RwLock::new(HashMap::new()),
+        data_search_cancel_flags: RwLock::new(HashMap::new()),
+        fan_out_cancel_flags: RwLock::new(HashMap::new()),
+        api_server: tokio::sync::Mutex::new(None),
+        running_schedules: Mutex::new(HashSet::new()),
     };
 
     // Clone DB path for background task
     let db_path_for_bg = db_path.clone();
 
     tauri::Builder::default()
-        .setup(move |_app| {
+        .setup(move |app| {
             // Spawn background auto-archive task
             spawn_auto_archive_task(db_path_for_bg);
+            // Start the local automation API if the user turned it on
+            tauri::async_runtime::spawn(api::start_if_enabled(app.handle().clone()));
+            // Spawn the scheduled query runner
+            scheduler::spawn_scheduler_task(app.handle().clone());
             Ok(())
         })
         .manage(app_state)
@@ -125,10 +139,36 @@ pub fn run() {
             commands::cancel_query,
             commands::cancel_queries_for_connection,
             commands::get_query_status,
+            commands::validate_query,
             // Schema metadata commands (T025)
             commands::get_schema_info,
             commands::get_table_columns,
             commands::refresh_schema,
+            // Object scripting commands
+            commands::script_object,
+            commands::script_drop_objects,
+            // Schema compare commands
+            commands::save_schema_snapshot,
+            commands::compare_schemas,
+            // Dependency analysis commands
+            commands::get_object_dependencies,
+            // Definition search commands
+            commands::search_definitions,
+            // Data search commands
+            commands::search_data,
+            commands::cancel_data_search,
+            // Fan-out query commands
+            commands::execute_fan_out,
+            commands::cancel_fan_out,
+            // Storage statistics commands
+            commands::get_table_storage_stats,
+            commands::get_index_fragmentation,
+            commands::get_database_storage_summary,
+            // ER diagram commands
+            commands::generate_er_diagram,
+            // Data dictionary commands
+            commands::set_object_description,
+            commands::generate_data_dictionary,
             // Export commands (T034, T035, T036)
             commands::export_to_csv,
             commands::export_to_json,
@@ -161,6 +201,12 @@ pub fn run() {
             commands::update_history_retention_days,
             commands::purge_archived_tabs_now,
             commands::purge_all_archived_tabs,
+            // Query history commands
+            commands::search_query_history,
+            commands::get_query_history_entry,
+            commands::reopen_query_history_entry,
+            commands::delete_query_history_entry,
+            commands::purge_query_history,
             // App Settings commands
             commands::get_app_settings,
             commands::update_app_settings,
@@ -177,6 +223,31 @@ pub fn run() {
             commands::get_virtual_references,
             commands::save_virtual_reference,
             commands::delete_virtual_reference,
+            commands::infer_virtual_references,
+            commands::accept_reference_candidates,
+            commands::export_virtual_references,
+            commands::import_virtual_references,
+            // Reference navigation commands
+            commands::get_reference_parent_rows,
+            commands::get_reference_child_rows,
+            commands::generate_join_clause,
+            // SQL formatting and linting commands
+            commands::format_sql,
+            commands::lint_sql,
+            commands::get_completions,
+            // Local automation API
+            commands::get_api_status,
+            commands::set_api_enabled,
+            commands::get_api_audit_log,
+            commands::clear_api_audit_log,
+            // Scheduled queries
+            commands::get_schedules,
+            commands::create_schedule,
+            commands::update_schedule,
+            commands::delete_schedule,
+            commands::run_schedule_now,
+            commands::get_schedule_runs,
+            commands::get_schedule_alerts,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -249,6 +320,16 @@ fn spawn_auto_archive_task(db_path: std::path::PathBuf) {
                 }
                 Err(e) => eprintln!("[Auto-Archive] Failed to cleanup old tabs: {}", e),
             }
+
+            // Query history shares the archive retention period
+            match db.cleanup_old_query_history(retention_days) {
+                Ok(count) => {
+                    if count > 0 {
+                        println!("[Auto-Archive] Cleaned up {} old query history entries", count);
+                    }
+                }
+                Err(e) => eprintln!("[Auto-Archive] Failed to cleanup query history: {}", e),
+            }
         }
     });
 }
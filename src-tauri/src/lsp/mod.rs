@@ -0,0 +1,7 @@
+// Language Server
+// LSP over stdio for external editors, backed by the app's spaces and schema cache
+
+pub mod protocol;
+pub mod server;
+
+pub use server::{run_stdio, Server, Target};
@@ -0,0 +1,173 @@
+// LSP Wire Protocol
+// Content-Length framed JSON-RPC messages over a byte stream, and conversion
+// between byte offsets and LSP positions (lines and UTF-16 code units)
+
+use std::io::{self, BufRead, Write};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::jsonrpc::PARSE_ERROR;
+use crate::sql::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    pub line: u32,
+    pub character: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+/// Read one message. Returns None at end of input.
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            if length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let mut body = vec![0; length.unwrap_or(0)];
+    reader.read_exact(&mut body)?;
+    match serde_json::from_slice(&body) {
+        Ok(message) => Ok(Some(message)),
+        Err(e) => Ok(Some(json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": { "code": PARSE_ERROR, "message": e.to_string() },
+        }))),
+    }
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+/// Byte offset of an LSP position. Positions past the end of a line clamp to
+/// the line end, and lines past the end of the text to the text end.
+pub fn offset_at(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return text.len(),
+        }
+    }
+
+    let mut units = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if c == '\n' || units >= position.character as usize {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+/// LSP position of a byte offset
+pub fn position_at(text: &str, offset: usize) -> Position {
+    let offset = offset.min(text.len());
+    let before = text.get(..offset).unwrap_or(text);
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
+
+pub fn range_of(text: &str, span: Span) -> Range {
+    Range {
+        start: position_at(text, span.start),
+        end: position_at(text, span.end),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jsonrpc::{notification, response};
+
+    #[test]
+    fn messages_round_trip_through_framing() {
+        let mut buffer = Vec::new();
+        let message = notification("initialized", json!({}));
+        write_message(&mut buffer, &message).unwrap();
+        write_message(&mut buffer, &response(&json!(1), Value::Null)).unwrap();
+
+        let mut reader = io::Cursor::new(buffer);
+        assert_eq!(read_message(&mut reader).unwrap(), Some(message));
+        let second = read_message(&mut reader).unwrap().unwrap();
+        assert_eq!(second["id"], 1);
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+
+        let mut bad = io::Cursor::new(b"Content-Length: 3\r\n\r\n{x}".to_vec());
+        let error = read_message(&mut bad).unwrap().unwrap();
+        assert_eq!(error["error"]["code"], PARSE_ERROR);
+    }
+
+    #[test]
+    fn positions_count_utf16_units() {
+        let text = "SELECT 'é😀', x\nFROM t";
+        let x = text.find('x').unwrap();
+        let position = position_at(text, x);
+        assert_eq!(
+            position,
+            Position {
+                line: 0,
+                character: 14
+            }
+        );
+        assert_eq!(offset_at(text, position), x);
+
+        let from = text.find("FROM").unwrap();
+        assert_eq!(
+            offset_at(
+                text,
+                Position {
+                    line: 1,
+                    character: 0
+                }
+            ),
+            from
+        );
+        assert_eq!(
+            offset_at(
+                text,
+                Position {
+                    line: 0,
+                    character: 99
+                }
+            ),
+            from - 1
+        );
+        assert_eq!(
+            offset_at(
+                text,
+                Position {
+                    line: 9,
+                    character: 0
+                }
+            ),
+            text.len()
+        );
+    }
+}
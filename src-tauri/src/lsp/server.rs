@@ -0,0 +1,717 @@
+// SQL Language Server
+// Serves completion, hover, go-to-definition and diagnostics to external
+// editors from the spaces and schema cache stored in the Larik app database.
+// Nothing here connects to SQL Server: schemas come from what the app cached.
+
+use std::collections::HashMap;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::protocol::{offset_at, range_of, read_message, write_message, Position};
+use crate::db::compare::column_type;
+use crate::db::schema::{description, SchemaInfo};
+use crate::jsonrpc::{
+    error_response, notification, response, INVALID_PARAMS, INVALID_REQUEST, METHOD_NOT_FOUND,
+    PARSE_ERROR,
+};
+use crate::sql::{
+    complete, lint, resolve_symbol, syntax_diagnostics, tokenize, CompletionCatalog,
+    CompletionKind, Diagnostic, Severity, Symbol, TokenKind,
+};
+use crate::storage::{get_default_db_path, DatabaseManager, Space, VirtualReference};
+
+/// Comment that points a document at a space and database, e.g.
+/// `-- larik: space="Sales EU" database=Orders`
+const DIRECTIVE: &str = "larik:";
+
+/// Space and database a document runs against. Either part may come from
+/// the document's directive, the client's settings, or the space itself.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Target {
+    /// Space id or name (case-insensitive)
+    pub space: Option<String>,
+    pub database: Option<String>,
+}
+
+/// Cached schemas of one space, with the cache timestamps they were read at
+#[derive(Default)]
+struct SpaceSchemas {
+    versions: Vec<(String, String)>,
+    schemas: Vec<SchemaInfo>,
+}
+
+pub struct Server {
+    db: DatabaseManager,
+    defaults: Target,
+    documents: HashMap<String, String>,
+    schemas: HashMap<String, SpaceSchemas>,
+    /// Where routine and view definitions are written for go-to-definition
+    definitions_dir: PathBuf,
+    shutdown: bool,
+}
+
+/// Run the server on stdin/stdout until the client sends `exit`
+pub fn run_stdio() -> Result<(), String> {
+    let db_path = get_default_db_path().map_err(|e| e.to_string())?;
+    let db = DatabaseManager::new(db_path).map_err(|e| e.to_string())?;
+    let mut server = Server::new(db, std::env::temp_dir().join("larik-lsp"));
+
+    let mut input = BufReader::new(io::stdin().lock());
+    let mut output = io::stdout().lock();
+    while let Some(message) = read_message(&mut input).map_err(|e| e.to_string())? {
+        if message["method"] == "exit" {
+            return if server.shutdown {
+                Ok(())
+            } else {
+                Err("exit received before shutdown".to_string())
+            };
+        }
+        // Unparseable input comes back from `read_message` as a ready error
+        let replies = if message["error"]["code"] == PARSE_ERROR {
+            vec![message]
+        } else {
+            server.handle(&message)
+        };
+        for reply in replies {
+            write_message(&mut output, &reply).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+impl Server {
+    pub fn new(db: DatabaseManager, definitions_dir: PathBuf) -> Self {
+        Self {
+            db,
+            defaults: Target::default(),
+            documents: HashMap::new(),
+            schemas: HashMap::new(),
+            definitions_dir,
+            shutdown: false,
+        }
+    }
+
+    /// Handle one incoming message and return the messages to send back
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let Some(id) = message.get("id") else {
+            return self.notify(method, params);
+        };
+        if message.get("error").is_some() || message.get("result").is_some() {
+            return Vec::new();
+        }
+        if self.shutdown {
+            return vec![error_response(
+                id,
+                INVALID_REQUEST,
+                "server is shutting down",
+            )];
+        }
+
+        let result = match method {
+            "initialize" => {
+                if let Ok(target) = Target::deserialize(&params["initializationOptions"]) {
+                    self.defaults = target;
+                }
+                Ok(json!({
+                    "capabilities": {
+                        "textDocumentSync": 1,
+                        "completionProvider": { "triggerCharacters": [".", "@", "["] },
+                        "hoverProvider": true,
+                        "definitionProvider": true,
+                    },
+                    "serverInfo": { "name": "larik-lsp", "version": env!("CARGO_PKG_VERSION") },
+                }))
+            }
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "textDocument/completion" => self.completion(params),
+            "textDocument/hover" => self.hover(params),
+            "textDocument/definition" => self.definition(params),
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method {}", method))),
+        };
+        match result {
+            Ok(result) => vec![response(id, result)],
+            Err((code, message)) => vec![error_response(id, code, message)],
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.to_string(), text.to_string());
+                vec![self.diagnostics(uri)]
+            }
+            "textDocument/didChange" => {
+                // Full sync: the last change holds the whole text
+                let changes = params["contentChanges"].as_array();
+                let Some(text) = changes
+                    .and_then(|c| c.last())
+                    .and_then(|c| c["text"].as_str())
+                else {
+                    return Vec::new();
+                };
+                self.documents.insert(uri.to_string(), text.to_string());
+                vec![self.diagnostics(uri)]
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                vec![notification(
+                    "textDocument/publishDiagnostics",
+                    json!({ "uri": uri, "diagnostics": [] }),
+                )]
+            }
+            "workspace/didChangeConfiguration" => {
+                if let Ok(target) = Target::deserialize(&params["settings"]["larik"]) {
+                    self.defaults = target;
+                }
+                let uris: Vec<String> = self.documents.keys().cloned().collect();
+                uris.iter().map(|uri| self.diagnostics(uri)).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// The document text and byte offset of a `textDocument/*` request
+    fn document_offset(&self, params: &Value) -> Result<(String, usize), (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let text = self
+            .documents
+            .get(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("Document {} is not open", uri)))?;
+        let position = Position::deserialize(&params["position"])
+            .map_err(|e| (INVALID_PARAMS, e.to_string()))?;
+        Ok((uri.to_string(), offset_at(text, position)))
+    }
+
+    /// Space and database for a document, resolved against stored spaces
+    fn target(&self, text: &str) -> Option<(Space, String)> {
+        let directive = directive(text);
+        let wanted = directive.space.or_else(|| self.defaults.space.clone());
+        let spaces: Vec<Space> = self
+            .db
+            .get_all_spaces()
+            .ok()?
+            .into_iter()
+            .filter(|s| s.has_connection())
+            .collect();
+        let space = match wanted {
+            Some(wanted) => spaces
+                .into_iter()
+                .find(|s| s.id == wanted || s.name.eq_ignore_ascii_case(&wanted))?,
+            // Without a choice, only an unambiguous space is used
+            None if spaces.len() == 1 => spaces.into_iter().next()?,
+            None => return None,
+        };
+        let database = directive
+            .database
+            .or_else(|| self.defaults.database.clone())
+            .or_else(|| space.connection_database.clone())?;
+        Some((space, database))
+    }
+
+    /// Re-read the cached schemas of a space when the app has updated them
+    fn refresh_schemas(&mut self, space_id: &str) {
+        let versions = self.db.get_cached_databases(space_id).unwrap_or_default();
+        let entry = self.schemas.entry(space_id.to_string()).or_default();
+        if entry.versions == versions {
+            return;
+        }
+        entry.schemas = versions
+            .iter()
+            .filter_map(|(database, _)| self.db.get_schema(space_id, database).ok().flatten())
+            .collect();
+        entry.versions = versions;
+    }
+
+    /// Run `f` with the catalog of a document's space and database
+    fn with_catalog<T>(
+        &mut self,
+        uri: &str,
+        f: impl FnOnce(&str, &CompletionCatalog<'_>, &Space) -> T,
+    ) -> Option<T> {
+        let text = self.documents.get(uri)?;
+        let (space, database) = self.target(text)?;
+        self.refresh_schemas(&space.id);
+
+        let virtual_references: Vec<VirtualReference> = self
+            .db
+            .get_virtual_references(&space.id, &database)
+            .unwrap_or_default();
+        let schemas = &self.schemas[&space.id].schemas;
+        let databases: Vec<String> = schemas.iter().map(|s| s.database_name.clone()).collect();
+        let catalog = CompletionCatalog {
+            database: &database,
+            schemas,
+            databases: &databases,
+            virtual_references: &virtual_references,
+        };
+        Some(f(&self.documents[uri], &catalog, &space))
+    }
+
+    fn completion(&mut self, params: &Value) -> Result<Value, (i64, String)> {
+        let (uri, offset) = self.document_offset(params)?;
+        let list = self.with_catalog(&uri, |text, catalog, _| {
+            let completions = complete(text, offset, catalog);
+            let range = range_of(text, completions.replace);
+            let bracketed = text[completions.replace.start..].starts_with('[');
+            let items: Vec<Value> = completions
+                .items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| {
+                    // Clients filter on the replaced text, which keeps its `[`
+                    let filter = if bracketed {
+                        &item.insert_text
+                    } else {
+                        &item.label
+                    };
+                    json!({
+                        "label": item.label,
+                        "kind": completion_kind(item.kind),
+                        "detail": item.detail,
+                        "sortText": format!("{:04}", i),
+                        "filterText": filter,
+                        "textEdit": { "range": range, "newText": item.insert_text },
+                    })
+                })
+                .collect();
+            json!({ "isIncomplete": false, "items": items })
+        });
+        Ok(list.unwrap_or(Value::Null))
+    }
+
+    fn hover(&mut self, params: &Value) -> Result<Value, (i64, String)> {
+        let (uri, offset) = self.document_offset(params)?;
+        let hover = self.with_catalog(&uri, |text, catalog, _| {
+            let (span, symbol) = resolve_symbol(text, offset, catalog)?;
+            Some(json!({
+                "contents": { "kind": "markdown", "value": hover_text(&symbol) },
+                "range": range_of(text, span),
+            }))
+        });
+        Ok(hover.flatten().unwrap_or(Value::Null))
+    }
+
+    fn definition(&mut self, params: &Value) -> Result<Value, (i64, String)> {
+        let (uri, offset) = self.document_offset(params)?;
+        let definitions_dir = self.definitions_dir.clone();
+        let location = self.with_catalog(&uri, |text, catalog, space| {
+            let (_, symbol) = resolve_symbol(text, offset, catalog)?;
+            let (database, schema_name, name, definition) = match symbol {
+                Symbol::Table { database, table } => (
+                    database,
+                    &table.schema_name,
+                    &table.table_name,
+                    table.definition.as_ref()?,
+                ),
+                Symbol::Routine { database, routine } => (
+                    database,
+                    &routine.schema_name,
+                    &routine.routine_name,
+                    routine.definition.as_ref()?,
+                ),
+                Symbol::Column { .. } => return None,
+            };
+
+            let path = definitions_dir
+                .join(file_name(&space.name))
+                .join(file_name(database))
+                .join(format!(
+                    "{}.sql",
+                    file_name(&format!("{}.{}", schema_name, name))
+                ));
+            let written = std::fs::create_dir_all(path.parent()?)
+                .and_then(|_| std::fs::write(&path, definition));
+            if written.is_err() {
+                return None;
+            }
+            let start = json!({ "line": 0, "character": 0 });
+            Some(json!({
+                "uri": file_uri(&path),
+                "range": { "start": start, "end": start },
+            }))
+        });
+        Ok(location.flatten().unwrap_or(Value::Null))
+    }
+
+    /// `publishDiagnostics` for a document: statement structure plus lint
+    /// checks, type-aware when the document's schema is cached
+    fn diagnostics(&mut self, uri: &str) -> Value {
+        let text = self.documents.get(uri).cloned().unwrap_or_default();
+        let mut found = syntax_diagnostics(&text);
+        let linted = self.with_catalog(uri, |text, catalog, _| {
+            let schema = catalog
+                .schemas
+                .iter()
+                .find(|s| s.database_name.eq_ignore_ascii_case(catalog.database));
+            lint::lint_sql(text, schema)
+        });
+        found.extend(linted.unwrap_or_else(|| lint::lint_sql(&text, None)));
+        found.sort_by_key(|d| d.span.start);
+
+        let diagnostics: Vec<Value> = found.iter().map(|d| lsp_diagnostic(&text, d)).collect();
+        notification(
+            "textDocument/publishDiagnostics",
+            json!({ "uri": uri, "diagnostics": diagnostics }),
+        )
+    }
+}
+
+/// Target named by a `-- larik: space=... database=...` comment
+fn directive(text: &str) -> Target {
+    let mut target = Target::default();
+    for tok in tokenize(text) {
+        if tok.kind != TokenKind::LineComment {
+            continue;
+        }
+        let Some(rest) = tok.text[2..].trim_start().strip_prefix(DIRECTIVE) else {
+            continue;
+        };
+        let mut rest = rest.trim_start();
+        while let Some((key, value)) = rest.split_once('=') {
+            let (value, tail) = match value.strip_prefix('"') {
+                Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+                None => value.split_once(char::is_whitespace).unwrap_or((value, "")),
+            };
+            match key.trim().to_ascii_lowercase().as_str() {
+                "space" => target.space = Some(value.to_string()),
+                "database" => target.database = Some(value.to_string()),
+                _ => {}
+            }
+            rest = tail.trim_start();
+        }
+        break;
+    }
+    target
+}
+
+fn completion_kind(kind: CompletionKind) -> u32 {
+    match kind {
+        CompletionKind::Procedure => 2,
+        CompletionKind::Function => 3,
+        CompletionKind::Column => 5,
+        CompletionKind::Parameter | CompletionKind::Variable => 6,
+        CompletionKind::Table => 7,
+        CompletionKind::View => 8,
+        CompletionKind::Database | CompletionKind::Schema => 9,
+        CompletionKind::Keyword => 14,
+        CompletionKind::Join => 15,
+        CompletionKind::Alias => 18,
+    }
+}
+
+fn hover_text(symbol: &Symbol<'_>) -> String {
+    let mut text = match symbol {
+        Symbol::Table { database, table } => {
+            let kind = if table.table_type == "VIEW" {
+                "view"
+            } else {
+                "table"
+            };
+            let mut text = format!(
+                "{} `{}.{}.{}` ({} columns)",
+                kind,
+                database,
+                table.schema_name,
+                table.table_name,
+                table.columns.len()
+            );
+            if let Some(note) = description(&table.extended_properties) {
+                text.push_str("\n\n");
+                text.push_str(note);
+            }
+            text
+        }
+        Symbol::Column { table, column, .. } => {
+            let mut text = format!(
+                "```sql\n{}.{}.{} {}{}\n```",
+                table.schema_name,
+                table.table_name,
+                column.name,
+                column_type(column),
+                if column.is_nullable {
+                    " NULL"
+                } else {
+                    " NOT NULL"
+                }
+            );
+            let mut notes = Vec::new();
+            if column.is_primary_key {
+                notes.push("primary key".to_string());
+            }
+            if column.is_identity {
+                notes.push("identity".to_string());
+            }
+            if column.is_computed {
+                notes.push("computed".to_string());
+            }
+            if let Some(default) = &column.column_default {
+                notes.push(format!("default `{}`", default));
+            }
+            if !notes.is_empty() {
+                text.push_str("\n\n");
+                text.push_str(&notes.join(", "));
+            }
+            if let Some(note) = description(&column.extended_properties) {
+                text.push_str("\n\n");
+                text.push_str(note);
+            }
+            text
+        }
+        Symbol::Routine { database, routine } => {
+            let parameters: Vec<String> = routine
+                .parameters
+                .iter()
+                .filter(|p| !p.name.is_empty())
+                .map(|p| format!("{} {}", p.name, p.data_type))
+                .collect();
+            let mut text = format!(
+                "```sql\n{} {}.{}.{}({})",
+                routine.routine_type,
+                database,
+                routine.schema_name,
+                routine.routine_name,
+                parameters.join(", ")
+            );
+            if let Some(return_type) = &routine.return_type {
+                text.push_str(&format!(" RETURNS {}", return_type));
+            }
+            text.push_str("\n```");
+            if let Some(note) = description(&routine.extended_properties) {
+                text.push_str("\n\n");
+                text.push_str(note);
+            }
+            text
+        }
+    };
+    text.truncate(text.trim_end().len());
+    text
+}
+
+fn lsp_diagnostic(text: &str, diagnostic: &Diagnostic) -> Value {
+    let severity = match diagnostic.severity {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+        Severity::Info => 3,
+    };
+    json!({
+        "range": range_of(text, diagnostic.span),
+        "severity": severity,
+        "code": diagnostic.code,
+        "source": "larik",
+        "message": diagnostic.message,
+    })
+}
+
+/// A name usable as a file or directory name on every platform. Leading dots
+/// are replaced so `.` and `..` cannot step out of the definitions directory.
+fn file_name(name: &str) -> String {
+    let rest = name.trim_start_matches('.');
+    let mut file = "_".repeat(name.len() - rest.len());
+    file.extend(rest.chars().map(|c| {
+        if c.is_alphanumeric() || matches!(c, '.' | '-' | '_' | ' ') {
+            c
+        } else {
+            '_'
+        }
+    }));
+    if file.is_empty() {
+        file.push('_');
+    }
+    file
+}
+
+/// `file://` URI of an absolute path
+fn file_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from("file://");
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~:".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema::{ColumnInfo, ExtendedProperty, RoutineInfo, TableInfo};
+    use crate::storage::CreateSpaceInput;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static TEST_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    fn column(name: &str, data_type: &str) -> ColumnInfo {
+        ColumnInfo {
+            is_nullable: false,
+            is_primary_key: name == "Id",
+            ..ColumnInfo::for_test(name, data_type)
+        }
+    }
+
+    fn schema() -> SchemaInfo {
+        let mut total = column("Total", "money");
+        total.extended_properties = vec![ExtendedProperty {
+            name: "MS_Description".to_string(),
+            value: "Order total incl. tax".to_string(),
+        }];
+        SchemaInfo {
+            routines: vec![RoutineInfo {
+                schema_name: "dbo".to_string(),
+                routine_name: "CloseOrder".to_string(),
+                routine_type: "PROCEDURE".to_string(),
+                return_type: None,
+                parameters: Vec::new(),
+                definition: Some("CREATE PROCEDURE dbo.CloseOrder AS SELECT 1".to_string()),
+                modify_date: None,
+                extended_properties: Vec::new(),
+            }],
+            ..SchemaInfo::for_test(
+                "Shop",
+                vec![TableInfo::for_test(
+                    "Orders",
+                    vec![column("Id", "int"), total],
+                )],
+            )
+        }
+    }
+
+    fn create_server() -> (Server, PathBuf) {
+        let counter = TEST_COUNTER.fetch_add(1, Ordering::SeqCst);
+        let dir =
+            std::env::temp_dir().join(format!("larik_lsp_test_{}_{}", std::process::id(), counter));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = DatabaseManager::new(dir.join("larik.db")).unwrap();
+        let space = db
+            .create_space(CreateSpaceInput {
+                name: "Sales".to_string(),
+                color: None,
+                icon: None,
+                connection_host: Some("localhost".to_string()),
+                connection_port: None,
+                connection_database: Some("Shop".to_string()),
+                connection_username: None,
+                connection_password: None,
+                connection_trust_cert: None,
+                connection_encrypt: None,
+            })
+            .unwrap();
+        db.save_schema(&space.id, "Shop", &schema()).unwrap();
+        (Server::new(db, dir.join("definitions")), dir)
+    }
+
+    fn request(server: &mut Server, method: &str, params: Value) -> Value {
+        let replies = server
+            .handle(&json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }));
+        replies.into_iter().next().unwrap()
+    }
+
+    fn open(server: &mut Server, text: &str) -> Value {
+        let params = json!({ "textDocument": { "uri": "file:///q.sql", "text": text } });
+        let replies = server.handle(
+            &json!({ "jsonrpc": "2.0", "method": "textDocument/didOpen", "params": params }),
+        );
+        replies.into_iter().next().unwrap()
+    }
+
+    fn at(line: u32, character: u32) -> Value {
+        json!({ "textDocument": { "uri": "file:///q.sql" }, "position": { "line": line, "character": character } })
+    }
+
+    #[test]
+    fn serves_completion_hover_and_definition_from_the_schema_cache() {
+        let (mut server, dir) = create_server();
+        let init = request(&mut server, "initialize", json!({ "capabilities": {} }));
+        assert_eq!(init["result"]["capabilities"]["hoverProvider"], true);
+
+        open(
+            &mut server,
+            "SELECT o. FROM dbo.Orders o;\nEXEC dbo.CloseOrder",
+        );
+        let completion = request(&mut server, "textDocument/completion", at(0, 9));
+        let labels: Vec<&str> = completion["result"]["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|i| i["label"].as_str().unwrap())
+            .collect();
+        assert_eq!(labels, ["Id", "Total"]);
+
+        open(
+            &mut server,
+            "SELECT o.Total FROM dbo.Orders o;\nEXEC dbo.CloseOrder",
+        );
+        let hover = request(&mut server, "textDocument/hover", at(0, 11));
+        let value = hover["result"]["contents"]["value"].as_str().unwrap();
+        assert!(value.contains("dbo.Orders.Total money NOT NULL"));
+        assert!(value.ends_with("Order total incl. tax"));
+        assert_eq!(hover["result"]["range"]["start"]["character"], 9);
+
+        let definition = request(&mut server, "textDocument/definition", at(1, 12));
+        let uri = definition["result"]["uri"].as_str().unwrap();
+        assert!(uri.starts_with("file://") && uri.ends_with("/Sales/Shop/dbo.CloseOrder.sql"));
+        let written = dir.join("definitions/Sales/Shop/dbo.CloseOrder.sql");
+        assert!(std::fs::read_to_string(written)
+            .unwrap()
+            .starts_with("CREATE PROCEDURE"));
+
+        let unknown = request(&mut server, "textDocument/rename", at(0, 0));
+        assert_eq!(unknown["error"]["code"], METHOD_NOT_FOUND);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn publishes_syntax_and_lint_diagnostics() {
+        let (mut server, dir) = create_server();
+        let published = open(&mut server, "SELECT * FROM dbo.Orders WHERE (Id = 1");
+        let codes: Vec<&str> = published["params"]["diagnostics"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|d| d["code"].as_str().unwrap())
+            .collect();
+        assert_eq!(codes, ["SELECT_STAR", "UNBALANCED_PARENS"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn directives_choose_the_space_and_database() {
+        assert_eq!(
+            directive("-- larik: space=\"Sales EU\" database=Orders\nSELECT 1"),
+            Target {
+                space: Some("Sales EU".to_string()),
+                database: Some("Orders".to_string()),
+            }
+        );
+        assert_eq!(directive("-- nothing here\nSELECT 1"), Target::default());
+        assert_eq!(
+            file_uri(Path::new("C:\\tmp\\a b.sql")),
+            "file:///C:/tmp/a%20b.sql"
+        );
+    }
+
+    #[test]
+    fn file_names_stay_inside_their_directory() {
+        assert_eq!(file_name(".."), "__");
+        assert_eq!(file_name("."), "_");
+        assert_eq!(file_name(""), "_");
+        assert_eq!(file_name("../etc"), "___etc");
+        assert_eq!(file_name("dbo.Orders"), "dbo.Orders");
+    }
+}
@@ -0,0 +1,216 @@
+// Cron Expressions
+// Standard five-field cron (minute hour day-of-month month day-of-week) with
+// lists, ranges, steps, month/day names and the @hourly/@daily/... shorthands
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike};
+
+const MONTHS: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// Years searched ahead before giving up (e.g. "0 0 30 2 *" never fires)
+const SEARCH_YEARS: i32 = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronExpr {
+    /// Bit n set when value n matches
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// When both day fields are restricted a day matches if either does
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+fn parse_value(text: &str, min: u32, max: u32, names: &[&str]) -> Result<u32, String> {
+    let lower = text.to_ascii_lowercase();
+    let value = match names.iter().position(|n| *n == lower) {
+        Some(index) => index as u32 + min,
+        None => text
+            .parse::<u32>()
+            .map_err(|_| format!("Invalid value '{}'", text))?,
+    };
+    if value < min || value > max {
+        return Err(format!("{} is outside {}-{}", value, min, max));
+    }
+    Ok(value)
+}
+
+/// Bitset of the values a field matches
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| format!("Invalid step '{}'", step))?;
+                (range, Some(step))
+            }
+            None => (part, None),
+        };
+        let (low, high) = if range == "*" {
+            (min, max)
+        } else if let Some((low, high)) = range.split_once('-') {
+            (
+                parse_value(low, min, max, names)?,
+                parse_value(high, min, max, names)?,
+            )
+        } else {
+            let value = parse_value(range, min, max, names)?;
+            // "5/15" means from 5 to the end in steps of 15
+            (value, if step.is_some() { max } else { value })
+        };
+        if low > high {
+            return Err(format!("Range {} is backwards", range));
+        }
+        for value in (low..=high).step_by(step.unwrap_or(1) as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+impl CronExpr {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let expression = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!(
+                "Expected 5 fields (minute hour day month weekday), got {}",
+                fields.len()
+            ));
+        };
+        let field = |name: &str, text: &str, min, max, names| {
+            parse_field(text, min, max, names).map_err(|e| format!("{} field: {}", name, e))
+        };
+
+        let mut weekdays = field("Weekday", weekday, 0, 7, WEEKDAYS)?;
+        // 7 is another way of writing Sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays & !(1 << 7)) | 1;
+        }
+        Ok(Self {
+            minutes: field("Minute", minute, 0, 59, &[])?,
+            hours: field("Hour", hour, 0, 23, &[])?,
+            days: field("Day", day, 1, 31, &[])?,
+            months: field("Month", month, 1, 12, MONTHS)?,
+            weekdays,
+            days_restricted: !day.starts_with('*'),
+            weekdays_restricted: !weekday.starts_with('*'),
+        })
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    /// The first matching minute strictly after `after`
+    pub fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut t = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let last_year = after.year() + SEARCH_YEARS;
+        while t.year() <= last_year {
+            if self.months & (1 << t.month()) == 0 {
+                let (year, month) = if t.month() == 12 {
+                    (t.year() + 1, 1)
+                } else {
+                    (t.year(), t.month() + 1)
+                };
+                t = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+            } else if !self.day_matches(t.date()) {
+                t = t.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if self.hours & (1 << t.hour()) == 0 {
+                t = t.with_minute(0)? + Duration::hours(1);
+            } else if self.minutes & (1 << t.minute()) == 0 {
+                t += Duration::minutes(1);
+            } else {
+                return Some(t);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn next(expression: &str, after: &str) -> Option<String> {
+        CronExpr::parse(expression)
+            .unwrap()
+            .next_after(at(after))
+            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+    }
+
+    #[test]
+    fn finds_the_next_matching_minute() {
+        assert_eq!(
+            next("*/15 * * * *", "2026-03-10 10:07").as_deref(),
+            Some("2026-03-10 10:15")
+        );
+        assert_eq!(
+            next("0 2 * * *", "2026-03-10 02:00").as_deref(),
+            Some("2026-03-11 02:00")
+        );
+        assert_eq!(
+            next("30 9 * * mon-fri", "2026-03-13 10:00").as_deref(),
+            Some("2026-03-16 09:30")
+        );
+        assert_eq!(
+            next("0 0 1 jan,jul *", "2026-03-10 00:00").as_deref(),
+            Some("2026-07-01 00:00")
+        );
+        assert_eq!(
+            next("@monthly", "2026-12-15 08:00").as_deref(),
+            Some("2027-01-01 00:00")
+        );
+        // Sunday as 7, and day-of-month OR weekday when both are given
+        assert_eq!(
+            next("0 12 * * 7", "2026-03-10 00:00").as_deref(),
+            Some("2026-03-15 12:00")
+        );
+        assert_eq!(
+            next("0 0 20 * 0", "2026-03-10 00:00").as_deref(),
+            Some("2026-03-15 00:00")
+        );
+        assert_eq!(
+            next("0 0 29 2 *", "2026-03-01 00:00").as_deref(),
+            Some("2028-02-29 00:00")
+        );
+        assert_eq!(next("0 0 30 2 *", "2026-03-01 00:00"), None);
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        assert!(CronExpr::parse("* * * *").is_err());
+        assert!(CronExpr::parse("60 * * * *").is_err());
+        assert!(CronExpr::parse("*/0 * * * *").is_err());
+        assert!(CronExpr::parse("0 5-2 * * *").is_err());
+        assert!(CronExpr::parse("0 0 * foo *").is_err());
+        assert_eq!(
+            CronExpr::parse("0 24 * * *").unwrap_err(),
+            "Hour field: 24 is outside 0-23"
+        );
+    }
+}
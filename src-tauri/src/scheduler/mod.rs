@@ -0,0 +1,630 @@
+// Scheduled Queries
+// Background runner for schedules: executes the bound tab or snippet through
+// the QueryEngine, exports the result to a templated path, records the run
+// and raises an in-app alert when the schedule's condition is met
+
+pub mod cron;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use chrono::{DateTime, Local, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::time::{interval, Duration};
+
+use crate::commands::{self, AppState};
+use crate::db::{CellValue, ColumnInfo};
+use crate::export::{CsvExporter, ExportOptions, JsonExporter};
+use crate::storage::{
+    AlertCondition, DatabaseManager, Schedule, ScheduleInput, ScheduleRun, ScheduleSource, Snippet,
+};
+use cron::CronExpr;
+
+/// How often due schedules are checked
+const TICK_SECONDS: u64 = 30;
+
+/// Event emitted when a run meets its schedule's alert condition
+pub const ALERT_EVENT: &str = "schedule-alert";
+
+/// Payload of `ALERT_EVENT`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleAlert {
+    pub schedule_name: String,
+    pub run: ScheduleRun,
+}
+
+/// Timestamps as stored in the app database (UTC)
+pub fn db_time(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// "csv" or "json" from the explicit format or the output path's extension
+fn output_format(format: Option<&str>, path: &str) -> Option<String> {
+    let format = match format {
+        Some(format) => format.to_ascii_lowercase(),
+        None => PathBuf::from(path)
+            .extension()?
+            .to_string_lossy()
+            .to_ascii_lowercase(),
+    };
+    matches!(format.as_str(), "csv" | "json").then_some(format)
+}
+
+/// Check a schedule before saving it
+pub fn validate(input: &ScheduleInput) -> Result<(), String> {
+    if input.name.trim().is_empty() {
+        return Err("Schedule name is required".to_string());
+    }
+    match (&input.cron, input.interval_minutes) {
+        (Some(expression), None) => {
+            CronExpr::parse(expression)?;
+        }
+        (None, Some(minutes)) if minutes >= 1 => {}
+        (None, Some(_)) => return Err("Interval must be at least one minute".to_string()),
+        _ => return Err("Set either a cron expression or an interval".to_string()),
+    }
+    if let Some(path) = &input.output_path {
+        if !Path::new(path).is_absolute() {
+            return Err("Output path must be absolute".to_string());
+        }
+        if output_format(input.output_format.as_deref(), path).is_none() {
+            return Err("Output format must be csv or json".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// A snippet's SQL with the schedule's placeholder values filled in
+fn snippet_sql(snippet: &Snippet, values: &HashMap<String, String>) -> Result<String, String> {
+    let values: Vec<(String, String)> = values
+        .iter()
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    snippet
+        .expand(&values)
+        .map_err(|missing| format!("Snippet placeholders need values: {}", missing.join(", ")))
+}
+
+/// Check that a schedule's tab or snippet exists and a snippet expands with
+/// the schedule's values, so a broken source fails at save time
+pub fn check_source(db: &DatabaseManager, input: &ScheduleInput) -> Result<(), String> {
+    match &input.source {
+        ScheduleSource::Tab(id) => {
+            db.get_tab(id)
+                .map_err(|e| e.to_string())?
+                .ok_or("Tab not found")?;
+        }
+        ScheduleSource::Snippet(id) => {
+            let snippet = db
+                .get_snippet(id)
+                .map_err(|e| e.to_string())?
+                .ok_or("Snippet not found")?;
+            snippet_sql(&snippet, &input.snippet_values)?;
+        }
+    }
+    Ok(())
+}
+
+/// When a schedule next runs after `after`, or None when it never will.
+/// Cron expressions are read in local time.
+pub fn next_run(
+    cron: Option<&str>,
+    interval_minutes: Option<i64>,
+    after: DateTime<Utc>,
+) -> Result<Option<DateTime<Utc>>, String> {
+    if let Some(minutes) = interval_minutes {
+        return Ok(Some(after + chrono::Duration::minutes(minutes)));
+    }
+    let Some(expression) = cron else {
+        return Ok(None);
+    };
+    let expression = CronExpr::parse(expression)?;
+    let mut local = after.with_timezone(&Local).naive_local();
+    // Skip times that don't exist locally (daylight saving gaps)
+    while let Some(next) = expression.next_after(local) {
+        if let Some(time) = Local.from_local_datetime(&next).earliest() {
+            return Ok(Some(time.with_timezone(&Utc)));
+        }
+        local = next;
+    }
+    Ok(None)
+}
+
+/// `next_run` of a saved or new schedule as stored, None when disabled
+pub fn next_run_text(
+    input: &ScheduleInput,
+    after: DateTime<Utc>,
+) -> Result<Option<String>, String> {
+    if !input.enabled {
+        return Ok(None);
+    }
+    Ok(next_run(input.cron.as_deref(), input.interval_minutes, after)?.map(db_time))
+}
+
+/// Keep substituted values from adding directories or invalid characters
+fn path_safe(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// Fill {name}, {space}, {database}, {date}, {time} and {timestamp} in an
+/// output path template
+pub fn render_output_path(
+    template: &str,
+    schedule_name: &str,
+    space_name: &str,
+    database: &str,
+    at: DateTime<Local>,
+) -> PathBuf {
+    let path = template
+        .replace("{name}", &path_safe(schedule_name))
+        .replace("{space}", &path_safe(space_name))
+        .replace("{database}", &path_safe(database))
+        .replace("{date}", &at.format("%Y-%m-%d").to_string())
+        .replace("{time}", &at.format("%H%M%S").to_string())
+        .replace("{timestamp}", &at.format("%Y%m%d_%H%M%S").to_string());
+    PathBuf::from(path)
+}
+
+fn numeric(value: &CellValue) -> Option<f64> {
+    match value {
+        CellValue::Int(i) => Some(*i as f64),
+        CellValue::Float(f) => Some(*f),
+        CellValue::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+        CellValue::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// The alert message when `condition` holds for a result set
+pub fn evaluate_alert(
+    condition: &AlertCondition,
+    columns: &[ColumnInfo],
+    rows: &[Vec<CellValue>],
+) -> Result<Option<String>, String> {
+    match condition {
+        AlertCondition::RowCount { op, value } => {
+            let count = rows.len() as f64;
+            Ok(op
+                .holds(count, *value)
+                .then(|| format!("{} rows ({} {})", rows.len(), op.symbol(), value)))
+        }
+        AlertCondition::CellValue { column, op, value } => {
+            let index = columns
+                .iter()
+                .position(|c| c.name.eq_ignore_ascii_case(column))
+                .ok_or_else(|| format!("Alert column {} is not in the result", column))?;
+            let hit = rows.iter().enumerate().find_map(|(n, row)| {
+                let cell = row.get(index).and_then(numeric)?;
+                op.holds(cell, *value).then_some((n, cell))
+            });
+            Ok(hit.map(|(n, cell)| {
+                format!(
+                    "{} = {} in row {} ({} {})",
+                    columns[index].name,
+                    cell,
+                    n + 1,
+                    op.symbol(),
+                    value
+                )
+            }))
+        }
+    }
+}
+
+/// What a successful run produced
+struct Outcome {
+    row_count: i64,
+    output_path: Option<String>,
+    alert: Option<String>,
+}
+
+async fn execute(app: &AppHandle, schedule: &Schedule) -> Result<Outcome, String> {
+    let state = app.state::<AppState>();
+    let (space, sql, tab_database) = {
+        let db = state.db.lock().map_err(|e| e.to_string())?;
+        let space = db
+            .get_space(&schedule.space_id)
+            .map_err(|e| e.to_string())?
+            .ok_or("Space not found")?;
+        let (sql, tab_database) = match &schedule.source {
+            ScheduleSource::Tab(id) => {
+                let tab = db
+                    .get_tab(id)
+                    .map_err(|e| e.to_string())?
+                    .ok_or("Tab not found")?;
+                (tab.content.unwrap_or_default(), tab.database)
+            }
+            ScheduleSource::Snippet(id) => {
+                let snippet = db
+                    .get_snippet(id)
+                    .map_err(|e| e.to_string())?
+                    .ok_or("Snippet not found")?;
+                (snippet_sql(&snippet, &schedule.snippet_values)?, None)
+            }
+        };
+        (space, sql, tab_database)
+    };
+    if sql.trim().is_empty() {
+        return Err("Nothing to run".to_string());
+    }
+    let database = schedule
+        .database
+        .clone()
+        .or(tab_database)
+        .or_else(|| space.connection_database.clone())
+        .unwrap_or_default();
+
+    if state
+        .mssql_manager
+        .get_connection(&space.id)
+        .await
+        .is_none()
+    {
+        commands::connect_to_space(app.state(), space.id.clone()).await?;
+    }
+    let results = state
+        .query_engine
+        .execute_query(&space.id, &sql, Some(&database), false, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some(error) = results.iter().find_map(|r| r.error.clone()) {
+        return Err(error);
+    }
+
+    // The last result set is the one exported and checked
+    let result = results.iter().rev().find(|r| !r.columns.is_empty());
+    let (columns, rows) = match result {
+        Some(result) => (result.columns.as_slice(), result.rows.as_slice()),
+        None => (&[][..], &[][..]),
+    };
+    let row_count = match result {
+        Some(result) => result.rows.len() as i64,
+        None => results.iter().map(|r| r.row_count as i64).sum(),
+    };
+
+    let output_path = match &schedule.output_path {
+        Some(template) => {
+            if result.is_none() {
+                return Err("The query returned no result set to export".to_string());
+            }
+            let path = render_output_path(
+                template,
+                &schedule.name,
+                &space.name,
+                &database,
+                Local::now(),
+            );
+            if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+                std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+            }
+            let format = output_format(schedule.output_format.as_deref(), template);
+            let written = if format.as_deref() == Some("json") {
+                JsonExporter::new(ExportOptions {
+                    pretty_print: true,
+                    ..Default::default()
+                })
+                .export_to_file(&path, columns, rows, None)
+            } else {
+                CsvExporter::with_default_options().export_to_file(&path, columns, rows, None)
+            };
+            written.map_err(|e| format!("Export to {} failed: {}", path.display(), e))?;
+            Some(path.display().to_string())
+        }
+        None => None,
+    };
+
+    let alert = match &schedule.alert {
+        Some(condition) => evaluate_alert(condition, columns, rows)?,
+        None => None,
+    };
+
+    Ok(Outcome {
+        row_count,
+        output_path,
+        alert,
+    })
+}
+
+/// Run a schedule once, record the run and emit an alert if one was raised
+pub async fn run_schedule(app: &AppHandle, schedule: &Schedule) -> ScheduleRun {
+    let started = Instant::now();
+    let mut run = ScheduleRun {
+        id: uuid::Uuid::new_v4().to_string(),
+        schedule_id: schedule.id.clone(),
+        started_at: db_time(Utc::now()),
+        duration_ms: 0,
+        row_count: 0,
+        output_path: None,
+        error: None,
+        alert: None,
+    };
+    match execute(app, schedule).await {
+        Ok(outcome) => {
+            run.row_count = outcome.row_count;
+            run.output_path = outcome.output_path;
+            run.alert = outcome.alert;
+        }
+        Err(e) => run.error = Some(e),
+    }
+    run.duration_ms = started.elapsed().as_millis() as i64;
+
+    if let Ok(db) = app.state::<AppState>().db.lock() {
+        if let Err(e) = db.add_schedule_run(&run) {
+            eprintln!(
+                "[Scheduler] Failed to record run of {}: {}",
+                schedule.name, e
+            );
+        }
+    }
+    if let Some(error) = &run.error {
+        eprintln!("[Scheduler] {} failed: {}", schedule.name, error);
+    }
+    if run.alert.is_some() {
+        let alert = ScheduleAlert {
+            schedule_name: schedule.name.clone(),
+            run: run.clone(),
+        };
+        let _ = app.emit(ALERT_EVENT, &alert);
+    }
+    run
+}
+
+/// A schedule marked as running; the mark is cleared on drop, so a run that
+/// fails or panics does not leave the schedule skipped for good
+pub struct RunClaim {
+    app: AppHandle,
+    id: String,
+}
+
+impl Drop for RunClaim {
+    fn drop(&mut self) {
+        let state = self.app.state::<AppState>();
+        let mut running = state
+            .running_schedules
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        running.remove(&self.id);
+    }
+}
+
+/// Claim a schedule for a run; None when one is already in progress
+pub fn start_run(app: &AppHandle, id: &str) -> Result<Option<RunClaim>, String> {
+    let state = app.state::<AppState>();
+    let mut running = state.running_schedules.lock().map_err(|e| e.to_string())?;
+    Ok(running.insert(id.to_string()).then(|| RunClaim {
+        app: app.clone(),
+        id: id.to_string(),
+    }))
+}
+
+/// Check for due schedules every TICK_SECONDS and run each in its own task.
+/// A schedule still running, from an earlier slot or started by hand, is skipped.
+pub fn spawn_scheduler_task(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = interval(Duration::from_secs(TICK_SECONDS));
+
+        loop {
+            interval.tick().await;
+
+            let now = Utc::now();
+            let due = {
+                let state = app.state::<AppState>();
+                let Ok(db) = state.db.lock() else { continue };
+                match db.get_due_schedules(&db_time(now)) {
+                    Ok(due) => due,
+                    Err(e) => {
+                        eprintln!("[Scheduler] Failed to load due schedules: {}", e);
+                        continue;
+                    }
+                }
+            };
+
+            for schedule in due {
+                // Advance before running so a slow run isn't picked up again
+                let next = next_run(schedule.cron.as_deref(), schedule.interval_minutes, now)
+                    .unwrap_or_else(|e| {
+                        eprintln!("[Scheduler] {}: {}", schedule.name, e);
+                        None
+                    });
+                if let Ok(db) = app.state::<AppState>().db.lock() {
+                    let next = next.map(db_time);
+                    if let Err(e) =
+                        db.set_schedule_run_times(&schedule.id, &db_time(now), next.as_deref())
+                    {
+                        eprintln!("[Scheduler] Failed to update {}: {}", schedule.name, e);
+                        continue;
+                    }
+                }
+
+                let claim = match start_run(&app, &schedule.id) {
+                    Ok(Some(claim)) => claim,
+                    Ok(None) => {
+                        println!(
+                            "[Scheduler] {} is still running, skipping this slot",
+                            schedule.name
+                        );
+                        continue;
+                    }
+                    Err(e) => {
+                        eprintln!("[Scheduler] Failed to claim {}: {}", schedule.name, e);
+                        continue;
+                    }
+                };
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move {
+                    let _claim = claim;
+                    println!("[Scheduler] Running {}", schedule.name);
+                    run_schedule(&app, &schedule).await;
+                });
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Comparison;
+
+    fn column(name: &str) -> ColumnInfo {
+        ColumnInfo {
+            name: name.to_string(),
+            data_type: "int".to_string(),
+            nullable: true,
+        }
+    }
+
+    fn input(
+        cron: Option<&str>,
+        interval_minutes: Option<i64>,
+        output_path: Option<&str>,
+    ) -> ScheduleInput {
+        ScheduleInput {
+            name: "Totals".to_string(),
+            space_id: "s1".to_string(),
+            database: None,
+            source: ScheduleSource::Tab("t1".to_string()),
+            snippet_values: HashMap::new(),
+            cron: cron.map(str::to_string),
+            interval_minutes,
+            output_path: output_path.map(str::to_string),
+            output_format: None,
+            alert: None,
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn validates_timing_and_output() {
+        let dir = std::env::temp_dir();
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        assert!(validate(&input(Some("0 2 * * *"), None, Some(&path("{date}.csv")))).is_ok());
+        assert!(validate(&input(None, Some(15), Some(&path("out.JSON")))).is_ok());
+        assert!(validate(&input(None, None, None)).is_err());
+        assert!(validate(&input(Some("0 2 * * *"), Some(5), None)).is_err());
+        assert!(validate(&input(None, Some(0), None)).is_err());
+        assert!(validate(&input(Some("bad"), None, None)).is_err());
+        assert_eq!(
+            validate(&input(None, Some(5), Some(&path("out.xlsx")))),
+            Err("Output format must be csv or json".to_string())
+        );
+        assert_eq!(
+            validate(&input(None, Some(5), Some("out/{date}.csv"))),
+            Err("Output path must be absolute".to_string())
+        );
+    }
+
+    #[test]
+    fn expands_snippets_with_schedule_values() {
+        let snippet = Snippet {
+            id: "s1".to_string(),
+            trigger: "sel".to_string(),
+            name: "Select".to_string(),
+            content: "SELECT TOP ${1:100} * FROM ${cursor}".to_string(),
+            description: None,
+            is_builtin: true,
+            enabled: true,
+            category: None,
+            created_at: String::new(),
+            updated_at: String::new(),
+        };
+        assert_eq!(
+            snippet_sql(&snippet, &HashMap::new()),
+            Err("Snippet placeholders need values: cursor".to_string())
+        );
+        let values = HashMap::from([("cursor".to_string(), "dbo.Orders".to_string())]);
+        assert_eq!(
+            snippet_sql(&snippet, &values),
+            Ok("SELECT TOP 100 * FROM dbo.Orders".to_string())
+        );
+    }
+
+    #[test]
+    fn computes_next_runs() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 10, 10, 7, 30).unwrap();
+        assert_eq!(
+            next_run(None, Some(15), now).unwrap(),
+            Some(Utc.with_ymd_and_hms(2026, 3, 10, 10, 22, 30).unwrap())
+        );
+        let next = next_run(Some("*/5 * * * *"), None, now).unwrap().unwrap();
+        assert!(next > now && next - now <= chrono::Duration::minutes(5));
+        assert_eq!(next.timestamp() % 300, 0);
+
+        let mut disabled = input(None, Some(15), None);
+        disabled.enabled = false;
+        assert_eq!(next_run_text(&disabled, now), Ok(None));
+        assert_eq!(
+            next_run_text(&input(None, Some(15), None), now),
+            Ok(Some("2026-03-10 10:22:30".to_string()))
+        );
+    }
+
+    #[test]
+    fn renders_output_paths() {
+        let at = Local.with_ymd_and_hms(2026, 3, 10, 7, 5, 9).unwrap();
+        assert_eq!(
+            render_output_path(
+                "/reports/{space}/{name}_{date}_{time}.csv",
+                "Daily/EU",
+                "Sales",
+                "Orders",
+                at
+            ),
+            PathBuf::from("/reports/Sales/Daily_EU_2026-03-10_070509.csv")
+        );
+        assert_eq!(
+            render_output_path("{database}-{timestamp}.json", "x", "y", "Orders", at),
+            PathBuf::from("Orders-20260310_070509.json")
+        );
+    }
+
+    #[test]
+    fn evaluates_alert_conditions() {
+        let columns = vec![column("Region"), column("Total")];
+        let rows = vec![
+            vec![CellValue::String("EU".to_string()), CellValue::Int(400)],
+            vec![
+                CellValue::String("US".to_string()),
+                CellValue::String("1500.50".to_string()),
+            ],
+        ];
+
+        let row_count = AlertCondition::RowCount {
+            op: Comparison::Gt,
+            value: 1.0,
+        };
+        assert_eq!(
+            evaluate_alert(&row_count, &columns, &rows),
+            Ok(Some("2 rows (> 1)".to_string()))
+        );
+        let empty = AlertCondition::RowCount {
+            op: Comparison::Eq,
+            value: 0.0,
+        };
+        assert_eq!(evaluate_alert(&empty, &columns, &rows), Ok(None));
+
+        let threshold = AlertCondition::CellValue {
+            column: "total".to_string(),
+            op: Comparison::Ge,
+            value: 1000.0,
+        };
+        assert_eq!(
+            evaluate_alert(&threshold, &columns, &rows),
+            Ok(Some("Total = 1500.5 in row 2 (>= 1000)".to_string()))
+        );
+        let missing = AlertCondition::CellValue {
+            column: "Amount".to_string(),
+            op: Comparison::Ge,
+            value: 1.0,
+        };
+        assert!(evaluate_alert(&missing, &columns, &rows).is_err());
+    }
+}
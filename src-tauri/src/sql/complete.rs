@@ -0,0 +1,1821 @@
+// SQL Completion
+// Context-aware completions at a cursor position. Resolves table aliases, CTEs, derived
+// tables, temp tables and table variables declared in the batch and three-part names
+// against cached schema metadata, and suggests JOIN conditions and routine parameters.
+
+use std::collections::{BTreeSet, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::lexer::{
+    is_reserved, quote_identifier, significant_tokens, tokenize, Span, Token, TokenKind,
+};
+use super::lint::{
+    is_name_part, object_name, object_refs, query_scopes, skip_select_modifiers, ObjectRef,
+};
+use super::parser::{parse_statements_in, split_batches, Statement};
+use crate::db::joins::unique_alias;
+use crate::db::navigation::all_references;
+use crate::db::schema::{ColumnInfo, RoutineInfo, SchemaInfo, TableInfo};
+use crate::storage::VirtualReference;
+
+/// Most items returned for one request
+const MAX_ITEMS: usize = 200;
+/// Nesting followed when working out the columns of CTEs and derived tables
+const MAX_NESTING: usize = 4;
+
+/// Keywords offered once the user has typed part of one
+const KEYWORDS: &[&str] = &[
+    "SELECT",
+    "FROM",
+    "WHERE",
+    "JOIN",
+    "INNER JOIN",
+    "LEFT JOIN",
+    "RIGHT JOIN",
+    "FULL JOIN",
+    "CROSS JOIN",
+    "CROSS APPLY",
+    "OUTER APPLY",
+    "ON",
+    "AND",
+    "OR",
+    "NOT",
+    "NULL",
+    "IS NULL",
+    "IS NOT NULL",
+    "IN",
+    "EXISTS",
+    "BETWEEN",
+    "LIKE",
+    "GROUP BY",
+    "ORDER BY",
+    "HAVING",
+    "UNION",
+    "UNION ALL",
+    "DISTINCT",
+    "TOP",
+    "AS",
+    "CASE",
+    "WHEN",
+    "THEN",
+    "ELSE",
+    "END",
+    "INSERT INTO",
+    "VALUES",
+    "UPDATE",
+    "SET",
+    "DELETE",
+    "MERGE",
+    "EXEC",
+    "DECLARE",
+    "BEGIN",
+    "COMMIT",
+    "ROLLBACK",
+    "WITH",
+    "ASC",
+    "DESC",
+    "OFFSET",
+    "FETCH NEXT",
+];
+
+/// Keywords that decide what the cursor position expects
+const CLAUSE_KEYWORDS: &[&str] = &[
+    "SELECT", "FROM", "JOIN", "ON", "WHERE", "SET", "EXEC", "EXECUTE", "INTO", "UPDATE", "BY",
+    "HAVING", "AND", "OR", "DELETE", "MERGE", "USING", "VALUES", "WHEN", "THEN",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompletionKind {
+    Keyword,
+    Database,
+    Schema,
+    Table,
+    View,
+    Column,
+    Procedure,
+    Function,
+    Parameter,
+    Alias,
+    Join,
+    Variable,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompletionItem {
+    pub label: String,
+    pub kind: CompletionKind,
+    /// Type, owning table or signature shown next to the label
+    pub detail: Option<String>,
+    pub insert_text: String,
+    /// Higher ranks first
+    pub score: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Completions {
+    pub items: Vec<CompletionItem>,
+    /// The partially typed word the items replace
+    pub replace: Span,
+}
+
+/// Schema metadata completion draws on
+pub struct CompletionCatalog<'a> {
+    /// Database the tab runs against
+    pub database: &'a str,
+    /// Cached schemas of the current database and any other databases available
+    pub schemas: &'a [SchemaInfo],
+    /// Databases on the server, for three-part names
+    pub databases: &'a [String],
+    /// Virtual references of the current database, used for JOIN suggestions
+    pub virtual_references: &'a [VirtualReference],
+}
+
+impl<'a> CompletionCatalog<'a> {
+    /// Schema of `database`, or of the current database for None or `""`
+    fn schema(&self, database: Option<&str>) -> Option<&'a SchemaInfo> {
+        let database = database.filter(|d| !d.is_empty()).unwrap_or(self.database);
+        self.schemas
+            .iter()
+            .find(|s| s.database_name.eq_ignore_ascii_case(database))
+    }
+
+    /// Split `[db.][schema.]name` parts into (database, schema, name)
+    fn split(parts: &[String]) -> Option<(Option<&str>, Option<&str>, &str)> {
+        let n = parts.len();
+        let name = parts.last()?.as_str();
+        let schema = (n >= 2)
+            .then(|| parts[n - 2].as_str())
+            .filter(|s| !s.is_empty());
+        let database = (n >= 3).then(|| parts[n - 3].as_str());
+        Some((database, schema, name))
+    }
+
+    /// Table or view named by `parts`; unqualified names prefer `dbo`
+    fn table(&self, parts: &[String]) -> Option<(&'a SchemaInfo, &'a TableInfo)> {
+        let (database, schema_name, name) = Self::split(parts)?;
+        let schema = self.schema(database)?;
+        let mut matches = schema
+            .tables
+            .iter()
+            .filter(|t| t.table_name.eq_ignore_ascii_case(name));
+        let table = match schema_name {
+            Some(s) => matches.find(|t| t.schema_name.eq_ignore_ascii_case(s)),
+            None => {
+                let all: Vec<_> = matches.collect();
+                all.iter()
+                    .find(|t| t.schema_name.eq_ignore_ascii_case("dbo"))
+                    .or(all.first())
+                    .copied()
+            }
+        }?;
+        Some((schema, table))
+    }
+
+    fn routine(&self, parts: &[String]) -> Option<&'a RoutineInfo> {
+        let (database, schema_name, name) = Self::split(parts)?;
+        let schema_name = schema_name.unwrap_or("dbo");
+        self.schema(database)?.routines.iter().find(|r| {
+            r.routine_name.eq_ignore_ascii_case(name)
+                && r.schema_name.eq_ignore_ascii_case(schema_name)
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SourceColumn {
+    name: String,
+    data_type: Option<String>,
+}
+
+/// A table, CTE, derived table or table variable in scope
+#[derive(Debug, Clone)]
+struct Source {
+    /// Name as written (last part) and its alias
+    name: String,
+    alias: Option<String>,
+    columns: Vec<SourceColumn>,
+    /// Catalog table as (database, schema, table), for relationship lookups
+    table: Option<(String, String, String)>,
+    /// Byte offset of the reference, to tell which table was joined last
+    position: usize,
+}
+
+impl Source {
+    /// How columns of this source are qualified
+    fn qualifier(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// Temp tables, table variables and variables declared in the batch
+#[derive(Debug, Default)]
+struct Declarations {
+    tables: Vec<(String, Vec<SourceColumn>)>,
+    variables: Vec<(String, Option<String>)>,
+}
+
+impl Declarations {
+    fn table(&self, name: &str) -> Option<&[SourceColumn]> {
+        self.tables
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, columns)| columns.as_slice())
+    }
+}
+
+struct Resolver<'c, 'a> {
+    catalog: &'c CompletionCatalog<'a>,
+    declarations: Declarations,
+    ctes: Vec<(String, Vec<SourceColumn>)>,
+}
+
+/// What the cursor position expects
+#[derive(Debug, PartialEq)]
+enum Context {
+    /// A table or view after FROM, JOIN, UPDATE, INTO, ...
+    Table {
+        after_join: bool,
+    },
+    /// Right after ON
+    JoinCondition,
+    /// A procedure name after EXEC
+    Procedure,
+    /// Arguments of the EXEC'd procedure
+    ProcedureArguments(Vec<String>),
+    Expression,
+}
+
+/// Completions for the cursor at byte `offset` of `sql`
+pub fn complete(sql: &str, offset: usize, catalog: &CompletionCatalog<'_>) -> Completions {
+    let mut offset = offset.min(sql.len());
+    while !sql.is_char_boundary(offset) {
+        offset -= 1;
+    }
+
+    // The word being typed, if any
+    let mut replace = Span::new(offset, offset);
+    let mut prefix = "";
+    let mut bracketed = false;
+    if let Some(tok) = tokenize(sql)
+        .into_iter()
+        .find(|t| t.span.start < offset && offset <= t.span.end)
+    {
+        let inside = offset < tok.span.end;
+        match tok.kind {
+            TokenKind::LineComment => {
+                return Completions {
+                    items: Vec::new(),
+                    replace,
+                }
+            }
+            TokenKind::String if inside || tok.text.len() < 2 || !tok.text.ends_with('\'') => {
+                return Completions {
+                    items: Vec::new(),
+                    replace,
+                }
+            }
+            TokenKind::BlockComment if inside || !tok.text.ends_with("*/") => {
+                return Completions {
+                    items: Vec::new(),
+                    replace,
+                }
+            }
+            TokenKind::Word | TokenKind::Variable => {
+                replace = tok.span;
+                prefix = &sql[tok.span.start..offset];
+            }
+            TokenKind::QuotedIdentifier | TokenKind::Unknown if tok.text.starts_with('[') => {
+                replace = tok.span;
+                prefix = sql[tok.span.start + 1..offset].trim_end_matches(']');
+                bracketed = true;
+            }
+            _ => {}
+        }
+    }
+    let word_start = replace.start.min(offset);
+
+    let batch = split_batches(sql)
+        .into_iter()
+        .map(|b| b.span)
+        .find(|span| span.start <= offset && offset <= span.end)
+        .unwrap_or(Span::new(offset, offset));
+    let batch_tokens = offset_tokens(sql, batch);
+    let declarations = declarations(
+        &batch_tokens[..batch_tokens
+            .iter()
+            .position(|t| t.span.start >= word_start)
+            .unwrap_or(batch_tokens.len())],
+        catalog,
+    );
+
+    let statement = statement_at(sql, batch, offset);
+    let tokens = offset_tokens(sql, statement);
+    let mut resolver = Resolver {
+        catalog,
+        declarations,
+        ctes: Vec::new(),
+    };
+    resolver.ctes = resolver.ctes(&tokens, 0);
+
+    // Qualifier parts typed before the word: `o.`, `db.dbo.`, `db..`
+    let mut start = tokens
+        .iter()
+        .take_while(|t| t.span.end <= word_start)
+        .count();
+    let mut qualifier = Vec::new();
+    while start >= 2 && tokens[start - 1].kind == TokenKind::Dot {
+        if is_name_part(&tokens[start - 2]) {
+            qualifier.insert(0, tokens[start - 2].identifier());
+            start -= 2;
+        } else if tokens[start - 2].kind == TokenKind::Dot {
+            qualifier.insert(0, String::new());
+            start -= 1;
+        } else {
+            break;
+        }
+    }
+
+    // Innermost subquery around the cursor
+    let (lo, hi) = subquery_ranges(&tokens)
+        .into_iter()
+        .filter(|&(open, close)| open < start && start <= close)
+        .max_by_key(|&(open, _)| open)
+        .map(|(open, close)| (open + 1, close))
+        .unwrap_or((0, tokens.len()));
+    let scope = &tokens[lo..hi];
+    let before = &tokens[lo..start];
+    let sources = resolver.sources(scope, 0);
+
+    let context = context(before);
+    let mut builder = Builder {
+        prefix,
+        bracketed,
+        items: Vec::new(),
+    };
+    resolver.items(&mut builder, &context, &qualifier, &sources, before);
+
+    let mut items = builder.items;
+    let mut seen = HashSet::new();
+    items.retain(|i| seen.insert((i.kind, i.insert_text.to_lowercase())));
+    items.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then_with(|| a.label.to_lowercase().cmp(&b.label.to_lowercase()))
+    });
+    items.truncate(MAX_ITEMS);
+    Completions { items, replace }
+}
+
+/// A catalog object or column named in the text
+#[derive(Debug, Clone, Copy)]
+pub enum Symbol<'a> {
+    Table {
+        database: &'a str,
+        table: &'a TableInfo,
+    },
+    Column {
+        database: &'a str,
+        table: &'a TableInfo,
+        column: &'a ColumnInfo,
+    },
+    Routine {
+        database: &'a str,
+        routine: &'a RoutineInfo,
+    },
+}
+
+/// The catalog symbol under the cursor at byte `offset`, with the span of the
+/// name part it was found at. Aliases resolve to their table and columns to
+/// the table they come from; CTE and temp table names resolve to nothing.
+pub fn resolve_symbol<'a>(
+    sql: &str,
+    offset: usize,
+    catalog: &CompletionCatalog<'a>,
+) -> Option<(Span, Symbol<'a>)> {
+    let batch = split_batches(sql)
+        .into_iter()
+        .map(|b| b.span)
+        .find(|span| span.start <= offset && offset <= span.end)?;
+    let batch_tokens = offset_tokens(sql, batch);
+    let statement = statement_at(sql, batch, offset);
+    let tokens = offset_tokens(sql, statement);
+    let index = tokens
+        .iter()
+        .position(|t| t.span.start <= offset && offset <= t.span.end && is_name_part(t))?;
+    let span = tokens[index].span;
+
+    let declared = batch_tokens
+        .iter()
+        .position(|t| t.span.start >= span.start)
+        .unwrap_or(batch_tokens.len());
+    let mut resolver = Resolver {
+        catalog,
+        declarations: declarations(&batch_tokens[..declared], catalog),
+        ctes: Vec::new(),
+    };
+    resolver.ctes = resolver.ctes(&tokens, 0);
+
+    // The name up to and including the part under the cursor
+    let mut start = index;
+    while start >= 2
+        && tokens[start - 1].kind == TokenKind::Dot
+        && (is_name_part(&tokens[start - 2]) || tokens[start - 2].kind == TokenKind::Dot)
+    {
+        start -= if is_name_part(&tokens[start - 2]) {
+            2
+        } else {
+            1
+        };
+    }
+    let mut parts = Vec::new();
+    for (i, tok) in tokens[start..=index].iter().enumerate() {
+        if is_name_part(tok) {
+            parts.push(tok.identifier());
+        } else if i > 0 && tokens[start + i - 1].kind == TokenKind::Dot {
+            parts.push(String::new());
+        }
+    }
+
+    let called = tokens
+        .get(index + 1)
+        .is_some_and(|t| t.kind == TokenKind::LeftParen)
+        || (start > 0
+            && ["EXEC", "EXECUTE"]
+                .iter()
+                .any(|k| tokens[start - 1].is_keyword(k)));
+    if called {
+        if let Some(routine) = catalog.routine(&parts) {
+            return Some((span, routine_symbol(catalog, &parts, routine)));
+        }
+    }
+
+    let (lo, hi) = subquery_ranges(&tokens)
+        .into_iter()
+        .filter(|&(open, close)| open < index && index <= close)
+        .max_by_key(|&(open, _)| open)
+        .map(|(open, close)| (open + 1, close))
+        .unwrap_or((0, tokens.len()));
+    let sources = resolver.sources(&tokens[lo..hi], 0);
+    let catalog_table = |source: &Source| {
+        let (database, schema_name, table_name) = source.table.as_ref()?;
+        let parts = [database.clone(), schema_name.clone(), table_name.clone()];
+        catalog.table(&parts)
+    };
+    let column_symbol = |source: &Source, name: &str| {
+        let (schema, table) = catalog_table(source)?;
+        let column = table
+            .columns
+            .iter()
+            .find(|c| c.name.eq_ignore_ascii_case(name))?;
+        Some(Symbol::Column {
+            database: &schema.database_name,
+            table,
+            column,
+        })
+    };
+
+    let (name, qualifier) = parts.split_last()?;
+    if let Some(q) = qualifier.last() {
+        // `alias.column`
+        let symbol = sources
+            .iter()
+            .filter(|s| s.qualifier().eq_ignore_ascii_case(q))
+            .find_map(|s| column_symbol(s, name));
+        if symbol.is_some() {
+            return symbol.map(|symbol| (span, symbol));
+        }
+    } else {
+        // An alias, or a column of one of the tables in scope
+        if let Some(source) = sources.iter().find(|s| {
+            s.alias
+                .as_deref()
+                .is_some_and(|a| a.eq_ignore_ascii_case(name))
+        }) {
+            let (schema, table) = catalog_table(source)?;
+            let database = &schema.database_name;
+            return Some((span, Symbol::Table { database, table }));
+        }
+        if let Some(symbol) = sources.iter().find_map(|s| column_symbol(s, name)) {
+            return Some((span, symbol));
+        }
+        if resolver
+            .ctes
+            .iter()
+            .any(|(cte, _)| cte.eq_ignore_ascii_case(name))
+            || resolver.declarations.table(name).is_some()
+        {
+            return None;
+        }
+    }
+
+    if let Some((schema, table)) = catalog.table(&parts) {
+        let database = &schema.database_name;
+        return Some((span, Symbol::Table { database, table }));
+    }
+    let routine = catalog.routine(&parts)?;
+    Some((span, routine_symbol(catalog, &parts, routine)))
+}
+
+fn routine_symbol<'a>(
+    catalog: &CompletionCatalog<'a>,
+    parts: &[String],
+    routine: &'a RoutineInfo,
+) -> Symbol<'a> {
+    let database = (parts.len() >= 3)
+        .then(|| catalog.schema(Some(&parts[parts.len() - 3])))
+        .flatten()
+        .or_else(|| catalog.schema(None))
+        .map_or(catalog.database, |s| s.database_name.as_str());
+    Symbol::Routine { database, routine }
+}
+
+/// Significant tokens of `span` with spans relative to the whole text
+fn offset_tokens(sql: &str, span: Span) -> Vec<Token<'_>> {
+    significant_tokens(span.text(sql))
+        .into_iter()
+        .map(|t| Token {
+            span: Span::new(t.span.start + span.start, t.span.end + span.start),
+            ..t
+        })
+        .collect()
+}
+
+/// The innermost statement the cursor is in. Past the end of a terminated
+/// statement the cursor starts a new, empty one.
+fn statement_at(sql: &str, span: Span, offset: usize) -> Span {
+    fn find(sql: &str, statements: &[Statement], offset: usize) -> Span {
+        let Some(stmt) = statements.iter().rev().find(|s| s.span.start <= offset) else {
+            return Span::new(offset, offset);
+        };
+        if stmt.terminated && offset >= stmt.span.end {
+            return Span::new(offset, offset);
+        }
+        if !stmt.children.is_empty() && offset <= stmt.span.end {
+            return find(sql, &stmt.children, offset);
+        }
+        if let Some(as_span) = stmt.module_body_as(sql).filter(|s| offset >= s.end) {
+            let body = parse_statements_in(sql, Span::new(as_span.end, stmt.span.end));
+            return find(sql, &body, offset);
+        }
+        Span::new(stmt.span.start, stmt.span.end.max(offset))
+    }
+    find(sql, &parse_statements_in(sql, span), offset)
+}
+
+fn matching_paren(tokens: &[Token<'_>], open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, tok) in tokens.iter().enumerate().skip(open) {
+        match tok.kind {
+            TokenKind::LeftParen => depth += 1,
+            TokenKind::RightParen => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `(SELECT ...)` ranges as (open paren, close paren). An unclosed subquery
+/// runs to the end.
+fn subquery_ranges(tokens: &[Token<'_>]) -> Vec<(usize, usize)> {
+    (0..tokens.len())
+        .filter(|&i| {
+            tokens[i].kind == TokenKind::LeftParen
+                && tokens
+                    .get(i + 1)
+                    .is_some_and(|t| t.is_keyword("SELECT") || t.is_keyword("WITH"))
+        })
+        .map(|i| (i, matching_paren(tokens, i).unwrap_or(tokens.len())))
+        .collect()
+}
+
+/// Column definitions of a `( ... )` list starting at `open`
+fn column_definitions(tokens: &[Token<'_>], open: usize) -> Vec<SourceColumn> {
+    let close = matching_paren(tokens, open).unwrap_or(tokens.len());
+    let mut columns = Vec::new();
+    let mut item_start = open + 1;
+    let mut depth = 0usize;
+    for i in open + 1..=close {
+        match tokens.get(i).map(|t| t.kind) {
+            Some(TokenKind::LeftParen) => depth += 1,
+            Some(TokenKind::RightParen) if depth > 0 => depth -= 1,
+            Some(TokenKind::Comma) if depth > 0 => {}
+            _ if i == close || tokens[i].kind == TokenKind::Comma => {
+                let item = &tokens[item_start..i];
+                if let Some(name) = item.first().filter(|t| is_name_part(t)) {
+                    let data_type = item.get(1).filter(|t| t.kind == TokenKind::Word).map(|t| {
+                        let mut data_type = t.text.to_lowercase();
+                        if item.get(2).is_some_and(|t| t.kind == TokenKind::LeftParen) {
+                            let end = matching_paren(item, 2).unwrap_or(item.len() - 1);
+                            data_type.extend(item[2..=end].iter().map(|t| t.text));
+                        }
+                        data_type
+                    });
+                    columns.push(SourceColumn {
+                        name: name.identifier(),
+                        data_type,
+                    });
+                }
+                item_start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    columns
+}
+
+/// Temp tables, table variables and variables declared before the cursor
+fn declarations(tokens: &[Token<'_>], catalog: &CompletionCatalog<'_>) -> Declarations {
+    let mut declarations = Declarations::default();
+    for (i, tok) in tokens.iter().enumerate() {
+        match tok.kind {
+            TokenKind::Variable if !tok.text.starts_with("@@") => {
+                let mut next = i + 1;
+                if tokens.get(next).is_some_and(|t| t.is_keyword("AS")) {
+                    next += 1;
+                }
+                if tokens.get(next).is_some_and(|t| t.is_keyword("TABLE"))
+                    && tokens
+                        .get(next + 1)
+                        .is_some_and(|t| t.kind == TokenKind::LeftParen)
+                {
+                    let columns = column_definitions(tokens, next + 1);
+                    declarations.tables.push((tok.text.to_string(), columns));
+                }
+                // Declarations and parameters are followed by their type
+                let data_type = tokens
+                    .get(next)
+                    .filter(|t| t.kind == TokenKind::Word)
+                    .filter(|t| t.is_keyword("TABLE") || !is_reserved(&t.text.to_ascii_uppercase()))
+                    .map(|t| t.text.to_lowercase());
+                if data_type.is_some()
+                    && !declarations
+                        .variables
+                        .iter()
+                        .any(|(name, _)| name.eq_ignore_ascii_case(tok.text))
+                {
+                    declarations
+                        .variables
+                        .push((tok.text.to_string(), data_type));
+                }
+            }
+            _ if tok.is_keyword("TABLE")
+                && i > 0
+                && tokens[i - 1].is_keyword("CREATE")
+                && tokens.get(i + 1).is_some_and(|t| t.is_temp_table())
+                && tokens
+                    .get(i + 2)
+                    .is_some_and(|t| t.kind == TokenKind::LeftParen) =>
+            {
+                let columns = column_definitions(tokens, i + 2);
+                declarations
+                    .tables
+                    .push((tokens[i + 1].text.to_string(), columns));
+            }
+            _ if tok.is_keyword("INTO") && tokens.get(i + 1).is_some_and(|t| t.is_temp_table()) => {
+                // SELECT ... INTO #name: columns come from the select list
+                let Some(select) = tokens[..i].iter().rposition(|t| t.is_keyword("SELECT")) else {
+                    continue;
+                };
+                let end = tokens[i..]
+                    .iter()
+                    .position(|t| t.kind == TokenKind::Semicolon)
+                    .map_or(tokens.len(), |n| i + n);
+                let resolver = Resolver {
+                    catalog,
+                    declarations: Declarations::default(),
+                    ctes: Vec::new(),
+                };
+                let columns = resolver.output_columns(&tokens[select..end], 0);
+                declarations
+                    .tables
+                    .push((tokens[i + 1].text.to_string(), columns));
+            }
+            _ => {}
+        }
+    }
+    declarations
+}
+
+/// Nearest clause keyword before the cursor at the cursor's paren depth
+fn clause(before: &[Token<'_>]) -> Option<String> {
+    let mut depth = 0usize;
+    for tok in before.iter().rev() {
+        match tok.kind {
+            TokenKind::RightParen => depth += 1,
+            TokenKind::LeftParen if depth == 0 => return None,
+            TokenKind::LeftParen => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 {
+            if let Some(kw) = tok
+                .keyword()
+                .filter(|k| CLAUSE_KEYWORDS.contains(&k.as_str()))
+            {
+                return Some(kw);
+            }
+        }
+    }
+    None
+}
+
+fn context(before: &[Token<'_>]) -> Context {
+    let Some(prev) = before.last() else {
+        return Context::Expression;
+    };
+    let prev_kw = prev.keyword();
+    match prev_kw.as_deref() {
+        Some("FROM" | "UPDATE" | "INTO" | "TABLE" | "MERGE" | "USING") => {
+            return Context::Table { after_join: false }
+        }
+        Some("JOIN") => return Context::Table { after_join: true },
+        Some("ON") => return Context::JoinCondition,
+        Some("EXEC" | "EXECUTE") => return Context::Procedure,
+        _ => {}
+    }
+
+    let clause = clause(before);
+    if prev.kind == TokenKind::Comma && clause.as_deref() == Some("FROM") {
+        return Context::Table { after_join: false };
+    }
+    if let Some(exec) = before
+        .iter()
+        .rposition(|t| t.is_keyword("EXEC") || t.is_keyword("EXECUTE"))
+        .filter(|_| matches!(clause.as_deref(), Some("EXEC" | "EXECUTE")))
+    {
+        let mut start = exec + 1;
+        if before
+            .get(start)
+            .is_some_and(|t| t.kind == TokenKind::Variable)
+            && before.get(start + 1).is_some_and(|t| t.text == "=")
+        {
+            start += 2;
+            if start == before.len() {
+                return Context::Procedure;
+            }
+        }
+        if let Some((parts, end)) = object_name(before, start) {
+            if end < before.len() || prev.kind != TokenKind::Dot {
+                return Context::ProcedureArguments(parts);
+            }
+        }
+    }
+    Context::Expression
+}
+
+impl<'c, 'a> Resolver<'c, 'a> {
+    /// CTEs defined by a leading `WITH name [(cols)] AS (...)` list
+    fn ctes(&self, tokens: &[Token<'_>], nesting: usize) -> Vec<(String, Vec<SourceColumn>)> {
+        let mut ctes = Vec::new();
+        let mut i = 1;
+        if !tokens.first().is_some_and(|t| t.is_keyword("WITH")) {
+            return ctes;
+        }
+        while i < tokens.len() && is_name_part(&tokens[i]) {
+            let name = tokens[i].identifier();
+            i += 1;
+            let mut explicit = None;
+            if tokens
+                .get(i)
+                .is_some_and(|t| t.kind == TokenKind::LeftParen)
+            {
+                explicit = Some(column_definitions(tokens, i));
+                i = matching_paren(tokens, i).map_or(tokens.len(), |close| close + 1);
+            }
+            if !tokens.get(i).is_some_and(|t| t.is_keyword("AS"))
+                || !tokens
+                    .get(i + 1)
+                    .is_some_and(|t| t.kind == TokenKind::LeftParen)
+            {
+                break;
+            }
+            let close = matching_paren(tokens, i + 1).unwrap_or(tokens.len());
+            let columns = explicit.unwrap_or_else(|| {
+                let nested = Resolver {
+                    catalog: self.catalog,
+                    declarations: Declarations::default(),
+                    ctes: ctes.clone(),
+                };
+                nested.output_columns(&tokens[i + 2..close], nesting + 1)
+            });
+            ctes.push((name, columns));
+            i = close + 1;
+            if !tokens.get(i).is_some_and(|t| t.kind == TokenKind::Comma) {
+                break;
+            }
+            i += 1;
+        }
+        ctes
+    }
+
+    /// Tables, CTEs, derived tables and table variables referenced directly
+    /// in `tokens`, not in nested subqueries
+    fn sources(&self, tokens: &[Token<'_>], nesting: usize) -> Vec<Source> {
+        let nested: Vec<(usize, usize)> = subquery_ranges(tokens);
+        let nested_spans: Vec<Span> = nested
+            .iter()
+            .map(|&(open, close)| {
+                Span::new(
+                    tokens[open].span.start,
+                    tokens.get(close).map_or(usize::MAX, |t| t.span.end),
+                )
+            })
+            .collect();
+        let in_nested = |offset: usize| {
+            nested_spans
+                .iter()
+                .any(|s| s.start < offset && offset < s.end)
+        };
+
+        // Tables after FROM/JOIN, plus those of a comma-separated FROM list
+        let mut refs = object_refs(tokens, &query_scopes(tokens));
+        for (i, tok) in tokens.iter().enumerate() {
+            if tok.kind != TokenKind::Comma || clause(&tokens[..i]).as_deref() != Some("FROM") {
+                continue;
+            }
+            let Some((parts, end)) = object_name(tokens, i + 1) else {
+                continue;
+            };
+            if tokens
+                .get(end)
+                .is_some_and(|t| t.kind == TokenKind::LeftParen)
+            {
+                continue;
+            }
+            let mut next = end;
+            if tokens.get(next).is_some_and(|t| t.is_keyword("AS")) {
+                next += 1;
+            }
+            refs.push(ObjectRef {
+                parts,
+                span: Span::new(tokens[i + 1].span.start, tokens[end - 1].span.end),
+                alias: tokens
+                    .get(next)
+                    .filter(|t| is_name_part(t))
+                    .map(|t| t.identifier()),
+            });
+        }
+
+        let mut sources = Vec::new();
+        for r in refs {
+            if in_nested(r.span.start) {
+                continue;
+            }
+            let name = r.name().to_string();
+            let mut source = Source {
+                name: name.clone(),
+                alias: r.alias.clone(),
+                columns: Vec::new(),
+                table: None,
+                position: r.span.start,
+            };
+            if let Some((_, columns)) = self
+                .ctes
+                .iter()
+                .find(|(cte, _)| r.parts.len() == 1 && cte.eq_ignore_ascii_case(&name))
+            {
+                source.columns = columns.clone();
+            } else if let Some(columns) = self.declarations.table(&name) {
+                source.columns = columns.to_vec();
+            } else if let Some((schema, table)) = self.catalog.table(&r.parts) {
+                source.name = table.table_name.clone();
+                source.columns = table
+                    .columns
+                    .iter()
+                    .map(|c| SourceColumn {
+                        name: c.name.clone(),
+                        data_type: Some(c.data_type.clone()),
+                    })
+                    .collect();
+                source.table = Some((
+                    schema.database_name.clone(),
+                    table.schema_name.clone(),
+                    table.table_name.clone(),
+                ));
+            }
+            sources.push(source);
+        }
+
+        for (i, tok) in tokens.iter().enumerate() {
+            let introduces_table = ["FROM", "JOIN", "APPLY", "UPDATE", "INTO"]
+                .iter()
+                .any(|k| tok.is_keyword(k))
+                || tok.kind == TokenKind::Comma;
+            if !introduces_table || in_nested(tok.span.start) {
+                continue;
+            }
+            let Some(next) = tokens.get(i + 1) else {
+                continue;
+            };
+
+            // Table variable, or (SELECT ...) derived table
+            let (columns, mut after) = if next.kind == TokenKind::Variable {
+                match self.declarations.table(next.text) {
+                    Some(columns) => (columns.to_vec(), i + 2),
+                    None => continue,
+                }
+            } else if let Some(&(open, close)) = nested.iter().find(|(open, _)| *open == i + 1) {
+                if nesting >= MAX_NESTING {
+                    continue;
+                }
+                let columns =
+                    self.output_columns(&tokens[open + 1..close.min(tokens.len())], nesting + 1);
+                (columns, close + 1)
+            } else {
+                continue;
+            };
+
+            if tokens.get(after).is_some_and(|t| t.is_keyword("AS")) {
+                after += 1;
+            }
+            let alias = tokens
+                .get(after)
+                .filter(|t| is_name_part(t))
+                .map(|t| t.identifier());
+            let columns = match tokens.get(after + 1) {
+                Some(t) if alias.is_some() && t.kind == TokenKind::LeftParen => {
+                    column_definitions(tokens, after + 1)
+                }
+                _ => columns,
+            };
+            let name = if next.kind == TokenKind::Variable {
+                next.text.to_string()
+            } else {
+                alias.clone().unwrap_or_default()
+            };
+            sources.push(Source {
+                name,
+                alias,
+                columns,
+                table: None,
+                position: next.span.start,
+            });
+        }
+
+        sources.sort_by_key(|s| s.position);
+        sources
+    }
+
+    /// Column names a SELECT produces
+    fn output_columns(&self, tokens: &[Token<'_>], nesting: usize) -> Vec<SourceColumn> {
+        let mut depth = 0usize;
+        let Some(select) = tokens.iter().position(|t| {
+            match t.kind {
+                TokenKind::LeftParen => depth += 1,
+                TokenKind::RightParen => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            depth == 0 && t.is_keyword("SELECT")
+        }) else {
+            return Vec::new();
+        };
+        let sources = self.sources(tokens, nesting);
+
+        // Split the select list on top-level commas
+        let mut items: Vec<&[Token<'_>]> = Vec::new();
+        let mut item_start = skip_select_modifiers(tokens, select);
+        let mut depth = 0usize;
+        let mut i = item_start;
+        while i <= tokens.len() {
+            let tok = tokens.get(i);
+            match tok.map(|t| t.kind) {
+                Some(TokenKind::LeftParen) => depth += 1,
+                Some(TokenKind::RightParen) => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            let ends = tok.is_none_or(|t| {
+                depth == 0
+                    && [
+                        "FROM",
+                        "INTO",
+                        "WHERE",
+                        "GROUP",
+                        "ORDER",
+                        "UNION",
+                        "EXCEPT",
+                        "INTERSECT",
+                        "OPTION",
+                    ]
+                    .iter()
+                    .any(|k| t.is_keyword(k))
+            });
+            if ends || (depth == 0 && tok.is_some_and(|t| t.kind == TokenKind::Comma)) {
+                if i > item_start {
+                    items.push(&tokens[item_start..i]);
+                }
+                item_start = i + 1;
+                if ends {
+                    break;
+                }
+            }
+            i += 1;
+        }
+
+        let mut columns = Vec::new();
+        for item in items {
+            let last = item[item.len() - 1];
+
+            // `*` or `q.*`
+            if last.kind == TokenKind::Operator && last.text == "*" {
+                let qualifier = (item.len() >= 3).then(|| item[item.len() - 3].identifier());
+                for source in &sources {
+                    if qualifier
+                        .as_ref()
+                        .is_none_or(|q| source.qualifier().eq_ignore_ascii_case(q))
+                    {
+                        columns.extend(source.columns.iter().cloned());
+                    }
+                }
+                continue;
+            }
+
+            // `alias = expr`, `expr AS alias`, `expr alias` or a column reference
+            let name = if item.len() >= 2 && is_name_part(&item[0]) && item[1].text == "=" {
+                Some(item[0].identifier())
+            } else if let Some(pos) = item.iter().rposition(|t| t.is_keyword("AS")) {
+                item.get(pos + 1)
+                    .map(|t| t.identifier().trim_matches('\'').to_string())
+            } else if is_name_part(&last) || last.kind == TokenKind::String {
+                Some(last.identifier().trim_matches('\'').to_string())
+            } else {
+                None
+            };
+            let Some(name) = name else {
+                continue;
+            };
+
+            // Keep the type of a plain column reference
+            let data_type = match item {
+                [column] => sources
+                    .iter()
+                    .flat_map(|s| &s.columns)
+                    .find(|c| c.name.eq_ignore_ascii_case(&column.identifier())),
+                [qualifier, dot, column] if dot.kind == TokenKind::Dot => sources
+                    .iter()
+                    .filter(|s| s.qualifier().eq_ignore_ascii_case(&qualifier.identifier()))
+                    .flat_map(|s| &s.columns)
+                    .find(|c| c.name.eq_ignore_ascii_case(&column.identifier())),
+                _ => None,
+            }
+            .and_then(|c| c.data_type.clone());
+            columns.push(SourceColumn { name, data_type });
+        }
+        columns
+    }
+
+    fn items(
+        &self,
+        out: &mut Builder<'_>,
+        context: &Context,
+        qualifier: &[String],
+        sources: &[Source],
+        before: &[Token<'_>],
+    ) {
+        match context {
+            Context::Table { after_join } => {
+                if qualifier.is_empty() {
+                    for (name, _) in &self.ctes {
+                        out.push(
+                            name,
+                            CompletionKind::Table,
+                            Some("CTE".to_string()),
+                            None,
+                            110,
+                        );
+                    }
+                    for (name, _) in &self.declarations.tables {
+                        let detail = if name.starts_with('@') {
+                            "table variable"
+                        } else {
+                            "temp table"
+                        };
+                        out.push(
+                            name,
+                            CompletionKind::Table,
+                            Some(detail.to_string()),
+                            None,
+                            105,
+                        );
+                    }
+                    if *after_join {
+                        self.join_tables(out, sources);
+                    }
+                }
+                self.objects(out, qualifier, false);
+            }
+            Context::Procedure => self.objects(out, qualifier, true),
+            Context::ProcedureArguments(parts) => {
+                if let Some(routine) = self.catalog.routine(parts) {
+                    let named: HashSet<String> = before
+                        .windows(2)
+                        .filter(|w| w[0].kind == TokenKind::Variable && w[1].text == "=")
+                        .map(|w| w[0].text.to_lowercase())
+                        .collect();
+                    for p in &routine.parameters {
+                        if p.name.is_empty() || named.contains(&p.name.to_lowercase()) {
+                            continue;
+                        }
+                        let mut detail = p.data_type.clone();
+                        if p.parameter_mode != "IN" {
+                            detail.push_str(" OUTPUT");
+                        }
+                        if p.has_default {
+                            detail.push_str(", optional");
+                        }
+                        let score = if p.has_default { 100 } else { 120 } - p.ordinal_position;
+                        out.push(
+                            &p.name,
+                            CompletionKind::Parameter,
+                            Some(detail),
+                            Some(format!("{} = ", p.name)),
+                            score,
+                        );
+                    }
+                }
+                self.variables(out, 70);
+            }
+            Context::JoinCondition | Context::Expression => {
+                if *context == Context::JoinCondition && qualifier.is_empty() {
+                    self.join_conditions(out, sources);
+                }
+                self.expression(out, qualifier, sources);
+            }
+        }
+    }
+
+    /// Tables, views or procedures, narrowed by a `schema.` or `db.[schema].` qualifier
+    fn objects(&self, out: &mut Builder<'_>, qualifier: &[String], procedures: bool) {
+        let catalog = self.catalog;
+        let (database, schema_name) = match qualifier {
+            [] => (None, None),
+            [x] => {
+                // `x.` is a schema of the current database or a database
+                if let Some(schema) = catalog.schema(Some(x)) {
+                    for s in &schema.schemas {
+                        out.push(
+                            s,
+                            CompletionKind::Schema,
+                            Some(schema.database_name.clone()),
+                            None,
+                            60,
+                        );
+                    }
+                }
+                (None, Some(x.as_str()))
+            }
+            [.., db, schema] => (
+                Some(db.as_str()),
+                Some(schema.as_str())
+                    .filter(|s| !s.is_empty())
+                    .or(Some("dbo")),
+            ),
+        };
+        let Some(schema) = catalog.schema(database) else {
+            return;
+        };
+
+        let qualify = |object_schema: &str, name: &str, out: &Builder<'_>| {
+            if schema_name.is_some() {
+                out.quote(name)
+            } else {
+                format!("{}.{}", out.quote(object_schema), out.quote(name))
+            }
+        };
+        let in_schema = |s: &str| schema_name.is_none_or(|n| n.eq_ignore_ascii_case(s));
+
+        if procedures {
+            for r in schema
+                .routines
+                .iter()
+                .filter(|r| r.routine_type == "PROCEDURE" && in_schema(&r.schema_name))
+            {
+                let insert = qualify(&r.schema_name, &r.routine_name, out);
+                out.push(
+                    &r.routine_name,
+                    CompletionKind::Procedure,
+                    Some(signature(r)),
+                    Some(insert),
+                    100,
+                );
+            }
+        } else {
+            for t in schema.tables.iter().filter(|t| in_schema(&t.schema_name)) {
+                let (kind, score) = if t.table_type == "VIEW" {
+                    (CompletionKind::View, 95)
+                } else {
+                    (CompletionKind::Table, 100)
+                };
+                let insert = qualify(&t.schema_name, &t.table_name, out);
+                out.push(
+                    &t.table_name,
+                    kind,
+                    Some(t.schema_name.clone()),
+                    Some(insert),
+                    score,
+                );
+            }
+        }
+
+        if qualifier.is_empty() {
+            for s in &schema.schemas {
+                out.push(s, CompletionKind::Schema, None, None, 60);
+            }
+            for db in catalog.databases {
+                out.push(db, CompletionKind::Database, None, None, 50);
+            }
+        }
+    }
+
+    fn expression(&self, out: &mut Builder<'_>, qualifier: &[String], sources: &[Source]) {
+        let catalog = self.catalog;
+        if let Some(q) = qualifier.last() {
+            // Columns of an alias or (schema-qualified) table name
+            let matching: Vec<&Source> = sources
+                .iter()
+                .filter(|s| {
+                    s.qualifier().eq_ignore_ascii_case(q)
+                        && (qualifier.len() == 1
+                            || s.table.as_ref().is_some_and(|(_, schema, _)| {
+                                schema.eq_ignore_ascii_case(&qualifier[qualifier.len() - 2])
+                            }))
+                })
+                .collect();
+            for source in &matching {
+                for c in &source.columns {
+                    out.push(
+                        &c.name,
+                        CompletionKind::Column,
+                        c.data_type.clone(),
+                        None,
+                        100,
+                    );
+                }
+            }
+            if !matching.is_empty() {
+                return;
+            }
+
+            // Functions of `schema.` / `db.schema.`, or schemas of `db.`
+            let (database, schema_name) = match qualifier {
+                [x] => {
+                    if let Some(schema) = catalog.schema(Some(x)) {
+                        for s in &schema.schemas {
+                            out.push(s, CompletionKind::Schema, None, None, 60);
+                        }
+                    }
+                    (None, x.as_str())
+                }
+                [.., db, schema] => (Some(db.as_str()), schema.as_str()),
+                [] => unreachable!(),
+            };
+            if let Some(schema) = catalog.schema(database) {
+                for r in schema.routines.iter().filter(|r| {
+                    r.routine_type == "FUNCTION" && r.schema_name.eq_ignore_ascii_case(schema_name)
+                }) {
+                    let insert = format!("{}(", out.quote(&r.routine_name));
+                    out.push(
+                        &r.routine_name,
+                        CompletionKind::Function,
+                        Some(signature(r)),
+                        Some(insert),
+                        80,
+                    );
+                }
+            }
+            return;
+        }
+
+        // Unqualified columns; names found in several sources insert qualified
+        let mut counts = std::collections::HashMap::new();
+        for c in sources.iter().flat_map(|s| &s.columns) {
+            *counts.entry(c.name.to_lowercase()).or_insert(0) += 1;
+        }
+        for source in sources {
+            for c in &source.columns {
+                let ambiguous = counts[&c.name.to_lowercase()] > 1;
+                let insert = ambiguous
+                    .then(|| format!("{}.{}", out.quote(source.qualifier()), out.quote(&c.name)));
+                let detail = match &c.data_type {
+                    Some(t) => format!("{} · {}", source.qualifier(), t),
+                    None => source.qualifier().to_string(),
+                };
+                out.push(&c.name, CompletionKind::Column, Some(detail), insert, 100);
+            }
+            if !source.name.is_empty() {
+                out.push(
+                    source.qualifier(),
+                    CompletionKind::Alias,
+                    Some(source.name.clone()),
+                    None,
+                    90,
+                );
+            }
+        }
+
+        self.variables(out, if out.prefix.starts_with('@') { 120 } else { 70 });
+
+        if let Some(schema) = catalog.schema(None) {
+            for r in schema
+                .routines
+                .iter()
+                .filter(|r| r.routine_type == "FUNCTION")
+            {
+                let insert = format!(
+                    "{}.{}(",
+                    out.quote(&r.schema_name),
+                    out.quote(&r.routine_name)
+                );
+                out.push(
+                    &r.routine_name,
+                    CompletionKind::Function,
+                    Some(signature(r)),
+                    Some(insert),
+                    60,
+                );
+            }
+        }
+
+        if !out.prefix.is_empty() && !out.bracketed {
+            for kw in KEYWORDS {
+                out.push(kw, CompletionKind::Keyword, None, None, 40);
+            }
+        }
+    }
+
+    fn variables(&self, out: &mut Builder<'_>, score: i32) {
+        for (name, data_type) in &self.declarations.variables {
+            out.push(
+                name,
+                CompletionKind::Variable,
+                data_type.clone(),
+                None,
+                score,
+            );
+        }
+    }
+
+    /// References between two catalog tables, as ON conditions written from
+    /// `from`'s side: `from.col = to.col [AND ...]`
+    fn conditions(&self, from: &Source, to: &Source) -> Vec<(String, String)> {
+        let (Some(a), Some(b)) = (&from.table, &to.table) else {
+            return Vec::new();
+        };
+        if !a.0.eq_ignore_ascii_case(&b.0) {
+            return Vec::new();
+        }
+        let Some(schema) = self.catalog.schema(Some(&a.0)) else {
+            return Vec::new();
+        };
+        let virtual_references = if a.0.eq_ignore_ascii_case(self.catalog.database) {
+            self.catalog.virtual_references
+        } else {
+            &[]
+        };
+        let is = |schema_name: &str, table_name: &str, t: &(String, String, String)| {
+            schema_name.eq_ignore_ascii_case(&t.1) && table_name.eq_ignore_ascii_case(&t.2)
+        };
+
+        let mut conditions = Vec::new();
+        for r in all_references(schema, virtual_references) {
+            let from_is_source = is(&r.source_schema, &r.source_table, a)
+                && is(&r.target_schema, &r.target_table, b);
+            let from_is_target = is(&r.target_schema, &r.target_table, a)
+                && is(&r.source_schema, &r.source_table, b);
+            if !from_is_source && !from_is_target {
+                continue;
+            }
+            let text = r
+                .columns
+                .iter()
+                .map(|c| {
+                    let (mine, theirs) = if from_is_source {
+                        (&c.source_column, &c.target_column)
+                    } else {
+                        (&c.target_column, &c.source_column)
+                    };
+                    format!(
+                        "{}.{} = {}.{}",
+                        from.qualifier(),
+                        quote_name(mine),
+                        to.qualifier(),
+                        quote_name(theirs)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(" AND ");
+            let detail = r
+                .constraint_name
+                .clone()
+                .unwrap_or_else(|| "virtual reference".to_string());
+            conditions.push((text, detail));
+        }
+        conditions
+    }
+
+    /// ON conditions linking the last joined table to the tables before it
+    fn join_conditions(&self, out: &mut Builder<'_>, sources: &[Source]) {
+        let Some((joined, earlier)) = sources.split_last() else {
+            return;
+        };
+        for (distance, other) in earlier.iter().rev().enumerate() {
+            for (text, detail) in self.conditions(joined, other) {
+                out.push(
+                    &text,
+                    CompletionKind::Join,
+                    Some(detail),
+                    None,
+                    150 - distance as i32,
+                );
+            }
+        }
+    }
+
+    /// `schema.Table alias ON ...` for tables related to those already in scope
+    fn join_tables(&self, out: &mut Builder<'_>, sources: &[Source]) {
+        let mut used: BTreeSet<String> = sources
+            .iter()
+            .map(|s| s.qualifier().to_lowercase())
+            .collect();
+        for source in sources.iter().rev() {
+            let Some((database, _, _)) = &source.table else {
+                continue;
+            };
+            let Some(schema) = self.catalog.schema(Some(database)) else {
+                continue;
+            };
+            for table in &schema.tables {
+                let mut candidate = Source {
+                    name: table.table_name.clone(),
+                    alias: None,
+                    columns: Vec::new(),
+                    table: Some((
+                        schema.database_name.clone(),
+                        table.schema_name.clone(),
+                        table.table_name.clone(),
+                    )),
+                    position: 0,
+                };
+                if candidate.table == source.table {
+                    continue;
+                }
+                let conditions = self.conditions(&candidate, source);
+                if conditions.is_empty() {
+                    continue;
+                }
+                let alias = unique_alias(&table.table_name, &mut used.clone());
+                candidate.alias = Some(alias.clone());
+                for (text, detail) in self.conditions(&candidate, source) {
+                    let insert = format!(
+                        "{}.{} {} ON {}",
+                        quote_name(&table.schema_name),
+                        quote_name(&table.table_name),
+                        alias,
+                        text
+                    );
+                    out.push(&insert, CompletionKind::Join, Some(detail), None, 150);
+                }
+            }
+            used.insert(source.qualifier().to_lowercase());
+        }
+    }
+}
+
+/// `(@a int, @b date) -> int` for a routine
+fn signature(routine: &RoutineInfo) -> String {
+    let parameters: Vec<String> = routine
+        .parameters
+        .iter()
+        .filter(|p| !p.name.is_empty())
+        .map(|p| format!("{} {}", p.name, p.data_type))
+        .collect();
+    match &routine.return_type {
+        Some(return_type) => format!("({}) -> {}", parameters.join(", "), return_type),
+        None => format!("({})", parameters.join(", ")),
+    }
+}
+
+/// A name as it can be typed: bare when it is a plain identifier, else `[...]`
+fn quote_name(name: &str) -> String {
+    let plain = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '#' || c == '@')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '#' | '@' | '$'));
+    if plain && !is_reserved(&name.to_ascii_uppercase()) {
+        name.to_string()
+    } else {
+        quote_identifier(name)
+    }
+}
+
+/// Collects items matching the typed prefix
+struct Builder<'p> {
+    prefix: &'p str,
+    /// The word was started with `[`, so names are inserted delimited
+    bracketed: bool,
+    items: Vec<CompletionItem>,
+}
+
+impl Builder<'_> {
+    fn quote(&self, name: &str) -> String {
+        if self.bracketed {
+            quote_identifier(name)
+        } else {
+            quote_name(name)
+        }
+    }
+
+    fn push(
+        &mut self,
+        label: &str,
+        kind: CompletionKind,
+        detail: Option<String>,
+        insert_text: Option<String>,
+        score: i32,
+    ) {
+        let matches = label
+            .get(..self.prefix.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(self.prefix));
+        if !matches {
+            return;
+        }
+        let insert_text = insert_text.unwrap_or_else(|| match kind {
+            CompletionKind::Keyword | CompletionKind::Join | CompletionKind::Variable => {
+                label.to_string()
+            }
+            _ => self.quote(label),
+        });
+        self.items.push(CompletionItem {
+            label: label.to_string(),
+            kind,
+            detail,
+            insert_text,
+            score,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema::{ColumnInfo, ParameterInfo, RelationshipInfo};
+
+    fn column(name: &str, data_type: &str) -> ColumnInfo {
+        ColumnInfo::for_test(name, data_type)
+    }
+
+    fn table(name: &str, columns: Vec<ColumnInfo>) -> TableInfo {
+        TableInfo::for_test(name, columns)
+    }
+
+    fn parameter(name: &str, ordinal: i32, has_default: bool) -> ParameterInfo {
+        ParameterInfo {
+            name: name.to_string(),
+            data_type: "int".to_string(),
+            max_length: None,
+            precision: None,
+            scale: None,
+            parameter_mode: "IN".to_string(),
+            ordinal_position: ordinal,
+            has_default,
+        }
+    }
+
+    fn schema(database: &str, tables: Vec<TableInfo>) -> SchemaInfo {
+        SchemaInfo::for_test(database, tables)
+    }
+
+    fn schemas() -> Vec<SchemaInfo> {
+        let mut shop = schema(
+            "shop",
+            vec![
+                table(
+                    "Orders",
+                    vec![
+                        column("Id", "int"),
+                        column("CustomerId", "int"),
+                        column("Total", "money"),
+                    ],
+                ),
+                table(
+                    "Customers",
+                    vec![column("Id", "int"), column("Name", "nvarchar")],
+                ),
+            ],
+        );
+        shop.relationships = vec![RelationshipInfo::for_test(
+            "FK_Orders_Customers",
+            ("Orders", "CustomerId"),
+            ("Customers", "Id"),
+        )];
+        shop.routines = vec![RoutineInfo {
+            schema_name: "dbo".to_string(),
+            routine_name: "PlaceOrder".to_string(),
+            routine_type: "PROCEDURE".to_string(),
+            return_type: None,
+            parameters: vec![
+                parameter("@CustomerId", 1, false),
+                parameter("@Quantity", 2, false),
+                parameter("@Note", 3, true),
+            ],
+            definition: None,
+            modify_date: None,
+            extended_properties: Vec::new(),
+        }];
+        let archive = schema(
+            "archive",
+            vec![table("OldOrders", vec![column("OrderId", "int")])],
+        );
+        vec![shop, archive]
+    }
+
+    /// Complete at the `|` in `sql`
+    fn complete_at(sql: &str) -> Completions {
+        let offset = sql.find('|').unwrap();
+        let sql = sql.replace('|', "");
+        let schemas = schemas();
+        let databases = vec!["archive".to_string(), "shop".to_string()];
+        let catalog = CompletionCatalog {
+            database: "shop",
+            schemas: &schemas,
+            databases: &databases,
+            virtual_references: &[],
+        };
+        complete(&sql, offset, &catalog)
+    }
+
+    fn labels(sql: &str) -> Vec<String> {
+        complete_at(sql)
+            .items
+            .into_iter()
+            .map(|i| i.label)
+            .collect()
+    }
+
+    #[test]
+    fn completes_columns_of_aliases() {
+        assert_eq!(
+            labels("SELECT o.| FROM dbo.Orders o"),
+            ["CustomerId", "Id", "Total"]
+        );
+        assert_eq!(labels("SELECT o.cu| FROM dbo.Orders AS o"), ["CustomerId"]);
+
+        let completions = complete_at(
+            "SELECT Na|, o.Id FROM dbo.Orders o JOIN dbo.Customers c ON c.Id = o.CustomerId",
+        );
+        assert_eq!(completions.replace, Span::new(7, 9));
+        assert_eq!(completions.items[0].label, "Name");
+        assert_eq!(completions.items[0].detail.as_deref(), Some("c · nvarchar"));
+
+        // Shared names insert qualified
+        let items = complete_at("SELECT I| FROM dbo.Orders o JOIN dbo.Customers c ON 1 = 1").items;
+        let inserts: Vec<_> = items
+            .iter()
+            .filter(|i| i.label == "Id")
+            .map(|i| i.insert_text.as_str())
+            .collect();
+        assert_eq!(inserts, ["o.Id", "c.Id"]);
+    }
+
+    #[test]
+    fn completes_ctes_derived_tables_and_batch_tables() {
+        assert_eq!(
+            labels("WITH recent AS (SELECT Id, Total AS Amount FROM dbo.Orders) SELECT r.| FROM recent r"),
+            ["Amount", "Id"]
+        );
+        assert_eq!(
+            labels("SELECT d.| FROM (SELECT CustomerId, COUNT(*) AS Orders, o.* FROM dbo.Orders o GROUP BY CustomerId) d"),
+            ["CustomerId", "Id", "Orders", "Total"]
+        );
+        let batch = "CREATE TABLE #work (Id int, Note nvarchar(50));\nDECLARE @codes TABLE (Code char(3));\n";
+        assert_eq!(
+            labels(&format!(
+                "{}SELECT w.| FROM #work w JOIN @codes c ON 1 = 1",
+                batch
+            )),
+            ["Id", "Note"]
+        );
+        assert_eq!(
+            labels(&format!(
+                "{}SELECT c.| FROM #work w JOIN @codes c ON 1 = 1",
+                batch
+            )),
+            ["Code"]
+        );
+        assert_eq!(labels(&format!("{}SELECT * FROM #|", batch)), ["#work"]);
+        assert!(labels("SELECT Id INTO #ids FROM dbo.Orders;\nSELECT i.| FROM #ids i") == ["Id"]);
+
+        // Inside a subquery only its own tables are in scope
+        let items =
+            complete_at("SELECT * FROM dbo.Customers c WHERE c.Id IN (SELECT N| FROM dbo.Orders)")
+                .items;
+        assert!(items.iter().all(|i| i.kind != CompletionKind::Column));
+    }
+
+    #[test]
+    fn completes_three_part_names() {
+        assert_eq!(labels("SELECT * FROM archive.dbo.|"), ["OldOrders"]);
+        assert_eq!(labels("SELECT * FROM archive..|"), ["OldOrders"]);
+        assert_eq!(labels("SELECT * FROM archive.|"), ["dbo"]);
+        assert_eq!(
+            labels("SELECT x.| FROM archive.dbo.OldOrders x"),
+            ["OrderId"]
+        );
+
+        let items = complete_at("SELECT * FROM Ord|").items;
+        assert_eq!(items[0].insert_text, "dbo.Orders");
+    }
+
+    #[test]
+    fn suggests_join_conditions_from_relationships() {
+        let items = complete_at("SELECT * FROM dbo.Orders o JOIN dbo.Customers c ON |").items;
+        assert_eq!(items[0].kind, CompletionKind::Join);
+        assert_eq!(items[0].label, "c.Id = o.CustomerId");
+        assert_eq!(items[0].detail.as_deref(), Some("FK_Orders_Customers"));
+
+        let items = complete_at("SELECT * FROM dbo.Orders o JOIN |").items;
+        assert_eq!(
+            items[0].insert_text,
+            "dbo.Customers c ON c.Id = o.CustomerId"
+        );
+    }
+
+    #[test]
+    fn suggests_missing_procedure_parameters() {
+        let items = complete_at("EXEC dbo.PlaceOrder @CustomerId = 1, |").items;
+        let names: Vec<_> = items.iter().map(|i| i.label.as_str()).collect();
+        assert_eq!(names, ["@Quantity", "@Note"]);
+        assert_eq!(items[0].insert_text, "@Quantity = ");
+        assert_eq!(items[1].detail.as_deref(), Some("int, optional"));
+        assert_eq!(labels("EXEC Pla|"), ["PlaceOrder"]);
+    }
+
+    #[test]
+    fn resolves_symbols_under_the_cursor() {
+        let schemas = schemas();
+        let catalog = CompletionCatalog {
+            database: "shop",
+            schemas: &schemas,
+            databases: &[],
+            virtual_references: &[],
+        };
+        let resolve = |sql: &str| {
+            let offset = sql.find('|').unwrap();
+            resolve_symbol(&sql.replace('|', ""), offset, &catalog).map(|(_, symbol)| symbol)
+        };
+        let name = |symbol: Option<Symbol<'_>>| match symbol {
+            Some(Symbol::Table { table, .. }) => format!("table {}", table.table_name),
+            Some(Symbol::Column { table, column, .. }) => {
+                format!("column {}.{}", table.table_name, column.name)
+            }
+            Some(Symbol::Routine { routine, .. }) => format!("routine {}", routine.routine_name),
+            None => "none".to_string(),
+        };
+
+        assert_eq!(
+            name(resolve("SELECT o.To|tal FROM dbo.Orders o")),
+            "column Orders.Total"
+        );
+        assert_eq!(
+            name(resolve("SELECT o|.Total FROM dbo.Orders o")),
+            "table Orders"
+        );
+        assert_eq!(
+            name(resolve("SELECT Na|me FROM dbo.Orders o, dbo.Customers c")),
+            "column Customers.Name"
+        );
+        assert_eq!(
+            name(resolve("SELECT * FROM archive.dbo.Old|Orders")),
+            "table OldOrders"
+        );
+        assert_eq!(
+            name(resolve("EXEC dbo.Place|Order 1, 2")),
+            "routine PlaceOrder"
+        );
+        assert_eq!(
+            name(resolve("WITH o AS (SELECT 1 AS x) SELECT x FROM o|")),
+            "none"
+        );
+    }
+
+    #[test]
+    fn variables_keywords_and_strings() {
+        assert_eq!(
+            labels("DECLARE @limit int = 5;\nSELECT TOP (@l|) * FROM dbo.Orders"),
+            ["@limit"]
+        );
+        assert!(labels("SELECT * FROM dbo.Orders WH|").contains(&"WHERE".to_string()));
+        assert!(complete_at("SELECT 'o.|' FROM dbo.Orders o")
+            .items
+            .is_empty());
+        assert!(complete_at("SELECT 1 -- o.|").items.is_empty());
+    }
+}
@@ -0,0 +1,165 @@
+// Editor Diagnostics and Edits
+// Position-carrying results shared by the formatter, linter and validators
+
+use serde::{Deserialize, Serialize};
+
+use super::lexer::{line_col, Span};
+
+/// Diagnostic severity, matching the editor's marker levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+/// A problem found in a SQL document. `span` is in bytes; line/column values
+/// are 1-based with columns counted in characters.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub code: String,
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl Diagnostic {
+    pub fn new(
+        source: &str,
+        span: Span,
+        severity: Severity,
+        code: &str,
+        message: impl Into<String>,
+    ) -> Self {
+        let (line, column) = line_col(source, span.start);
+        let (end_line, end_column) = line_col(source, span.end);
+        Self {
+            code: code.to_string(),
+            severity,
+            message: message.into(),
+            span,
+            line,
+            column,
+            end_line,
+            end_column,
+        }
+    }
+}
+
+/// Replace the text at `span` with `new_text`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextEdit {
+    pub span: Span,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    pub new_text: String,
+}
+
+impl TextEdit {
+    pub fn new(source: &str, span: Span, new_text: String) -> Self {
+        let (line, column) = line_col(source, span.start);
+        let (end_line, end_column) = line_col(source, span.end);
+        Self {
+            span,
+            line,
+            column,
+            end_line,
+            end_column,
+            new_text,
+        }
+    }
+
+    /// Minimal single edit turning `before` into `after`: the common prefix
+    /// and suffix are left untouched. Returns None when nothing changed.
+    pub fn between(before: &str, after: &str) -> Option<Self> {
+        if before == after {
+            return None;
+        }
+
+        let prefix = before
+            .char_indices()
+            .zip(after.chars())
+            .find(|((_, a), b)| a != b)
+            .map(|((i, _), _)| i)
+            .unwrap_or(before.len().min(after.len()));
+        let prefix = floor_boundary(after, prefix);
+
+        let max_suffix = (before.len() - prefix).min(after.len() - prefix);
+        let suffix = before[prefix..]
+            .chars()
+            .rev()
+            .zip(after[prefix..].chars().rev())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a.len_utf8())
+            .scan(0usize, |total, len| {
+                *total += len;
+                Some(*total)
+            })
+            .take_while(|total| *total <= max_suffix)
+            .last()
+            .unwrap_or(0);
+
+        Some(Self::new(
+            before,
+            Span::new(prefix, before.len() - suffix),
+            after[prefix..after.len() - suffix].to_string(),
+        ))
+    }
+}
+
+fn floor_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(before: &str, edit: &TextEdit) -> String {
+        format!(
+            "{}{}{}",
+            &before[..edit.span.start],
+            edit.new_text,
+            &before[edit.span.end..]
+        )
+    }
+
+    #[test]
+    fn diagnostic_positions_are_one_based() {
+        let sql = "SELECT 1\nFROM é t";
+        let diag = Diagnostic::new(sql, Span::new(17, 18), Severity::Info, "X", "m");
+        assert_eq!(
+            (diag.line, diag.column, diag.end_line, diag.end_column),
+            (2, 8, 2, 9)
+        );
+    }
+
+    #[test]
+    fn edit_between_covers_only_the_change() {
+        let before = "select a from t";
+        let after = "SELECT a FROM t";
+        let edit = TextEdit::between(before, after).unwrap();
+        assert_eq!(edit.span, Span::new(0, 13));
+        assert_eq!(apply(before, &edit), after);
+    }
+
+    #[test]
+    fn edit_between_handles_insertions_and_unicode() {
+        let before = "SELECT 'é' x";
+        let after = "SELECT 'é' AS x";
+        let edit = TextEdit::between(before, after).unwrap();
+        assert_eq!(edit.new_text, "AS ");
+        assert_eq!(apply(before, &edit), after);
+        assert!(TextEdit::between(after, after).is_none());
+    }
+}
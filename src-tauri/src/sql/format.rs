@@ -0,0 +1,1108 @@
+// T-SQL Formatter
+// Token-based pretty printer: keyword casing, clause layout, indentation of
+// SELECT lists, JOINs, CASE expressions, subqueries and BEGIN...END blocks.
+// Comments are always preserved; text inside strings and identifiers is never
+// changed.
+
+use serde::{Deserialize, Serialize};
+
+use super::diagnostic::TextEdit;
+use super::lexer::{is_non_reserved_keyword, is_reserved, tokenize, Span, Token, TokenKind};
+use super::parser::{parse_statements_in, split_batches, Statement};
+
+/// How keywords are cased in formatted output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum KeywordCase {
+    #[default]
+    Upper,
+    Lower,
+    Preserve,
+}
+
+/// Where list commas go when a list is broken over lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CommaStyle {
+    /// `a,` at the end of the line
+    #[default]
+    Trailing,
+    /// `, a` at the start of the next line
+    Leading,
+}
+
+/// Formatter options
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FormatOptions {
+    pub keyword_case: KeywordCase,
+    /// Spaces per indentation level
+    pub indent_width: usize,
+    pub comma_style: CommaStyle,
+    /// Blank lines between top-level statements
+    pub lines_between_statements: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            keyword_case: KeywordCase::Upper,
+            indent_width: 2,
+            comma_style: CommaStyle::Trailing,
+            lines_between_statements: 1,
+        }
+    }
+}
+
+/// Keywords written like functions: no space before their `(`
+const FUNCTION_KEYWORDS: &[&str] = &[
+    "COALESCE",
+    "CONTAINS",
+    "CONTAINSTABLE",
+    "CONVERT",
+    "FREETEXT",
+    "FREETEXTTABLE",
+    "IDENTITY",
+    "LEFT",
+    "NULLIF",
+    "OPENDATASOURCE",
+    "OPENQUERY",
+    "OPENROWSET",
+    "OPENXML",
+    "RIGHT",
+    "TRY_CONVERT",
+];
+
+/// Format a whole document
+pub fn format_sql(sql: &str, options: &FormatOptions) -> String {
+    let batches = split_batches(sql);
+    if batches.is_empty() {
+        return sql.to_string();
+    }
+
+    // Keep the document's line endings
+    let eol = if sql.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut formatter = Formatter {
+        sql,
+        options,
+        w: Writer::new(options, eol),
+    };
+
+    for (i, batch) in batches.iter().enumerate() {
+        if i > 0 {
+            formatter.w.blank_lines(1);
+        }
+        formatter.region(batch.span, 0, true);
+
+        if let Some(separator) = batch.separator {
+            let text = separator.text(sql).trim();
+            formatter.w.newline(0);
+            formatter
+                .w
+                .token(&format!("{}{}", formatter.case("GO"), &text[2..]), false);
+        }
+    }
+
+    let mut out = formatter.w.finish();
+    if sql.ends_with('\n') {
+        out.push_str(eol);
+    }
+    out
+}
+
+/// Format a document and return the edit that applies the result (empty when
+/// the document is already formatted)
+pub fn format_edits(sql: &str, options: &FormatOptions) -> Vec<TextEdit> {
+    TextEdit::between(sql, &format_sql(sql, options))
+        .into_iter()
+        .collect()
+}
+
+struct Writer {
+    out: String,
+    eol: &'static str,
+    indent_width: usize,
+    /// Indentation level of the current line
+    level: usize,
+    at_line_start: bool,
+    /// Put a space before the next token unless it starts a line
+    separate: bool,
+}
+
+impl Writer {
+    fn new(options: &FormatOptions, eol: &'static str) -> Self {
+        Self {
+            out: String::new(),
+            eol,
+            indent_width: options.indent_width,
+            level: 0,
+            at_line_start: true,
+            separate: false,
+        }
+    }
+
+    /// Start a new line at `level` (no-op apart from the level when the
+    /// current line is still empty)
+    fn newline(&mut self, level: usize) {
+        if !self.at_line_start {
+            let trimmed = self.out.trim_end_matches(' ').len();
+            self.out.truncate(trimmed);
+            self.out.push_str(self.eol);
+            self.at_line_start = true;
+        }
+        self.level = level;
+    }
+
+    fn blank_lines(&mut self, count: usize) {
+        self.newline(self.level);
+        if self.out.is_empty() {
+            return;
+        }
+        let mut existing = 0;
+        let mut rest = self.out.as_str();
+        while let Some(stripped) = rest.strip_suffix(self.eol) {
+            existing += 1;
+            rest = stripped;
+        }
+        for _ in existing..=count {
+            self.out.push_str(self.eol);
+        }
+    }
+
+    fn token(&mut self, text: &str, space_before: bool) {
+        if self.at_line_start {
+            self.out
+                .extend(std::iter::repeat_n(' ', self.level * self.indent_width));
+        } else if space_before || self.separate {
+            self.out.push(' ');
+        }
+        self.out.push_str(text);
+        self.at_line_start = false;
+        self.separate = false;
+    }
+
+    fn finish(self) -> String {
+        self.out.trim_end().to_string()
+    }
+}
+
+struct Formatter<'a> {
+    sql: &'a str,
+    options: &'a FormatOptions,
+    w: Writer,
+}
+
+/// Last token written, for spacing decisions
+#[derive(Clone)]
+struct Prev {
+    kind: TokenKind,
+    upper: String,
+    /// A `+`/`-` sign rather than a binary operator
+    unary: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameKind {
+    /// A query scope where clause keywords start lines
+    Query,
+    /// Any other statement: written inline
+    Plain,
+    /// Inline parentheses (function arguments, IN lists, ...)
+    Paren,
+    /// Parentheses whose comma-separated items go on their own lines
+    /// (table definitions)
+    ListParen,
+    Case,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Clause {
+    None,
+    /// Comma-separated items, one per line (SELECT, GROUP BY, SET, ...)
+    List,
+    /// FROM: items and JOINs on their own lines
+    From,
+    /// WHERE/HAVING: AND/OR start lines
+    Condition,
+    /// CTE list: each definition starts a line at the clause level
+    With,
+    /// Written inline after the clause keyword
+    Inline,
+}
+
+struct Frame {
+    kind: FrameKind,
+    /// Level of the frame's clause keywords (CASE: level of `CASE`)
+    base: usize,
+    /// Level to put the closing `)` at, for subqueries and list parens
+    close_level: usize,
+    clause: Clause,
+    verb: Option<String>,
+    /// BETWEEN seen; its AND is not a condition break
+    between: bool,
+    /// Any token written in this frame yet
+    started: bool,
+}
+
+impl Frame {
+    fn new(kind: FrameKind, base: usize, close_level: usize) -> Self {
+        Self {
+            kind,
+            base,
+            close_level,
+            clause: Clause::None,
+            verb: None,
+            between: false,
+            started: false,
+        }
+    }
+}
+
+impl<'a> Formatter<'a> {
+    fn case(&self, word: &str) -> String {
+        match self.options.keyword_case {
+            KeywordCase::Upper => word.to_ascii_uppercase(),
+            KeywordCase::Lower => word.to_ascii_lowercase(),
+            KeywordCase::Preserve => word.to_string(),
+        }
+    }
+
+    /// All non-whitespace tokens in `span`, with absolute spans
+    fn tokens(&self, span: Span) -> Vec<Token<'a>> {
+        tokenize(span.text(self.sql))
+            .into_iter()
+            .filter(|t| !matches!(t.kind, TokenKind::Whitespace | TokenKind::Newline))
+            .map(|t| Token {
+                span: Span::new(t.span.start + span.start, t.span.end + span.start),
+                ..t
+            })
+            .collect()
+    }
+
+    /// Format the statements of `span`, keeping comments between them
+    fn region(&mut self, span: Span, level: usize, top: bool) {
+        let statements = parse_statements_in(self.sql, span);
+        self.statement_list(&statements, span, level, top);
+    }
+
+    fn statement_list(&mut self, statements: &[Statement], span: Span, level: usize, top: bool) {
+        let mut cursor = span.start;
+        for (i, stmt) in statements.iter().enumerate() {
+            // A stray `;` (as in `;WITH`) terminates the previous statement
+            let gap = Span::new(cursor, stmt.span.start);
+            let stray_semicolon = self
+                .tokens(gap)
+                .iter()
+                .any(|t| t.kind == TokenKind::Semicolon);
+            if i > 0 && stray_semicolon && !statements[i - 1].terminated {
+                self.w.token(";", false);
+            }
+
+            // Comments trailing the previous statement on its line stay there
+            let split = self.trailing_comments_end(Span::new(cursor, stmt.span.start));
+            self.comments(Span::new(cursor, split), level);
+            if i > 0 && top {
+                self.w.blank_lines(self.options.lines_between_statements);
+            }
+            self.comments(Span::new(split, stmt.span.start), level);
+            self.w.newline(level);
+            self.statement(stmt, level);
+            cursor = stmt.span.end;
+        }
+        self.comments(Span::new(cursor, span.end), level);
+    }
+
+    /// End of the comments in `gap` that start on the line where it begins
+    fn trailing_comments_end(&self, gap: Span) -> usize {
+        let line_end = self.sql[gap.start..gap.end]
+            .find('\n')
+            .map(|p| gap.start + p)
+            .unwrap_or(gap.end);
+        self.tokens(gap)
+            .iter()
+            .take_while(|t| t.span.start < line_end)
+            .last()
+            .map(|t| t.span.end)
+            .unwrap_or(gap.start)
+    }
+
+    /// Write the comments found in a gap between statements. A comment on the
+    /// same source line as the preceding code stays on that line.
+    fn comments(&mut self, gap: Span, level: usize) {
+        let mut cursor = gap.start;
+        for tok in self.tokens(gap).into_iter().filter(is_comment) {
+            let same_line = !self.sql[cursor..tok.span.start].contains('\n');
+            if !same_line || self.w.at_line_start {
+                self.w.newline(level);
+            }
+            self.w.token(tok.text, true);
+            if tok.kind == TokenKind::LineComment {
+                self.w.newline(level);
+            }
+            cursor = tok.span.end;
+        }
+    }
+
+    fn statement(&mut self, stmt: &Statement, level: usize) {
+        let tokens = self.tokens(stmt.span);
+        let first = tokens
+            .iter()
+            .find(|t| !is_comment(t))
+            .and_then(|t| t.keyword());
+
+        match first.as_deref() {
+            Some("BEGIN") if !stmt.children.is_empty() || is_block(&tokens) => {
+                self.block(stmt, &tokens, level)
+            }
+            Some("IF") | Some("WHILE") if !stmt.children.is_empty() => {
+                self.conditional(stmt, level)
+            }
+            _ => match stmt.module_body_as(self.sql) {
+                Some(as_span) => {
+                    self.simple(Span::new(stmt.span.start, as_span.start), level);
+                    self.w.newline(level);
+                    self.w.token(&self.case("AS"), false);
+                    self.region(Span::new(as_span.end, stmt.span.end), level, false);
+                }
+                None => self.simple(stmt.span, level),
+            },
+        }
+    }
+
+    /// `BEGIN [TRY|CATCH] ... END [TRY|CATCH]`
+    fn block(&mut self, stmt: &Statement, tokens: &[Token<'_>], level: usize) {
+        let significant: Vec<&Token<'_>> = tokens.iter().filter(|t| !is_comment(t)).collect();
+        let mut head_end = significant[0].span.end;
+        if significant
+            .get(1)
+            .is_some_and(|t| t.is_keyword("TRY") || t.is_keyword("CATCH"))
+        {
+            head_end = significant[1].span.end;
+        }
+
+        // END [TRY|CATCH] [;] at the end, when the block is closed
+        let mut tail_start = stmt.span.end;
+        let mut idx = significant.len();
+        if idx > 1 && significant[idx - 1].kind == TokenKind::Semicolon {
+            idx -= 1;
+        }
+        if idx > 1
+            && (significant[idx - 1].is_keyword("TRY") || significant[idx - 1].is_keyword("CATCH"))
+        {
+            idx -= 1;
+        }
+        if idx > 1
+            && significant[idx - 1].is_keyword("END")
+            && significant[idx - 1].span.start >= head_end
+        {
+            tail_start = significant[idx - 1].span.start;
+        }
+
+        self.simple(Span::new(stmt.span.start, head_end), level);
+        self.statement_list(
+            &stmt.children,
+            Span::new(head_end, tail_start),
+            level + 1,
+            false,
+        );
+        if tail_start < stmt.span.end {
+            self.w.newline(level);
+            self.simple(Span::new(tail_start, stmt.span.end), level);
+        }
+    }
+
+    /// `IF <cond> <stmt> [ELSE <stmt>]` / `WHILE <cond> <stmt>`
+    fn conditional(&mut self, stmt: &Statement, level: usize) {
+        let body = &stmt.children[0];
+        self.simple(Span::new(stmt.span.start, body.span.start), level);
+        self.branch(body, level);
+
+        if let Some(else_body) = stmt.children.get(1) {
+            self.w.newline(level);
+            self.simple(Span::new(body.span.end, else_body.span.start), level);
+            if else_body.is("IF") {
+                self.statement(else_body, level);
+            } else {
+                self.branch(else_body, level);
+            }
+        }
+    }
+
+    fn branch(&mut self, body: &Statement, level: usize) {
+        if body.is("BEGIN") {
+            self.w.newline(level);
+            self.statement(body, level);
+        } else {
+            self.w.newline(level + 1);
+            self.statement(body, level + 1);
+        }
+    }
+
+    /// Lay out one simple statement (or a fragment such as an IF condition)
+    fn simple(&mut self, span: Span, level: usize) {
+        let tokens = self.tokens(span);
+        let leading = tokens
+            .iter()
+            .find(|t| !is_comment(t))
+            .and_then(|t| t.keyword());
+        let root_kind = match leading.as_deref() {
+            Some("SELECT" | "INSERT" | "UPDATE" | "DELETE" | "MERGE" | "WITH") => FrameKind::Query,
+            _ => FrameKind::Plain,
+        };
+        let table_definition = matches!(leading.as_deref(), Some("CREATE" | "DECLARE"));
+        self.w.separate = true;
+
+        let mut stack = vec![Frame::new(root_kind, level, level)];
+        let mut prev: Option<Prev> = None;
+        // Between `TABLE`/`INTO` and the `(` of a column list
+        let mut name_context = false;
+        let mut table_pending = false;
+        let mut force_newline = false;
+        let mut i = 0;
+
+        while i < tokens.len() {
+            let tok = tokens[i];
+            if force_newline {
+                let level = self.w.level;
+                self.w.newline(level);
+                force_newline = false;
+            }
+
+            match tok.kind {
+                TokenKind::LineComment | TokenKind::BlockComment => {
+                    self.w.token(tok.text, true);
+                    force_newline = tok.kind == TokenKind::LineComment;
+                    i += 1;
+                    continue;
+                }
+                TokenKind::LeftParen => {
+                    let space = paren_space(prev.as_ref(), name_context);
+                    self.w.token("(", space);
+                    let next = tokens[i + 1..].iter().find(|t| !is_comment(t));
+                    if next.is_some_and(|t| t.is_keyword("SELECT") || t.is_keyword("WITH")) {
+                        let close = self.w.level;
+                        stack.push(Frame::new(FrameKind::Query, close + 1, close));
+                    } else if table_pending {
+                        let close = self.w.level;
+                        stack.push(Frame::new(FrameKind::ListParen, close + 1, close));
+                        self.w.newline(close + 1);
+                    } else {
+                        stack.push(Frame::new(FrameKind::Paren, self.w.level, self.w.level));
+                    }
+                    table_pending = false;
+                }
+                TokenKind::RightParen => {
+                    while stack.len() > 1 {
+                        let frame = stack.pop().expect("stack has more than one frame");
+                        match frame.kind {
+                            FrameKind::Query | FrameKind::ListParen => {
+                                self.w.newline(frame.close_level);
+                                break;
+                            }
+                            FrameKind::Paren => break,
+                            _ => {}
+                        }
+                    }
+                    self.w.token(")", false);
+                }
+                TokenKind::Comma => {
+                    self.comma(stack.last().expect("root frame"));
+                }
+                TokenKind::Semicolon | TokenKind::Dot => {
+                    self.w.token(tok.text, false);
+                }
+                TokenKind::Operator => {
+                    // A label's colon stays attached (`retry:`)
+                    let space = tok.text != ":"
+                        && prev.as_ref().is_some_and(|p| {
+                            !p.unary && !matches!(p.kind, TokenKind::LeftParen | TokenKind::Dot)
+                        });
+                    self.w.token(tok.text, space);
+                    let unary = matches!(tok.text, "-" | "+" | "~")
+                        && prev.as_ref().is_none_or(|p| {
+                            matches!(
+                                p.kind,
+                                TokenKind::Operator | TokenKind::LeftParen | TokenKind::Comma
+                            ) || is_reserved(&p.upper)
+                        });
+                    prev = Some(Prev {
+                        kind: tok.kind,
+                        upper: tok.text.to_string(),
+                        unary,
+                    });
+                    i += 1;
+                    continue;
+                }
+                TokenKind::Word => {
+                    i = self.word(&tokens, i, &mut stack, prev.as_ref());
+                    let upper = tokens[i].text.to_ascii_uppercase();
+                    if upper == "TABLE" && table_definition {
+                        table_pending = true;
+                    }
+                    name_context = matches!(upper.as_str(), "INTO" | "TABLE" | "VIEW")
+                        || (name_context && !is_reserved(&upper));
+                    prev = Some(Prev {
+                        kind: tok.kind,
+                        upper,
+                        unary: false,
+                    });
+                    i += 1;
+                    continue;
+                }
+                _ => {
+                    let space = default_space(prev.as_ref());
+                    self.w.token(tok.text, space);
+                    name_context = name_context
+                        && matches!(tok.kind, TokenKind::QuotedIdentifier | TokenKind::Dot);
+                }
+            }
+
+            if !matches!(tok.kind, TokenKind::Dot | TokenKind::QuotedIdentifier) {
+                name_context = false;
+            }
+            if let Some(frame) = stack.last_mut() {
+                frame.started = true;
+            }
+            prev = Some(Prev {
+                kind: tok.kind,
+                upper: tok.text.to_string(),
+                unary: false,
+            });
+            i += 1;
+        }
+    }
+
+    fn comma(&mut self, frame: &Frame) {
+        let break_level = match (frame.kind, frame.clause) {
+            (FrameKind::Query, Clause::List | Clause::From) => Some(frame.base + 1),
+            (FrameKind::Query, Clause::With) => Some(frame.base),
+            (FrameKind::ListParen, _) => Some(frame.base),
+            _ => None,
+        };
+
+        match (break_level, self.options.comma_style) {
+            (Some(level), CommaStyle::Trailing) => {
+                self.w.token(",", false);
+                self.w.newline(level);
+            }
+            (Some(level), CommaStyle::Leading) => {
+                self.w.newline(level);
+                self.w.token(",", false);
+            }
+            (None, _) => self.w.token(",", false),
+        }
+    }
+
+    /// Write the word at `i` (and any words it combines with, such as
+    /// `GROUP BY` or `LEFT OUTER JOIN`); returns the index of the last word
+    /// consumed
+    fn word(
+        &mut self,
+        tokens: &[Token<'_>],
+        i: usize,
+        stack: &mut Vec<Frame>,
+        prev: Option<&Prev>,
+    ) -> usize {
+        let tok = tokens[i];
+        let kw = tok.text.to_ascii_uppercase();
+        let after_dot = prev.is_some_and(|p| p.kind == TokenKind::Dot)
+            || tokens.get(i + 1).is_some_and(|t| t.kind == TokenKind::Dot);
+        let text = if !after_dot && (is_reserved(&kw) || is_non_reserved_keyword(&kw)) {
+            self.case(tok.text)
+        } else {
+            tok.text.to_string()
+        };
+        let next_is =
+            |offset: usize, word: &str| tokens.get(i + offset).is_some_and(|t| t.is_keyword(word));
+        let space = default_space(prev);
+
+        if after_dot {
+            self.w.token(&text, space);
+            return i;
+        }
+
+        let frame_kind = stack.last().map(|f| f.kind);
+
+        // CASE expressions nest anywhere
+        if kw == "CASE" {
+            self.w.token(&text, space);
+            let level = self.w.level;
+            stack.push(Frame::new(FrameKind::Case, level, level));
+            return i;
+        }
+        if frame_kind == Some(FrameKind::Case) {
+            let base = stack.last().map(|f| f.base).unwrap_or(0);
+            match kw.as_str() {
+                "WHEN" | "ELSE" => {
+                    self.w.newline(base + 1);
+                    self.w.token(&text, false);
+                    return i;
+                }
+                "END" => {
+                    self.w.newline(base);
+                    self.w.token(&text, false);
+                    stack.pop();
+                    return i;
+                }
+                _ => {}
+            }
+        }
+
+        if frame_kind != Some(FrameKind::Query) {
+            self.w.token(&text, space);
+            return i;
+        }
+
+        let frame = stack.last_mut().expect("query frame");
+        let base = frame.base;
+        let verb = frame.verb.clone();
+        let started = frame.started;
+        frame.started = true;
+
+        match kw.as_str() {
+            "SELECT" => {
+                self.w.newline(base);
+                self.w.token(&text, false);
+                let last = self.select_modifiers(tokens, i);
+                frame.clause = Clause::List;
+                frame.verb.get_or_insert_with(|| "SELECT".to_string());
+                self.w.newline(base + 1);
+                last
+            }
+            "FROM" | "WHERE" | "HAVING" => {
+                self.w.newline(base);
+                self.w.token(&text, false);
+                frame.clause = match kw.as_str() {
+                    "FROM" => Clause::From,
+                    _ => Clause::Condition,
+                };
+                frame.between = false;
+                self.w.newline(base + 1);
+                i
+            }
+            "GROUP" | "ORDER" if next_is(1, "BY") => {
+                self.w.newline(base);
+                self.w.token(&text, false);
+                self.w.token(&self.case("BY"), true);
+                frame.clause = Clause::List;
+                self.w.newline(base + 1);
+                i + 1
+            }
+            "UNION" | "EXCEPT" | "INTERSECT" => {
+                self.w.newline(base);
+                self.w.token(&text, false);
+                frame.clause = Clause::None;
+                if kw == "UNION" && next_is(1, "ALL") {
+                    self.w.token(&self.case("ALL"), true);
+                    return i + 1;
+                }
+                i
+            }
+            "INSERT" | "DELETE" | "MERGE" | "UPDATE" if verb.is_none() => {
+                self.w.newline(base);
+                self.w.token(&text, false);
+                frame.clause = Clause::Inline;
+                frame.verb = Some(kw.clone());
+                let joined = match kw.as_str() {
+                    "INSERT" | "MERGE" => "INTO",
+                    "DELETE" => "FROM",
+                    _ => "",
+                };
+                if !joined.is_empty() && next_is(1, joined) {
+                    self.w.token(&self.case(joined), true);
+                    return i + 1;
+                }
+                i
+            }
+            "VALUES" if verb.as_deref() == Some("INSERT") => {
+                self.w.newline(base);
+                self.w.token(&text, false);
+                frame.clause = Clause::List;
+                self.w.newline(base + 1);
+                i
+            }
+            "SET" if verb.as_deref() == Some("UPDATE") => {
+                self.w.newline(base);
+                self.w.token(&text, false);
+                frame.clause = Clause::List;
+                self.w.newline(base + 1);
+                i
+            }
+            "INTO" if verb.as_deref() == Some("SELECT") => {
+                self.w.newline(base);
+                self.w.token(&text, false);
+                frame.clause = Clause::Inline;
+                i
+            }
+            "USING" | "WHEN" if verb.as_deref() == Some("MERGE") => {
+                self.w.newline(base);
+                self.w.token(&text, false);
+                frame.clause = Clause::Inline;
+                i
+            }
+            "OUTPUT" | "OPTION" | "OFFSET" => {
+                self.w.newline(base);
+                self.w.token(&text, false);
+                frame.clause = Clause::Inline;
+                i
+            }
+            "FOR"
+                if tokens.get(i + 1).is_some_and(|t| {
+                    t.is_keyword("XML") || t.is_keyword("JSON") || t.is_keyword("BROWSE")
+                }) =>
+            {
+                self.w.newline(base);
+                self.w.token(&text, false);
+                frame.clause = Clause::Inline;
+                i
+            }
+            "WITH" if !started => {
+                self.w.token(&text, space);
+                frame.clause = Clause::With;
+                i
+            }
+            "AND" | "OR" if frame.clause == Clause::Condition => {
+                if kw == "AND" && frame.between {
+                    frame.between = false;
+                    self.w.token(&text, space);
+                } else {
+                    self.w.newline(base + 1);
+                    self.w.token(&text, false);
+                }
+                i
+            }
+            "BETWEEN" => {
+                frame.between = true;
+                self.w.token(&text, space);
+                i
+            }
+            _ if frame.clause == Clause::From => match join_length(tokens, i) {
+                Some(len) => {
+                    self.w.newline(base + 1);
+                    for (n, t) in tokens[i..i + len].iter().enumerate() {
+                        self.w.token(&self.case(t.text), n > 0);
+                    }
+                    i + len - 1
+                }
+                None => {
+                    self.w.token(&text, space);
+                    i
+                }
+            },
+            _ => {
+                self.w.token(&text, space);
+                i
+            }
+        }
+    }
+
+    /// `DISTINCT`/`ALL`, `TOP n`/`TOP (n)`, `PERCENT`, `WITH TIES` stay on
+    /// the SELECT line. Returns the index of the last token consumed.
+    fn select_modifiers(&mut self, tokens: &[Token<'_>], select: usize) -> usize {
+        let mut i = select;
+        if tokens
+            .get(i + 1)
+            .is_some_and(|t| t.is_keyword("DISTINCT") || t.is_keyword("ALL"))
+        {
+            i += 1;
+            self.w.token(&self.case(tokens[i].text), true);
+        }
+        if !tokens.get(i + 1).is_some_and(|t| t.is_keyword("TOP")) {
+            return i;
+        }
+        i += 1;
+        self.w.token(&self.case(tokens[i].text), true);
+
+        match tokens.get(i + 1).map(|t| t.kind) {
+            Some(TokenKind::Number) | Some(TokenKind::Variable) => {
+                i += 1;
+                self.w.token(tokens[i].text, true);
+            }
+            Some(TokenKind::LeftParen) => {
+                let mut depth = 0usize;
+                while let Some(t) = tokens.get(i + 1) {
+                    let space = match t.kind {
+                        TokenKind::LeftParen => tokens[i].is_keyword("TOP"),
+                        TokenKind::RightParen => false,
+                        _ => tokens[i].kind != TokenKind::LeftParen,
+                    };
+                    i += 1;
+                    self.w.token(t.text, space);
+                    match t.kind {
+                        TokenKind::LeftParen => depth += 1,
+                        TokenKind::RightParen => {
+                            depth = depth.saturating_sub(1);
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        if tokens.get(i + 1).is_some_and(|t| t.is_keyword("PERCENT")) {
+            i += 1;
+            self.w.token(&self.case(tokens[i].text), true);
+        }
+        if tokens.get(i + 1).is_some_and(|t| t.is_keyword("WITH"))
+            && tokens.get(i + 2).is_some_and(|t| t.is_keyword("TIES"))
+        {
+            self.w.token(&self.case("WITH"), true);
+            self.w.token(&self.case("TIES"), true);
+            i += 2;
+        }
+        i
+    }
+}
+
+fn is_comment(tok: &Token<'_>) -> bool {
+    matches!(tok.kind, TokenKind::LineComment | TokenKind::BlockComment)
+}
+
+/// `BEGIN` that opens a block (not `BEGIN TRAN` and friends)
+fn is_block(tokens: &[Token<'_>]) -> bool {
+    let mut significant = tokens.iter().filter(|t| !is_comment(t));
+    significant.next();
+    !significant.next().is_some_and(|t| {
+        [
+            "TRAN",
+            "TRANSACTION",
+            "DISTRIBUTED",
+            "DIALOG",
+            "CONVERSATION",
+        ]
+        .iter()
+        .any(|k| t.is_keyword(k))
+    })
+}
+
+/// Number of words in a JOIN/APPLY operator starting at `i`
+fn join_length(tokens: &[Token<'_>], i: usize) -> Option<usize> {
+    let word = |offset: usize| tokens.get(i + offset).and_then(|t| t.keyword());
+    let first = word(0)?;
+    match first.as_str() {
+        "JOIN" => Some(1),
+        "INNER" | "LEFT" | "RIGHT" | "FULL" | "CROSS" | "OUTER" => match word(1).as_deref() {
+            Some("JOIN") => Some(2),
+            Some("APPLY") if first == "CROSS" || first == "OUTER" => Some(2),
+            Some("OUTER") if word(2).as_deref() == Some("JOIN") => Some(3),
+            // join hints: INNER HASH JOIN, LEFT OUTER MERGE JOIN, ...
+            Some("HASH" | "LOOP" | "MERGE" | "REMOTE") if word(2).as_deref() == Some("JOIN") => {
+                Some(3)
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn default_space(prev: Option<&Prev>) -> bool {
+    prev.is_some_and(|p| !p.unary && !matches!(p.kind, TokenKind::LeftParen | TokenKind::Dot))
+}
+
+/// Space before `(`: after keywords and operators, but not for function
+/// calls, except a table name followed by its column list
+fn paren_space(prev: Option<&Prev>, name_context: bool) -> bool {
+    let Some(p) = prev else {
+        return false;
+    };
+    if p.unary {
+        return false;
+    }
+    match p.kind {
+        TokenKind::Word => {
+            name_context
+                || (is_reserved(&p.upper) && !FUNCTION_KEYWORDS.contains(&p.upper.as_str()))
+        }
+        TokenKind::QuotedIdentifier => name_context,
+        TokenKind::LeftParen | TokenKind::Dot => false,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fmt(sql: &str) -> String {
+        format_sql(sql, &FormatOptions::default())
+    }
+
+    #[test]
+    fn formats_select_lists_and_clauses() {
+        let out = fmt(
+            "select a, b.c as d from dbo.t b where a = 1 and c between 1 and 2 order by a desc",
+        );
+        assert_eq!(
+            out,
+            "SELECT\n  a,\n  b.c AS d\nFROM\n  dbo.t b\nWHERE\n  a = 1\n  AND c BETWEEN 1 AND 2\nORDER BY\n  a DESC"
+        );
+    }
+
+    #[test]
+    fn keeps_modifiers_on_select_line_and_joins_indented() {
+        let out = fmt("SELECT DISTINCT TOP (10) x.id FROM x LEFT OUTER JOIN y ON y.id = x.id CROSS APPLY f(x.id) z");
+        assert_eq!(
+            out,
+            "SELECT DISTINCT TOP (10)\n  x.id\nFROM\n  x\n  LEFT OUTER JOIN y ON y.id = x.id\n  CROSS APPLY f(x.id) z"
+        );
+    }
+
+    #[test]
+    fn indents_case_and_subqueries() {
+        let out = fmt("SELECT CASE WHEN a > 0 THEN 'p' ELSE 'n' END AS s FROM (SELECT a FROM t) q");
+        assert_eq!(
+            out,
+            "SELECT\n  CASE\n    WHEN a > 0 THEN 'p'\n    ELSE 'n'\n  END AS s\nFROM\n  (\n    SELECT\n      a\n    FROM\n      t\n  ) q"
+        );
+    }
+
+    #[test]
+    fn leading_comma_style() {
+        let options = FormatOptions {
+            comma_style: CommaStyle::Leading,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_sql("SELECT a, b FROM t", &options),
+            "SELECT\n  a\n  , b\nFROM\n  t"
+        );
+    }
+
+    #[test]
+    fn keyword_case_options_leave_identifiers_and_strings_alone() {
+        let options = FormatOptions {
+            keyword_case: KeywordCase::Lower,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_sql("SELECT [From], 'SELECT' FROM t.[Order]", &options),
+            "select\n  [From],\n  'SELECT'\nfrom\n  t.[Order]"
+        );
+    }
+
+    #[test]
+    fn formats_dml_statements() {
+        assert_eq!(
+            fmt("update t set a = 1, b = -2 where id = @id"),
+            "UPDATE t\nSET\n  a = 1,\n  b = -2\nWHERE\n  id = @id"
+        );
+        assert_eq!(
+            fmt("insert into dbo.t (a, b) values (1, 2), (3, 4)"),
+            "INSERT INTO dbo.t (a, b)\nVALUES\n  (1, 2),\n  (3, 4)"
+        );
+        assert_eq!(
+            fmt("delete from t where x is null"),
+            "DELETE FROM t\nWHERE\n  x IS NULL"
+        );
+    }
+
+    #[test]
+    fn formats_blocks_conditionals_and_batches() {
+        let sql = "if @x = 1 begin select 1; print 'a' end else select 2\ngo\nselect 3";
+        assert_eq!(
+            fmt(sql),
+            "IF @x = 1\nBEGIN\n  SELECT\n    1;\n  PRINT 'a'\nEND\nELSE\n  SELECT\n    2\nGO\n\nSELECT\n  3"
+        );
+    }
+
+    #[test]
+    fn formats_procedure_bodies_and_table_definitions() {
+        let sql = "create procedure dbo.p @a int as begin set NOCOUNT on; select @a end";
+        assert_eq!(
+            fmt(sql),
+            "CREATE PROCEDURE dbo.p @a int\nAS\nBEGIN\n  SET NOCOUNT ON;\n  SELECT\n    @a\nEND"
+        );
+        assert_eq!(
+            fmt("create table dbo.t (id int not null, name nvarchar(50))"),
+            "CREATE TABLE dbo.t (\n  id int NOT NULL,\n  name nvarchar(50)\n)"
+        );
+    }
+
+    #[test]
+    fn else_if_stays_on_one_line() {
+        assert_eq!(
+            fmt("IF @a = 1 PRINT 'a' ELSE IF @a = 2 PRINT 'b'"),
+            "IF @a = 1\n  PRINT 'a'\nELSE IF @a = 2\n  PRINT 'b'"
+        );
+    }
+
+    #[test]
+    fn trailing_comment_stays_with_its_statement() {
+        assert_eq!(fmt("PRINT 1 -- one\nPRINT 2"), "PRINT 1 -- one\n\nPRINT 2");
+    }
+
+    #[test]
+    fn stray_semicolons_attach_to_the_previous_statement() {
+        assert_eq!(
+            fmt("PRINT 1\n;WITH c AS (SELECT 1 a) SELECT a FROM c")
+                .lines()
+                .next(),
+            Some("PRINT 1;")
+        );
+        assert!(!fmt("PRINT 1;\n;PRINT 2").contains(";;"));
+    }
+
+    #[test]
+    fn preserves_comments() {
+        let sql = "-- header\nSELECT a -- first\n, b /* second */ FROM t";
+        let out = fmt(sql);
+        assert!(out.starts_with("-- header\nSELECT\n  a -- first\n"));
+        assert!(out.contains("/* second */"));
+    }
+
+    #[test]
+    fn keeps_dollar_identifiers_together() {
+        let out = fmt("merge t using s on t.id = s.id when matched then delete output $action;");
+        assert!(out.ends_with("OUTPUT $action;"), "{}", out);
+        assert_eq!(
+            fmt("select $partition.pf(1), $IDENTITY from t"),
+            "SELECT\n  $partition.pf(1),\n  $IDENTITY\nFROM\n  t"
+        );
+    }
+
+    #[test]
+    fn keeps_label_colons_attached() {
+        let out = fmt("retry: print 1\ngoto retry");
+        assert!(out.starts_with("retry:"), "{}", out);
+        assert!(!out.contains(" :"), "{}", out);
+    }
+
+    #[test]
+    fn cases_non_reserved_keywords() {
+        assert_eq!(
+            fmt("select sum(x) over (partition by g order by d rows between unbounded preceding and current row) from t"),
+            "SELECT\n  sum(x) OVER (PARTITION BY g ORDER BY d ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW)\nFROM\n  t"
+        );
+        assert_eq!(fmt("set nocount on"), "SET NOCOUNT ON");
+        assert_eq!(fmt("select t.rows from t"), "SELECT\n  t.rows\nFROM\n  t");
+    }
+
+    #[test]
+    fn keeps_crlf_line_endings() {
+        let out = fmt("select a, b from t\r\ngo\r\nprint 'x\ny'\r\n");
+        assert_eq!(
+            out,
+            "SELECT\r\n  a,\r\n  b\r\nFROM\r\n  t\r\nGO\r\n\r\nPRINT 'x\ny'\r\n"
+        );
+        assert!(!fmt("select 1\nfrom t").contains('\r'));
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let sql = "with c as (select id, count(*) n from t group by id) select * from c where n > 1 union all select 1, 2";
+        let once = fmt(sql);
+        assert_eq!(fmt(&once), once);
+    }
+
+    #[test]
+    fn edits_are_empty_for_formatted_text() {
+        let formatted = fmt("select 1");
+        assert!(format_edits(&formatted, &FormatOptions::default()).is_empty());
+        let edits = format_edits("select 1", &FormatOptions::default());
+        assert_eq!(edits.len(), 1);
+    }
+}
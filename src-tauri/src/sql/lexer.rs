@@ -0,0 +1,723 @@
+// T-SQL Lexer
+// Splits SQL text into tokens with byte spans. Strings, comments and quoted
+// identifiers are single tokens, so nothing downstream has to re-scan them.
+
+use serde::{Deserialize, Serialize};
+
+/// Byte range into the source text (`start..end`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Slice the span out of the text it was produced from
+    pub fn text<'a>(&self, source: &'a str) -> &'a str {
+        &source[self.start..self.end]
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TokenKind {
+    /// Spaces and tabs (newlines are their own token)
+    Whitespace,
+    Newline,
+    /// `-- ...` up to (not including) the end of the line
+    LineComment,
+    /// `/* ... */`, which nests in T-SQL
+    BlockComment,
+    /// `'...'` or `N'...'` with `''` escapes
+    String,
+    /// Bare word: keyword, identifier, or `#temp` / `##global` table name
+    Word,
+    /// `[...]` or `"..."` delimited identifier
+    QuotedIdentifier,
+    /// `@local` or `@@system` variable
+    Variable,
+    /// Integer, decimal, float or `0x` binary literal
+    Number,
+    /// Operators such as `=`, `<>`, `+=`, `::`
+    Operator,
+    LeftParen,
+    RightParen,
+    Comma,
+    Dot,
+    Semicolon,
+    /// Anything the lexer doesn't recognize, including an unterminated
+    /// string/comment/identifier (which runs to the end of the input)
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    pub span: Span,
+}
+
+impl<'a> Token<'a> {
+    /// Whitespace, newlines and comments
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self.kind,
+            TokenKind::Whitespace
+                | TokenKind::Newline
+                | TokenKind::LineComment
+                | TokenKind::BlockComment
+        )
+    }
+
+    /// Case-insensitive keyword check for bare words
+    pub fn is_keyword(&self, keyword: &str) -> bool {
+        self.kind == TokenKind::Word && self.text.eq_ignore_ascii_case(keyword)
+    }
+
+    /// Upper-cased word text, or None for anything that isn't a bare word
+    pub fn keyword(&self) -> Option<String> {
+        if self.kind == TokenKind::Word {
+            Some(self.text.to_ascii_uppercase())
+        } else {
+            None
+        }
+    }
+
+    /// `#name` or `##name` temp table reference
+    pub fn is_temp_table(&self) -> bool {
+        self.kind == TokenKind::Word
+            && self.text.starts_with('#')
+            && !self.text.trim_start_matches('#').is_empty()
+    }
+
+    /// Identifier text with `[]`/`""` delimiters removed and escapes resolved
+    pub fn identifier(&self) -> String {
+        unquote_identifier(self.text)
+    }
+}
+
+/// T-SQL reserved words plus a few unambiguous non-reserved keywords
+const KEYWORDS: &[&str] = &[
+    "ADD",
+    "ALL",
+    "ALTER",
+    "AND",
+    "ANY",
+    "APPLY",
+    "AS",
+    "ASC",
+    "AUTHORIZATION",
+    "BACKUP",
+    "BEGIN",
+    "BETWEEN",
+    "BREAK",
+    "BROWSE",
+    "BULK",
+    "BY",
+    "CASCADE",
+    "CASE",
+    "CATCH",
+    "CHECK",
+    "CHECKPOINT",
+    "CLOSE",
+    "CLUSTERED",
+    "COALESCE",
+    "COLLATE",
+    "COLUMN",
+    "COMMIT",
+    "COMPUTE",
+    "CONSTRAINT",
+    "CONTAINS",
+    "CONTAINSTABLE",
+    "CONTINUE",
+    "CONVERT",
+    "CREATE",
+    "CROSS",
+    "CURRENT",
+    "CURRENT_DATE",
+    "CURRENT_TIME",
+    "CURRENT_TIMESTAMP",
+    "CURRENT_USER",
+    "CURSOR",
+    "DATABASE",
+    "DBCC",
+    "DEALLOCATE",
+    "DECLARE",
+    "DEFAULT",
+    "DELETE",
+    "DENY",
+    "DESC",
+    "DISTINCT",
+    "DISTRIBUTED",
+    "DROP",
+    "ELSE",
+    "END",
+    "ESCAPE",
+    "EXCEPT",
+    "EXEC",
+    "EXECUTE",
+    "EXISTS",
+    "EXIT",
+    "EXTERNAL",
+    "FETCH",
+    "FILLFACTOR",
+    "FOR",
+    "FOREIGN",
+    "FREETEXT",
+    "FREETEXTTABLE",
+    "FROM",
+    "FULL",
+    "FUNCTION",
+    "GOTO",
+    "GRANT",
+    "GROUP",
+    "HAVING",
+    "HOLDLOCK",
+    "IDENTITY",
+    "IDENTITY_INSERT",
+    "IF",
+    "IN",
+    "INDEX",
+    "INNER",
+    "INSERT",
+    "INTERSECT",
+    "INTO",
+    "IS",
+    "JOIN",
+    "KEY",
+    "KILL",
+    "LEFT",
+    "LIKE",
+    "MATCHED",
+    "MERGE",
+    "NOCHECK",
+    "NONCLUSTERED",
+    "NOT",
+    "NULL",
+    "NULLIF",
+    "OF",
+    "OFF",
+    "ON",
+    "OPEN",
+    "OPENDATASOURCE",
+    "OPENQUERY",
+    "OPENROWSET",
+    "OPENXML",
+    "OPTION",
+    "OR",
+    "ORDER",
+    "OUTER",
+    "OVER",
+    "PERCENT",
+    "PIVOT",
+    "PRIMARY",
+    "PRINT",
+    "PROC",
+    "PROCEDURE",
+    "RAISERROR",
+    "RECONFIGURE",
+    "REFERENCES",
+    "RESTORE",
+    "RETURN",
+    "RETURNS",
+    "REVERT",
+    "REVOKE",
+    "RIGHT",
+    "ROLLBACK",
+    "ROWCOUNT",
+    "SAVE",
+    "SCHEMA",
+    "SELECT",
+    "SET",
+    "STATISTICS",
+    "TABLE",
+    "TABLESAMPLE",
+    "THEN",
+    "THROW",
+    "TO",
+    "TOP",
+    "TRAN",
+    "TRANSACTION",
+    "TRIGGER",
+    "TRUNCATE",
+    "TRY",
+    "TRY_CONVERT",
+    "UNION",
+    "UNIQUE",
+    "UNPIVOT",
+    "UPDATE",
+    "USE",
+    "VALUES",
+    "VIEW",
+    "WAITFOR",
+    "WHEN",
+    "WHERE",
+    "WHILE",
+    "WITH",
+    "WITHIN",
+];
+
+/// Whether an upper-cased word is a keyword rather than an identifier
+pub fn is_reserved(upper: &str) -> bool {
+    KEYWORDS.contains(&upper)
+}
+
+/// Non-reserved keywords: cased like keywords by the formatter, but still
+/// valid identifiers, so they don't end names or aliases
+const NON_RESERVED_KEYWORDS: &[&str] = &[
+    "FOLLOWING",
+    "INCLUDE",
+    "NEXT",
+    "NOCOUNT",
+    "NOLOCK",
+    "OFFSET",
+    "ONLY",
+    "OUTPUT",
+    "PARTITION",
+    "PRECEDING",
+    "RANGE",
+    "READONLY",
+    "ROW",
+    "ROWS",
+    "UNBOUNDED",
+    "USING",
+    "XACT_ABORT",
+];
+
+/// Whether an upper-cased word is a non-reserved keyword
+pub fn is_non_reserved_keyword(upper: &str) -> bool {
+    NON_RESERVED_KEYWORDS.contains(&upper)
+}
+
+/// Remove `[...]` / `"..."` delimiters from an identifier, resolving doubled
+/// closing delimiters. Bare identifiers are returned unchanged.
+pub fn unquote_identifier(text: &str) -> String {
+    if text.len() >= 2 && text.starts_with('[') && text.ends_with(']') {
+        text[1..text.len() - 1].replace("]]", "]")
+    } else if text.len() >= 2 && text.starts_with('"') && text.ends_with('"') {
+        text[1..text.len() - 1].replace("\"\"", "\"")
+    } else {
+        text.to_string()
+    }
+}
+
+/// Wrap an identifier in `[...]`, doubling any closing bracket
+pub fn quote_identifier(name: &str) -> String {
+    format!("[{}]", name.replace(']', "]]"))
+}
+
+/// Tokenize T-SQL text. Every byte of the input belongs to exactly one token,
+/// so concatenating the token texts reproduces the input.
+pub fn tokenize(sql: &str) -> Vec<Token<'_>> {
+    let bytes = sql.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0usize;
+
+    while i < bytes.len() {
+        let start = i;
+        let b = bytes[i];
+        let next = bytes.get(i + 1).copied();
+
+        let kind = match b {
+            b'\n' => {
+                i += 1;
+                TokenKind::Newline
+            }
+            b'\r' if next == Some(b'\n') => {
+                i += 2;
+                TokenKind::Newline
+            }
+            b' ' | b'\t' | b'\r' | 0x0b | 0x0c => {
+                i += 1;
+                while i < bytes.len() && is_inline_space(bytes, i) {
+                    i += 1;
+                }
+                TokenKind::Whitespace
+            }
+            b'-' if next == Some(b'-') => {
+                while i < bytes.len()
+                    && bytes[i] != b'\n'
+                    && !(bytes[i] == b'\r' && bytes.get(i + 1) == Some(&b'\n'))
+                {
+                    i += 1;
+                }
+                TokenKind::LineComment
+            }
+            b'/' if next == Some(b'*') => {
+                let (end, closed) = scan_block_comment(bytes, i);
+                i = end;
+                if closed {
+                    TokenKind::BlockComment
+                } else {
+                    TokenKind::Unknown
+                }
+            }
+            b'\'' => {
+                let (end, closed) = scan_delimited(bytes, i + 1, b'\'');
+                i = end;
+                if closed {
+                    TokenKind::String
+                } else {
+                    TokenKind::Unknown
+                }
+            }
+            b'N' | b'n' if next == Some(b'\'') => {
+                let (end, closed) = scan_delimited(bytes, i + 2, b'\'');
+                i = end;
+                if closed {
+                    TokenKind::String
+                } else {
+                    TokenKind::Unknown
+                }
+            }
+            b'[' => {
+                let (end, closed) = scan_delimited(bytes, i + 1, b']');
+                i = end;
+                if closed {
+                    TokenKind::QuotedIdentifier
+                } else {
+                    TokenKind::Unknown
+                }
+            }
+            b'"' => {
+                let (end, closed) = scan_delimited(bytes, i + 1, b'"');
+                i = end;
+                if closed {
+                    TokenKind::QuotedIdentifier
+                } else {
+                    TokenKind::Unknown
+                }
+            }
+            b'@' => {
+                i += 1;
+                if bytes.get(i) == Some(&b'@') {
+                    i += 1;
+                }
+                i = scan_word(sql, i);
+                TokenKind::Variable
+            }
+            b'#' => {
+                i += 1;
+                if bytes.get(i) == Some(&b'#') {
+                    i += 1;
+                }
+                i = scan_word(sql, i);
+                TokenKind::Word
+            }
+            // $action, $PARTITION, $IDENTITY, $ROWGUID
+            b'$' if i + 1 < bytes.len() && is_word_start(sql, i + 1) => {
+                i = scan_word(sql, i + 1);
+                TokenKind::Word
+            }
+            b'0' if matches!(next, Some(b'x') | Some(b'X')) => {
+                i += 2;
+                while i < bytes.len() && bytes[i].is_ascii_hexdigit() {
+                    i += 1;
+                }
+                TokenKind::Number
+            }
+            b'0'..=b'9' => {
+                i = scan_number(bytes, i);
+                TokenKind::Number
+            }
+            b'.' if next.is_some_and(|c| c.is_ascii_digit()) => {
+                i = scan_number(bytes, i);
+                TokenKind::Number
+            }
+            b'(' => {
+                i += 1;
+                TokenKind::LeftParen
+            }
+            b')' => {
+                i += 1;
+                TokenKind::RightParen
+            }
+            b',' => {
+                i += 1;
+                TokenKind::Comma
+            }
+            b'.' => {
+                i += 1;
+                TokenKind::Dot
+            }
+            b';' => {
+                i += 1;
+                TokenKind::Semicolon
+            }
+            b'<' | b'>' | b'!' | b'=' | b'+' | b'-' | b'*' | b'/' | b'%' | b'&' | b'|' | b'^'
+            | b'~' | b':' => {
+                i += operator_len(b, next);
+                TokenKind::Operator
+            }
+            _ if is_word_start(sql, i) => {
+                i = scan_word(sql, i);
+                TokenKind::Word
+            }
+            _ => {
+                // Advance one whole character so spans stay on UTF-8 boundaries
+                let c = sql[i..].chars().next().unwrap_or('\0');
+                i += c.len_utf8().max(1);
+                if c.is_whitespace() {
+                    TokenKind::Whitespace
+                } else {
+                    TokenKind::Unknown
+                }
+            }
+        };
+
+        tokens.push(Token {
+            kind,
+            text: &sql[start..i],
+            span: Span::new(start, i),
+        });
+    }
+
+    tokens
+}
+
+/// Tokenize and drop whitespace and comments
+pub fn significant_tokens(sql: &str) -> Vec<Token<'_>> {
+    tokenize(sql)
+        .into_iter()
+        .filter(|t| !t.is_trivia())
+        .collect()
+}
+
+/// 1-based line number of a byte offset
+pub fn line_of(source: &str, offset: usize) -> usize {
+    let offset = offset.min(source.len());
+    source.as_bytes()[..offset]
+        .iter()
+        .filter(|&&b| b == b'\n')
+        .count()
+        + 1
+}
+
+/// 1-based (line, column) of a byte offset; columns count characters
+pub fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let offset = offset.min(source.len());
+    let line_start = source[..offset].rfind('\n').map(|p| p + 1).unwrap_or(0);
+    (
+        line_of(source, offset),
+        source[line_start..offset].chars().count() + 1,
+    )
+}
+
+fn is_inline_space(bytes: &[u8], i: usize) -> bool {
+    match bytes[i] {
+        b' ' | b'\t' | 0x0b | 0x0c => true,
+        b'\r' => bytes.get(i + 1) != Some(&b'\n'),
+        _ => false,
+    }
+}
+
+fn is_word_start(sql: &str, i: usize) -> bool {
+    sql[i..]
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+}
+
+fn scan_word(sql: &str, mut i: usize) -> usize {
+    for c in sql[i..].chars() {
+        if c.is_alphanumeric() || c == '_' || c == '@' || c == '#' || c == '$' {
+            i += c.len_utf8();
+        } else {
+            break;
+        }
+    }
+    i
+}
+
+fn scan_number(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() && bytes[i].is_ascii_digit() {
+        i += 1;
+    }
+    if i < bytes.len() && bytes[i] == b'.' {
+        i += 1;
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            i += 1;
+        }
+    }
+    if i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+        let mut j = i + 1;
+        if j < bytes.len() && (bytes[j] == b'+' || bytes[j] == b'-') {
+            j += 1;
+        }
+        if j < bytes.len() && bytes[j].is_ascii_digit() {
+            i = j;
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
+            }
+        }
+    }
+    i
+}
+
+/// Scan up to and including `close`, treating a doubled `close` as an escape.
+/// Returns the end offset and whether the closing delimiter was found.
+fn scan_delimited(bytes: &[u8], mut i: usize, close: u8) -> (usize, bool) {
+    while i < bytes.len() {
+        if bytes[i] == close {
+            if bytes.get(i + 1) == Some(&close) {
+                i += 2;
+                continue;
+            }
+            return (i + 1, true);
+        }
+        i += 1;
+    }
+    (bytes.len(), false)
+}
+
+/// Scan a (possibly nested) block comment starting at `/*`
+fn scan_block_comment(bytes: &[u8], mut i: usize) -> (usize, bool) {
+    let mut depth = 0usize;
+    while i < bytes.len() {
+        if bytes[i] == b'/' && bytes.get(i + 1) == Some(&b'*') {
+            depth += 1;
+            i += 2;
+        } else if bytes[i] == b'*' && bytes.get(i + 1) == Some(&b'/') {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return (i, true);
+            }
+        } else {
+            i += 1;
+        }
+    }
+    (bytes.len(), false)
+}
+
+fn operator_len(b: u8, next: Option<u8>) -> usize {
+    match (b, next) {
+        (b'<', Some(b'=' | b'>')) => 2,
+        (b'>', Some(b'=')) => 2,
+        (b'!', Some(b'=' | b'<' | b'>')) => 2,
+        (b':', Some(b':')) => 2,
+        (b'+' | b'-' | b'*' | b'/' | b'%' | b'&' | b'|' | b'^', Some(b'=')) => 2,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(sql: &str) -> Vec<(TokenKind, &str)> {
+        significant_tokens(sql)
+            .into_iter()
+            .map(|t| (t.kind, t.text))
+            .collect()
+    }
+
+    #[test]
+    fn tokens_cover_the_whole_input() {
+        let sql = "SELECT [a]], b], N'x''y' /* c /* nested */ */ -- tail\r\nFROM #t;";
+        let rebuilt: String = tokenize(sql).iter().map(|t| t.text).collect();
+        assert_eq!(rebuilt, sql);
+    }
+
+    #[test]
+    fn strings_comments_and_identifiers_are_single_tokens() {
+        let toks = kinds("SELECT 'a;b', N'GO', [x;y], \"q\" /* ; */ FROM t -- ;");
+        assert_eq!(
+            toks,
+            vec![
+                (TokenKind::Word, "SELECT"),
+                (TokenKind::String, "'a;b'"),
+                (TokenKind::Comma, ","),
+                (TokenKind::String, "N'GO'"),
+                (TokenKind::Comma, ","),
+                (TokenKind::QuotedIdentifier, "[x;y]"),
+                (TokenKind::Comma, ","),
+                (TokenKind::QuotedIdentifier, "\"q\""),
+                (TokenKind::Word, "FROM"),
+                (TokenKind::Word, "t"),
+            ]
+        );
+    }
+
+    #[test]
+    fn block_comments_nest() {
+        let toks = tokenize("/* a /* b */ c */SELECT");
+        assert_eq!(toks[0].kind, TokenKind::BlockComment);
+        assert_eq!(toks[0].text, "/* a /* b */ c */");
+        assert!(toks[1].is_keyword("select"));
+    }
+
+    #[test]
+    fn variables_temp_tables_and_numbers() {
+        let toks = kinds("@x @@ROWCOUNT #t ##g 12 3.5e2 0xFF .5");
+        assert_eq!(
+            toks.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+            vec![
+                TokenKind::Variable,
+                TokenKind::Variable,
+                TokenKind::Word,
+                TokenKind::Word,
+                TokenKind::Number,
+                TokenKind::Number,
+                TokenKind::Number,
+                TokenKind::Number,
+            ]
+        );
+        assert!(significant_tokens("##g")[0].is_temp_table());
+    }
+
+    #[test]
+    fn dollar_pseudo_columns_are_single_words() {
+        let toks = kinds("OUTPUT $action, $PARTITION.pf(1), $IDENTITY");
+        assert_eq!(toks[1], (TokenKind::Word, "$action"));
+        assert_eq!(toks[3], (TokenKind::Word, "$PARTITION"));
+        assert_eq!(toks[4], (TokenKind::Dot, "."));
+        assert_eq!(toks[10], (TokenKind::Word, "$IDENTITY"));
+    }
+
+    #[test]
+    fn multi_char_operators() {
+        let toks = kinds("a <> b >= c += 1 :: d");
+        let ops: Vec<&str> = toks
+            .iter()
+            .filter(|(k, _)| *k == TokenKind::Operator)
+            .map(|(_, t)| *t)
+            .collect();
+        assert_eq!(ops, vec!["<>", ">=", "+=", "::"]);
+    }
+
+    #[test]
+    fn unterminated_string_runs_to_end() {
+        let toks = tokenize("SELECT 'abc");
+        assert_eq!(toks.last().unwrap().kind, TokenKind::Unknown);
+        assert_eq!(toks.last().unwrap().text, "'abc");
+    }
+
+    #[test]
+    fn unquote_resolves_escapes() {
+        assert_eq!(unquote_identifier("[a]]b]"), "a]b");
+        assert_eq!(unquote_identifier("\"a\"\"b\""), "a\"b");
+        assert_eq!(unquote_identifier("plain"), "plain");
+        assert_eq!(quote_identifier("a]b"), "[a]]b]");
+        assert_eq!(unquote_identifier(&quote_identifier("a]b")), "a]b");
+    }
+
+    #[test]
+    fn line_and_column_positions() {
+        let sql = "SELECT 1\nFROM t\n  WHERE x";
+        assert_eq!(line_of(sql, 0), 1);
+        assert_eq!(line_col(sql, sql.find("FROM").unwrap()), (2, 1));
+        assert_eq!(line_col(sql, sql.find("WHERE").unwrap()), (3, 3));
+    }
+}
@@ -0,0 +1,734 @@
+// T-SQL Linter
+// Static checks over parsed statements: SELECT *, UPDATE/DELETE without WHERE,
+// NOLOCK hints, unqualified object names and (with schema metadata) joins that
+// compare columns of different type families.
+
+use std::collections::{HashMap, HashSet};
+
+use super::diagnostic::{Diagnostic, Severity};
+use super::lexer::{is_reserved, significant_tokens, Span, Token, TokenKind};
+use super::parser::{parse_statements_in, split_batches, Statement};
+use crate::db::schema::SchemaInfo;
+
+pub const SELECT_STAR: &str = "SELECT_STAR";
+pub const UPDATE_WITHOUT_WHERE: &str = "UPDATE_WITHOUT_WHERE";
+pub const DELETE_WITHOUT_WHERE: &str = "DELETE_WITHOUT_WHERE";
+pub const NOLOCK_HINT: &str = "NOLOCK_HINT";
+pub const UNQUALIFIED_OBJECT: &str = "UNQUALIFIED_OBJECT";
+pub const IMPLICIT_CONVERSION: &str = "IMPLICIT_CONVERSION";
+
+/// Words that end a table reference instead of being its alias
+const NOT_ALIASES: &[&str] = &["USING", "OUTPUT", "OFFSET", "WINDOW"];
+
+/// Lint a document. Pass the cached schema to enable type-aware checks.
+pub fn lint_sql(sql: &str, schema: Option<&SchemaInfo>) -> Vec<Diagnostic> {
+    let mut linter = Linter {
+        sql,
+        schema,
+        diagnostics: Vec::new(),
+    };
+
+    for tok in significant_tokens(sql) {
+        if tok.is_keyword("NOLOCK") || tok.is_keyword("READUNCOMMITTED") {
+            linter.diagnostics.push(Diagnostic::new(
+                sql,
+                tok.span,
+                Severity::Warning,
+                NOLOCK_HINT,
+                format!(
+                    "{} reads uncommitted data and can return missing or duplicate rows",
+                    tok.text
+                ),
+            ));
+        }
+    }
+
+    for batch in split_batches(sql) {
+        let statements = parse_statements_in(sql, batch.span);
+        linter.statements(&statements);
+    }
+
+    linter.diagnostics.sort_by_key(|d| d.span.start);
+    linter.diagnostics
+}
+
+struct Linter<'a> {
+    sql: &'a str,
+    schema: Option<&'a SchemaInfo>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// A table (or procedure) referenced by a statement
+pub(super) struct ObjectRef {
+    pub(super) parts: Vec<String>,
+    pub(super) span: Span,
+    pub(super) alias: Option<String>,
+}
+
+impl ObjectRef {
+    pub(super) fn name(&self) -> &str {
+        self.parts.last().map(String::as_str).unwrap_or("")
+    }
+
+    fn schema(&self) -> Option<&str> {
+        (self.parts.len() >= 2)
+            .then(|| self.parts[self.parts.len() - 2].as_str())
+            .filter(|s| !s.is_empty())
+    }
+}
+
+impl<'a> Linter<'a> {
+    fn statements(&mut self, statements: &[Statement]) {
+        for stmt in statements {
+            if !stmt.children.is_empty() {
+                self.statements(&stmt.children);
+            } else if let Some(as_span) = stmt.module_body_as(self.sql) {
+                let body = parse_statements_in(self.sql, Span::new(as_span.end, stmt.span.end));
+                self.statements(&body);
+            } else {
+                self.statement(stmt);
+            }
+        }
+    }
+
+    fn push(&mut self, span: Span, severity: Severity, code: &str, message: String) {
+        self.diagnostics
+            .push(Diagnostic::new(self.sql, span, severity, code, message));
+    }
+
+    fn statement(&mut self, stmt: &Statement) {
+        let tokens: Vec<Token<'_>> = significant_tokens(stmt.text(self.sql))
+            .into_iter()
+            .map(|t| Token {
+                span: Span::new(t.span.start + stmt.span.start, t.span.end + stmt.span.start),
+                ..t
+            })
+            .collect();
+        let scopes = query_scopes(&tokens);
+
+        self.select_star(&tokens, &scopes);
+        self.missing_where(stmt, &tokens);
+
+        let ctes = cte_names(&tokens);
+        let refs = object_refs(&tokens, &scopes);
+        self.unqualified(&refs, &ctes);
+        if let Some(schema) = self.schema {
+            self.join_conversions(&tokens, &scopes, &refs, schema);
+        }
+    }
+
+    fn select_star(&mut self, tokens: &[Token<'_>], scopes: &[Scope]) {
+        for (i, tok) in tokens.iter().enumerate() {
+            if !tok.is_keyword("SELECT") || !scopes[i].query {
+                continue;
+            }
+            // EXISTS (SELECT * ...) is idiomatic
+            if i >= 2
+                && tokens[i - 1].kind == TokenKind::LeftParen
+                && tokens[i - 2].is_keyword("EXISTS")
+            {
+                continue;
+            }
+
+            let depth = scopes[i].depth;
+            let mut item_start = skip_select_modifiers(tokens, i);
+            while item_start < tokens.len() {
+                if let Some(span) = star_item(tokens, item_start) {
+                    self.push(
+                        span,
+                        Severity::Info,
+                        SELECT_STAR,
+                        "Avoid SELECT *; list the columns you need".to_string(),
+                    );
+                }
+
+                // Next item: after a comma at this depth, until the list ends
+                let next = tokens[item_start..].iter().enumerate().position(|(n, t)| {
+                    let scope = scopes[item_start + n];
+                    scope.depth < depth
+                        || (scope.depth == depth
+                            && (t.kind == TokenKind::Comma || ends_select_list(t)))
+                });
+                match next {
+                    Some(n) if tokens[item_start + n].kind == TokenKind::Comma => {
+                        item_start += n + 1
+                    }
+                    _ => break,
+                }
+            }
+        }
+    }
+
+    fn missing_where(&mut self, stmt: &Statement, tokens: &[Token<'_>]) {
+        let (verb, code) = if stmt.is("UPDATE") {
+            ("UPDATE", UPDATE_WITHOUT_WHERE)
+        } else if stmt.is("DELETE") {
+            ("DELETE", DELETE_WITHOUT_WHERE)
+        } else {
+            return;
+        };
+
+        let mut depth = 0usize;
+        let mut verb_tok: Option<&Token<'_>> = None;
+        for (i, tok) in tokens.iter().enumerate() {
+            match tok.kind {
+                TokenKind::LeftParen => depth += 1,
+                TokenKind::RightParen => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            if depth > 0 {
+                continue;
+            }
+            if verb_tok.is_none() && tok.is_keyword(verb) {
+                if tokens
+                    .get(i + 1)
+                    .is_some_and(|t| t.is_keyword("STATISTICS"))
+                {
+                    return;
+                }
+                verb_tok = Some(tok);
+            } else if verb_tok.is_some() && tok.is_keyword("WHERE") {
+                return;
+            }
+        }
+
+        if let Some(tok) = verb_tok {
+            self.push(
+                tok.span,
+                Severity::Warning,
+                code,
+                format!("{} without a WHERE clause affects every row", verb),
+            );
+        }
+    }
+
+    fn unqualified(&mut self, refs: &[ObjectRef], ctes: &HashSet<String>) {
+        let aliases: HashSet<String> = refs
+            .iter()
+            .filter_map(|r| r.alias.as_ref())
+            .map(|a| a.to_lowercase())
+            .collect();
+
+        for r in refs {
+            let name = r.name();
+            if r.parts.len() > 1
+                || name.starts_with('#')
+                || ctes.contains(&name.to_lowercase())
+                || aliases.contains(&name.to_lowercase())
+                || name.to_lowercase().starts_with("sp_")
+                || name.to_lowercase().starts_with("xp_")
+            {
+                continue;
+            }
+            self.push(
+                r.span,
+                Severity::Info,
+                UNQUALIFIED_OBJECT,
+                format!("Object name '{}' is not schema-qualified", name),
+            );
+        }
+    }
+
+    fn join_conversions(
+        &mut self,
+        tokens: &[Token<'_>],
+        scopes: &[Scope],
+        refs: &[ObjectRef],
+        schema: &SchemaInfo,
+    ) {
+        let mut by_alias: HashMap<String, &ObjectRef> = HashMap::new();
+        for r in refs {
+            by_alias.insert(r.name().to_lowercase(), r);
+            if let Some(alias) = &r.alias {
+                by_alias.insert(alias.to_lowercase(), r);
+            }
+        }
+
+        let column_type = |qualifier: &str, column: &str| -> Option<String> {
+            let r = by_alias.get(&qualifier.to_lowercase())?;
+            let table = schema.tables.iter().find(|t| {
+                t.table_name.eq_ignore_ascii_case(r.name())
+                    && r.schema()
+                        .is_none_or(|s| t.schema_name.eq_ignore_ascii_case(s))
+            })?;
+            table
+                .columns
+                .iter()
+                .find(|c| c.name.eq_ignore_ascii_case(column))
+                .map(|c| c.data_type.to_lowercase())
+        };
+
+        let mut in_on = false;
+        let mut on_depth = 0usize;
+        for (i, tok) in tokens.iter().enumerate() {
+            if tok.is_keyword("ON") && scopes[i].query {
+                in_on = true;
+                on_depth = scopes[i].depth;
+                continue;
+            }
+            if in_on
+                && scopes[i].depth == on_depth
+                && (tok.kind == TokenKind::RightParen || ends_on_clause(tok))
+            {
+                in_on = false;
+            }
+            if !in_on || tok.kind != TokenKind::Operator || tok.text != "=" {
+                continue;
+            }
+
+            let (Some((left_span, left)), Some((right_span, right))) =
+                (column_before(tokens, i), column_after(tokens, i))
+            else {
+                continue;
+            };
+            let (Some(left_type), Some(right_type)) = (
+                column_type(&left.0, &left.1),
+                column_type(&right.0, &right.1),
+            ) else {
+                continue;
+            };
+
+            if type_family(&left_type) != type_family(&right_type) {
+                self.push(
+                    Span::new(left_span.start, right_span.end),
+                    Severity::Warning,
+                    IMPLICIT_CONVERSION,
+                    format!(
+                        "Join compares {}.{} ({}) with {}.{} ({}); the implicit conversion can prevent index use",
+                        left.0, left.1, left_type, right.0, right.1, right_type
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// Paren depth of a token and whether it sits directly in a query (top level
+/// or a subquery) rather than inside a function call or list
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Scope {
+    pub(super) depth: usize,
+    pub(super) query: bool,
+}
+
+pub(super) fn query_scopes(tokens: &[Token<'_>]) -> Vec<Scope> {
+    let mut stack: Vec<bool> = Vec::new();
+    let mut scopes = Vec::with_capacity(tokens.len());
+    for (i, tok) in tokens.iter().enumerate() {
+        if tok.kind == TokenKind::RightParen {
+            stack.pop();
+        }
+        scopes.push(Scope {
+            depth: stack.len(),
+            query: stack.last().copied().unwrap_or(true),
+        });
+        if tok.kind == TokenKind::LeftParen {
+            let subquery = tokens
+                .get(i + 1)
+                .is_some_and(|t| t.is_keyword("SELECT") || t.is_keyword("WITH"));
+            stack.push(subquery);
+        }
+    }
+    scopes
+}
+
+/// Index of the first select-list item after `SELECT [DISTINCT|ALL] [TOP ...]`
+pub(super) fn skip_select_modifiers(tokens: &[Token<'_>], select: usize) -> usize {
+    let mut i = select + 1;
+    if tokens
+        .get(i)
+        .is_some_and(|t| t.is_keyword("DISTINCT") || t.is_keyword("ALL"))
+    {
+        i += 1;
+    }
+    if tokens.get(i).is_some_and(|t| t.is_keyword("TOP")) {
+        i += 1;
+        if tokens
+            .get(i)
+            .is_some_and(|t| t.kind == TokenKind::LeftParen)
+        {
+            let mut depth = 0usize;
+            while let Some(t) = tokens.get(i) {
+                i += 1;
+                match t.kind {
+                    TokenKind::LeftParen => depth += 1,
+                    TokenKind::RightParen => {
+                        depth -= 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        } else {
+            i += 1;
+        }
+        if tokens.get(i).is_some_and(|t| t.is_keyword("PERCENT")) {
+            i += 1;
+        }
+        if tokens.get(i).is_some_and(|t| t.is_keyword("WITH"))
+            && tokens.get(i + 1).is_some_and(|t| t.is_keyword("TIES"))
+        {
+            i += 2;
+        }
+    }
+    i
+}
+
+/// `*` or `qualifier.*` as a select item
+fn star_item(tokens: &[Token<'_>], i: usize) -> Option<Span> {
+    let mut j = i;
+    while tokens.get(j).is_some_and(is_name_part)
+        && tokens.get(j + 1).is_some_and(|t| t.kind == TokenKind::Dot)
+    {
+        j += 2;
+    }
+    let star = tokens.get(j)?;
+    (star.kind == TokenKind::Operator && star.text == "*")
+        .then(|| Span::new(tokens[i].span.start, star.span.end))
+}
+
+fn ends_select_list(tok: &Token<'_>) -> bool {
+    [
+        "FROM",
+        "INTO",
+        "WHERE",
+        "GROUP",
+        "HAVING",
+        "ORDER",
+        "UNION",
+        "EXCEPT",
+        "INTERSECT",
+        "OPTION",
+        "FOR",
+    ]
+    .iter()
+    .any(|k| tok.is_keyword(k))
+}
+
+fn ends_on_clause(tok: &Token<'_>) -> bool {
+    [
+        "JOIN",
+        "INNER",
+        "LEFT",
+        "RIGHT",
+        "FULL",
+        "CROSS",
+        "OUTER",
+        "WHERE",
+        "GROUP",
+        "HAVING",
+        "ORDER",
+        "UNION",
+        "EXCEPT",
+        "INTERSECT",
+        "OPTION",
+        "WHEN",
+        "SET",
+        "OUTPUT",
+    ]
+    .iter()
+    .any(|k| tok.is_keyword(k))
+}
+
+pub(super) fn is_name_part(tok: &Token<'_>) -> bool {
+    match tok.kind {
+        TokenKind::QuotedIdentifier => true,
+        TokenKind::Word => !is_reserved(&tok.text.to_ascii_uppercase()),
+        _ => false,
+    }
+}
+
+/// Multi-part name starting at `i` (`db.schema.name`, `db..name`). Returns
+/// the parts and the index just past the name.
+pub(super) fn object_name(tokens: &[Token<'_>], i: usize) -> Option<(Vec<String>, usize)> {
+    if !tokens.get(i).is_some_and(is_name_part) {
+        return None;
+    }
+    let mut parts = vec![tokens[i].identifier()];
+    let mut j = i + 1;
+    while tokens.get(j).is_some_and(|t| t.kind == TokenKind::Dot) {
+        match tokens.get(j + 1) {
+            Some(t) if is_name_part(t) => {
+                parts.push(t.identifier());
+                j += 2;
+            }
+            Some(t) if t.kind == TokenKind::Dot => {
+                parts.push(String::new());
+                j += 1;
+            }
+            _ => break,
+        }
+    }
+    Some((parts, j))
+}
+
+/// Tables named after FROM/JOIN/USING and statement targets, plus EXEC'd
+/// procedures. Table-valued function calls are skipped.
+pub(super) fn object_refs(tokens: &[Token<'_>], scopes: &[Scope]) -> Vec<ObjectRef> {
+    let mut refs = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let tok = &tokens[i];
+        let Some(kw) = tok.keyword() else {
+            i += 1;
+            continue;
+        };
+
+        let mut start = match kw.as_str() {
+            "FROM" | "JOIN" | "USING" if scopes[i].query => i + 1,
+            "UPDATE" | "MERGE" | "INSERT" | "DELETE"
+                if scopes[i].query && i == first_verb(tokens) =>
+            {
+                i + 1
+            }
+            "EXEC" | "EXECUTE" if i == 0 => i + 1,
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        if matches!(kw.as_str(), "MERGE" | "INSERT")
+            && tokens.get(start).is_some_and(|t| t.is_keyword("INTO"))
+        {
+            start += 1;
+        }
+        if kw == "DELETE" && tokens.get(start).is_some_and(|t| t.is_keyword("FROM")) {
+            start += 1;
+        }
+        if matches!(kw.as_str(), "EXEC" | "EXECUTE")
+            && tokens
+                .get(start)
+                .is_some_and(|t| t.kind == TokenKind::Variable)
+            && tokens.get(start + 1).is_some_and(|t| t.text == "=")
+        {
+            start += 2;
+        }
+
+        let Some((parts, end)) = object_name(tokens, start) else {
+            i = start.max(i + 1);
+            continue;
+        };
+        if tokens
+            .get(end)
+            .is_some_and(|t| t.kind == TokenKind::LeftParen)
+            && !matches!(kw.as_str(), "INSERT")
+        {
+            // Table-valued function, or UPDATE(col) in a trigger
+            i = end;
+            continue;
+        }
+
+        let mut alias = None;
+        let mut next = end;
+        if tokens.get(next).is_some_and(|t| t.is_keyword("AS")) {
+            next += 1;
+        }
+        if let Some(t) = tokens.get(next) {
+            let usable = is_name_part(t) && !NOT_ALIASES.iter().any(|w| t.is_keyword(w));
+            if usable && !matches!(kw.as_str(), "EXEC" | "EXECUTE" | "INSERT") {
+                alias = Some(t.identifier());
+            }
+        }
+
+        refs.push(ObjectRef {
+            parts,
+            span: Span::new(tokens[start].span.start, tokens[end - 1].span.end),
+            alias,
+        });
+        i = end;
+    }
+    refs
+}
+
+/// Index of the statement's main DML verb (after any CTE definitions)
+fn first_verb(tokens: &[Token<'_>]) -> usize {
+    let mut depth = 0usize;
+    for (i, tok) in tokens.iter().enumerate() {
+        match tok.kind {
+            TokenKind::LeftParen => depth += 1,
+            TokenKind::RightParen => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        if depth == 0
+            && ["SELECT", "INSERT", "UPDATE", "DELETE", "MERGE"]
+                .iter()
+                .any(|k| tok.is_keyword(k))
+        {
+            return i;
+        }
+    }
+    usize::MAX
+}
+
+/// Names defined by a leading `WITH name [(cols)] AS (...)` list
+fn cte_names(tokens: &[Token<'_>]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    if !tokens.first().is_some_and(|t| t.is_keyword("WITH")) {
+        return names;
+    }
+
+    let mut depth = 0usize;
+    let mut expect_name = true;
+    for tok in &tokens[1..] {
+        match tok.kind {
+            TokenKind::LeftParen => depth += 1,
+            TokenKind::RightParen => depth = depth.saturating_sub(1),
+            TokenKind::Comma if depth == 0 => expect_name = true,
+            _ if depth == 0 && expect_name && is_name_part(tok) => {
+                names.insert(tok.identifier().to_lowercase());
+                expect_name = false;
+            }
+            _ if depth == 0 && tok.keyword().is_some_and(|k| k != "AS") => break,
+            _ => {}
+        }
+    }
+    names
+}
+
+/// `qualifier.column` ending just before the operator at `op`
+fn column_before(tokens: &[Token<'_>], op: usize) -> Option<(Span, (String, String))> {
+    if op < 3 {
+        return None;
+    }
+    let (q, dot, c) = (&tokens[op - 3], &tokens[op - 2], &tokens[op - 1]);
+    (is_name_part(q) && dot.kind == TokenKind::Dot && is_name_part(c)).then(|| {
+        (
+            Span::new(q.span.start, c.span.end),
+            (q.identifier(), c.identifier()),
+        )
+    })
+}
+
+/// `qualifier.column` starting just after the operator at `op`
+fn column_after(tokens: &[Token<'_>], op: usize) -> Option<(Span, (String, String))> {
+    let (q, dot, c) = (
+        tokens.get(op + 1)?,
+        tokens.get(op + 2)?,
+        tokens.get(op + 3)?,
+    );
+    if tokens
+        .get(op + 4)
+        .is_some_and(|t| t.kind == TokenKind::Dot || t.kind == TokenKind::LeftParen)
+    {
+        return None;
+    }
+    (is_name_part(q) && dot.kind == TokenKind::Dot && is_name_part(c)).then(|| {
+        (
+            Span::new(q.span.start, c.span.end),
+            (q.identifier(), c.identifier()),
+        )
+    })
+}
+
+/// Coarse type family; comparing across families forces a conversion
+fn type_family(data_type: &str) -> &str {
+    match data_type {
+        "tinyint" | "smallint" | "int" | "bigint" => "integer",
+        "decimal" | "numeric" | "money" | "smallmoney" => "decimal",
+        "float" | "real" => "float",
+        "char" | "varchar" | "text" => "ansi",
+        "nchar" | "nvarchar" | "ntext" | "sysname" => "unicode",
+        "date" | "datetime" | "datetime2" | "smalldatetime" | "datetimeoffset" => "datetime",
+        "binary" | "varbinary" | "image" => "binary",
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema::{ColumnInfo, TableInfo};
+
+    fn codes(sql: &str) -> Vec<String> {
+        lint_sql(sql, None).into_iter().map(|d| d.code).collect()
+    }
+
+    fn column(name: &str, data_type: &str) -> ColumnInfo {
+        ColumnInfo::for_test(name, data_type)
+    }
+
+    fn schema() -> SchemaInfo {
+        SchemaInfo::for_test(
+            "db",
+            vec![
+                TableInfo::for_test(
+                    "Orders",
+                    vec![column("CustomerCode", "varchar"), column("Id", "int")],
+                ),
+                TableInfo::for_test(
+                    "Customers",
+                    vec![column("Code", "nvarchar"), column("OrderId", "bigint")],
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn flags_select_star_but_not_count_star_or_exists() {
+        assert_eq!(codes("SELECT * FROM dbo.t"), vec![SELECT_STAR]);
+        assert_eq!(codes("SELECT a, t.* FROM dbo.t t"), vec![SELECT_STAR]);
+        assert!(codes("SELECT COUNT(*), a * 2 FROM dbo.t").is_empty());
+        assert!(codes("IF EXISTS (SELECT * FROM dbo.t) PRINT 1").is_empty());
+        let diags = lint_sql("SELECT TOP (5) *\nFROM dbo.t", None);
+        assert_eq!((diags[0].line, diags[0].column), (1, 16));
+    }
+
+    #[test]
+    fn flags_update_and_delete_without_where() {
+        assert_eq!(codes("UPDATE dbo.t SET a = 1"), vec![UPDATE_WITHOUT_WHERE]);
+        assert_eq!(codes("DELETE FROM dbo.t"), vec![DELETE_WITHOUT_WHERE]);
+        assert!(codes("UPDATE dbo.t SET a = (SELECT 1) WHERE id = 1").is_empty());
+        assert!(codes("DELETE FROM dbo.t WHERE id IN (SELECT id FROM dbo.x)").is_empty());
+        assert!(codes("UPDATE STATISTICS dbo.t").is_empty());
+        assert_eq!(
+            codes("WITH c AS (SELECT id FROM dbo.t WHERE x = 1) DELETE FROM c"),
+            vec![DELETE_WITHOUT_WHERE]
+        );
+    }
+
+    #[test]
+    fn flags_nolock_hints() {
+        assert_eq!(
+            codes("SELECT a FROM dbo.t WITH (NOLOCK) WHERE 1 = 1"),
+            vec![NOLOCK_HINT]
+        );
+        assert!(codes("SELECT 'NOLOCK' AS note").is_empty());
+    }
+
+    #[test]
+    fn flags_unqualified_objects() {
+        assert_eq!(
+            codes("SELECT a FROM t JOIN dbo.u ON u.id = t.id"),
+            vec![UNQUALIFIED_OBJECT]
+        );
+        assert_eq!(codes("EXEC my_proc 1"), vec![UNQUALIFIED_OBJECT]);
+        assert!(codes("EXEC sp_who2").is_empty());
+        assert!(codes("SELECT a FROM #tmp t CROSS APPLY STRING_SPLIT(t.a, ',') s").is_empty());
+        assert!(codes("WITH c AS (SELECT a FROM dbo.t) SELECT a FROM c").is_empty());
+        assert!(codes("UPDATE x SET a = 1 FROM dbo.t x WHERE x.id = 1").is_empty());
+        assert!(codes("SELECT TRIM(' ' FROM a) FROM dbo.t").is_empty());
+    }
+
+    #[test]
+    fn lints_inside_blocks_and_procedure_bodies() {
+        let sql = "CREATE PROCEDURE dbo.p AS\nBEGIN\n  IF 1 = 1\n    DELETE FROM dbo.t\nEND";
+        let diags = lint_sql(sql, None);
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].code, DELETE_WITHOUT_WHERE);
+        assert_eq!(diags[0].line, 4);
+    }
+
+    #[test]
+    fn flags_joins_across_type_families_with_schema() {
+        let schema = schema();
+        let sql = "SELECT o.Id FROM dbo.Orders o JOIN dbo.Customers c ON c.Code = o.CustomerCode AND c.OrderId = o.Id";
+        let diags = lint_sql(sql, Some(&schema));
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].code, IMPLICIT_CONVERSION);
+        assert!(diags[0].message.contains("nvarchar"));
+        assert_eq!(diags[0].span.text(sql), "c.Code = o.CustomerCode");
+        assert!(lint_sql(sql, None).is_empty());
+    }
+}
@@ -0,0 +1,24 @@
+// T-SQL Language Support
+// Shared lexer and statement parser used by query execution and editor tooling
+
+pub mod complete;
+pub mod diagnostic;
+pub mod format;
+pub mod lexer;
+pub mod lint;
+pub mod parser;
+pub mod sqlcmd;
+
+pub use complete::{
+    complete, resolve_symbol, CompletionCatalog, CompletionItem, CompletionKind, Completions,
+    Symbol,
+};
+pub use diagnostic::{Diagnostic, Severity, TextEdit};
+pub use lexer::{
+    is_reserved, line_col, line_of, quote_identifier, significant_tokens, tokenize,
+    unquote_identifier, Span, Token, TokenKind,
+};
+pub use parser::{
+    parse_statements, parse_statements_in, split_batches, split_terminated, syntax_diagnostics,
+    Batch, Statement,
+};
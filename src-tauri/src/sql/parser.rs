@@ -0,0 +1,1124 @@
+// T-SQL Statement Parser
+// Finds batch (GO) and statement boundaries on top of the lexer. This is not a
+// full grammar: it understands enough structure (BEGIN...END, CASE...END,
+// IF/ELSE, WHILE, CTEs, MERGE, module bodies) to know where statements start
+// and end, with or without semicolons.
+
+use serde::{Deserialize, Serialize};
+
+use super::diagnostic::{Diagnostic, Severity};
+use super::lexer::{significant_tokens, tokenize, Span, Token, TokenKind};
+
+/// A batch of SQL between `GO` separators
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Batch {
+    /// Batch text, excluding the separator line
+    pub span: Span,
+    /// Count from `GO n` (1 for a plain `GO` or the final unterminated batch)
+    pub repeat: u32,
+    /// The `GO` separator that ended this batch, if any
+    pub separator: Option<Span>,
+}
+
+impl Batch {
+    pub fn text<'a>(&self, source: &'a str) -> &'a str {
+        self.span.text(source)
+    }
+}
+
+/// A parsed statement. Control-flow statements (`BEGIN...END`, `IF`, `WHILE`)
+/// carry their nested statements in `children`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Statement {
+    /// From the first to the last token of the statement, including its `;`
+    pub span: Span,
+    /// Upper-cased leading keyword. For `WITH` CTEs this is the verb of the
+    /// main statement (`SELECT`, `UPDATE`, ...) rather than `WITH`.
+    pub keyword: Option<String>,
+    /// Whether the statement ended with an explicit `;`
+    pub terminated: bool,
+    pub children: Vec<Statement>,
+}
+
+impl Statement {
+    pub fn text<'a>(&self, source: &'a str) -> &'a str {
+        self.span.text(source)
+    }
+
+    /// Keyword equality check (case-insensitive)
+    pub fn is(&self, keyword: &str) -> bool {
+        self.keyword
+            .as_deref()
+            .is_some_and(|k| k.eq_ignore_ascii_case(keyword))
+    }
+
+    /// This statement followed by all nested statements, depth-first
+    pub fn walk(&self) -> Vec<&Statement> {
+        let mut out = vec![self];
+        for child in &self.children {
+            out.extend(child.walk());
+        }
+        out
+    }
+
+    /// For `CREATE/ALTER PROC|FUNCTION|TRIGGER|VIEW`, the `AS` keyword that
+    /// starts the module body. The body statements can be parsed with
+    /// `parse_statements_in(source, Span::new(as_span.end, self.span.end))`.
+    pub fn module_body_as(&self, source: &str) -> Option<Span> {
+        if !self.is("CREATE") && !self.is("ALTER") {
+            return None;
+        }
+
+        let tokens = significant_tokens(self.text(source));
+        let object = if tokens.get(1).is_some_and(|t| t.is_keyword("OR"))
+            && tokens.get(2).is_some_and(|t| t.is_keyword("ALTER"))
+        {
+            tokens.get(3)
+        } else {
+            tokens.get(1)
+        };
+        let is_module = object
+            .and_then(|t| t.keyword())
+            .is_some_and(|k| MODULE_OBJECTS.contains(&k.as_str()));
+        if !is_module {
+            return None;
+        }
+
+        let mut depth = 0usize;
+        for (i, tok) in tokens.iter().enumerate() {
+            match tok.kind {
+                TokenKind::LeftParen => depth += 1,
+                TokenKind::RightParen => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            if depth > 0 || !tok.is_keyword("AS") {
+                continue;
+            }
+            // `@param AS int` and `EXECUTE AS OWNER` are not the body
+            let starts_body = tokens.get(i + 1).is_none_or(|next| {
+                next.keyword()
+                    .is_some_and(|k| STATEMENT_KEYWORDS.contains(&k.as_str()) || k == "WITH")
+            });
+            if starts_body {
+                return Some(Span::new(
+                    tok.span.start + self.span.start,
+                    tok.span.end + self.span.start,
+                ));
+            }
+        }
+        None
+    }
+}
+
+/// Keywords that begin a new statement wherever they appear at the top level
+/// of another one (outside parentheses and CASE expressions), subject to the
+/// context checks in `starts_new_statement`.
+pub(crate) const STATEMENT_KEYWORDS: &[&str] = &[
+    "ALTER",
+    "BACKUP",
+    "BEGIN",
+    "BREAK",
+    "BULK",
+    "CHECKPOINT",
+    "CLOSE",
+    "COMMIT",
+    "CONTINUE",
+    "CREATE",
+    "DBCC",
+    "DEALLOCATE",
+    "DECLARE",
+    "DELETE",
+    "DENY",
+    "DISABLE",
+    "DROP",
+    "ENABLE",
+    "EXEC",
+    "EXECUTE",
+    "FETCH",
+    "GOTO",
+    "GRANT",
+    "IF",
+    "INSERT",
+    "KILL",
+    "MERGE",
+    "OPEN",
+    "PRINT",
+    "RAISERROR",
+    "RECONFIGURE",
+    "RESTORE",
+    "RETURN",
+    "REVOKE",
+    "ROLLBACK",
+    "SAVE",
+    "SELECT",
+    "SET",
+    "THROW",
+    "TRUNCATE",
+    "UPDATE",
+    "USE",
+    "WAITFOR",
+    "WHILE",
+];
+
+/// Verbs that can follow a CTE definition
+const CTE_VERBS: &[&str] = &["SELECT", "INSERT", "UPDATE", "DELETE", "MERGE"];
+
+/// `BEGIN` followed by one of these is a statement, not a block
+const BEGIN_NON_BLOCK: &[&str] = &[
+    "TRAN",
+    "TRANSACTION",
+    "DISTRIBUTED",
+    "DIALOG",
+    "CONVERSATION",
+];
+
+/// `END` followed by one of these is a statement, not a block end
+const END_NON_BLOCK: &[&str] = &["CONVERSATION"];
+
+/// Objects whose CREATE/ALTER body runs to the end of the batch
+const MODULE_OBJECTS: &[&str] = &["PROC", "PROCEDURE", "FUNCTION", "TRIGGER", "VIEW"];
+
+/// Split SQL into batches on `GO` separators. `GO` separates only when it is
+/// the first token on its line, optionally followed by a repeat count and a
+/// `--` comment; occurrences inside strings and comments are ignored. Batches
+/// that contain nothing but whitespace are dropped.
+pub fn split_batches(sql: &str) -> Vec<Batch> {
+    let tokens = tokenize(sql);
+    let mut batches = Vec::new();
+    let mut batch_start = 0usize;
+    let mut at_line_start = true;
+    let mut i = 0usize;
+
+    while i < tokens.len() {
+        let tok = tokens[i];
+
+        if at_line_start && tok.is_keyword("GO") {
+            if let Some((repeat, end_idx)) = match_go_separator(&tokens, i) {
+                let separator_end = tokens[end_idx].span.end;
+                push_batch(
+                    &mut batches,
+                    sql,
+                    Span::new(batch_start, tok.span.start),
+                    repeat,
+                    Some(Span::new(tok.span.start, separator_end)),
+                );
+                batch_start = separator_end;
+                i = end_idx + 1;
+                at_line_start = false;
+                continue;
+            }
+        }
+
+        match tok.kind {
+            TokenKind::Newline => at_line_start = true,
+            TokenKind::Whitespace => {}
+            // A block comment that spans lines leaves us mid-line on its last line
+            _ => at_line_start = false,
+        }
+        i += 1;
+    }
+
+    push_batch(
+        &mut batches,
+        sql,
+        Span::new(batch_start, sql.len()),
+        1,
+        None,
+    );
+    batches
+}
+
+fn push_batch(
+    batches: &mut Vec<Batch>,
+    sql: &str,
+    span: Span,
+    repeat: u32,
+    separator: Option<Span>,
+) {
+    if span.text(sql).trim().is_empty() {
+        return;
+    }
+    batches.push(Batch {
+        span,
+        repeat,
+        separator,
+    });
+}
+
+/// If `tokens[idx]` is a `GO` separator, return its repeat count and the index
+/// of the last token on the separator line (before the newline)
+fn match_go_separator(tokens: &[Token<'_>], idx: usize) -> Option<(u32, usize)> {
+    let mut repeat = 1u32;
+    let mut last = idx;
+    let mut j = idx + 1;
+    let mut seen_count = false;
+
+    while j < tokens.len() {
+        let tok = tokens[j];
+        match tok.kind {
+            TokenKind::Whitespace => {}
+            TokenKind::Newline => break,
+            TokenKind::LineComment => {
+                last = j;
+                break;
+            }
+            TokenKind::Number if !seen_count => {
+                repeat = tok.text.parse::<u32>().ok()?.max(1);
+                seen_count = true;
+                last = j;
+            }
+            _ => return None,
+        }
+        j += 1;
+    }
+
+    Some((repeat, last))
+}
+
+/// Parse the statements of a single batch
+pub fn parse_statements(sql: &str) -> Vec<Statement> {
+    parse_statements_in(sql, Span::new(0, sql.len()))
+}
+
+/// Parse the statements within `span` of `sql`. Spans in the result are
+/// relative to `sql`, so a batch can be parsed in place.
+pub fn parse_statements_in(sql: &str, span: Span) -> Vec<Statement> {
+    let tokens: Vec<Token<'_>> = tokenize(span.text(sql))
+        .into_iter()
+        .filter(|t| !t.is_trivia())
+        .map(|t| Token {
+            span: Span::new(t.span.start + span.start, t.span.end + span.start),
+            ..t
+        })
+        .collect();
+
+    let mut parser = Parser { tokens, pos: 0 };
+    parser.parse_list(false)
+}
+
+/// Split a batch into chunks ending at explicit `;` terminators. Statements
+/// without a `;` are grouped with whatever follows them, and a chunk never
+/// splits a block, IF/ELSE or module body. Leading stray semicolons (as in
+/// `;WITH`) are dropped from each chunk.
+pub fn split_terminated(sql: &str) -> Vec<Span> {
+    let statements = parse_statements(sql);
+    let mut chunks = Vec::new();
+    let mut chunk_start = 0usize;
+
+    let mut push = |start: usize, end: usize| {
+        let text = &sql[start..end];
+        let leading = text.len()
+            - text
+                .trim_start_matches(|c: char| c == ';' || c.is_whitespace())
+                .len();
+        let trailing = text.len() - text.trim_end().len();
+        if start + leading < end - trailing {
+            chunks.push(Span::new(start + leading, end - trailing));
+        }
+    };
+
+    for stmt in &statements {
+        if stmt.terminated {
+            push(chunk_start, stmt.span.end);
+            chunk_start = stmt.span.end;
+        }
+    }
+    push(chunk_start, sql.len());
+
+    chunks
+}
+
+/// Unterminated string, comment or delimited identifier
+pub const UNTERMINATED: &str = "UNTERMINATED";
+/// `(` without `)` or the other way round
+pub const UNBALANCED_PARENS: &str = "UNBALANCED_PARENS";
+/// `BEGIN` block without `END`, or a stray `END`
+pub const UNMATCHED_BLOCK: &str = "UNMATCHED_BLOCK";
+
+/// Structural problems the statement parser can see without a schema:
+/// unterminated tokens, unbalanced parentheses and unmatched BEGIN/END
+pub fn syntax_diagnostics(sql: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for tok in tokenize(sql) {
+        if tok.kind != TokenKind::Unknown {
+            continue;
+        }
+        let upper = tok.text.to_ascii_uppercase();
+        let message = if upper.starts_with('\'') || upper.starts_with("N'") {
+            "Unterminated string literal"
+        } else if upper.starts_with("/*") {
+            "Unterminated block comment"
+        } else if upper.starts_with('[') || upper.starts_with('"') {
+            "Unterminated delimited identifier"
+        } else {
+            continue;
+        };
+        diagnostics.push(Diagnostic::new(
+            sql,
+            tok.span,
+            Severity::Error,
+            UNTERMINATED,
+            message,
+        ));
+    }
+
+    for batch in split_batches(sql) {
+        let mut open = Vec::new();
+        for tok in significant_tokens(batch.text(sql)) {
+            let offset = batch.span.start;
+            let span = Span::new(tok.span.start + offset, tok.span.end + offset);
+            match tok.kind {
+                TokenKind::LeftParen => open.push(span),
+                TokenKind::RightParen if open.pop().is_none() => {
+                    diagnostics.push(Diagnostic::new(
+                        sql,
+                        span,
+                        Severity::Error,
+                        UNBALANCED_PARENS,
+                        "Unmatched `)`",
+                    ));
+                }
+                _ => {}
+            }
+        }
+        for span in open {
+            diagnostics.push(Diagnostic::new(
+                sql,
+                span,
+                Severity::Error,
+                UNBALANCED_PARENS,
+                "`(` is never closed",
+            ));
+        }
+
+        for stmt in parse_statements_in(sql, batch.span).iter().flat_map(|s| s.walk()) {
+            let tokens = significant_tokens(stmt.text(sql));
+            let Some(first) = tokens.first() else {
+                continue;
+            };
+            let offset = stmt.span.start;
+            let span = Span::new(first.span.start + offset, first.span.end + offset);
+            let ends_block = stmt.is("END")
+                && !tokens
+                    .get(1)
+                    .is_some_and(|t| END_NON_BLOCK.iter().any(|k| t.is_keyword(k)));
+            if ends_block {
+                diagnostics.push(Diagnostic::new(
+                    sql,
+                    span,
+                    Severity::Error,
+                    UNMATCHED_BLOCK,
+                    "END without a matching BEGIN",
+                ));
+            }
+            let is_block = stmt.is("BEGIN")
+                && !tokens
+                    .get(1)
+                    .is_some_and(|t| BEGIN_NON_BLOCK.iter().any(|k| t.is_keyword(k)));
+            let closed = tokens
+                .iter()
+                .rev()
+                .find(|t| {
+                    t.kind != TokenKind::Semicolon && !t.is_keyword("TRY") && !t.is_keyword("CATCH")
+                })
+                .is_some_and(|t| t.is_keyword("END"));
+            if is_block && !closed {
+                diagnostics.push(Diagnostic::new(
+                    sql,
+                    span,
+                    Severity::Error,
+                    UNMATCHED_BLOCK,
+                    "BEGIN without a matching END",
+                ));
+            }
+        }
+    }
+
+    diagnostics.sort_by_key(|d| d.span.start);
+    diagnostics
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+}
+
+/// Per-statement context used to decide whether a keyword continues the
+/// current statement or starts the next one
+struct SimpleState {
+    leading: Option<String>,
+    /// Verb of the statement; differs from `leading` for CTEs
+    verb: Option<String>,
+    /// INSERT: the row source (VALUES/SELECT/EXEC/DEFAULT VALUES) has been seen
+    insert_source_seen: bool,
+    /// UPDATE: its SET clause has been seen
+    set_seen: bool,
+    /// GRANT/DENY/REVOKE: past the permission list (ON/TO/FROM seen)
+    permissions_done: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self, offset: usize) -> Option<&Token<'a>> {
+        self.tokens.get(self.pos + offset)
+    }
+
+    fn current(&self) -> Option<&Token<'a>> {
+        self.peek(0)
+    }
+
+    fn current_is(&self, keyword: &str) -> bool {
+        self.current().is_some_and(|t| t.is_keyword(keyword))
+    }
+
+    fn peek_is_any(&self, offset: usize, keywords: &[&str]) -> bool {
+        self.peek(offset)
+            .is_some_and(|t| keywords.iter().any(|k| t.is_keyword(k)))
+    }
+
+    /// `END` that closes a block (as opposed to `END CONVERSATION`)
+    fn at_block_end(&self) -> bool {
+        self.current_is("END") && !self.peek_is_any(1, END_NON_BLOCK)
+    }
+
+    fn parse_list(&mut self, in_block: bool) -> Vec<Statement> {
+        let mut statements = Vec::new();
+        loop {
+            while self
+                .current()
+                .is_some_and(|t| t.kind == TokenKind::Semicolon)
+            {
+                self.pos += 1;
+            }
+            if self.current().is_none() || (in_block && self.at_block_end()) {
+                break;
+            }
+            statements.push(self.parse_statement());
+        }
+        statements
+    }
+
+    fn parse_statement(&mut self) -> Statement {
+        let keyword = self.current().and_then(|t| t.keyword());
+        match keyword.as_deref() {
+            Some("BEGIN") if !self.peek_is_any(1, BEGIN_NON_BLOCK) => self.parse_block(),
+            Some("IF") => self.parse_conditional(true),
+            Some("WHILE") => self.parse_conditional(false),
+            Some("CREATE") | Some("ALTER") if self.is_module_definition() => self.parse_to_end(),
+            _ => self.parse_simple(),
+        }
+    }
+
+    /// `BEGIN [TRY|CATCH] ... END [TRY|CATCH] [;]`
+    fn parse_block(&mut self) -> Statement {
+        let start = self.tokens[self.pos].span.start;
+        let mut end = self.tokens[self.pos].span.end;
+        self.pos += 1;
+        if self.current_is("TRY") || self.current_is("CATCH") {
+            end = self.tokens[self.pos].span.end;
+            self.pos += 1;
+        }
+
+        let children = self.parse_list(true);
+        if let Some(last) = children.last() {
+            end = last.span.end;
+        }
+
+        if self.at_block_end() {
+            end = self.tokens[self.pos].span.end;
+            self.pos += 1;
+            if self.current_is("TRY") || self.current_is("CATCH") {
+                end = self.tokens[self.pos].span.end;
+                self.pos += 1;
+            }
+        }
+
+        let terminated = self.eat_semicolon(&mut end);
+        Statement {
+            span: Span::new(start, end),
+            keyword: Some("BEGIN".to_string()),
+            terminated,
+            children,
+        }
+    }
+
+    /// `IF <cond> <stmt> [ELSE <stmt>]` or `WHILE <cond> <stmt>`
+    fn parse_conditional(&mut self, allow_else: bool) -> Statement {
+        let start = self.tokens[self.pos].span.start;
+        let keyword = self.tokens[self.pos].keyword();
+        let mut end = self.tokens[self.pos].span.end;
+        self.pos += 1;
+
+        // Condition: everything up to the first statement keyword outside
+        // parentheses and CASE expressions
+        let mut depth = 0usize;
+        let mut case_depth = 0usize;
+        while let Some(tok) = self.current().copied() {
+            if depth == 0 && case_depth == 0 && self.begins_body(&tok) {
+                break;
+            }
+            track_nesting(&tok, &mut depth, &mut case_depth);
+            end = tok.span.end;
+            self.pos += 1;
+        }
+
+        let mut children = Vec::new();
+        if self.current().is_some() && !self.at_block_end() {
+            children.push(self.parse_statement());
+        }
+
+        if allow_else && self.current_is("ELSE") {
+            end = self.tokens[self.pos].span.end;
+            self.pos += 1;
+            if self.current().is_some() && !self.at_block_end() {
+                children.push(self.parse_statement());
+            }
+        }
+
+        let terminated = children.last().is_some_and(|c| c.terminated);
+        if let Some(last) = children.last() {
+            end = end.max(last.span.end);
+        }
+
+        Statement {
+            span: Span::new(start, end),
+            keyword,
+            terminated,
+            children,
+        }
+    }
+
+    /// A keyword that starts the body of an IF/WHILE
+    fn begins_body(&self, tok: &Token<'_>) -> bool {
+        let Some(kw) = tok.keyword() else {
+            return false;
+        };
+        if kw == "UPDATE" && self.peek(1).is_some_and(|t| t.kind == TokenKind::LeftParen) {
+            // UPDATE(column) inside a trigger condition
+            return false;
+        }
+        STATEMENT_KEYWORDS.contains(&kw.as_str()) || kw == "WITH"
+    }
+
+    /// `CREATE [OR ALTER] PROC|FUNCTION|TRIGGER|VIEW` or the `ALTER` form
+    fn is_module_definition(&self) -> bool {
+        let mut offset = 1;
+        if self.peek_is_any(1, &["OR"]) && self.peek_is_any(2, &["ALTER"]) {
+            offset = 3;
+        }
+        self.peek_is_any(offset, MODULE_OBJECTS)
+    }
+
+    /// Consume the rest of the batch as one statement
+    fn parse_to_end(&mut self) -> Statement {
+        let start = self.tokens[self.pos].span.start;
+        let keyword = self.tokens[self.pos].keyword();
+        let last = *self.tokens.last().expect("called with tokens remaining");
+        self.pos = self.tokens.len();
+        Statement {
+            span: Span::new(start, last.span.end),
+            keyword,
+            terminated: last.kind == TokenKind::Semicolon,
+            children: Vec::new(),
+        }
+    }
+
+    fn parse_simple(&mut self) -> Statement {
+        let first = self.tokens[self.pos];
+        let start = first.span.start;
+        let mut end = first.span.end;
+        let leading = first.keyword();
+        let mut state = SimpleState {
+            verb: leading.clone().filter(|k| k != "WITH"),
+            leading,
+            insert_source_seen: false,
+            set_seen: false,
+            permissions_done: false,
+        };
+
+        let mut depth = 0usize;
+        let mut case_depth = 0usize;
+        let mut first_word: Option<String> = None;
+        let mut terminated = false;
+        let mut is_first = true;
+
+        while let Some(tok) = self.current().copied() {
+            if !is_first && depth == 0 && case_depth == 0 {
+                if tok.kind == TokenKind::Semicolon {
+                    end = tok.span.end;
+                    self.pos += 1;
+                    terminated = true;
+                    break;
+                }
+                if self.at_block_end() || tok.is_keyword("ELSE") {
+                    break;
+                }
+                if self.starts_new_statement(&tok, &state) {
+                    break;
+                }
+                self.note_clause(&tok, &mut state);
+            }
+            if is_first && tok.kind == TokenKind::Semicolon {
+                // Defensive: parse_list skips semicolons before calling us
+                end = tok.span.end;
+                self.pos += 1;
+                terminated = true;
+                break;
+            }
+
+            if first_word.is_none() {
+                first_word = tok.keyword();
+            }
+            track_nesting(&tok, &mut depth, &mut case_depth);
+            end = tok.span.end;
+            self.pos += 1;
+            is_first = false;
+        }
+
+        Statement {
+            span: Span::new(start, end),
+            keyword: state.verb.or(state.leading).or(first_word),
+            terminated,
+            children: Vec::new(),
+        }
+    }
+
+    /// Record clauses that change how later keywords are interpreted
+    fn note_clause(&self, tok: &Token<'_>, state: &mut SimpleState) {
+        let Some(kw) = tok.keyword() else {
+            return;
+        };
+        match kw.as_str() {
+            "VALUES" | "SELECT" | "EXEC" | "EXECUTE" if state.verb.as_deref() == Some("INSERT") => {
+                state.insert_source_seen = true;
+            }
+            "SET" if state.verb.as_deref() == Some("UPDATE") => state.set_seen = true,
+            "ON" | "TO" | "FROM" => state.permissions_done = true,
+            _ => {}
+        }
+        if state.verb.is_none()
+            && state.leading.as_deref() == Some("WITH")
+            && CTE_VERBS.contains(&kw.as_str())
+        {
+            state.verb = Some(kw);
+        }
+    }
+
+    /// Whether `tok` (at the top level of the current statement) begins the
+    /// next statement rather than continuing this one
+    fn starts_new_statement(&self, tok: &Token<'_>, state: &SimpleState) -> bool {
+        let Some(kw) = tok.keyword() else {
+            return false;
+        };
+        if !STATEMENT_KEYWORDS.contains(&kw.as_str()) {
+            return false;
+        }
+
+        let prev = self.pos.checked_sub(1).and_then(|p| self.tokens.get(p));
+        let prev_kw = prev.and_then(|t| t.keyword()).unwrap_or_default();
+        let next = self.peek(1);
+        let leading = state.leading.as_deref().unwrap_or_default();
+        let verb = state.verb.as_deref().unwrap_or_default();
+
+        // Qualified names (`dbo.Update`) and option lists (`WITH ROLLBACK
+        // IMMEDIATE`, `WITH GRANT OPTION`, `WITH EXECUTE AS`)
+        if prev.is_some_and(|t| t.kind == TokenKind::Dot)
+            || next.is_some_and(|t| t.kind == TokenKind::Dot)
+        {
+            return false;
+        }
+        if prev_kw == "WITH" {
+            return false;
+        }
+        // Permission lists: GRANT SELECT, INSERT, EXECUTE ON ...
+        if matches!(leading, "GRANT" | "DENY" | "REVOKE") && !state.permissions_done {
+            return false;
+        }
+        // A CTE's main statement belongs to it
+        if leading == "WITH" && state.verb.is_none() {
+            return false;
+        }
+
+        match kw.as_str() {
+            "SELECT" => {
+                if matches!(
+                    prev_kw.as_str(),
+                    "UNION" | "ALL" | "EXCEPT" | "INTERSECT" | "FOR" | "AS"
+                ) {
+                    return false;
+                }
+                verb != "INSERT" || state.insert_source_seen
+            }
+            "EXEC" | "EXECUTE" => verb != "INSERT" || state.insert_source_seen,
+            "INSERT" | "UPDATE" | "DELETE" | "MERGE" => {
+                if prev.is_some_and(|t| t.kind == TokenKind::Comma) {
+                    return false;
+                }
+                if matches!(
+                    prev_kw.as_str(),
+                    "THEN" | "BULK" | "ON" | "FOR" | "AFTER" | "OF"
+                ) {
+                    return false;
+                }
+                if kw == "UPDATE" && next.is_some_and(|t| t.kind == TokenKind::LeftParen) {
+                    return false;
+                }
+                if kw == "MERGE"
+                    && (next.is_some_and(|t| t.is_keyword("JOIN"))
+                        || matches!(
+                            prev_kw.as_str(),
+                            "INNER" | "LEFT" | "RIGHT" | "FULL" | "OUTER" | "CROSS"
+                        ))
+                {
+                    return false;
+                }
+                true
+            }
+            "SET" => {
+                if matches!(prev_kw.as_str(), "UPDATE" | "DELETE") {
+                    return false;
+                }
+                if leading == "ALTER" {
+                    return false;
+                }
+                verb != "UPDATE" || state.set_seen
+            }
+            "ALTER" | "DROP" | "ENABLE" | "DISABLE" => leading != "ALTER",
+            "IF" => {
+                // DROP TABLE IF EXISTS t / ALTER TABLE ... DROP COLUMN IF EXISTS c
+                let exists_clause = next.is_some_and(|t| t.is_keyword("EXISTS"))
+                    && self.peek(2).is_some_and(|t| t.kind != TokenKind::LeftParen);
+                !exists_clause
+            }
+            "FETCH" => !matches!(prev_kw.as_str(), "ROW" | "ROWS"),
+            _ => true,
+        }
+    }
+
+    fn eat_semicolon(&mut self, end: &mut usize) -> bool {
+        if self
+            .current()
+            .is_some_and(|t| t.kind == TokenKind::Semicolon)
+        {
+            *end = self.tokens[self.pos].span.end;
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Track parenthesis and CASE...END nesting
+fn track_nesting(tok: &Token<'_>, depth: &mut usize, case_depth: &mut usize) {
+    match tok.kind {
+        TokenKind::LeftParen => *depth += 1,
+        TokenKind::RightParen => *depth = depth.saturating_sub(1),
+        TokenKind::Word if tok.is_keyword("CASE") => *case_depth += 1,
+        TokenKind::Word if tok.is_keyword("END") && *case_depth > 0 => *case_depth -= 1,
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batch_texts(sql: &str) -> Vec<(&str, u32)> {
+        split_batches(sql)
+            .iter()
+            .map(|b| (b.text(sql).trim(), b.repeat))
+            .collect()
+    }
+
+    fn keywords(sql: &str) -> Vec<String> {
+        parse_statements(sql)
+            .into_iter()
+            .map(|s| s.keyword.unwrap_or_default())
+            .collect()
+    }
+
+    fn chunks(sql: &str) -> Vec<&str> {
+        split_terminated(sql).iter().map(|s| s.text(sql)).collect()
+    }
+
+    // --- split_batches ---
+
+    #[test]
+    fn go_splits_batches_case_insensitively() {
+        assert_eq!(
+            batch_texts("SELECT 1\nGO\nSELECT 2\ngo\nSELECT 3"),
+            vec![("SELECT 1", 1), ("SELECT 2", 1), ("SELECT 3", 1)]
+        );
+    }
+
+    #[test]
+    fn go_with_count_is_a_separator() {
+        assert_eq!(
+            batch_texts("INSERT INTO t DEFAULT VALUES\nGO 5\nSELECT COUNT(*) FROM t"),
+            vec![
+                ("INSERT INTO t DEFAULT VALUES", 5),
+                ("SELECT COUNT(*) FROM t", 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn go_with_trailing_comment_is_a_separator() {
+        assert_eq!(batch_texts("SELECT 1\n  GO -- next\nSELECT 2").len(), 2);
+    }
+
+    #[test]
+    fn go_inside_strings_and_comments_is_ignored() {
+        let sql = "SELECT 'a\nGO\nb'\n/*\nGO\n*/\nSELECT 2";
+        assert_eq!(split_batches(sql).len(), 1);
+    }
+
+    #[test]
+    fn go_not_alone_on_its_line_is_not_a_separator() {
+        assert_eq!(split_batches("SELECT 1 GO\nSELECT 2").len(), 1);
+        assert_eq!(split_batches("GO TO x\nSELECT 2").len(), 1);
+        assert_eq!(split_batches("SELECT 1\nGOTO done").len(), 1);
+    }
+
+    #[test]
+    fn empty_batches_are_dropped_and_separator_recorded() {
+        let sql = "GO\n\nSELECT 1\nGO\nGO\n";
+        let batches = split_batches(sql);
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].separator.unwrap().text(sql), "GO");
+    }
+
+    // --- parse_statements ---
+
+    #[test]
+    fn statements_split_without_semicolons() {
+        assert_eq!(
+            keywords(
+                "DECLARE @x int = 1\nSELECT @x\nUPDATE t SET a = @x WHERE id = 1\nPRINT 'done'"
+            ),
+            vec!["DECLARE", "SELECT", "UPDATE", "PRINT"]
+        );
+    }
+
+    #[test]
+    fn update_set_and_session_set_are_distinguished() {
+        assert_eq!(
+            keywords("UPDATE t SET a = 1 SET @x = 2"),
+            vec!["UPDATE", "SET"]
+        );
+        assert_eq!(keywords("SET NOCOUNT ON SELECT 1"), vec!["SET", "SELECT"]);
+    }
+
+    #[test]
+    fn insert_select_and_insert_exec_are_one_statement() {
+        assert_eq!(
+            keywords("INSERT INTO t (a) SELECT a FROM s"),
+            vec!["INSERT"]
+        );
+        assert_eq!(
+            keywords("INSERT INTO t EXEC dbo.p\nSELECT 1"),
+            vec!["INSERT", "SELECT"]
+        );
+    }
+
+    #[test]
+    fn union_and_subqueries_stay_in_one_statement() {
+        assert_eq!(
+            keywords("SELECT a FROM t UNION ALL SELECT a FROM u WHERE a IN (SELECT b FROM v)"),
+            vec!["SELECT"]
+        );
+    }
+
+    #[test]
+    fn cte_takes_the_main_verb() {
+        let stmts = parse_statements(";WITH c AS (SELECT 1 AS x) UPDATE t SET a = c.x FROM c;");
+        assert_eq!(stmts.len(), 1);
+        assert!(stmts[0].is("UPDATE"));
+        assert!(stmts[0].terminated);
+    }
+
+    #[test]
+    fn merge_actions_stay_inside_merge() {
+        let sql = "MERGE INTO t USING s ON t.id = s.id\n\
+                   WHEN MATCHED THEN UPDATE SET t.a = s.a\n\
+                   WHEN NOT MATCHED THEN INSERT (id, a) VALUES (s.id, s.a)\n\
+                   WHEN NOT MATCHED BY SOURCE THEN DELETE;\nSELECT 1;";
+        assert_eq!(keywords(sql), vec!["MERGE", "SELECT"]);
+    }
+
+    #[test]
+    fn nested_begin_end_and_case_end() {
+        let sql = "BEGIN\n  SELECT CASE WHEN 1 = 1 THEN 'a' ELSE 'b' END;\n  BEGIN\n    UPDATE t SET a = 1;\n  END\nEND;\nSELECT 2;";
+        let stmts = parse_statements(sql);
+        assert_eq!(stmts.len(), 2);
+        assert!(stmts[0].is("BEGIN"));
+        assert!(stmts[0].terminated);
+        let inner: Vec<_> = stmts[0]
+            .walk()
+            .iter()
+            .filter_map(|s| s.keyword.clone())
+            .collect();
+        assert_eq!(inner, vec!["BEGIN", "SELECT", "BEGIN", "UPDATE"]);
+    }
+
+    #[test]
+    fn begin_tran_is_not_a_block() {
+        assert_eq!(
+            keywords("BEGIN TRAN; UPDATE t SET a = 1; COMMIT;"),
+            vec!["BEGIN", "UPDATE", "COMMIT"]
+        );
+    }
+
+    #[test]
+    fn try_catch_blocks() {
+        let sql = "BEGIN TRY\n  SELECT 1/0;\nEND TRY\nBEGIN CATCH\n  THROW;\nEND CATCH";
+        let stmts = parse_statements(sql);
+        assert_eq!(stmts.len(), 2);
+        assert_eq!(stmts[0].text(sql), "BEGIN TRY\n  SELECT 1/0;\nEND TRY");
+        assert!(stmts[1].children[0].is("THROW"));
+    }
+
+    #[test]
+    fn if_else_with_single_statements() {
+        let sql = "IF @x = 1 SELECT 1; ELSE SELECT 2; SELECT 3;";
+        let stmts = parse_statements(sql);
+        assert_eq!(stmts.len(), 2);
+        assert!(stmts[0].is("IF"));
+        assert_eq!(stmts[0].children.len(), 2);
+        assert!(stmts[1].is("SELECT"));
+    }
+
+    #[test]
+    fn if_exists_condition_with_subquery() {
+        let sql = "IF NOT EXISTS (SELECT 1 FROM t WHERE a = 1) INSERT INTO t (a) VALUES (1)";
+        let stmts = parse_statements(sql);
+        assert_eq!(stmts.len(), 1);
+        assert!(stmts[0].children[0].is("INSERT"));
+    }
+
+    #[test]
+    fn drop_if_exists_is_one_statement() {
+        assert_eq!(
+            keywords("DROP TABLE IF EXISTS #t\nCREATE TABLE #t (a int)"),
+            vec!["DROP", "CREATE"]
+        );
+    }
+
+    #[test]
+    fn create_procedure_body_runs_to_end_of_batch() {
+        let sql = "CREATE OR ALTER PROCEDURE dbo.p AS\nBEGIN\n  SET NOCOUNT ON;\n  SELECT 1;\nEND";
+        let stmts = parse_statements(sql);
+        assert_eq!(stmts.len(), 1);
+        assert!(stmts[0].is("CREATE"));
+        assert_eq!(stmts[0].text(sql), sql);
+    }
+
+    #[test]
+    fn module_body_as_skips_parameter_casts() {
+        let sql = "CREATE FUNCTION dbo.f(@x INT) RETURNS INT WITH SCHEMABINDING AS\nBEGIN RETURN CAST(@x AS INT) END";
+        let stmts = parse_statements(sql);
+        let body = stmts[0].module_body_as(sql).unwrap();
+        assert_eq!(body.start, sql.find("AS\n").unwrap());
+
+        let sql = "SELECT CAST(1 AS INT) AS x";
+        assert!(parse_statements(sql)[0].module_body_as(sql).is_none());
+    }
+
+    #[test]
+    fn while_loop_with_fetch() {
+        let sql = "WHILE @@FETCH_STATUS = 0\nBEGIN\n  FETCH NEXT FROM c INTO @x\nEND\nCLOSE c";
+        assert_eq!(keywords(sql), vec!["WHILE", "CLOSE"]);
+    }
+
+    #[test]
+    fn offset_fetch_is_part_of_select() {
+        assert_eq!(
+            keywords("SELECT a FROM t ORDER BY a OFFSET 10 ROWS FETCH NEXT 5 ROWS ONLY"),
+            vec!["SELECT"]
+        );
+    }
+
+    #[test]
+    fn grant_permission_list() {
+        assert_eq!(
+            keywords("GRANT SELECT, INSERT, EXECUTE ON SCHEMA::dbo TO app\nSELECT 1"),
+            vec!["GRANT", "SELECT"]
+        );
+    }
+
+    // --- split_terminated ---
+
+    #[test]
+    fn chunks_split_on_top_level_semicolons_only() {
+        assert_eq!(
+            chunks("SELECT 1; SELECT 2;"),
+            vec!["SELECT 1;", "SELECT 2;"]
+        );
+        assert_eq!(
+            chunks("BEGIN SELECT 1; SELECT 2; END; SELECT 3"),
+            vec!["BEGIN SELECT 1; SELECT 2; END;", "SELECT 3"]
+        );
+    }
+
+    #[test]
+    fn chunks_drop_leading_semicolons() {
+        assert_eq!(
+            chunks(";WITH c AS (SELECT 1 x) SELECT * FROM c;"),
+            vec!["WITH c AS (SELECT 1 x) SELECT * FROM c;"]
+        );
+    }
+
+    #[test]
+    fn chunks_keep_unterminated_statements_with_the_next_terminator() {
+        assert_eq!(
+            chunks("SELECT 1\nSELECT 2; SELECT 3;"),
+            vec!["SELECT 1\nSELECT 2;", "SELECT 3;"]
+        );
+    }
+
+    #[test]
+    fn chunks_keep_leading_comments() {
+        assert_eq!(
+            chunks("-- first\nSELECT 1; /* second */ SELECT 2;"),
+            vec!["-- first\nSELECT 1;", "/* second */ SELECT 2;"]
+        );
+    }
+
+    #[test]
+    fn syntax_diagnostics_find_structural_errors() {
+        let codes = |sql: &str| -> Vec<(String, String)> {
+            syntax_diagnostics(sql)
+                .into_iter()
+                .map(|d| (d.code, sql[d.span.start..d.span.end].to_string()))
+                .collect()
+        };
+        assert!(codes("BEGIN TRAN; IF 1 = 1 BEGIN SELECT (1) END; COMMIT").is_empty());
+        assert!(codes("SELECT CASE WHEN 1 = 1 THEN 'a' END").is_empty());
+        assert_eq!(
+            codes("SELECT (1\nGO\nSELECT 1)"),
+            vec![
+                (UNBALANCED_PARENS.to_string(), "(".to_string()),
+                (UNBALANCED_PARENS.to_string(), ")".to_string()),
+            ]
+        );
+        assert_eq!(
+            codes("BEGIN TRY SELECT 1"),
+            vec![(UNMATCHED_BLOCK.to_string(), "BEGIN".to_string())]
+        );
+        assert_eq!(
+            codes("SELECT 1; END"),
+            vec![(UNMATCHED_BLOCK.to_string(), "END".to_string())]
+        );
+        assert_eq!(
+            codes("SELECT 'abc"),
+            vec![(UNTERMINATED.to_string(), "'abc".to_string())]
+        );
+    }
+
+    #[test]
+    fn syntax_diagnostics_accept_end_conversation() {
+        let sql = "DECLARE @h UNIQUEIDENTIFIER;\nEND CONVERSATION @h;\n\
+                   IF 1 = 1 BEGIN END CONVERSATION @h WITH CLEANUP; END";
+        assert!(syntax_diagnostics(sql).is_empty());
+    }
+}
@@ -0,0 +1,496 @@
+// SQLCMD Mode Preprocessor
+// Expands sqlcmd directives (:setvar, $(Var), :r, :on error) into plain T-SQL
+// batches before execution. Only used when a tab opts into sqlcmd mode.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use super::parser::split_batches;
+
+/// Maximum `:r` nesting depth, guards against runaway includes
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Errors raised while expanding a sqlcmd script
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SqlcmdError {
+    #[error("'{name}' scripting variable not defined (line {line})")]
+    UndefinedVariable { name: String, line: usize },
+
+    #[error("Unsupported sqlcmd command ':{command}' (line {line})")]
+    UnsupportedCommand { command: String, line: usize },
+
+    #[error("Invalid sqlcmd directive (line {line}): {message}")]
+    InvalidDirective { line: usize, message: String },
+
+    #[error("Cannot include '{path}': {reason}")]
+    IncludeFailed { path: String, reason: String },
+}
+
+/// What to do when a batch fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnError {
+    /// Keep running the remaining batches (sqlcmd default)
+    #[default]
+    Ignore,
+    /// Stop the script at the first failing batch
+    Exit,
+}
+
+/// Inputs for expanding a script
+#[derive(Debug, Clone, Default)]
+pub struct SqlcmdOptions {
+    /// Predefined variables, as if set with `:setvar` before the script
+    pub variables: HashMap<String, String>,
+    /// Directory relative `:r` paths resolve against (the tab's file location)
+    pub base_dir: Option<PathBuf>,
+}
+
+/// One expanded batch, ready to execute
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SqlcmdBatch {
+    /// Batch text after variable substitution and includes
+    pub text: String,
+    /// Number of times to run the batch (`GO n`)
+    pub repeat: u32,
+    /// Error handling in effect when the batch runs
+    pub on_error: OnError,
+}
+
+/// Expand a sqlcmd script into executable batches
+pub fn preprocess(script: &str, options: &SqlcmdOptions) -> Result<Vec<SqlcmdBatch>, SqlcmdError> {
+    let mut expander = Expander {
+        variables: options
+            .variables
+            .iter()
+            .map(|(k, v)| (k.to_uppercase(), v.clone()))
+            .collect(),
+        output: String::new(),
+        on_error_marks: Vec::new(),
+        include_stack: Vec::new(),
+    };
+    expander.expand(script, options.base_dir.as_deref())?;
+
+    let output = expander.output;
+    let batches = split_batches(&output)
+        .into_iter()
+        .map(|batch| {
+            let on_error = expander
+                .on_error_marks
+                .iter()
+                .take_while(|(offset, _)| *offset <= batch.span.end)
+                .last()
+                .map(|(_, action)| *action)
+                .unwrap_or_default();
+            SqlcmdBatch {
+                text: batch.text(&output).trim().to_string(),
+                repeat: batch.repeat,
+                on_error,
+            }
+        })
+        .filter(|batch| !batch.text.is_empty())
+        .collect();
+
+    Ok(batches)
+}
+
+struct Expander {
+    /// Variable values keyed by upper-cased name (sqlcmd names are case-insensitive)
+    variables: HashMap<String, String>,
+    output: String,
+    /// Output offsets where `:on error` changed, in order
+    on_error_marks: Vec<(usize, OnError)>,
+    /// Files currently being included, for cycle detection
+    include_stack: Vec<PathBuf>,
+}
+
+impl Expander {
+    fn expand(&mut self, script: &str, base_dir: Option<&Path>) -> Result<(), SqlcmdError> {
+        let mut open = Open::None;
+
+        for (index, line) in script.split_inclusive('\n').enumerate() {
+            let line_no = index + 1;
+            let in_literal = open != Open::None;
+            let trimmed = line.trim();
+            if !in_literal && trimmed.starts_with(':') {
+                self.directive(&trimmed[1..], line_no, base_dir)?;
+                self.output.push('\n');
+            } else if !in_literal && trimmed.starts_with("!!") {
+                return Err(SqlcmdError::UnsupportedCommand {
+                    command: "!!".to_string(),
+                    line: line_no,
+                });
+            } else {
+                open = open.advance(line);
+                let expanded = self.substitute(line, line_no)?;
+                self.output.push_str(&expanded);
+            }
+        }
+
+        if !self.output.is_empty() && !self.output.ends_with('\n') {
+            self.output.push('\n');
+        }
+        Ok(())
+    }
+
+    fn directive(
+        &mut self,
+        text: &str,
+        line: usize,
+        base_dir: Option<&Path>,
+    ) -> Result<(), SqlcmdError> {
+        let (command, rest) = split_word(text);
+        match command.to_lowercase().as_str() {
+            "setvar" => {
+                let (name, value) = split_word(rest);
+                if !is_variable_name(name) {
+                    return Err(SqlcmdError::InvalidDirective {
+                        line,
+                        message: format!("invalid variable name '{}'", name),
+                    });
+                }
+                let value = value.trim();
+                if value.is_empty() {
+                    self.variables.remove(&name.to_uppercase());
+                } else {
+                    let value = unquote(value).ok_or_else(|| SqlcmdError::InvalidDirective {
+                        line,
+                        message: "unterminated quoted value".to_string(),
+                    })?;
+                    self.variables.insert(name.to_uppercase(), value);
+                }
+                Ok(())
+            }
+            "r" => {
+                let raw = rest.trim();
+                if raw.is_empty() {
+                    return Err(SqlcmdError::InvalidDirective {
+                        line,
+                        message: ":r requires a file name".to_string(),
+                    });
+                }
+                let path = unquote(raw).ok_or_else(|| SqlcmdError::InvalidDirective {
+                    line,
+                    message: "unterminated quoted file name".to_string(),
+                })?;
+                let path = self.substitute(&path, line)?;
+                self.include(&path, base_dir)
+            }
+            "on" => {
+                let (word, action) = split_word(rest);
+                let action = match (
+                    word.to_lowercase().as_str(),
+                    action.trim().to_lowercase().as_str(),
+                ) {
+                    ("error", "exit") => OnError::Exit,
+                    ("error", "ignore") => OnError::Ignore,
+                    _ => {
+                        return Err(SqlcmdError::InvalidDirective {
+                            line,
+                            message: "expected ':on error exit' or ':on error ignore'".to_string(),
+                        })
+                    }
+                };
+                self.on_error_marks.push((self.output.len(), action));
+                Ok(())
+            }
+            _ => Err(SqlcmdError::UnsupportedCommand {
+                command: command.to_string(),
+                line,
+            }),
+        }
+    }
+
+    fn include(&mut self, path: &str, base_dir: Option<&Path>) -> Result<(), SqlcmdError> {
+        let failed = |reason: String| SqlcmdError::IncludeFailed {
+            path: path.to_string(),
+            reason,
+        };
+
+        let requested = PathBuf::from(path);
+        let resolved = if requested.is_absolute() {
+            requested
+        } else {
+            match base_dir {
+                Some(dir) => dir.join(requested),
+                None => {
+                    return Err(failed(
+                        "relative path used but the tab has no file location".to_string(),
+                    ))
+                }
+            }
+        };
+        let resolved = resolved.canonicalize().map_err(|e| failed(e.to_string()))?;
+
+        if self.include_stack.contains(&resolved) {
+            return Err(failed("circular include".to_string()));
+        }
+        if self.include_stack.len() >= MAX_INCLUDE_DEPTH {
+            return Err(failed(format!(
+                "includes nested deeper than {}",
+                MAX_INCLUDE_DEPTH
+            )));
+        }
+
+        let content = std::fs::read_to_string(&resolved).map_err(|e| failed(e.to_string()))?;
+        let content = content
+            .strip_prefix('\u{feff}')
+            .unwrap_or(&content)
+            .to_string();
+
+        self.include_stack.push(resolved.clone());
+        let result = self.expand(&content, resolved.parent());
+        self.include_stack.pop();
+        result
+    }
+
+    /// Replace every `$(Name)` reference. sqlcmd substitutes inside string
+    /// literals too, so this works on raw text.
+    fn substitute(&self, text: &str, line: usize) -> Result<String, SqlcmdError> {
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(start) = rest.find("$(") {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            match after.find(')') {
+                Some(end) if is_variable_name(&after[..end]) => {
+                    let name = &after[..end];
+                    let value = self.variables.get(&name.to_uppercase()).ok_or_else(|| {
+                        SqlcmdError::UndefinedVariable {
+                            name: name.to_string(),
+                            line,
+                        }
+                    })?;
+                    out.push_str(value);
+                    rest = &after[end + 1..];
+                }
+                _ => {
+                    out.push_str("$(");
+                    rest = after;
+                }
+            }
+        }
+
+        out.push_str(rest);
+        Ok(out)
+    }
+}
+
+/// Literal still open at the end of a line. A line that starts inside a
+/// string, quoted identifier or block comment is text, not a directive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Open {
+    None,
+    /// Inside a literal closed by this character (`'`, `"` or `]`)
+    Quote(char),
+    /// Inside a block comment at this nesting depth
+    Comment(u32),
+}
+
+impl Open {
+    fn advance(self, line: &str) -> Open {
+        let mut state = self;
+        let mut chars = line.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            state = match state {
+                Open::None => match (c, chars.peek()) {
+                    ('-', Some('-')) => return Open::None,
+                    ('/', Some('*')) => {
+                        chars.next();
+                        Open::Comment(1)
+                    }
+                    ('\'', _) => Open::Quote('\''),
+                    ('"', _) => Open::Quote('"'),
+                    ('[', _) => Open::Quote(']'),
+                    _ => Open::None,
+                },
+                Open::Quote(close) if c == close => {
+                    if chars.peek() == Some(&close) {
+                        chars.next();
+                        Open::Quote(close)
+                    } else {
+                        Open::None
+                    }
+                }
+                Open::Comment(depth) => match (c, chars.peek()) {
+                    ('/', Some('*')) => {
+                        chars.next();
+                        Open::Comment(depth + 1)
+                    }
+                    ('*', Some('/')) => {
+                        chars.next();
+                        if depth == 1 {
+                            Open::None
+                        } else {
+                            Open::Comment(depth - 1)
+                        }
+                    }
+                    _ => Open::Comment(depth),
+                },
+                open => open,
+            };
+        }
+
+        state
+    }
+}
+
+fn split_word(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    match text.find(char::is_whitespace) {
+        Some(pos) => (&text[..pos], &text[pos..]),
+        None => (text, ""),
+    }
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Strip surrounding double quotes (with `""` escapes); unquoted text is
+/// returned as-is. Returns `None` for an unterminated quote.
+fn unquote(text: &str) -> Option<String> {
+    let Some(inner) = text.strip_prefix('"') else {
+        return Some(text.to_string());
+    };
+    let inner = inner.strip_suffix('"')?;
+    Some(inner.replace("\"\"", "\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn run(script: &str) -> Vec<SqlcmdBatch> {
+        preprocess(script, &SqlcmdOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn substitutes_setvar_values() {
+        let batches = run(":setvar Table dbo.Users\nSELECT * FROM $(Table)");
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].text, "SELECT * FROM dbo.Users");
+    }
+
+    #[test]
+    fn variables_are_case_insensitive_and_substituted_in_strings() {
+        let batches = run(":setvar Name \"O'Neil \"\"Jr\"\"\"\nPRINT '$(NAME)'");
+        assert_eq!(batches[0].text, "PRINT 'O'Neil \"Jr\"'");
+    }
+
+    #[test]
+    fn predefined_variables_are_available() {
+        let mut options = SqlcmdOptions::default();
+        options
+            .variables
+            .insert("db".to_string(), "Sales".to_string());
+        let batches = preprocess("USE $(DB)", &options).unwrap();
+        assert_eq!(batches[0].text, "USE Sales");
+    }
+
+    #[test]
+    fn reports_undefined_variable_with_line() {
+        let err = preprocess("SELECT 1\nSELECT $(Missing)", &SqlcmdOptions::default()).unwrap_err();
+        assert_eq!(
+            err,
+            SqlcmdError::UndefinedVariable {
+                name: "Missing".to_string(),
+                line: 2
+            }
+        );
+    }
+
+    #[test]
+    fn keeps_go_repeat_count() {
+        let batches = run("INSERT INTO T DEFAULT VALUES\nGO 10\nSELECT COUNT(*) FROM T");
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].repeat, 10);
+        assert_eq!(batches[1].repeat, 1);
+    }
+
+    #[test]
+    fn on_error_applies_to_following_batches() {
+        let batches = run("SELECT 1\nGO\n:on error exit\nSELECT 2\nGO\n:ON ERROR IGNORE\nSELECT 3");
+        let actions: Vec<OnError> = batches.iter().map(|b| b.on_error).collect();
+        assert_eq!(
+            actions,
+            vec![OnError::Ignore, OnError::Exit, OnError::Ignore]
+        );
+    }
+
+    #[test]
+    fn ignores_directive_lines_inside_block_comments_and_strings() {
+        let batches = run("/*\n:setvar X 1\n*/\nSELECT 'a\n:r nothing.sql\n'");
+        assert_eq!(batches.len(), 1);
+        assert!(batches[0].text.contains(":setvar X 1"));
+        assert!(batches[0].text.contains(":r nothing.sql"));
+    }
+
+    #[test]
+    fn quotes_in_directives_do_not_hide_following_directives() {
+        let batches = run(":setvar A \"it's\"\n:setvar B [x\nSELECT '$(A)', $(B)");
+        assert_eq!(batches[0].text, "SELECT 'it's', [x");
+    }
+
+    #[test]
+    fn rejects_unsupported_commands() {
+        let err = preprocess(":connect server1", &SqlcmdOptions::default()).unwrap_err();
+        assert!(
+            matches!(err, SqlcmdError::UnsupportedCommand { ref command, line: 1 } if command == "connect")
+        );
+    }
+
+    #[test]
+    fn inlines_included_files_relative_to_base_dir() {
+        let dir = std::env::temp_dir().join(format!("larik_sqlcmd_{}", Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        std::fs::write(
+            dir.join("lib").join("inner.sql"),
+            ":setvar Col id\nSELECT $(Col) FROM T\nGO",
+        )
+        .unwrap();
+        std::fs::write(dir.join("outer.sql"), ":r lib/inner.sql").unwrap();
+
+        let options = SqlcmdOptions {
+            base_dir: Some(dir.clone()),
+            ..Default::default()
+        };
+        let batches = preprocess(":r \"outer.sql\"\nSELECT '$(Col)'", &options).unwrap();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].text, "SELECT id FROM T");
+        assert_eq!(batches[1].text, "SELECT 'id'");
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn detects_circular_includes() {
+        let dir = std::env::temp_dir().join(format!("larik_sqlcmd_{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.sql"), ":r b.sql").unwrap();
+        std::fs::write(dir.join("b.sql"), ":r a.sql").unwrap();
+
+        let options = SqlcmdOptions {
+            base_dir: Some(dir.clone()),
+            ..Default::default()
+        };
+        let err = preprocess(":r a.sql", &options).unwrap_err();
+        assert!(
+            matches!(err, SqlcmdError::IncludeFailed { ref reason, .. } if reason == "circular include")
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn relative_include_without_base_dir_fails() {
+        let err = preprocess(":r x.sql", &SqlcmdOptions::default()).unwrap_err();
+        assert!(matches!(err, SqlcmdError::IncludeFailed { .. }));
+    }
+}
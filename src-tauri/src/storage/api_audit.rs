@@ -0,0 +1,106 @@
+// Local API audit trail
+// One row per call made through the automation API, with its outcome
+
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::database::{DatabaseManager, StorageResult};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiAuditEntry {
+    pub id: String,
+    pub method: String,
+    /// Request parameters as JSON, shortened for large payloads
+    pub params: Option<String>,
+    pub error: Option<String>,
+    pub duration_ms: i64,
+    pub created_at: String,
+}
+
+impl DatabaseManager {
+    /// Record an API call
+    pub fn add_api_audit_entry(
+        &self,
+        method: &str,
+        params: Option<&str>,
+        error: Option<&str>,
+        duration_ms: i64,
+    ) -> StorageResult<()> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO api_audit_log (id, method, params, error, duration_ms)
+                 VALUES (?, ?, ?, ?, ?)",
+                params![
+                    Uuid::new_v4().to_string(),
+                    method,
+                    params,
+                    error,
+                    duration_ms
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Recent API calls, newest first
+    pub fn get_api_audit_log(&self, limit: usize) -> StorageResult<Vec<ApiAuditEntry>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, method, params, error, duration_ms, created_at
+                 FROM api_audit_log
+                 ORDER BY created_at DESC, rowid DESC
+                 LIMIT ?",
+            )?;
+            let entries = stmt
+                .query_map(params![limit as i64], |row| {
+                    Ok(ApiAuditEntry {
+                        id: row.get(0)?,
+                        method: row.get(1)?,
+                        params: row.get(2)?,
+                        error: row.get(3)?,
+                        duration_ms: row.get(4)?,
+                        created_at: row.get(5)?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(entries)
+        })
+    }
+
+    /// Delete all audit entries. Returns the number removed.
+    pub fn clear_api_audit_log(&self) -> StorageResult<usize> {
+        self.with_connection(|conn| conn.execute("DELETE FROM api_audit_log", []))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_and_lists_calls() {
+        let db_path =
+            std::env::temp_dir().join(format!("larik_api_audit_test_{}.db", Uuid::new_v4()));
+        let db = DatabaseManager::new(db_path).unwrap();
+
+        db.add_api_audit_entry("get_spaces", None, None, 3).unwrap();
+        db.add_api_audit_entry(
+            "execute_query",
+            Some(r#"{"query":"SELECT 1"}"#),
+            Some("Login failed"),
+            40,
+        )
+        .unwrap();
+
+        let log = db.get_api_audit_log(10).unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].method, "execute_query");
+        assert_eq!(log[0].error.as_deref(), Some("Login failed"));
+        assert_eq!(log[1].params, None);
+        assert_eq!(db.get_api_audit_log(1).unwrap().len(), 1);
+
+        assert_eq!(db.clear_api_audit_log().unwrap(), 2);
+        assert!(db.get_api_audit_log(10).unwrap().is_empty());
+    }
+}
@@ -273,6 +273,21 @@ impl DatabaseManager {
             "#
         )?;
 
+        // Migration: Column pairs of composite virtual references. References
+        // saved before this table existed have no rows and use their single
+        // source_column/target_column pair.
+        conn.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS virtual_reference_columns (
+                reference_id TEXT NOT NULL REFERENCES virtual_references(id) ON DELETE CASCADE,
+                ordinal INTEGER NOT NULL,
+                source_column TEXT NOT NULL,
+                target_column TEXT NOT NULL,
+                PRIMARY KEY (reference_id, ordinal)
+            );
+            "#
+        )?;
+
         // Migration: Add folder_id column to pinned_tabs
         let has_folder_id: bool = conn.query_row(
             "SELECT COUNT(*) > 0 FROM pragma_table_info('pinned_tabs') WHERE name = 'folder_id'",
@@ -360,6 +375,120 @@ impl DatabaseManager {
             )?;
         }
 
+        // Migration: Create query_history table (one row per executed statement)
+        conn.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS query_history (
+                id TEXT PRIMARY KEY,
+                space_id TEXT,
+                connection_id TEXT NOT NULL,
+                database TEXT,
+                query_text TEXT NOT NULL,
+                started_at TEXT NOT NULL,
+                duration_ms INTEGER NOT NULL DEFAULT 0,
+                row_count INTEGER NOT NULL DEFAULT 0,
+                error TEXT,
+                is_selection INTEGER NOT NULL DEFAULT 0,
+                FOREIGN KEY (space_id) REFERENCES spaces(id) ON DELETE SET NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_query_history_space_id ON query_history(space_id);
+            CREATE INDEX IF NOT EXISTS idx_query_history_started_at ON query_history(started_at);
+
+            CREATE VIRTUAL TABLE IF NOT EXISTS query_history_fts USING fts5(
+                query_text,
+                content='query_history',
+                content_rowid='rowid'
+            );
+
+            CREATE TRIGGER IF NOT EXISTS query_history_fts_insert AFTER INSERT ON query_history BEGIN
+                INSERT INTO query_history_fts(rowid, query_text) VALUES (new.rowid, new.query_text);
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS query_history_fts_delete AFTER DELETE ON query_history BEGIN
+                INSERT INTO query_history_fts(query_history_fts, rowid, query_text)
+                VALUES ('delete', old.rowid, old.query_text);
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS query_history_fts_update AFTER UPDATE ON query_history BEGIN
+                INSERT INTO query_history_fts(query_history_fts, rowid, query_text)
+                VALUES ('delete', old.rowid, old.query_text);
+                INSERT INTO query_history_fts(rowid, query_text) VALUES (new.rowid, new.query_text);
+            END;
+            "#
+        )?;
+
+        // Migration: Create api_audit_log table (calls made through the local API)
+        conn.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS api_audit_log (
+                id TEXT PRIMARY KEY,
+                method TEXT NOT NULL,
+                params TEXT,
+                error TEXT,
+                duration_ms INTEGER NOT NULL DEFAULT 0,
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_api_audit_log_created_at ON api_audit_log(created_at);
+            "#
+        )?;
+
+        // Migration: Create schedules and schedule_runs tables (scheduled queries)
+        conn.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS schedules (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                space_id TEXT NOT NULL,
+                database TEXT,
+                source_type TEXT NOT NULL,
+                source_id TEXT NOT NULL,
+                cron TEXT,
+                interval_minutes INTEGER,
+                output_path TEXT,
+                output_format TEXT,
+                alert TEXT,
+                enabled INTEGER NOT NULL DEFAULT 1,
+                last_run_at TEXT,
+                next_run_at TEXT,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+                FOREIGN KEY (space_id) REFERENCES spaces(id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS schedule_runs (
+                id TEXT PRIMARY KEY,
+                schedule_id TEXT NOT NULL,
+                started_at TEXT NOT NULL,
+                duration_ms INTEGER NOT NULL DEFAULT 0,
+                row_count INTEGER NOT NULL DEFAULT 0,
+                output_path TEXT,
+                error TEXT,
+                alert TEXT,
+                FOREIGN KEY (schedule_id) REFERENCES schedules(id) ON DELETE CASCADE
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_schedules_next_run_at ON schedules(next_run_at);
+            CREATE INDEX IF NOT EXISTS idx_schedule_runs_schedule_id ON schedule_runs(schedule_id, started_at);
+            "#
+        )?;
+
+        // Migration: Add snippet_values column to schedules (JSON object of
+        // placeholder values for snippet sources)
+        let has_snippet_values: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('schedules') WHERE name = 'snippet_values'",
+            [],
+            |row| row.get(0),
+        )?;
+
+        if !has_snippet_values {
+            conn.execute(
+                "ALTER TABLE schedules ADD COLUMN snippet_values TEXT",
+                [],
+            )?;
+        }
+
         // Migration: Create FTS5 virtual table for full-text search
         let has_fts: bool = conn.query_row(
             "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type='table' AND name='archived_tabs_fts'",
@@ -259,31 +259,7 @@ impl DatabaseManager {
         space_id: Option<&str>,
         limit: usize,
     ) -> StorageResult<Vec<ArchiveSearchResult>> {
-        // Sanitize query for FTS5: remove special characters that could cause syntax errors
-        let search_query = query
-            .split_whitespace()
-            .filter_map(|word| {
-                // Remove FTS5 special characters, keep only alphanumeric and underscore
-                let sanitized: String = word
-                    .chars()
-                    .filter(|c| c.is_alphanumeric() || *c == '_')
-                    .collect();
-                
-                if sanitized.is_empty() {
-                    return None;
-                }
-                
-                // FTS5 reserved keywords need to be quoted
-                let upper = sanitized.to_uppercase();
-                if upper == "AND" || upper == "OR" || upper == "NOT" || upper == "NEAR" {
-                    return Some(format!("\"{}\"", sanitized));
-                }
-                
-                // Append * for prefix matching
-                Some(format!("{}*", sanitized))
-            })
-            .collect::<Vec<_>>()
-            .join(" ");
+        let search_query = fts_match_query(query);
 
         if search_query.is_empty() {
             return Ok(Vec::new());
@@ -582,6 +558,35 @@ impl DatabaseManager {
     }
 }
 
+/// Build an FTS5 MATCH expression from user input: special characters are
+/// removed, reserved keywords quoted and every word prefix-matched
+pub(super) fn fts_match_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .filter_map(|word| {
+            // Remove FTS5 special characters, keep only alphanumeric and underscore
+            let sanitized: String = word
+                .chars()
+                .filter(|c| c.is_alphanumeric() || *c == '_')
+                .collect();
+
+            if sanitized.is_empty() {
+                return None;
+            }
+
+            // FTS5 reserved keywords need to be quoted
+            let upper = sanitized.to_uppercase();
+            if upper == "AND" || upper == "OR" || upper == "NOT" || upper == "NEAR" {
+                return Some(format!("\"{}\"", sanitized));
+            }
+
+            // Append * for prefix matching
+            Some(format!("{}*", sanitized))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
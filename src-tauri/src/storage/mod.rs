@@ -1,11 +1,14 @@
 // Local persistence for UI state
 // This module handles saving/loading pinned tabs, spaces, and other UI state
 
+pub mod api_audit;
 pub mod crud;
 pub mod database;
 pub mod folders;
 pub mod history;
 pub mod notes;
+pub mod query_history;
+pub mod schedules;
 pub mod schema_cache;
 pub mod snippets;
 pub mod spaces;
@@ -13,12 +16,22 @@ pub mod state;
 pub mod tabs;
 pub mod virtual_references;
 
+pub use api_audit::ApiAuditEntry;
 pub use database::{get_default_db_path, DatabaseManager, StorageError, StorageResult};
 pub use folders::{CreateFolderInput, TabFolder, UpdateFolderInput};
 pub use history::{ArchiveSearchResult, ArchivedTab};
 pub use notes::StickyNote;
+pub use query_history::{
+    CreateQueryHistoryInput, QueryHistoryEntry, QueryHistoryFilter, QueryHistorySearchResult,
+    QueryHistoryStatus,
+};
+pub use schedules::{
+    AlertCondition, Comparison, Schedule, ScheduleInput, ScheduleRun, ScheduleSource,
+};
 pub use snippets::{CreateSnippetInput, Snippet, UpdateSnippetInput};
 pub use spaces::{CreateSpaceInput, Space, UpdateSpaceInput};
 pub use state::{AppSettings, AutoArchiveSettings};
 pub use tabs::{CreateTabInput, Tab, TabType, UpdateTabInput};
-pub use virtual_references::VirtualReference;
+pub use virtual_references::{
+    PortableReference, VirtualReference, VirtualReferenceColumn, VirtualReferenceSet,
+};
@@ -0,0 +1,454 @@
+// Query execution history
+// Every statement run from a tab, with timing and outcome, searchable via FTS5
+
+use rusqlite::{params, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::database::{DatabaseManager, StorageResult};
+use super::history::fts_match_query;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryHistoryEntry {
+    pub id: String,
+    pub space_id: Option<String>,
+    pub connection_id: String,
+    pub database: Option<String>,
+    pub query_text: String,
+    pub started_at: String,
+    pub duration_ms: i64,
+    pub row_count: i64,
+    pub error: Option<String>,
+    pub is_selection: bool,
+}
+
+/// Input for recording an executed statement
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateQueryHistoryInput {
+    pub connection_id: String,
+    pub database: Option<String>,
+    pub query_text: String,
+    pub started_at: String,
+    pub duration_ms: i64,
+    pub row_count: i64,
+    pub error: Option<String>,
+    pub is_selection: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueryHistoryStatus {
+    Success,
+    Error,
+}
+
+/// Filters for listing and searching history. `from`/`to` are inclusive
+/// date or datetime strings compared against `started_at`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueryHistoryFilter {
+    pub space_id: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub status: Option<QueryHistoryStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryHistorySearchResult {
+    pub entry: QueryHistoryEntry,
+    pub rank: f64,
+    pub snippet: Option<String>,
+}
+
+const ENTRY_COLUMNS: &str = "qh.id, qh.space_id, qh.connection_id, qh.database, qh.query_text, qh.started_at,
+     qh.duration_ms, qh.row_count, qh.error, qh.is_selection";
+
+fn entry_from_row(row: &Row) -> rusqlite::Result<QueryHistoryEntry> {
+    Ok(QueryHistoryEntry {
+        id: row.get(0)?,
+        space_id: row.get(1)?,
+        connection_id: row.get(2)?,
+        database: row.get(3)?,
+        query_text: row.get(4)?,
+        started_at: row.get(5)?,
+        duration_ms: row.get(6)?,
+        row_count: row.get(7)?,
+        error: row.get(8)?,
+        is_selection: row.get::<_, i32>(9)? != 0,
+    })
+}
+
+/// WHERE conditions and parameters for a filter, starting with `conditions`
+fn filter_clause(
+    filter: &QueryHistoryFilter,
+    mut conditions: Vec<String>,
+    mut params: Vec<Box<dyn rusqlite::ToSql>>,
+) -> (String, Vec<Box<dyn rusqlite::ToSql>>) {
+    if let Some(space_id) = &filter.space_id {
+        conditions.push("qh.space_id = ?".to_string());
+        params.push(Box::new(space_id.clone()));
+    }
+    if let Some(from) = &filter.from {
+        conditions.push("julianday(qh.started_at) >= julianday(?)".to_string());
+        params.push(Box::new(from.clone()));
+    }
+    if let Some(to) = &filter.to {
+        // A bare date includes the whole day
+        let to = if to.len() == 10 { format!("{} 23:59:59", to) } else { to.clone() };
+        conditions.push("julianday(qh.started_at) <= julianday(?)".to_string());
+        params.push(Box::new(to));
+    }
+    match filter.status {
+        Some(QueryHistoryStatus::Success) => conditions.push("qh.error IS NULL".to_string()),
+        Some(QueryHistoryStatus::Error) => conditions.push("qh.error IS NOT NULL".to_string()),
+        None => {}
+    }
+
+    let clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+    (clause, params)
+}
+
+impl DatabaseManager {
+    /// Record executed statements. `space_id` is set when the connection
+    /// belongs to a space (space connections use the space ID).
+    pub fn add_query_history_entries(
+        &self,
+        inputs: Vec<CreateQueryHistoryInput>,
+    ) -> StorageResult<Vec<QueryHistoryEntry>> {
+        self.with_connection_mut(|conn| {
+            let tx = conn.transaction()?;
+            let mut entries = Vec::with_capacity(inputs.len());
+
+            for input in inputs {
+                let id = Uuid::new_v4().to_string();
+                tx.execute(
+                    "INSERT INTO query_history
+                     (id, space_id, connection_id, database, query_text, started_at,
+                      duration_ms, row_count, error, is_selection)
+                     VALUES (?1, (SELECT id FROM spaces WHERE id = ?2), ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    params![
+                        &id,
+                        &input.connection_id,
+                        &input.database,
+                        &input.query_text,
+                        &input.started_at,
+                        input.duration_ms,
+                        input.row_count,
+                        &input.error,
+                        input.is_selection as i32,
+                    ],
+                )?;
+                let space_id: Option<String> = tx.query_row(
+                    "SELECT space_id FROM query_history WHERE id = ?",
+                    params![&id],
+                    |row| row.get(0),
+                )?;
+
+                entries.push(QueryHistoryEntry {
+                    id,
+                    space_id,
+                    connection_id: input.connection_id,
+                    database: input.database,
+                    query_text: input.query_text,
+                    started_at: input.started_at,
+                    duration_ms: input.duration_ms,
+                    row_count: input.row_count,
+                    error: input.error,
+                    is_selection: input.is_selection,
+                });
+            }
+
+            tx.commit()?;
+            Ok(entries)
+        })
+    }
+
+    /// Get a single history entry
+    pub fn get_query_history_entry(&self, id: &str) -> StorageResult<Option<QueryHistoryEntry>> {
+        self.with_connection(|conn| {
+            conn.query_row(
+                &format!("SELECT {} FROM query_history qh WHERE qh.id = ?", ENTRY_COLUMNS),
+                params![id],
+                entry_from_row,
+            )
+            .optional()
+        })
+    }
+
+    /// List history newest first, or search it with FTS5 (LIKE fallback) when
+    /// `query` is not empty
+    pub fn search_query_history(
+        &self,
+        query: &str,
+        filter: &QueryHistoryFilter,
+        limit: Option<usize>,
+        offset: Option<usize>,
+    ) -> StorageResult<Vec<QueryHistorySearchResult>> {
+        let limit = limit.unwrap_or(100);
+        let offset = offset.unwrap_or(0);
+        let query_trimmed = query.trim();
+
+        if query_trimmed.is_empty() {
+            return self.list_query_history(filter, limit, offset);
+        }
+
+        match self.search_query_history_fts(query_trimmed, filter, limit, offset) {
+            Ok(results) => Ok(results),
+            Err(_) => self.search_query_history_like(query_trimmed, filter, limit, offset),
+        }
+    }
+
+    fn list_query_history(
+        &self,
+        filter: &QueryHistoryFilter,
+        limit: usize,
+        offset: usize,
+    ) -> StorageResult<Vec<QueryHistorySearchResult>> {
+        let (clause, mut params) = filter_clause(filter, Vec::new(), Vec::new());
+        params.push(Box::new(limit as i64));
+        params.push(Box::new(offset as i64));
+        let sql = format!(
+            "SELECT {} FROM query_history qh {} ORDER BY qh.started_at DESC LIMIT ? OFFSET ?",
+            ENTRY_COLUMNS, clause
+        );
+
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(&sql)?;
+            let results = stmt
+                .query_map(rusqlite::params_from_iter(params.iter()), |row| {
+                    Ok(QueryHistorySearchResult {
+                        entry: entry_from_row(row)?,
+                        rank: 0.0,
+                        snippet: None,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(results)
+        })
+    }
+
+    fn search_query_history_fts(
+        &self,
+        query: &str,
+        filter: &QueryHistoryFilter,
+        limit: usize,
+        offset: usize,
+    ) -> StorageResult<Vec<QueryHistorySearchResult>> {
+        let search_query = fts_match_query(query);
+        if search_query.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let (clause, mut params) = filter_clause(
+            filter,
+            vec!["query_history_fts MATCH ?".to_string()],
+            vec![Box::new(search_query)],
+        );
+        params.push(Box::new(limit as i64));
+        params.push(Box::new(offset as i64));
+        let sql = format!(
+            "SELECT {},
+                    fts.rank,
+                    snippet(query_history_fts, 0, '<mark>', '</mark>', '...', 64) as snippet
+             FROM query_history_fts fts
+             JOIN query_history qh ON fts.rowid = qh.rowid
+             {}
+             ORDER BY rank
+             LIMIT ? OFFSET ?",
+            ENTRY_COLUMNS, clause
+        );
+
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(&sql)?;
+            let results = stmt
+                .query_map(rusqlite::params_from_iter(params.iter()), |row| {
+                    Ok(QueryHistorySearchResult {
+                        entry: entry_from_row(row)?,
+                        rank: row.get(10)?,
+                        snippet: row.get(11)?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(results)
+        })
+    }
+
+    fn search_query_history_like(
+        &self,
+        query: &str,
+        filter: &QueryHistoryFilter,
+        limit: usize,
+        offset: usize,
+    ) -> StorageResult<Vec<QueryHistorySearchResult>> {
+        let like_pattern = format!("%{}%", query.replace(['%', '_'], ""));
+        let (clause, mut params) = filter_clause(
+            filter,
+            vec!["qh.query_text LIKE ? COLLATE NOCASE".to_string()],
+            vec![Box::new(like_pattern)],
+        );
+        params.push(Box::new(limit as i64));
+        params.push(Box::new(offset as i64));
+        let sql = format!(
+            "SELECT {} FROM query_history qh {} ORDER BY qh.started_at DESC LIMIT ? OFFSET ?",
+            ENTRY_COLUMNS, clause
+        );
+
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(&sql)?;
+            let results = stmt
+                .query_map(rusqlite::params_from_iter(params.iter()), |row| {
+                    let entry = entry_from_row(row)?;
+                    let snippet = Some(entry.query_text.clone());
+                    Ok(QueryHistorySearchResult {
+                        entry,
+                        rank: 0.0, // No ranking for LIKE search
+                        snippet,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(results)
+        })
+    }
+
+    /// Permanently delete a history entry
+    pub fn delete_query_history_entry(&self, id: &str) -> StorageResult<bool> {
+        self.with_connection(|conn| {
+            let affected = conn.execute("DELETE FROM query_history WHERE id = ?", params![id])?;
+            Ok(affected > 0)
+        })
+    }
+
+    /// Cleanup history entries older than specified retention period
+    pub fn cleanup_old_query_history(&self, retention_days: i32) -> StorageResult<usize> {
+        self.with_connection(|conn| {
+            let affected = conn.execute(
+                "DELETE FROM query_history
+                 WHERE julianday('now') - julianday(started_at) > ?",
+                params![retention_days],
+            )?;
+            Ok(affected)
+        })
+    }
+
+    /// Permanently delete ALL history entries
+    pub fn purge_query_history(&self) -> StorageResult<usize> {
+        self.with_connection(|conn| {
+            let affected = conn.execute("DELETE FROM query_history", [])?;
+            Ok(affected)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::spaces::CreateSpaceInput;
+
+    fn create_test_db() -> DatabaseManager {
+        let temp_dir = std::env::temp_dir();
+        let db_path = temp_dir.join(format!("larik_query_history_test_{}.db", Uuid::new_v4()));
+        DatabaseManager::new(db_path).unwrap()
+    }
+
+    fn input(connection_id: &str, query_text: &str, started_at: &str, error: Option<&str>) -> CreateQueryHistoryInput {
+        CreateQueryHistoryInput {
+            connection_id: connection_id.to_string(),
+            database: Some("master".to_string()),
+            query_text: query_text.to_string(),
+            started_at: started_at.to_string(),
+            duration_ms: 12,
+            row_count: 3,
+            error: error.map(str::to_string),
+            is_selection: false,
+        }
+    }
+
+    #[test]
+    fn test_record_links_space_connections() {
+        let db = create_test_db();
+        let space = db
+            .create_space(CreateSpaceInput {
+                name: "Work".to_string(),
+                color: None,
+                icon: None,
+                connection_host: None,
+                connection_port: None,
+                connection_database: None,
+                connection_username: None,
+                connection_password: None,
+                connection_trust_cert: None,
+                connection_encrypt: None,
+            })
+            .unwrap();
+
+        let entries = db
+            .add_query_history_entries(vec![
+                input(&space.id, "SELECT 1", "2026-01-01 10:00:00", None),
+                input("standalone", "SELECT 2", "2026-01-01 10:00:01", None),
+            ])
+            .unwrap();
+
+        assert_eq!(entries[0].space_id.as_deref(), Some(space.id.as_str()));
+        assert_eq!(entries[1].space_id, None);
+        let stored = db.get_query_history_entry(&entries[0].id).unwrap().unwrap();
+        assert_eq!(stored.query_text, "SELECT 1");
+        assert_eq!(stored.row_count, 3);
+    }
+
+    #[test]
+    fn test_search_with_filters() {
+        let db = create_test_db();
+        db.add_query_history_entries(vec![
+            input("c1", "SELECT * FROM customers", "2026-01-01 10:00:00", None),
+            input("c1", "SELECT * FROM customer_orders", "2026-01-02 10:00:00", Some("Invalid object name")),
+            input("c1", "DELETE FROM invoices", "2026-01-03 10:00:00", None),
+        ])
+        .unwrap();
+
+        let all = db.search_query_history("", &QueryHistoryFilter::default(), None, None).unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].entry.query_text, "DELETE FROM invoices");
+
+        let found = db.search_query_history("customer", &QueryHistoryFilter::default(), None, None).unwrap();
+        assert_eq!(found.len(), 2);
+        assert!(found[0].snippet.as_deref().unwrap().contains("<mark>"));
+
+        let errors = QueryHistoryFilter {
+            status: Some(QueryHistoryStatus::Error),
+            ..Default::default()
+        };
+        let found = db.search_query_history("customer", &errors, None, None).unwrap();
+        assert_eq!(found.len(), 1);
+        assert!(found[0].entry.error.is_some());
+
+        let day = QueryHistoryFilter {
+            from: Some("2026-01-02".to_string()),
+            to: Some("2026-01-02".to_string()),
+            ..Default::default()
+        };
+        let found = db.search_query_history("", &day, None, None).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].entry.started_at, "2026-01-02 10:00:00");
+    }
+
+    #[test]
+    fn test_cleanup_old_query_history() {
+        let db = create_test_db();
+        let recent = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        db.add_query_history_entries(vec![
+            input("c1", "SELECT 1", "2020-01-01 00:00:00", None),
+            input("c1", "SELECT 2", &recent, None),
+        ])
+        .unwrap();
+
+        assert_eq!(db.cleanup_old_query_history(90).unwrap(), 1);
+        let remaining = db.search_query_history("", &QueryHistoryFilter::default(), None, None).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(db.search_query_history("SELECT", &QueryHistoryFilter::default(), None, None).unwrap().len(), 1);
+        assert_eq!(db.purge_query_history().unwrap(), 1);
+    }
+}
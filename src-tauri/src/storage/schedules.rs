@@ -0,0 +1,544 @@
+// Scheduled queries
+// Schedules bind a tab or snippet to a space, database and timing; each run
+// is recorded with its output file and any alert it raised
+
+use std::collections::HashMap;
+
+use rusqlite::{params, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::database::{DatabaseManager, StorageError, StorageResult};
+
+/// What a schedule runs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "id", rename_all = "lowercase")]
+pub enum ScheduleSource {
+    Tab(String),
+    Snippet(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Comparison {
+    #[serde(rename = ">")]
+    Gt,
+    #[serde(rename = ">=")]
+    Ge,
+    #[serde(rename = "<")]
+    Lt,
+    #[serde(rename = "<=")]
+    Le,
+    #[serde(rename = "=")]
+    Eq,
+    #[serde(rename = "<>")]
+    Ne,
+}
+
+impl Comparison {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Eq => "=",
+            Comparison::Ne => "<>",
+        }
+    }
+
+    pub fn holds(self, left: f64, right: f64) -> bool {
+        match self {
+            Comparison::Gt => left > right,
+            Comparison::Ge => left >= right,
+            Comparison::Lt => left < right,
+            Comparison::Le => left <= right,
+            Comparison::Eq => left == right,
+            Comparison::Ne => left != right,
+        }
+    }
+}
+
+/// When a run raises an alert. `CellValue` fires when any row's value in
+/// `column` meets the threshold.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AlertCondition {
+    RowCount {
+        op: Comparison,
+        value: f64,
+    },
+    CellValue {
+        column: String,
+        op: Comparison,
+        value: f64,
+    },
+}
+
+/// Input for creating or replacing a schedule. Exactly one of `cron`
+/// (five fields, local time) and `interval_minutes` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleInput {
+    pub name: String,
+    pub space_id: String,
+    /// Overrides the tab's or space's database
+    pub database: Option<String>,
+    pub source: ScheduleSource,
+    /// Values for a snippet source's `${name}` placeholders
+    #[serde(default)]
+    pub snippet_values: HashMap<String, String>,
+    pub cron: Option<String>,
+    pub interval_minutes: Option<i64>,
+    /// Where to write results; may contain {name}, {date}, {time}, ... placeholders
+    pub output_path: Option<String>,
+    /// "csv" or "json"; defaults to the output path's extension
+    pub output_format: Option<String>,
+    pub alert: Option<AlertCondition>,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Schedule {
+    pub id: String,
+    pub name: String,
+    pub space_id: String,
+    pub database: Option<String>,
+    pub source: ScheduleSource,
+    pub snippet_values: HashMap<String, String>,
+    pub cron: Option<String>,
+    pub interval_minutes: Option<i64>,
+    pub output_path: Option<String>,
+    pub output_format: Option<String>,
+    pub alert: Option<AlertCondition>,
+    pub enabled: bool,
+    pub last_run_at: Option<String>,
+    pub next_run_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleRun {
+    pub id: String,
+    pub schedule_id: String,
+    pub started_at: String,
+    pub duration_ms: i64,
+    pub row_count: i64,
+    /// The file written, after placeholder expansion
+    pub output_path: Option<String>,
+    pub error: Option<String>,
+    /// Alert message when the schedule's condition was met
+    pub alert: Option<String>,
+}
+
+const SCHEDULE_COLUMNS: &str = "id, name, space_id, database, source_type, source_id, cron,
+     interval_minutes, output_path, output_format, alert, enabled, last_run_at, next_run_at,
+     created_at, updated_at, snippet_values";
+
+const RUN_COLUMNS: &str =
+    "id, schedule_id, started_at, duration_ms, row_count, output_path, error, alert";
+
+fn source_columns(source: &ScheduleSource) -> (&'static str, &str) {
+    match source {
+        ScheduleSource::Tab(id) => ("tab", id),
+        ScheduleSource::Snippet(id) => ("snippet", id),
+    }
+}
+
+fn schedule_from_row(row: &Row) -> rusqlite::Result<Schedule> {
+    let source_type: String = row.get(4)?;
+    let source_id: String = row.get(5)?;
+    let source = match source_type.as_str() {
+        "snippet" => ScheduleSource::Snippet(source_id),
+        _ => ScheduleSource::Tab(source_id),
+    };
+    let alert: Option<String> = row.get(10)?;
+    let snippet_values: Option<String> = row.get(16)?;
+    Ok(Schedule {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        space_id: row.get(2)?,
+        database: row.get(3)?,
+        source,
+        snippet_values: snippet_values
+            .and_then(|v| serde_json::from_str(&v).ok())
+            .unwrap_or_default(),
+        cron: row.get(6)?,
+        interval_minutes: row.get(7)?,
+        output_path: row.get(8)?,
+        output_format: row.get(9)?,
+        alert: alert.and_then(|a| serde_json::from_str(&a).ok()),
+        enabled: row.get::<_, i32>(11)? != 0,
+        last_run_at: row.get(12)?,
+        next_run_at: row.get(13)?,
+        created_at: row.get(14)?,
+        updated_at: row.get(15)?,
+    })
+}
+
+fn run_from_row(row: &Row) -> rusqlite::Result<ScheduleRun> {
+    Ok(ScheduleRun {
+        id: row.get(0)?,
+        schedule_id: row.get(1)?,
+        started_at: row.get(2)?,
+        duration_ms: row.get(3)?,
+        row_count: row.get(4)?,
+        output_path: row.get(5)?,
+        error: row.get(6)?,
+        alert: row.get(7)?,
+    })
+}
+
+fn alert_json(alert: &Option<AlertCondition>) -> StorageResult<Option<String>> {
+    alert
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()
+        .map_err(|e| StorageError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))
+}
+
+fn snippet_values_json(values: &HashMap<String, String>) -> StorageResult<Option<String>> {
+    if values.is_empty() {
+        return Ok(None);
+    }
+    serde_json::to_string(values)
+        .map(Some)
+        .map_err(|e| StorageError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))
+}
+
+impl DatabaseManager {
+    /// Create a schedule. `next_run_at` is computed by the caller.
+    pub fn create_schedule(
+        &self,
+        input: &ScheduleInput,
+        next_run_at: Option<&str>,
+    ) -> StorageResult<Schedule> {
+        let id = Uuid::new_v4().to_string();
+        let (source_type, source_id) = source_columns(&input.source);
+        let alert = alert_json(&input.alert)?;
+        let snippet_values = snippet_values_json(&input.snippet_values)?;
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO schedules
+                 (id, name, space_id, database, source_type, source_id, cron, interval_minutes,
+                  output_path, output_format, alert, enabled, next_run_at, snippet_values)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                params![
+                    &id,
+                    &input.name,
+                    &input.space_id,
+                    &input.database,
+                    source_type,
+                    source_id,
+                    &input.cron,
+                    input.interval_minutes,
+                    &input.output_path,
+                    &input.output_format,
+                    &alert,
+                    input.enabled as i32,
+                    next_run_at,
+                    &snippet_values,
+                ],
+            )?;
+            Ok(())
+        })?;
+        self.get_schedule(&id)?
+            .ok_or(StorageError::Sqlite(rusqlite::Error::QueryReturnedNoRows))
+    }
+
+    /// Replace a schedule's settings
+    pub fn update_schedule(
+        &self,
+        id: &str,
+        input: &ScheduleInput,
+        next_run_at: Option<&str>,
+    ) -> StorageResult<Option<Schedule>> {
+        let (source_type, source_id) = source_columns(&input.source);
+        let alert = alert_json(&input.alert)?;
+        let snippet_values = snippet_values_json(&input.snippet_values)?;
+        let updated = self.with_connection(|conn| {
+            conn.execute(
+                "UPDATE schedules
+                 SET name = ?2, space_id = ?3, database = ?4, source_type = ?5, source_id = ?6,
+                     cron = ?7, interval_minutes = ?8, output_path = ?9, output_format = ?10,
+                     alert = ?11, enabled = ?12, next_run_at = ?13, snippet_values = ?14,
+                     updated_at = datetime('now')
+                 WHERE id = ?1",
+                params![
+                    id,
+                    &input.name,
+                    &input.space_id,
+                    &input.database,
+                    source_type,
+                    source_id,
+                    &input.cron,
+                    input.interval_minutes,
+                    &input.output_path,
+                    &input.output_format,
+                    &alert,
+                    input.enabled as i32,
+                    next_run_at,
+                    &snippet_values,
+                ],
+            )
+        })?;
+        if updated == 0 {
+            return Ok(None);
+        }
+        self.get_schedule(id)
+    }
+
+    pub fn get_schedule(&self, id: &str) -> StorageResult<Option<Schedule>> {
+        self.with_connection(|conn| {
+            conn.query_row(
+                &format!("SELECT {} FROM schedules WHERE id = ?", SCHEDULE_COLUMNS),
+                params![id],
+                schedule_from_row,
+            )
+            .optional()
+        })
+    }
+
+    /// All schedules, optionally only those of one space
+    pub fn get_schedules(&self, space_id: Option<&str>) -> StorageResult<Vec<Schedule>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM schedules WHERE ?1 IS NULL OR space_id = ?1 ORDER BY name",
+                SCHEDULE_COLUMNS
+            ))?;
+            let schedules = stmt
+                .query_map(params![space_id], schedule_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(schedules)
+        })
+    }
+
+    /// Enabled schedules whose next run is at or before `now` (UTC, "%Y-%m-%d %H:%M:%S")
+    pub fn get_due_schedules(&self, now: &str) -> StorageResult<Vec<Schedule>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM schedules
+                 WHERE enabled = 1 AND next_run_at IS NOT NULL AND next_run_at <= ?
+                 ORDER BY next_run_at",
+                SCHEDULE_COLUMNS
+            ))?;
+            let schedules = stmt
+                .query_map(params![now], schedule_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(schedules)
+        })
+    }
+
+    /// Record when a schedule last ran and when it runs next
+    pub fn set_schedule_run_times(
+        &self,
+        id: &str,
+        last_run_at: &str,
+        next_run_at: Option<&str>,
+    ) -> StorageResult<()> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "UPDATE schedules SET last_run_at = ?2, next_run_at = ?3 WHERE id = ?1",
+                params![id, last_run_at, next_run_at],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Record a run made outside the schedule, leaving next_run_at alone
+    pub fn set_schedule_last_run(&self, id: &str, last_run_at: &str) -> StorageResult<()> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "UPDATE schedules SET last_run_at = ?2 WHERE id = ?1",
+                params![id, last_run_at],
+            )?;
+            Ok(())
+        })
+    }
+
+    pub fn delete_schedule(&self, id: &str) -> StorageResult<bool> {
+        self.with_connection(|conn| {
+            let deleted = conn.execute("DELETE FROM schedules WHERE id = ?", params![id])?;
+            Ok(deleted > 0)
+        })
+    }
+
+    /// Record a finished run
+    pub fn add_schedule_run(&self, run: &ScheduleRun) -> StorageResult<()> {
+        self.with_connection(|conn| {
+            conn.execute(
+                &format!(
+                    "INSERT INTO schedule_runs ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    RUN_COLUMNS
+                ),
+                params![
+                    &run.id,
+                    &run.schedule_id,
+                    &run.started_at,
+                    run.duration_ms,
+                    run.row_count,
+                    &run.output_path,
+                    &run.error,
+                    &run.alert,
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Runs of one schedule, newest first
+    pub fn get_schedule_runs(
+        &self,
+        schedule_id: &str,
+        limit: usize,
+    ) -> StorageResult<Vec<ScheduleRun>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM schedule_runs WHERE schedule_id = ?
+                 ORDER BY started_at DESC, rowid DESC LIMIT ?",
+                RUN_COLUMNS
+            ))?;
+            let runs = stmt
+                .query_map(params![schedule_id, limit as i64], run_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(runs)
+        })
+    }
+
+    /// Runs that raised an alert, newest first, across all schedules
+    pub fn get_schedule_alerts(&self, limit: usize) -> StorageResult<Vec<ScheduleRun>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM schedule_runs WHERE alert IS NOT NULL
+                 ORDER BY started_at DESC, rowid DESC LIMIT ?",
+                RUN_COLUMNS
+            ))?;
+            let runs = stmt
+                .query_map(params![limit as i64], run_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(runs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::spaces::CreateSpaceInput;
+
+    fn create_test_db() -> DatabaseManager {
+        let db_path =
+            std::env::temp_dir().join(format!("larik_schedules_test_{}.db", Uuid::new_v4()));
+        DatabaseManager::new(db_path).unwrap()
+    }
+
+    fn input(space_id: &str) -> ScheduleInput {
+        ScheduleInput {
+            name: "Nightly totals".to_string(),
+            space_id: space_id.to_string(),
+            database: Some("Sales".to_string()),
+            source: ScheduleSource::Snippet("sel".to_string()),
+            snippet_values: HashMap::from([("cursor".to_string(), "dbo.Orders".to_string())]),
+            cron: Some("0 2 * * *".to_string()),
+            interval_minutes: None,
+            output_path: Some("/tmp/{name}_{date}.csv".to_string()),
+            output_format: None,
+            alert: Some(AlertCondition::CellValue {
+                column: "Total".to_string(),
+                op: Comparison::Ge,
+                value: 1000.0,
+            }),
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn test_schedule_crud_and_runs() {
+        let db = create_test_db();
+        let space = db
+            .create_space(CreateSpaceInput {
+                name: "Work".to_string(),
+                color: None,
+                icon: None,
+                connection_host: None,
+                connection_port: None,
+                connection_database: None,
+                connection_username: None,
+                connection_password: None,
+                connection_trust_cert: None,
+                connection_encrypt: None,
+            })
+            .unwrap();
+
+        let schedule = db
+            .create_schedule(&input(&space.id), Some("2026-01-01 02:00:00"))
+            .unwrap();
+        assert_eq!(schedule.source, ScheduleSource::Snippet("sel".to_string()));
+        assert_eq!(schedule.alert, input(&space.id).alert);
+        assert_eq!(schedule.snippet_values, input(&space.id).snippet_values);
+        assert_eq!(db.get_schedules(Some(&space.id)).unwrap().len(), 1);
+        assert_eq!(db.get_schedules(Some("other")).unwrap().len(), 0);
+
+        assert_eq!(
+            db.get_due_schedules("2026-01-01 01:59:59").unwrap().len(),
+            0
+        );
+        assert_eq!(
+            db.get_due_schedules("2026-01-01 02:00:00").unwrap().len(),
+            1
+        );
+
+        let mut changed = input(&space.id);
+        changed.enabled = false;
+        changed.source = ScheduleSource::Tab("t1".to_string());
+        let updated = db
+            .update_schedule(&schedule.id, &changed, Some("2026-01-01 02:00:00"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(updated.source, ScheduleSource::Tab("t1".to_string()));
+        assert_eq!(
+            db.get_due_schedules("2026-01-02 00:00:00").unwrap().len(),
+            0
+        );
+
+        for (id, alert) in [("r1", None), ("r2", Some("Total = 1500 in row 1"))] {
+            db.add_schedule_run(&ScheduleRun {
+                id: id.to_string(),
+                schedule_id: schedule.id.clone(),
+                started_at: format!("2026-01-01 02:00:0{}", id.len()),
+                duration_ms: 5,
+                row_count: 3,
+                output_path: None,
+                error: None,
+                alert: alert.map(str::to_string),
+            })
+            .unwrap();
+        }
+        assert_eq!(db.get_schedule_runs(&schedule.id, 10).unwrap().len(), 2);
+        let alerts = db.get_schedule_alerts(10).unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].id, "r2");
+
+        db.set_schedule_run_times(&schedule.id, "2026-01-01 02:00:00", None)
+            .unwrap();
+        let reloaded = db.get_schedule(&schedule.id).unwrap().unwrap();
+        assert_eq!(reloaded.last_run_at.as_deref(), Some("2026-01-01 02:00:00"));
+        assert_eq!(reloaded.next_run_at, None);
+
+        db.set_schedule_run_times(
+            &schedule.id,
+            "2026-01-01 02:00:00",
+            Some("2026-01-02 02:00:00"),
+        )
+        .unwrap();
+        db.set_schedule_last_run(&schedule.id, "2026-01-01 09:30:00")
+            .unwrap();
+        let reloaded = db.get_schedule(&schedule.id).unwrap().unwrap();
+        assert_eq!(reloaded.last_run_at.as_deref(), Some("2026-01-01 09:30:00"));
+        assert_eq!(reloaded.next_run_at.as_deref(), Some("2026-01-02 02:00:00"));
+
+        // Deleting a space removes its schedules and their runs
+        db.delete_space(&space.id).unwrap();
+        assert!(db.get_schedule(&schedule.id).unwrap().is_none());
+        assert!(db.get_schedule_runs(&schedule.id, 10).unwrap().is_empty());
+    }
+}
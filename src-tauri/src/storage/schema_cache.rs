@@ -63,6 +63,23 @@ impl DatabaseManager {
         })
     }
 
+    /// Databases with a cached schema for a connection, as
+    /// (database_name, updated_at) pairs ordered by name
+    pub fn get_cached_databases(
+        &self,
+        connection_id: &str,
+    ) -> StorageResult<Vec<(String, String)>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT database_name, updated_at FROM schema_cache WHERE connection_id = ?1 ORDER BY database_name",
+            )?;
+            let rows = stmt.query_map(params![connection_id], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?;
+            rows.collect()
+        })
+    }
+
     /// Clear schema cache entry
     pub fn clear_schema(
         &self,
@@ -107,18 +124,17 @@ mod tests {
         (manager, db_path)
     }
 
+    fn sample_schema() -> SchemaInfo {
+        SchemaInfo {
+            fetched_at: "2023-01-01T00:00:00Z".to_string(),
+            ..SchemaInfo::for_test("testdb", vec![])
+        }
+    }
+
     #[test]
     fn test_schema_cache_crud() {
         let (manager, db_path) = create_test_db();
-
-        let schema_info = SchemaInfo {
-            database_name: "testdb".to_string(),
-            schemas: vec!["dbo".to_string()],
-            tables: vec![],
-            relationships: vec![],
-            routines: vec![],
-            fetched_at: "2023-01-01T00:00:00Z".to_string(),
-        };
+        let schema_info = sample_schema();
 
         // Cache miss
         assert!(manager.get_schema("conn1", "testdb").unwrap().is_none());
@@ -155,4 +171,33 @@ mod tests {
 
         let _ = std::fs::remove_file(&db_path);
     }
+
+    #[test]
+    fn test_get_cached_databases() {
+        let (manager, db_path) = create_test_db();
+        let schema_info = sample_schema();
+
+        assert!(manager.get_cached_databases("conn1").unwrap().is_empty());
+
+        manager
+            .save_schema("conn1", "testdb2", &schema_info)
+            .unwrap();
+        manager
+            .save_schema("conn1", "testdb1", &schema_info)
+            .unwrap();
+        manager
+            .save_schema("conn2", "other", &schema_info)
+            .unwrap();
+
+        // Ordered by name, limited to the connection
+        let cached: Vec<String> = manager
+            .get_cached_databases("conn1")
+            .unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(cached, vec!["testdb1", "testdb2"]);
+
+        let _ = std::fs::remove_file(&db_path);
+    }
 }
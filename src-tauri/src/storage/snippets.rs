@@ -1,6 +1,7 @@
 // SQL Code Snippets storage (T046)
 // Handles persistence of user-defined and built-in SQL snippets
 
+use regex::Regex;
 use rusqlite::params;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -29,6 +30,36 @@ pub struct Snippet {
     pub updated_at: String,
 }
 
+impl Snippet {
+    /// Replace `${name}` / `${name:default}` placeholders with the given values,
+    /// falling back to the default. Returns the names of placeholders that
+    /// have neither.
+    pub fn expand(&self, values: &[(String, String)]) -> Result<String, Vec<String>> {
+        let placeholder = Regex::new(r"\$\{(\w+)(?::([^}]*))?\}").expect("valid regex");
+        let mut missing = Vec::new();
+        let expanded = placeholder.replace_all(&self.content, |caps: &regex::Captures<'_>| {
+            let name = &caps[1];
+            if let Some((_, value)) = values.iter().rev().find(|(n, _)| n == name) {
+                return value.clone();
+            }
+            match caps.get(2) {
+                Some(default) => default.as_str().to_string(),
+                None => {
+                    if !missing.iter().any(|m| m == name) {
+                        missing.push(name.to_string());
+                    }
+                    String::new()
+                }
+            }
+        });
+        if missing.is_empty() {
+            Ok(expanded.into_owned())
+        } else {
+            Err(missing)
+        }
+    }
+}
+
 /// Input for creating a new snippet
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateSnippetInput {
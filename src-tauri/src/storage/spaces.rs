@@ -34,6 +34,16 @@ impl Space {
     pub fn has_connection(&self) -> bool {
         self.connection_host.is_some() && self.connection_database.is_some()
     }
+
+    /// Server as written in a connection string: `host`, or `host,port` when
+    /// the port is not the default 1433
+    pub fn server_name(&self) -> Option<String> {
+        let host = self.connection_host.as_ref()?;
+        Some(match self.connection_port {
+            Some(port) if port != 1433 => format!("{},{}", host, port),
+            _ => host.clone(),
+        })
+    }
 }
 
 /// Input for creating a new space with its connection
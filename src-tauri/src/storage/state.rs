@@ -20,6 +20,7 @@ pub struct AppSettings {
     pub enable_sticky_notes: bool,
     pub max_result_rows: i32,
     pub reference_preview_row_limit: i32,
+    pub schema_cache_ttl_minutes: i32,
 }
 
 impl DatabaseManager {
@@ -138,6 +139,28 @@ impl DatabaseManager {
         self.set_setting("reference_preview_row_limit", &row_limit.to_string())
     }
 
+    /// Get schema cache TTL in minutes (0 = never refresh automatically)
+    pub fn get_schema_cache_ttl_minutes(&self) -> StorageResult<i32> {
+        let value = self.get_setting("schema_cache_ttl_minutes")?;
+        Ok(value.and_then(|v| v.parse().ok()).unwrap_or(60))
+    }
+
+    /// Set schema cache TTL in minutes
+    pub fn set_schema_cache_ttl_minutes(&self, minutes: i32) -> StorageResult<()> {
+        self.set_setting("schema_cache_ttl_minutes", &minutes.to_string())
+    }
+
+    /// Get whether the local automation API should run (off unless enabled)
+    pub fn get_api_enabled(&self) -> StorageResult<bool> {
+        let value = self.get_setting("api_enabled")?;
+        Ok(value.as_deref() == Some("true"))
+    }
+
+    /// Set whether the local automation API should run
+    pub fn set_api_enabled(&self, enabled: bool) -> StorageResult<()> {
+        self.set_setting("api_enabled", if enabled { "true" } else { "false" })
+    }
+
     /// Get last opened space ID
     pub fn get_last_space_id(&self) -> StorageResult<Option<String>> {
         self.get_setting("last_space_id")
@@ -183,6 +206,7 @@ impl DatabaseManager {
             enable_sticky_notes: self.get_enable_sticky_notes()?,
             max_result_rows: self.get_max_result_rows()?,
             reference_preview_row_limit: self.get_reference_preview_row_limit()?,
+            schema_cache_ttl_minutes: self.get_schema_cache_ttl_minutes()?,
         })
     }
 
@@ -194,6 +218,7 @@ impl DatabaseManager {
         self.set_enable_sticky_notes(settings.enable_sticky_notes)?;
         self.set_max_result_rows(settings.max_result_rows)?;
         self.set_reference_preview_row_limit(settings.reference_preview_row_limit)?;
+        self.set_schema_cache_ttl_minutes(settings.schema_cache_ttl_minutes)?;
         Ok(())
     }
 
@@ -220,6 +245,9 @@ impl DatabaseManager {
         if self.get_setting("reference_preview_row_limit")?.is_none() {
             self.set_setting("reference_preview_row_limit", "200")?;
         }
+        if self.get_setting("schema_cache_ttl_minutes")?.is_none() {
+            self.set_setting("schema_cache_ttl_minutes", "60")?;
+        }
         Ok(())
     }
 }
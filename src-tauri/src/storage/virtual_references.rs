@@ -1,11 +1,13 @@
 // Virtual (user-defined) foreign key storage.
 //
 // Some databases model a lookup relationship without declaring a FOREIGN KEY.
-// A virtual reference lets the user point a column at its lookup table by hand
-// so the reference preview works the same way it does for a real constraint.
-// These live only in Larik's local database, scoped to connection + database.
+// A virtual reference lets the user point a column (or an ordered set of
+// columns, for composite business keys) at its lookup table by hand so the
+// reference preview works the same way it does for a real constraint.
+// These live in Larik's local database, scoped to connection + database, and
+// can be exported to a portable JSON set keyed by server and database names.
 
-use rusqlite::params;
+use rusqlite::{params, Connection};
 use serde::{Deserialize, Serialize};
 
 use super::database::{DatabaseManager, StorageResult};
@@ -18,14 +20,123 @@ pub struct VirtualReference {
     pub database_name: String,
     pub source_schema: String,
     pub source_table: String,
+    /// First (or only) source column
     pub source_column: String,
     pub target_schema: String,
     pub target_table: String,
+    /// First (or only) target column
     pub target_column: String,
+    /// Every column pair in key order, including the first
+    #[serde(default)]
+    pub columns: Vec<VirtualReferenceColumn>,
     pub created_at: String,
     pub updated_at: String,
 }
 
+/// One source -> target column pair of a virtual reference.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VirtualReferenceColumn {
+    pub source_column: String,
+    pub target_column: String,
+}
+
+impl VirtualReference {
+    /// Column pairs in key order. Falls back to the single pair when
+    /// `columns` is empty, as in references built before composite keys.
+    pub fn column_pairs(&self) -> Vec<VirtualReferenceColumn> {
+        if self.columns.is_empty() {
+            vec![VirtualReferenceColumn {
+                source_column: self.source_column.clone(),
+                target_column: self.target_column.clone(),
+            }]
+        } else {
+            self.columns.clone()
+        }
+    }
+
+    /// Source columns in key order, lowercased for comparison
+    fn source_key(&self) -> Vec<String> {
+        self.column_pairs()
+            .iter()
+            .map(|c| c.source_column.to_lowercase())
+            .collect()
+    }
+}
+
+/// A virtual reference without local ids, for sharing between spaces.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PortableReference {
+    pub source_schema: String,
+    pub source_table: String,
+    pub target_schema: String,
+    pub target_table: String,
+    pub columns: Vec<VirtualReferenceColumn>,
+}
+
+/// A shareable set of virtual references for one database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VirtualReferenceSet {
+    pub version: u32,
+    /// Server the references were curated against, as `host` or `host,port`
+    pub server: Option<String>,
+    pub database: String,
+    pub exported_at: String,
+    pub references: Vec<PortableReference>,
+}
+
+impl VirtualReferenceSet {
+    pub const VERSION: u32 = 1;
+
+    pub fn new(server: Option<String>, database: &str, references: &[VirtualReference]) -> Self {
+        Self {
+            version: Self::VERSION,
+            server,
+            database: database.to_string(),
+            exported_at: chrono::Utc::now().to_rfc3339(),
+            references: references
+                .iter()
+                .map(|r| PortableReference {
+                    source_schema: r.source_schema.clone(),
+                    source_table: r.source_table.clone(),
+                    target_schema: r.target_schema.clone(),
+                    target_table: r.target_table.clone(),
+                    columns: r.column_pairs(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Local references for a connection + database, with fresh ids.
+    /// References without columns are skipped.
+    pub fn to_references(
+        &self,
+        connection_id: &str,
+        database_name: &str,
+    ) -> Vec<VirtualReference> {
+        let now = chrono::Utc::now().to_rfc3339();
+        self.references
+            .iter()
+            .filter_map(|r| {
+                let first = r.columns.first()?;
+                Some(VirtualReference {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    connection_id: connection_id.to_string(),
+                    database_name: database_name.to_string(),
+                    source_schema: r.source_schema.clone(),
+                    source_table: r.source_table.clone(),
+                    source_column: first.source_column.clone(),
+                    target_schema: r.target_schema.clone(),
+                    target_table: r.target_table.clone(),
+                    target_column: first.target_column.clone(),
+                    columns: r.columns.clone(),
+                    created_at: now.clone(),
+                    updated_at: now.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
 impl DatabaseManager {
     /// All virtual references defined for a connection + database.
     pub fn get_virtual_references(
@@ -44,7 +155,7 @@ impl DatabaseManager {
                  ORDER BY source_table, source_column",
             )?;
 
-            let references = stmt
+            let mut references: Vec<VirtualReference> = stmt
                 .query_map(params![connection_id, database_name], |row| {
                     Ok(VirtualReference {
                         id: row.get(0)?,
@@ -56,6 +167,7 @@ impl DatabaseManager {
                         target_schema: row.get(6)?,
                         target_table: row.get(7)?,
                         target_column: row.get(8)?,
+                        columns: Vec::new(),
                         created_at: row.get(9)?,
                         updated_at: row.get(10)?,
                     })
@@ -63,53 +175,33 @@ impl DatabaseManager {
                 .filter_map(|r| r.ok())
                 .collect();
 
+            for reference in &mut references {
+                reference.columns = reference_columns(conn, &reference.id)?;
+                if reference.columns.is_empty() {
+                    reference.columns = reference.column_pairs();
+                }
+            }
+
             Ok(references)
         })
     }
 
-    /// Save a virtual reference, replacing any existing one for the same source
-    /// column. Identifier comparisons are case-insensitive, matching SQL Server.
+    /// Save a virtual reference, replacing any existing one from the same
+    /// source columns. Identifier comparisons are case-insensitive, matching
+    /// SQL Server.
     pub fn save_virtual_reference(&self, reference: &VirtualReference) -> StorageResult<()> {
-        self.with_connection(|conn| {
-            conn.execute(
-                "DELETE FROM virtual_references
-                 WHERE connection_id = ?1
-                   AND database_name = ?2 COLLATE NOCASE
-                   AND source_schema = ?3 COLLATE NOCASE
-                   AND source_table = ?4 COLLATE NOCASE
-                   AND source_column = ?5 COLLATE NOCASE",
-                params![
-                    reference.connection_id,
-                    reference.database_name,
-                    reference.source_schema,
-                    reference.source_table,
-                    reference.source_column,
-                ],
-            )?;
-
-            conn.execute(
-                "INSERT INTO virtual_references
-                    (id, connection_id, database_name,
-                     source_schema, source_table, source_column,
-                     target_schema, target_table, target_column,
-                     created_at, updated_at)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
-                params![
-                    reference.id,
-                    reference.connection_id,
-                    reference.database_name,
-                    reference.source_schema,
-                    reference.source_table,
-                    reference.source_column,
-                    reference.target_schema,
-                    reference.target_table,
-                    reference.target_column,
-                    reference.created_at,
-                    reference.updated_at,
-                ],
-            )?;
+        self.save_virtual_references(std::slice::from_ref(reference))
+    }
 
-            Ok(())
+    /// Save several virtual references in one transaction, each replacing any
+    /// existing reference from the same source columns.
+    pub fn save_virtual_references(&self, references: &[VirtualReference]) -> StorageResult<()> {
+        self.with_connection_mut(|conn| {
+            let tx = conn.transaction()?;
+            for reference in references {
+                replace_reference(&tx, reference)?;
+            }
+            tx.commit()
         })
     }
 
@@ -122,6 +214,97 @@ impl DatabaseManager {
     }
 }
 
+fn reference_columns(
+    conn: &Connection,
+    reference_id: &str,
+) -> rusqlite::Result<Vec<VirtualReferenceColumn>> {
+    let mut stmt = conn.prepare(
+        "SELECT source_column, target_column
+         FROM virtual_reference_columns
+         WHERE reference_id = ?1
+         ORDER BY ordinal",
+    )?;
+    let columns = stmt
+        .query_map(params![reference_id], |row| {
+            Ok(VirtualReferenceColumn {
+                source_column: row.get(0)?,
+                target_column: row.get(1)?,
+            })
+        })?
+        .collect();
+    columns
+}
+
+fn replace_reference(conn: &Connection, reference: &VirtualReference) -> rusqlite::Result<()> {
+    let columns = reference.column_pairs();
+    let source_key = reference.source_key();
+
+    // Drop references from the same table whose source columns match
+    let mut stmt = conn.prepare(
+        "SELECT id, source_column, target_column
+         FROM virtual_references
+         WHERE connection_id = ?1
+           AND database_name = ?2 COLLATE NOCASE
+           AND source_schema = ?3 COLLATE NOCASE
+           AND source_table = ?4 COLLATE NOCASE",
+    )?;
+    let existing: Vec<(String, String, String)> = stmt
+        .query_map(
+            params![
+                reference.connection_id,
+                reference.database_name,
+                reference.source_schema,
+                reference.source_table,
+            ],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?
+        .collect::<rusqlite::Result<_>>()?;
+    for (id, source_column, target_column) in existing {
+        let existing = VirtualReference {
+            columns: reference_columns(conn, &id)?,
+            source_column,
+            target_column,
+            ..reference.clone()
+        };
+        if existing.source_key() == source_key {
+            conn.execute("DELETE FROM virtual_references WHERE id = ?1", params![id])?;
+        }
+    }
+
+    conn.execute(
+        "INSERT INTO virtual_references
+            (id, connection_id, database_name,
+             source_schema, source_table, source_column,
+             target_schema, target_table, target_column,
+             created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            reference.id,
+            reference.connection_id,
+            reference.database_name,
+            reference.source_schema,
+            reference.source_table,
+            columns[0].source_column,
+            reference.target_schema,
+            reference.target_table,
+            columns[0].target_column,
+            reference.created_at,
+            reference.updated_at,
+        ],
+    )?;
+
+    for (ordinal, column) in columns.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO virtual_reference_columns
+                (reference_id, ordinal, source_column, target_column)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![reference.id, ordinal as i64, column.source_column, column.target_column],
+        )?;
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -145,6 +328,7 @@ mod tests {
             target_schema: "dbo".to_string(),
             target_table: target_table.to_string(),
             target_column: "Id".to_string(),
+            columns: Vec::new(),
             created_at: "2026-07-30T00:00:00Z".to_string(),
             updated_at: "2026-07-30T00:00:00Z".to_string(),
         }
@@ -184,4 +368,91 @@ mod tests {
 
         let _ = std::fs::remove_file(&db_path);
     }
+
+    #[test]
+    fn test_save_virtual_references_in_bulk() {
+        let (db, db_path) = create_test_db();
+
+        db.save_virtual_reference(&sample("v1", "Status", "TransactionStatus")).unwrap();
+        db.save_virtual_references(&[
+            sample("v2", "UserId", "User"),
+            sample("v3", "STATUS", "ApplicationStatus"),
+        ])
+        .unwrap();
+
+        let all = db.get_virtual_references("space1", "AppDb").unwrap();
+        assert_eq!(all.len(), 2);
+        assert!(all.iter().any(|r| r.id == "v3" && r.target_table == "ApplicationStatus"));
+
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn test_composite_references() {
+        let (db, db_path) = create_test_db();
+
+        let pair = |source: &str, target: &str| VirtualReferenceColumn {
+            source_column: source.to_string(),
+            target_column: target.to_string(),
+        };
+        let composite = VirtualReference {
+            source_column: String::new(),
+            target_column: String::new(),
+            columns: vec![pair("TenantId", "TenantId"), pair("OrderNo", "OrderNo")],
+            target_table: "Order".to_string(),
+            ..sample("c1", "", "")
+        };
+        db.save_virtual_reference(&sample("v1", "TenantId", "Tenant")).unwrap();
+        db.save_virtual_reference(&composite).unwrap();
+
+        // A composite key does not replace a single-column reference sharing
+        // its first column
+        let all = db.get_virtual_references("space1", "AppDb").unwrap();
+        assert_eq!(all.len(), 2);
+        let stored = all.iter().find(|r| r.id == "c1").unwrap();
+        assert_eq!(stored.source_column, "TenantId");
+        assert_eq!(stored.columns, composite.columns);
+        let single = all.iter().find(|r| r.id == "v1").unwrap();
+        assert_eq!(single.columns, vec![pair("TenantId", "Id")]);
+
+        // The same source columns replace it, and deleting removes its columns
+        db.save_virtual_reference(&VirtualReference {
+            id: "c2".to_string(),
+            columns: vec![pair("tenantid", "TenantId"), pair("orderno", "Number")],
+            ..composite.clone()
+        })
+        .unwrap();
+        let all = db.get_virtual_references("space1", "AppDb").unwrap();
+        assert_eq!(all.len(), 2);
+        assert!(all.iter().any(|r| r.id == "c2"));
+        assert!(db.delete_virtual_reference("c2").unwrap());
+        let orphans: i64 = db
+            .with_connection(|conn| {
+                conn.query_row(
+                    "SELECT COUNT(*) FROM virtual_reference_columns WHERE reference_id = 'c2'",
+                    [],
+                    |row| row.get(0),
+                )
+            })
+            .unwrap();
+        assert_eq!(orphans, 0);
+
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn test_portable_reference_sets_round_trip() {
+        let references = vec![sample("v1", "UserId", "User")];
+        let set = VirtualReferenceSet::new(Some("sql01,1444".to_string()), "AppDb", &references);
+        let json = serde_json::to_string(&set).unwrap();
+        assert!(!json.contains("space1"), "exports carry no space ids");
+
+        let set: VirtualReferenceSet = serde_json::from_str(&json).unwrap();
+        let imported = set.to_references("space2", "AppDb");
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].connection_id, "space2");
+        assert_ne!(imported[0].id, "v1");
+        assert_eq!(imported[0].source_column, "UserId");
+        assert_eq!(imported[0].column_pairs(), references[0].column_pairs());
+    }
 }